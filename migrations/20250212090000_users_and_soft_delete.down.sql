@@ -0,0 +1,3 @@
+ALTER TABLE answers DROP COLUMN IF EXISTS deleted_by, DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE questions DROP COLUMN IF EXISTS deleted_by, DROP COLUMN IF EXISTS deleted_at;
+DROP TABLE IF EXISTS users;
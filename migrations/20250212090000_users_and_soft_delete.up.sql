@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS users (
+    user_uuid uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    username VARCHAR(64) NOT NULL UNIQUE,
+    role VARCHAR(16) NOT NULL DEFAULT 'user' CHECK (role IN ('user', 'moderator', 'admin')),
+    -- SHA-256 of the API key; the key itself is only shown once, when the user is created
+    api_key_hash CHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE questions
+    ADD COLUMN deleted_at TIMESTAMP,
+    ADD COLUMN deleted_by uuid REFERENCES users (user_uuid) ON DELETE SET NULL;
+
+ALTER TABLE answers
+    ADD COLUMN deleted_at TIMESTAMP,
+    ADD COLUMN deleted_by uuid REFERENCES users (user_uuid) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS questions_deleted_at_idx ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS answers_deleted_at_idx ON answers (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,5 @@
+DROP TRIGGER IF EXISTS answers_bump_version ON answers;
+DROP TRIGGER IF EXISTS questions_bump_version ON questions;
+DROP FUNCTION IF EXISTS bump_row_version();
+ALTER TABLE answers DROP COLUMN IF EXISTS version;
+ALTER TABLE questions DROP COLUMN IF EXISTS version;
@@ -0,0 +1,15 @@
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE answers ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
+
+-- Every update produces a new version, which is what ETags are derived from
+CREATE OR REPLACE FUNCTION bump_row_version() RETURNS trigger AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER questions_bump_version BEFORE UPDATE ON questions
+    FOR EACH ROW EXECUTE FUNCTION bump_row_version();
+CREATE TRIGGER answers_bump_version BEFORE UPDATE ON answers
+    FOR EACH ROW EXECUTE FUNCTION bump_row_version();
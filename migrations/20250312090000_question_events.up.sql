@@ -0,0 +1,10 @@
+-- Changes to a question's answers, streamed to watchers of the question and kept so they can resume
+CREATE TABLE IF NOT EXISTS question_events (
+    event_id BIGSERIAL PRIMARY KEY,
+    question_uuid uuid NOT NULL REFERENCES questions(question_uuid) ON DELETE CASCADE,
+    event_type VARCHAR(64) NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS question_events_question_idx ON question_events (question_uuid, event_id);
@@ -0,0 +1,6 @@
+ALTER TABLE users DROP COLUMN IF EXISTS reputation;
+DROP TABLE IF EXISTS reputation_entries;
+DROP TABLE IF EXISTS votes;
+ALTER TABLE questions DROP COLUMN IF EXISTS accepted_answer_uuid;
+ALTER TABLE answers DROP COLUMN IF EXISTS author_uuid;
+ALTER TABLE questions DROP COLUMN IF EXISTS author_uuid;
@@ -0,0 +1,36 @@
+-- Who asked and answered, so that votes and acceptances credit them; NULL for anonymous posts
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS author_uuid uuid REFERENCES users (user_uuid) ON DELETE SET NULL;
+ALTER TABLE answers ADD COLUMN IF NOT EXISTS author_uuid uuid REFERENCES users (user_uuid) ON DELETE SET NULL;
+ALTER TABLE questions
+    ADD COLUMN IF NOT EXISTS accepted_answer_uuid uuid REFERENCES answers (answer_uuid) ON DELETE SET NULL;
+
+-- One vote per user and question or answer
+CREATE TABLE IF NOT EXISTS votes (
+    voter_uuid uuid NOT NULL REFERENCES users (user_uuid) ON DELETE CASCADE,
+    target_type VARCHAR(16) NOT NULL CHECK (target_type IN ('question', 'answer')),
+    target_uuid uuid NOT NULL,
+    value SMALLINT NOT NULL CHECK (value IN (-1, 1)),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (voter_uuid, target_uuid)
+);
+
+CREATE INDEX IF NOT EXISTS votes_target_idx ON votes (target_uuid);
+
+-- Append-only: undoing a vote or an acceptance appends an entry cancelling the original one
+CREATE TABLE IF NOT EXISTS reputation_entries (
+    entry_id BIGSERIAL PRIMARY KEY,
+    user_uuid uuid NOT NULL REFERENCES users (user_uuid) ON DELETE CASCADE,
+    -- E.g. answer_upvoted, downvote_cast, answer_accepted, vote_retracted
+    reason VARCHAR(32) NOT NULL,
+    amount INTEGER NOT NULL,
+    target_type VARCHAR(16) NOT NULL,
+    target_uuid uuid NOT NULL,
+    -- Who caused the change, e.g. the voter
+    actor_uuid uuid,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS reputation_entries_user_idx ON reputation_entries (user_uuid, entry_id);
+
+-- 1 plus the sum of the user's entries, kept up to date along with them
+ALTER TABLE users ADD COLUMN IF NOT EXISTS reputation INTEGER NOT NULL DEFAULT 1;
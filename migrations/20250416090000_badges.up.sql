@@ -0,0 +1,12 @@
+-- Badges earned by users; the rules awarding them live in badges.toml. Title and level are copied
+-- from the rule when the badge is awarded, so earned badges survive changes to the rules.
+CREATE TABLE IF NOT EXISTS user_badges (
+    user_uuid uuid NOT NULL REFERENCES users (user_uuid) ON DELETE CASCADE,
+    badge VARCHAR(64) NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    level VARCHAR(16) NOT NULL CHECK (level IN ('bronze', 'silver', 'gold')),
+    awarded_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_uuid, badge)
+);
+
+CREATE INDEX IF NOT EXISTS user_badges_badge_idx ON user_badges (badge);
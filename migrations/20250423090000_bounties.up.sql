@@ -0,0 +1,20 @@
+-- Reputation offered by the author of a question to attract answers. The amount is taken from the
+-- offerer when the bounty opens, and goes to the awarded answer, back to the offerer if the question is
+-- deleted, or is lost when the bounty expires without a suitable answer.
+CREATE TABLE IF NOT EXISTS bounties (
+    bounty_id BIGSERIAL PRIMARY KEY,
+    question_uuid uuid NOT NULL REFERENCES questions (question_uuid) ON DELETE CASCADE,
+    offered_by uuid NOT NULL REFERENCES users (user_uuid) ON DELETE CASCADE,
+    amount INTEGER NOT NULL CHECK (amount > 0),
+    status VARCHAR(16) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'awarded', 'refunded', 'expired')),
+    awarded_answer_uuid uuid REFERENCES answers (answer_uuid) ON DELETE SET NULL,
+    -- What the awarded answer got: the amount, or half of it when awarded automatically on expiry
+    awarded_amount INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL,
+    closed_at TIMESTAMPTZ
+);
+
+-- At most one open bounty per question
+CREATE UNIQUE INDEX IF NOT EXISTS bounties_open_question_idx ON bounties (question_uuid) WHERE status = 'open';
+CREATE INDEX IF NOT EXISTS bounties_open_expires_idx ON bounties (expires_at) WHERE status = 'open';
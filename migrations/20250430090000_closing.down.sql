@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS closure_votes;
+
+ALTER TABLE questions
+    DROP COLUMN IF EXISTS duplicate_of,
+    DROP COLUMN IF EXISTS closed_reason,
+    DROP COLUMN IF EXISTS closed_at;
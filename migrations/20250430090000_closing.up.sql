@@ -0,0 +1,20 @@
+-- A closed question takes no new answers until it is reopened. Duplicates point at the question they
+-- duplicate, which may itself be deleted later.
+ALTER TABLE questions
+    ADD COLUMN IF NOT EXISTS closed_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS closed_reason VARCHAR(32) CHECK (closed_reason IN ('duplicate', 'off_topic', 'needs_details')),
+    ADD COLUMN IF NOT EXISTS duplicate_of uuid REFERENCES questions (question_uuid) ON DELETE SET NULL,
+    ADD CONSTRAINT questions_closed_check CHECK ((closed_at IS NULL) = (closed_reason IS NULL)),
+    ADD CONSTRAINT questions_duplicate_check CHECK (duplicate_of IS NULL OR closed_reason = 'duplicate');
+
+-- The pending votes to close an open question, or to reopen a closed one. They are cleared whenever
+-- the question is closed or reopened, so every vote here is of the same kind.
+CREATE TABLE IF NOT EXISTS closure_votes (
+    question_uuid uuid NOT NULL REFERENCES questions (question_uuid) ON DELETE CASCADE,
+    voter_uuid uuid NOT NULL REFERENCES users (user_uuid) ON DELETE CASCADE,
+    -- NULL for reopen votes
+    reason VARCHAR(32) CHECK (reason IN ('duplicate', 'off_topic', 'needs_details')),
+    duplicate_of uuid REFERENCES questions (question_uuid) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (question_uuid, voter_uuid)
+);
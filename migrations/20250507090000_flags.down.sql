@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS flags;
+
+ALTER TABLE answers DROP COLUMN IF EXISTS hidden_at;
+ALTER TABLE questions DROP COLUMN IF EXISTS hidden_at;
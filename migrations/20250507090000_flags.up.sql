@@ -0,0 +1,26 @@
+-- Posts hidden automatically because of spam flags. Like deleted posts, they are only listed for
+-- moderators.
+ALTER TABLE questions ADD COLUMN IF NOT EXISTS hidden_at TIMESTAMPTZ;
+ALTER TABLE answers ADD COLUMN IF NOT EXISTS hidden_at TIMESTAMPTZ;
+
+-- Reports of questions and answers for moderators to review. A flag is pending until a moderator
+-- resolves it as helpful or declined, which counts towards the flagger's accuracy.
+CREATE TABLE IF NOT EXISTS flags (
+    flag_id BIGSERIAL PRIMARY KEY,
+    target_type VARCHAR(16) NOT NULL CHECK (target_type IN ('question', 'answer')),
+    target_uuid uuid NOT NULL,
+    flagged_by uuid NOT NULL REFERENCES users (user_uuid) ON DELETE CASCADE,
+    reason VARCHAR(32) NOT NULL CHECK (reason IN ('spam', 'rude', 'low_quality', 'other')),
+    details TEXT,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'helpful', 'declined')),
+    -- The moderator reviewing the flag; claims lapse after a while so flags aren't stuck
+    claimed_by uuid REFERENCES users (user_uuid) ON DELETE SET NULL,
+    claimed_at TIMESTAMPTZ,
+    resolved_by uuid REFERENCES users (user_uuid) ON DELETE SET NULL,
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (target_uuid, flagged_by)
+);
+
+CREATE INDEX IF NOT EXISTS flags_pending_idx ON flags (created_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS flags_flagged_by_idx ON flags (flagged_by);
@@ -0,0 +1,24 @@
+-- Changes to someone else's question or answer proposed by users who can't edit it themselves.
+-- A suggestion is pending until a reviewer approves it, which applies it to the post, or rejects it.
+CREATE TABLE IF NOT EXISTS suggested_edits (
+    edit_id BIGSERIAL PRIMARY KEY,
+    target_type VARCHAR(16) NOT NULL CHECK (target_type IN ('question', 'answer')),
+    target_uuid uuid NOT NULL,
+    suggested_by uuid NOT NULL REFERENCES users (user_uuid) ON DELETE CASCADE,
+    -- The new title and description of a question, or content of an answer; NULL leaves it as is
+    title VARCHAR(255),
+    description VARCHAR(255),
+    content VARCHAR(255),
+    summary TEXT,
+    -- The version of the post the edit was suggested against; it can't be approved once the post changed
+    base_version BIGINT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected')),
+    reviewed_by uuid REFERENCES users (user_uuid) ON DELETE SET NULL,
+    review_comment TEXT,
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- A post has at most one pending suggested edit at a time
+CREATE UNIQUE INDEX IF NOT EXISTS suggested_edits_pending_idx ON suggested_edits (target_uuid) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS suggested_edits_queue_idx ON suggested_edits (created_at) WHERE status = 'pending';
@@ -0,0 +1,4 @@
+DELETE FROM flags WHERE flagged_by IS NULL;
+ALTER TABLE flags ALTER COLUMN flagged_by SET NOT NULL;
+
+DROP TABLE IF EXISTS screening_decisions;
@@ -0,0 +1,23 @@
+-- Every decision content screening made on a new question or answer. Accepted and quarantined
+-- decisions also feed the repeated-content and burst rules.
+CREATE TABLE IF NOT EXISTS screening_decisions (
+    decision_id BIGSERIAL PRIMARY KEY,
+    -- NULL for anonymous posts
+    author_uuid uuid REFERENCES users (user_uuid) ON DELETE CASCADE,
+    target_type VARCHAR(16) NOT NULL CHECK (target_type IN ('question', 'answer')),
+    -- The post created, unless it was rejected
+    target_uuid uuid,
+    decision VARCHAR(16) NOT NULL CHECK (decision IN ('accepted', 'quarantined', 'rejected')),
+    -- The rule behind a quarantine or rejection, and why
+    rule VARCHAR(64),
+    reason TEXT,
+    -- SHA-256 of the normalized text of the post
+    content_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS screening_decisions_content_hash_idx ON screening_decisions (content_hash, created_at);
+CREATE INDEX IF NOT EXISTS screening_decisions_author_idx ON screening_decisions (author_uuid, created_at);
+
+-- Quarantined posts go to the flag queue as flags raised by screening, which have no flagger
+ALTER TABLE flags ALTER COLUMN flagged_by DROP NOT NULL;
@@ -0,0 +1,5 @@
+-- Trigram indexes to find questions similar to the one being asked
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX questions_title_trgm_idx ON questions USING GIN (title gin_trgm_ops);
+CREATE INDEX questions_description_trgm_idx ON questions USING GIN (description gin_trgm_ops);
@@ -0,0 +1,16 @@
+-- Links between questions, found in the question and answer texts that mention another question
+-- (or one of its answers) by ID or URL. Rebuilt for a post whenever it is created or edited.
+CREATE TABLE IF NOT EXISTS question_links (
+    -- The question or answer mentioning the linked question
+    source_type VARCHAR(16) NOT NULL CHECK (source_type IN ('question', 'answer')),
+    source_uuid UUID NOT NULL,
+    -- The question the source belongs to
+    question_uuid UUID NOT NULL REFERENCES questions (question_uuid) ON DELETE CASCADE,
+    linked_question_uuid UUID NOT NULL REFERENCES questions (question_uuid) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (source_uuid, linked_question_uuid),
+    CHECK (question_uuid <> linked_question_uuid)
+);
+
+CREATE INDEX question_links_question_idx ON question_links (question_uuid);
+CREATE INDEX question_links_linked_question_idx ON question_links (linked_question_uuid);
@@ -0,0 +1,5 @@
+-- Fails if a description or answer is longer than 255 characters
+ALTER TABLE suggested_edits ALTER COLUMN content TYPE VARCHAR(255);
+ALTER TABLE suggested_edits ALTER COLUMN description TYPE VARCHAR(255);
+ALTER TABLE answers ALTER COLUMN content TYPE VARCHAR(255);
+ALTER TABLE questions ALTER COLUMN description TYPE VARCHAR(255);
@@ -0,0 +1,6 @@
+-- Question descriptions and answers were limited to 255 characters by their columns; the API now
+-- enforces a far larger limit itself
+ALTER TABLE questions ALTER COLUMN description TYPE TEXT;
+ALTER TABLE answers ALTER COLUMN content TYPE TEXT;
+ALTER TABLE suggested_edits ALTER COLUMN description TYPE TEXT;
+ALTER TABLE suggested_edits ALTER COLUMN content TYPE TEXT;
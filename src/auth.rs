@@ -0,0 +1,81 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, State};
+use sha2::{Digest, Sha256};
+
+use crate::models::Actor;
+use crate::persistance::users_dao::UsersDao;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+pub fn generate_api_key() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// API keys are only stored in hashed form.
+pub fn hash_api_key(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+    MissingApiKey,
+    InvalidApiKey,
+    Unavailable,
+}
+
+/// Result of authenticating the current request, cached so the lookup runs at most once.
+struct Authentication(Result<Actor, AuthError>);
+
+async fn authenticate(request: &Request<'_>) -> Result<Actor, AuthError> {
+    let api_key = request
+        .headers()
+        .get_one(API_KEY_HEADER)
+        .ok_or(AuthError::MissingApiKey)?;
+
+    let users_dao = request
+        .guard::<&State<Box<dyn UsersDao + Send + Sync>>>()
+        .await
+        .succeeded()
+        .ok_or(AuthError::Unavailable)?;
+
+    match users_dao.get_user_by_api_key_hash(hash_api_key(api_key)).await {
+        Ok(Some(user)) => Ok(Actor {
+            user_uuid: user.user_uuid,
+            role: user.role,
+        }),
+        Ok(None) => Err(AuthError::InvalidApiKey),
+        Err(err) => {
+            error!("Error authenticating request: {:?}", err);
+            Err(AuthError::Unavailable)
+        }
+    }
+}
+
+/// Authenticates the request by its `X-Api-Key` header, running the lookup at most once per
+/// request however many guards and fairings ask.
+pub async fn authentication<'r>(request: &'r Request<'_>) -> &'r Result<Actor, AuthError> {
+    &request
+        .local_cache_async(async { Authentication(authenticate(request).await) })
+        .await
+        .0
+}
+
+/// Authenticates the caller by the `X-Api-Key` header. Use `Option<Actor>` for routes that
+/// also serve anonymous callers.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Actor {
+    type Error = &'r AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match authentication(request).await {
+            Ok(actor) => Outcome::Success(actor.clone()),
+            Err(err @ AuthError::Unavailable) => Outcome::Error((Status::InternalServerError, err)),
+            Err(err) => Outcome::Error((Status::Unauthorized, err)),
+        }
+    }
+}
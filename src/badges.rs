@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rocket::figment::providers::{Format, Toml};
+use rocket::figment::Figment;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{BadgeLevel, BadgeMetric, DBError, DomainEvent, PostType};
+use crate::outbox::OutboxHandler;
+use crate::persistance::badges_dao::BadgesDao;
+use crate::persistance::votes_dao::{AcceptEvent, VoteEvent};
+
+/// The `[badges]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct BadgesConfig {
+    /// Path of the badge rules file, relative to the working directory.
+    pub rules_file: String,
+}
+
+impl Default for BadgesConfig {
+    fn default() -> Self {
+        Self {
+            rules_file: "badges.toml".to_owned(),
+        }
+    }
+}
+
+/// A badge and when it is earned, as declared in the rules file.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct BadgeRule {
+    /// Stored with earned badges, so it must never change.
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    pub level: BadgeLevel,
+    pub metric: BadgeMetric,
+    pub threshold: i64,
+}
+
+/// The badge rules file: a list of `[[badges]]` tables.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct BadgeRules {
+    pub badges: Vec<BadgeRule>,
+}
+
+impl BadgeRules {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let rules = std::fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read badge rules from {}: {}", path, err))?;
+        Self::parse(&rules).map_err(|err| format!("Invalid badge rules in {}: {}", path, err))
+    }
+
+    pub fn parse(rules: &str) -> Result<Self, String> {
+        let rules: BadgeRules = Figment::from(Toml::string(rules))
+            .extract()
+            .map_err(|err| err.to_string())?;
+
+        let mut names = HashSet::new();
+        for badge in &rules.badges {
+            if badge.name.is_empty() || badge.name.len() > 64 {
+                return Err(format!("Badge names must be 1 to 64 bytes long, got {:?}", badge.name));
+            }
+            if !names.insert(&badge.name) {
+                return Err(format!("Badge {} is declared twice", badge.name));
+            }
+            if badge.threshold < 1 {
+                return Err(format!("The threshold of badge {} must be at least 1", badge.name));
+            }
+        }
+        Ok(rules)
+    }
+}
+
+/// Awards badges as questions, answers, votes and accepted answers are published by the outbox.
+/// Badges are never taken back, and awarding one twice is a no-op, so events can be redelivered.
+pub struct BadgeEngine {
+    rules: Arc<BadgeRules>,
+    badges_dao: Box<dyn BadgesDao + Send + Sync>,
+}
+
+impl BadgeEngine {
+    pub fn new(rules: Arc<BadgeRules>, badges_dao: Box<dyn BadgesDao + Send + Sync>) -> Self {
+        Self { rules, badges_dao }
+    }
+
+    /// The users whose progress the event may have changed.
+    async fn affected_users(&self, event: &DomainEvent) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let users = match event.event_type.as_str() {
+            "question.created" => self
+                .badges_dao
+                .get_author(PostType::Question, event.aggregate_uuid.clone())
+                .await?
+                .into_iter()
+                .collect(),
+            "answer.created" => self
+                .badges_dao
+                .get_author(PostType::Answer, event.aggregate_uuid.clone())
+                .await?
+                .into_iter()
+                .collect(),
+            "question.voted" | "answer.voted" => {
+                let vote: VoteEvent = serde_json::from_value(event.payload.clone())?;
+                std::iter::once(vote.voter_uuid).chain(vote.author_uuid).collect()
+            }
+            "answer.accepted" => {
+                let accept: AcceptEvent = serde_json::from_value(event.payload.clone())?;
+                accept.author_uuid.into_iter().collect()
+            }
+            _ => vec![],
+        };
+        Ok(users)
+    }
+
+    /// Awards the badges the user has earned but doesn't have yet.
+    pub async fn evaluate(&self, user_uuid: &str) -> Result<(), DBError> {
+        let owned: HashSet<String> = match self.badges_dao.get_user_badges(user_uuid.to_owned()).await {
+            Ok(badges) => badges.into_iter().map(|badge| badge.name).collect(),
+            // The user was deleted since
+            Err(DBError::NotFound(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let pending: Vec<&BadgeRule> = self.rules.badges.iter().filter(|badge| !owned.contains(&badge.name)).collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let progress = self.badges_dao.get_progress(user_uuid.to_owned()).await?;
+        for badge in pending {
+            if progress.get(badge.metric) < badge.threshold {
+                continue;
+            }
+            if self.badges_dao.award(user_uuid.to_owned(), &badge.name, &badge.title, badge.level).await? {
+                info!(user_uuid, badge = badge.name, "Awarded badge");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl OutboxHandler for BadgeEngine {
+    fn name(&self) -> &'static str {
+        "badges"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for user_uuid in self.affected_users(event).await? {
+            self.evaluate(&user_uuid).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+    use tokio::sync::Mutex;
+
+    use crate::models::UserBadge;
+    use crate::persistance::badges_dao::BadgeProgress;
+
+    /// Keeps badges in memory; every user has the same progress.
+    struct BadgesDaoMock {
+        progress: BadgeProgress,
+        awarded: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl BadgesDao for BadgesDaoMock {
+        async fn get_author(&self, _: PostType, _: String) -> Result<Option<String>, DBError> {
+            Ok(Some("author".to_owned()))
+        }
+        async fn get_progress(&self, _: String) -> Result<BadgeProgress, DBError> {
+            Ok(self.progress.clone())
+        }
+        async fn award(&self, user_uuid: String, name: &str, _: &str, _: BadgeLevel) -> Result<bool, DBError> {
+            let mut awarded = self.awarded.lock().await;
+            let badge = (user_uuid, name.to_owned());
+            if awarded.contains(&badge) {
+                return Ok(false);
+            }
+            awarded.push(badge);
+            Ok(true)
+        }
+        async fn get_user_badges(&self, user_uuid: String) -> Result<Vec<UserBadge>, DBError> {
+            let awarded = self.awarded.lock().await;
+            Ok(awarded
+                .iter()
+                .filter(|(user, _)| *user == user_uuid)
+                .map(|(_, name)| UserBadge {
+                    name: name.clone(),
+                    title: name.clone(),
+                    level: BadgeLevel::Bronze,
+                    awarded_at: "now".to_owned(),
+                })
+                .collect())
+        }
+        async fn count_awards(&self) -> Result<HashMap<String, i64>, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    fn event(event_type: &str, payload: serde_json::Value) -> DomainEvent {
+        DomainEvent {
+            event_id: 1,
+            aggregate_type: "answer".to_owned(),
+            aggregate_uuid: "123".to_owned(),
+            event_type: event_type.to_owned(),
+            payload,
+            created_at: "now".to_owned(),
+        }
+    }
+
+    #[test]
+    fn shipped_rules_should_be_valid() {
+        let rules = BadgeRules::parse(include_str!("../badges.toml")).unwrap();
+
+        assert!(rules.badges.iter().any(|badge| badge.metric == BadgeMetric::UpvotedAnswers && badge.threshold == 10));
+    }
+
+    #[test]
+    fn rules_should_be_rejected_when_invalid() {
+        let badge = |name: &str, threshold: i64| {
+            format!(
+                "[[badges]]\nname = {:?}\ntitle = \"t\"\ndescription = \"d\"\nlevel = \"gold\"\nmetric = \"answers\"\nthreshold = {}\n",
+                name, threshold
+            )
+        };
+
+        assert!(BadgeRules::parse(&(badge("a", 1) + &badge("b", 2))).is_ok());
+        assert!(BadgeRules::parse(&(badge("a", 1) + &badge("a", 2))).is_err());
+        assert!(BadgeRules::parse(&badge("a", 0)).is_err());
+        assert!(BadgeRules::parse(&badge("a", 1).replace("answers", "comments")).is_err());
+    }
+
+    #[tokio::test]
+    async fn votes_should_award_earned_badges_once() {
+        let rules = BadgeRules::parse(include_str!("../badges.toml")).unwrap();
+        let dao = BadgesDaoMock {
+            progress: BadgeProgress {
+                answers: 12,
+                upvoted_answers: 10,
+                votes_cast: 1,
+                ..BadgeProgress::default()
+            },
+            awarded: Mutex::new(vec![]),
+        };
+        let engine = BadgeEngine::new(Arc::new(rules), Box::new(dao));
+        let vote = serde_json::json!({
+            "target_type": "answer",
+            "target_uuid": "123",
+            "voter_uuid": "voter",
+            "author_uuid": "author",
+            "value": 1,
+        });
+
+        engine.handle(&event("answer.voted", vote.clone())).await.unwrap();
+        engine.handle(&event("answer.voted", vote)).await.unwrap();
+        engine.handle(&event("answer.deleted", serde_json::json!({}))).await.unwrap();
+
+        let dao = &engine.badges_dao;
+        let names = |badges: Vec<UserBadge>| badges.into_iter().map(|badge| badge.name).collect::<Vec<_>>();
+        assert_eq!(
+            names(dao.get_user_badges("author".to_owned()).await.unwrap()),
+            ["first_answer", "first_vote", "upvoted_answers_10"]
+        );
+        assert_eq!(names(dao.get_user_badges("voter".to_owned()).await.unwrap()).len(), 3);
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+
+use crate::jobs::JobHandler;
+use crate::persistance::bounties_dao::{BountiesDao, BountiesDaoImpl};
+
+/// The `[bounties]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct BountiesConfig {
+    pub min_amount: i32,
+    pub max_amount: i32,
+    /// How long a bounty stays open before it expires.
+    pub duration_days: u32,
+    /// The score an answer needs to be awarded half of an expired bounty.
+    pub auto_award_min_score: i64,
+}
+
+impl Default for BountiesConfig {
+    fn default() -> Self {
+        Self {
+            min_amount: 50,
+            max_amount: 500,
+            duration_days: 7,
+            auto_award_min_score: 2,
+        }
+    }
+}
+
+impl BountiesConfig {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(u64::from(self.duration_days) * 24 * 60 * 60)
+    }
+}
+
+/// Closes expired bounties, as the `expire_bounties` job, scheduled in `[jobs.schedules]`.
+pub struct ExpireBountiesJob {
+    min_score: i64,
+    bounties_dao: BountiesDaoImpl,
+}
+
+impl ExpireBountiesJob {
+    pub fn new(db: PgPool, config: &BountiesConfig) -> Self {
+        Self {
+            min_score: config.auto_award_min_score,
+            bounties_dao: BountiesDaoImpl::new(db),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl JobHandler for ExpireBountiesJob {
+    fn kind(&self) -> &'static str {
+        "expire_bounties"
+    }
+
+    async fn run(&self, _payload: &JsonValue) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut closed = 0;
+        loop {
+            let batch = self.bounties_dao.expire_due(self.min_score).await?;
+            if batch == 0 {
+                break;
+            }
+            closed += batch;
+        }
+        if closed > 0 {
+            info!(bounties = closed, "Closed expired bounties");
+        }
+        Ok(())
+    }
+}
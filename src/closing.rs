@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[closing]` table of the Rocket configuration. Voting to close or reopen questions needs
+/// `reputation.close` reputation; moderators' votes are binding.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct ClosingConfig {
+    /// How many users must vote to close a question, or to reopen it.
+    pub votes_needed: i64,
+}
+
+impl Default for ClosingConfig {
+    fn default() -> Self {
+        Self {
+            votes_needed: 3,
+        }
+    }
+}
@@ -1,34 +1,472 @@
-use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::{ContentType, Header, Method, Status};
-use rocket::{Request, Response};
+use std::convert::Infallible;
 
+use regex::Regex;
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::Responder;
+use rocket::{Build, Request, Response, Rocket, State};
+use serde::{Deserialize, Serialize};
+
+/// The `[cors]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Exact origins (`scheme://host[:port]`) allowed to call the API. `"*"` allows any origin, and
+    /// can't be combined with `allow_credentials`.
+    pub allowed_origins: Vec<String>,
+    /// Regular expressions matched against the whole `Origin` header.
+    pub allowed_origin_patterns: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub exposed_headers: Vec<String>,
+    /// How long (in seconds) browsers may cache a preflight response.
+    pub max_age: Option<u32>,
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_owned()],
+            allowed_origin_patterns: vec![],
+            allowed_methods: ["GET", "POST", "PATCH", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: [
+                "Content-Type",
+                "X-Api-Key",
+                "X-Request-Id",
+                "Idempotency-Key",
+                "If-Match",
+                "If-None-Match",
+                "Last-Event-ID",
+                "traceparent",
+                "tracestate",
+            ]
+            .map(String::from)
+            .to_vec(),
+            exposed_headers: [
+                "X-Request-Id",
+                "Idempotent-Replayed",
+                "ETag",
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "RateLimit-Reset",
+                "Retry-After",
+            ]
+            .map(String::from)
+            .to_vec(),
+            max_age: Some(3600),
+            allow_credentials: false,
+        }
+    }
+}
+
+/// A validated `CorsConfig`, managed as Rocket state.
+pub struct CorsPolicy {
+    any_origin: bool,
+    origins: Vec<String>,
+    origin_patterns: Vec<Regex>,
+    methods: Vec<Method>,
+    headers: Vec<String>,
+    exposed_headers: Vec<String>,
+    max_age: Option<u32>,
+    allow_credentials: bool,
+}
+
+impl CorsPolicy {
+    pub fn new(config: CorsConfig) -> Result<Self, String> {
+        let any_origin = config.allowed_origins.iter().any(|origin| origin == "*");
+        if any_origin && config.allow_credentials {
+            // Reflecting every origin with credentials would let any site act as the signed-in user.
+            return Err("CORS allowed_origins \"*\" cannot be combined with allow_credentials".to_owned());
+        }
+        let origin_patterns = config
+            .allowed_origin_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(&format!("^(?:{})$", pattern))
+                    .map_err(|err| format!("Invalid CORS origin pattern {}: {}", pattern, err))
+            })
+            .collect::<Result<_, _>>()?;
+        let methods = config
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                method
+                    .parse::<Method>()
+                    .map_err(|_| format!("Invalid CORS method: {}", method))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            any_origin,
+            origins: config.allowed_origins,
+            origin_patterns,
+            methods,
+            headers: config
+                .allowed_headers
+                .iter()
+                .map(|header| header.to_ascii_lowercase())
+                .collect(),
+            exposed_headers: config.exposed_headers,
+            max_age: config.max_age,
+            allow_credentials: config.allow_credentials,
+        })
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.any_origin
+            || self.origins.iter().any(|allowed| allowed == origin)
+            || self.origin_patterns.iter().any(|pattern| pattern.is_match(origin))
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.methods.iter().any(|allowed| allowed.as_str().eq_ignore_ascii_case(method))
+    }
+
+    fn allows_headers(&self, requested: &str) -> bool {
+        requested
+            .split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .all(|header| self.headers.contains(&header.to_ascii_lowercase()))
+    }
+
+    /// Whether `Access-Control-Allow-Origin` is a literal `*`, which `new` only allows without
+    /// credentials.
+    fn wildcard(&self) -> bool {
+        self.any_origin
+    }
+
+    fn origin_headers(&self, origin: &str, response: &mut Response<'_>) {
+        if self.wildcard() {
+            response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+            return;
+        }
+
+        response.set_header(Header::new("Access-Control-Allow-Origin", origin.to_owned()));
+        if self.allow_credentials {
+            response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+    }
+}
+
+/// Applies the CORS policy from the `[cors]` configuration table to every response and
+/// answers preflight requests through a catch-all `OPTIONS` route.
+#[allow(clippy::upper_case_acronyms)]
 pub struct CORS;
 
 #[rocket::async_trait]
 impl Fairing for CORS {
     fn info(&self) -> Info {
         Info {
-            name: "Add CORS headers to responses",
-            kind: Kind::Response,
+            name: "CORS policy",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = if rocket.figment().contains("cors") {
+            match rocket.figment().extract_inner::<CorsConfig>("cors") {
+                Ok(config) => config,
+                Err(err) => {
+                    error!("Invalid CORS configuration: {}", err);
+                    return Err(rocket);
+                }
+            }
+        } else {
+            CorsConfig::default()
+        };
+
+        match CorsPolicy::new(config) {
+            Ok(policy) => Ok(rocket.manage(policy).mount("/", routes![preflight])),
+            Err(err) => {
+                error!("{}", err);
+                Err(rocket)
+            }
         }
     }
 
-    // source: https://stackoverflow.com/questions/62412361/how-to-set-up-cors-or-options-for-rocket-rs
     async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
-        response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, DELETE, OPTIONS",
-        ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
-
-        // source: https://webprogramming.ninja/2022/08/25/handling-options-requests-in-rust-using-rocket-with-cors/
-        if request.method() == Method::Options {
-            let body = "";
-            response.set_header(ContentType::Plain);
-            response.set_sized_body(body.len(), std::io::Cursor::new(body));
-            response.set_status(Status::Ok);
-        }
-    }
-}
\ No newline at end of file
+        let Some(policy) = request.rocket().state::<CorsPolicy>() else {
+            return;
+        };
+
+        // Caches must not serve a response negotiated for one origin to another.
+        if !policy.wildcard() {
+            response.adjoin_header(Header::new("Vary", "Origin"));
+        }
+
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+        if !policy.allows_origin(origin) {
+            return;
+        }
+
+        policy.origin_headers(origin, response);
+        if !policy.exposed_headers.is_empty() {
+            response.set_header(Header::new(
+                "Access-Control-Expose-Headers",
+                policy.exposed_headers.join(", "),
+            ));
+        }
+    }
+}
+
+pub struct Preflight {
+    status: Status,
+    headers: Vec<Header<'static>>,
+}
+
+impl<'r> Responder<'r, 'static> for Preflight {
+    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let mut response = Response::build();
+        response.status(self.status);
+        for header in self.headers {
+            response.header(header);
+        }
+        response.ok()
+    }
+}
+
+/// The CORS headers of an `OPTIONS` request.
+pub struct PreflightRequest<'r> {
+    origin: Option<&'r str>,
+    method: Option<&'r str>,
+    headers: &'r str,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PreflightRequest<'r> {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        Outcome::Success(PreflightRequest {
+            origin: headers.get_one("Origin"),
+            method: headers.get_one("Access-Control-Request-Method"),
+            headers: headers.get_one("Access-Control-Request-Headers").unwrap_or(""),
+        })
+    }
+}
+
+#[options("/<_..>")]
+pub fn preflight(request: PreflightRequest<'_>, policy: &State<CorsPolicy>) -> Preflight {
+    let allowed = match (request.origin, request.method) {
+        (Some(origin), Some(method)) => {
+            policy.allows_origin(origin)
+                && policy.allows_method(method)
+                && policy.allows_headers(request.headers)
+        }
+        _ => false,
+    };
+
+    if !allowed {
+        return Preflight {
+            status: Status::Forbidden,
+            headers: vec![],
+        };
+    }
+
+    let methods = policy.methods.iter().map(|method| method.as_str()).collect::<Vec<_>>();
+    let mut preflight_headers = vec![
+        Header::new("Access-Control-Allow-Methods", methods.join(", ")),
+        Header::new("Access-Control-Allow-Headers", policy.headers.join(", ")),
+    ];
+    if let Some(max_age) = policy.max_age {
+        preflight_headers.push(Header::new("Access-Control-Max-Age", max_age.to_string()));
+    }
+
+    Preflight {
+        status: Status::NoContent,
+        headers: preflight_headers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::figment::providers::Serialized;
+    use rocket::local::asynchronous::Client;
+
+    #[get("/resource")]
+    fn resource() -> &'static str {
+        "ok"
+    }
+
+    async fn client(config: CorsConfig) -> Client {
+        let figment = rocket::Config::figment().merge(Serialized::default("cors", config));
+        let rocket = rocket::custom(figment)
+            .mount("/", routes![resource])
+            .attach(CORS);
+        Client::tracked(rocket).await.expect("valid rocket instance")
+    }
+
+    fn restricted_config() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: vec!["https://app.example.com".to_owned()],
+            allowed_origin_patterns: vec![r"https://[a-z]+\.preview\.example\.com".to_owned()],
+            allow_credentials: true,
+            ..CorsConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reflect_allowed_origin() {
+        let client = client(restricted_config()).await;
+
+        let response = client
+            .get("/resource")
+            .header(Header::new("Origin", "https://app.example.com"))
+            .dispatch()
+            .await;
+
+        let headers = response.headers();
+        assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some("https://app.example.com"));
+        assert_eq!(headers.get_one("Access-Control-Allow-Credentials"), Some("true"));
+        assert_eq!(
+            headers.get_one("Access-Control-Expose-Headers"),
+            Some("X-Request-Id, Idempotent-Replayed, ETag, RateLimit-Limit, RateLimit-Remaining, RateLimit-Reset, Retry-After")
+        );
+        assert_eq!(headers.get_one("Vary"), Some("Origin"));
+    }
+
+    #[tokio::test]
+    async fn should_allow_origin_matching_pattern() {
+        let client = client(restricted_config()).await;
+
+        let response = client
+            .get("/resource")
+            .header(Header::new("Origin", "https://feature.preview.example.com"))
+            .dispatch()
+            .await;
+
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://feature.preview.example.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_allow_denied_origin() {
+        let client = client(restricted_config()).await;
+
+        let response = client
+            .get("/resource")
+            .header(Header::new("Origin", "https://evil.example.org"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), None);
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Credentials"), None);
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+    }
+
+    #[tokio::test]
+    async fn should_use_wildcard_without_credentials() {
+        let client = client(CorsConfig::default()).await;
+
+        let response = client
+            .get("/resource")
+            .header(Header::new("Origin", "https://anywhere.example.org"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Credentials"), None);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_any_origin_with_credentials() {
+        let config = CorsConfig {
+            allow_credentials: true,
+            ..CorsConfig::default()
+        };
+        assert!(CorsPolicy::new(config.clone()).is_err());
+
+        let figment = rocket::Config::figment().merge(Serialized::default("cors", config));
+        let rocket = rocket::custom(figment).mount("/", routes![resource]).attach(CORS);
+        match Client::tracked(rocket).await {
+            Err(error) => assert!(matches!(error.kind(), rocket::error::ErrorKind::FailedFairings(_))),
+            Ok(_) => panic!("ignite should fail"),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_answer_allowed_preflight() {
+        let client = client(restricted_config()).await;
+
+        let response = client
+            .options("/resource")
+            .header(Header::new("Origin", "https://app.example.com"))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .header(Header::new("Access-Control-Request-Headers", "content-type, x-request-id"))
+            .dispatch()
+            .await;
+
+        let headers = response.headers();
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some("https://app.example.com"));
+        assert_eq!(headers.get_one("Access-Control-Allow-Methods"), Some("GET, POST, PATCH, DELETE"));
+        assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("3600"));
+    }
+
+    #[tokio::test]
+    async fn should_allow_api_key_in_default_preflight() {
+        let client = client(CorsConfig::default()).await;
+
+        let response = client
+            .options("/resource")
+            .header(Header::new("Origin", "https://anywhere.example.org"))
+            .header(Header::new("Access-Control-Request-Method", "DELETE"))
+            .header(Header::new("Access-Control-Request-Headers", "x-api-key, if-match"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::NoContent);
+        assert!(response
+            .headers()
+            .get_one("Access-Control-Allow-Headers")
+            .is_some_and(|headers| headers.split(", ").any(|header| header == "x-api-key")));
+    }
+
+    #[tokio::test]
+    async fn should_reject_preflight_from_denied_origin() {
+        let client = client(restricted_config()).await;
+
+        let response = client
+            .options("/resource")
+            .header(Header::new("Origin", "https://evil.example.org"))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Forbidden);
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), None);
+    }
+
+    #[tokio::test]
+    async fn should_reject_preflight_for_disallowed_method_or_header() {
+        let client = client(restricted_config()).await;
+
+        let response = client
+            .options("/resource")
+            .header(Header::new("Origin", "https://app.example.com"))
+            .header(Header::new("Access-Control-Request-Method", "PUT"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .options("/resource")
+            .header(Header::new("Origin", "https://app.example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .header(Header::new("Access-Control-Request-Headers", "x-secret"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+}
@@ -0,0 +1,133 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::{Build, Orbit, Rocket};
+use serde::{Deserialize, Serialize};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// The `[drain]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct DrainConfig {
+    /// How long readiness fails after `SIGTERM` before Rocket stops accepting connections, so that
+    /// load balancers stop sending requests here first. In-flight requests then get Rocket's
+    /// `shutdown.grace` period to finish.
+    pub delay_seconds: u64,
+}
+
+impl Default for DrainConfig {
+    fn default() -> Self {
+        Self { delay_seconds: 5 }
+    }
+}
+
+/// Whether the instance should get new requests; managed by `DrainFairing`.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    draining: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Spawns a background task that is stopped, at its next `.await`, once Rocket starts shutting
+/// down, so that it no longer uses the database by the time the pool closes.
+pub fn spawn_until_shutdown<F>(rocket: &Rocket<Orbit>, name: &'static str, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let shutdown = rocket.shutdown();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = shutdown => info!("Stopped {}", name),
+            _ = task => {}
+        }
+    });
+}
+
+/// Turns `SIGTERM` into a graceful shutdown: readiness fails first, then Rocket stops accepting
+/// connections and drains the in-flight requests.
+#[derive(Default)]
+pub struct DrainFairing {
+    readiness: Readiness,
+}
+
+#[rocket::async_trait]
+impl Fairing for DrainFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Connection draining",
+            kind: Kind::Ignite | Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        Ok(rocket.manage(self.readiness.clone()))
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let config = rocket
+            .figment()
+            .extract_inner::<DrainConfig>("drain")
+            .unwrap_or_default();
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(err) => {
+                error!("Error listening for SIGTERM: {:?}", err);
+                return;
+            }
+        };
+
+        let readiness = self.readiness.clone();
+        let shutdown = rocket.shutdown();
+        tokio::spawn(async move {
+            terminate.recv().await;
+            readiness.start_draining();
+            warn!("Received SIGTERM. Draining for {}s before shutting down.", config.delay_seconds);
+            tokio::time::sleep(Duration::from_secs(config.delay_seconds)).await;
+            shutdown.notify();
+        });
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        // Also covers shutdowns that didn't start with SIGTERM
+        self.readiness.start_draining();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+
+    #[tokio::test]
+    async fn readiness_should_fail_while_draining() {
+        let drain = DrainFairing::default();
+        let readiness = drain.readiness.clone();
+        let rocket = rocket::build()
+            .mount("/", routes![crate::handlers::liveness, crate::handlers::readiness])
+            .attach(drain);
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+
+        assert_eq!(client.get("/health/ready").dispatch().await.status(), Status::Ok);
+
+        readiness.start_draining();
+
+        let response = client.get("/health/ready").dispatch().await;
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert_eq!(response.into_string().await.unwrap(), r#"{"status":"draining"}"#);
+        assert_eq!(client.get("/health/live").dispatch().await.status(), Status::Ok);
+    }
+}
@@ -0,0 +1,238 @@
+use std::convert::Infallible;
+
+use rocket::http::{Header, Method, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{Request, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::models::{AnswerDetail, IfMatch, QuestionDetail};
+
+/// A row whose version changes whenever it does.
+pub trait Versioned {
+    fn id(&self) -> &str;
+    fn version(&self) -> i64;
+}
+
+impl Versioned for QuestionDetail {
+    fn id(&self) -> &str {
+        &self.question_uuid
+    }
+
+    fn version(&self) -> i64 {
+        self.version
+    }
+}
+
+impl Versioned for AnswerDetail {
+    fn id(&self) -> &str {
+        &self.answer_uuid
+    }
+
+    fn version(&self) -> i64 {
+        self.version
+    }
+}
+
+/// Strong ETag of a list of rows: it changes whenever a row is added, removed or changed.
+pub fn list_etag<T: Versioned>(rows: &[T]) -> String {
+    let mut hasher = Sha256::new();
+    for row in rows {
+        hasher.update(format!("{}:{}\n", row.id(), row.version()).as_bytes());
+    }
+    format!("\"{:.32x}\"", hasher.finalize())
+}
+
+/// ETag of a single row: its quoted version, which `If-Match` takes back.
+pub fn row_etag<T: Versioned>(row: &T) -> String {
+    format!("\"{}\"", row.version())
+}
+
+/// The opaque tags of an `If-Match`/`If-None-Match` header, or `None` for `*`.
+fn entity_tags(value: &str) -> Option<Vec<&str>> {
+    if value.trim() == "*" {
+        return None;
+    }
+    Some(value.split(',').map(str::trim).filter(|tag| !tag.is_empty()).collect())
+}
+
+/// Parses `If-Match`. Row ETags are their quoted version; weak tags never match.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(value) = request.headers().get_one("If-Match") else {
+            return Outcome::Success(IfMatch::Any);
+        };
+
+        let if_match = match entity_tags(value) {
+            None => IfMatch::Exists,
+            Some(tags) => IfMatch::Versions(
+                tags.iter()
+                    .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+                    .collect(),
+            ),
+        };
+        Outcome::Success(if_match)
+    }
+}
+
+/// A JSON response with an `ETag`, answered with `304 Not Modified` when a `GET` matches `If-None-Match`.
+pub struct Tagged<T> {
+    value: T,
+    etag: String,
+}
+
+impl<T: Versioned> Tagged<Vec<T>> {
+    pub fn list(rows: Vec<T>) -> Self {
+        Self {
+            etag: list_etag(&rows),
+            value: rows,
+        }
+    }
+}
+
+impl<T: Versioned> Tagged<T> {
+    pub fn row(row: T) -> Self {
+        Self {
+            etag: row_etag(&row),
+            value: row,
+        }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Tagged<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        // If-None-Match uses the weak comparison
+        let is_read = matches!(request.method(), Method::Get | Method::Head);
+        let not_modified = is_read && request.headers().get_one("If-None-Match").is_some_and(|value| {
+            entity_tags(value).is_none_or(|tags| {
+                tags.iter().any(|tag| tag.trim_start_matches("W/") == self.etag)
+            })
+        });
+
+        if not_modified {
+            return Response::build()
+                .status(Status::NotModified)
+                .header(Header::new("ETag", self.etag))
+                .ok();
+        }
+
+        Response::build_from(Json(self.value).respond_to(request)?)
+            .header(Header::new("ETag", self.etag))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::local::asynchronous::Client;
+
+    use crate::models::RenderedBody;
+
+    fn question(version: i64) -> QuestionDetail {
+        QuestionDetail {
+            question_uuid: "123".to_owned(),
+            title: "test title".to_owned(),
+            description: "test description".to_owned(),
+            body: RenderedBody::new("test description"),
+            created_at: "now".to_owned(),
+            deleted_at: None,
+            hidden_at: None,
+            accepted_answer_uuid: None,
+            bounty: None,
+            closed_reason: None,
+            duplicate_of: None,
+            version,
+        }
+    }
+
+    #[get("/questions?<version>")]
+    fn questions(version: i64) -> Tagged<Vec<QuestionDetail>> {
+        Tagged::list(vec![question(version)])
+    }
+
+    #[post("/question/edit")]
+    fn edited() -> Tagged<QuestionDetail> {
+        Tagged::row(question(3))
+    }
+
+    #[get("/if_match")]
+    fn if_match(if_match: IfMatch) -> String {
+        format!("{:?}", if_match)
+    }
+
+    async fn client() -> Client {
+        Client::tracked(rocket::build().mount("/", routes![questions, edited, if_match]))
+            .await
+            .expect("valid rocket instance")
+    }
+
+    #[test]
+    fn list_etag_should_change_with_versions() {
+        assert_eq!(list_etag(&[question(1)]), list_etag(&[question(1)]));
+        assert_ne!(list_etag(&[question(1)]), list_etag(&[question(2)]));
+        assert_ne!(list_etag(&[question(1)]), list_etag::<QuestionDetail>(&[]));
+    }
+
+    #[rocket::async_test]
+    async fn should_answer_not_modified_for_a_matching_etag() {
+        let client = client().await;
+
+        let response = client.get("/questions?version=1").dispatch().await;
+        let etag = response.headers().get_one("ETag").unwrap().to_owned();
+
+        let unchanged = client
+            .get("/questions?version=1")
+            .header(Header::new("If-None-Match", format!("W/{}", etag)))
+            .dispatch()
+            .await;
+        let changed = client
+            .get("/questions?version=2")
+            .header(Header::new("If-None-Match", etag.clone()))
+            .dispatch()
+            .await;
+
+        assert_eq!(unchanged.status(), Status::NotModified);
+        assert_eq!(unchanged.headers().get_one("ETag"), Some(etag.as_str()));
+        assert!(unchanged.into_string().await.is_none());
+        assert_eq!(changed.status(), Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn should_tag_a_row_with_its_version() {
+        let client = client().await;
+
+        let response = client
+            .post("/question/edit")
+            .header(Header::new("If-None-Match", r#""3""#))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some(r#""3""#));
+    }
+
+    #[rocket::async_test]
+    async fn should_parse_if_match() {
+        let client = client().await;
+
+        for (header, expected) in [
+            (None, IfMatch::Any),
+            (Some("*"), IfMatch::Exists),
+            (Some(r#""3", W/"4", "x""#), IfMatch::Versions(vec![3])),
+        ] {
+            let mut request = client.get("/if_match");
+            if let Some(header) = header {
+                request = request.header(Header::new("If-Match", header));
+            }
+            let body = request.dispatch().await.into_string().await;
+            assert_eq!(body, Some(format!("{:?}", expected)));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Build, Orbit, Request, Rocket};
+use sqlx::postgres::PgListener;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+
+use crate::drain::spawn_until_shutdown;
+use crate::persistance::question_events_dao::QUESTION_EVENTS_CHANNEL;
+
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+/// Watchers re-read their question's events this often even without a notification, in case one
+/// was lost while the listener was reconnecting.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Relays the `question_events` notifications of Postgres to every open event stream, so each
+/// instance holds a single listening connection however many clients are watching.
+pub struct QuestionEventsHub {
+    notifications: Sender<Uuid>,
+}
+
+impl QuestionEventsHub {
+    /// Starts watching a question for new events. Subscribe before reading the events recorded so
+    /// far, so that nothing committed in between is missed.
+    pub fn watch(&self, question_uuid: &str) -> QuestionWatch {
+        QuestionWatch {
+            notifications: self.notifications.subscribe(),
+            // A malformed UUID can't have any events
+            question_uuid: Some(Uuid::parse_str(question_uuid).unwrap_or_default()),
+        }
+    }
+
+    /// Starts watching every question for new events.
+    pub fn watch_all(&self) -> QuestionWatch {
+        QuestionWatch {
+            notifications: self.notifications.subscribe(),
+            question_uuid: None,
+        }
+    }
+}
+
+pub struct QuestionWatch {
+    notifications: Receiver<Uuid>,
+    /// `None` when watching every question.
+    question_uuid: Option<Uuid>,
+}
+
+impl QuestionWatch {
+    /// Waits until the question may have new events.
+    pub async fn changed(&mut self) {
+        let notified = async {
+            loop {
+                match self.notifications.recv().await {
+                    Ok(question_uuid) if self.question_uuid.is_none_or(|watched| watched == question_uuid) => return,
+                    Ok(_) => {}
+                    // Some notifications were dropped, ours may have been one of them
+                    Err(RecvError::Lagged(_)) => return,
+                    Err(RecvError::Closed) => std::future::pending().await,
+                }
+            }
+        };
+        let _ = tokio::time::timeout(POLL_INTERVAL, notified).await;
+    }
+}
+
+/// Listens for new question events and relays them through the managed `QuestionEventsHub`.
+pub struct QuestionEventsFairing {
+    db: PgPool,
+    notifications: Sender<Uuid>,
+}
+
+impl QuestionEventsFairing {
+    pub fn new(db: PgPool) -> Self {
+        let (notifications, _) = broadcast::channel(1024);
+        Self { db, notifications }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for QuestionEventsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Question events",
+            kind: Kind::Ignite | Kind::Liftoff,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        Ok(rocket.manage(QuestionEventsHub {
+            notifications: self.notifications.clone(),
+        }))
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let mut listener = match PgListener::connect_with(&self.db).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Error connecting the question events listener: {:?}", err);
+                return;
+            }
+        };
+        if let Err(err) = listener.listen(QUESTION_EVENTS_CHANNEL).await {
+            error!("Error listening for question events: {:?}", err);
+            return;
+        }
+
+        let notifications = self.notifications.clone();
+        spawn_until_shutdown(rocket, "question events listener", async move {
+            loop {
+                // The listener reconnects by itself on the next `recv` after losing its connection
+                match listener.recv().await {
+                    Ok(notification) => match Uuid::parse_str(notification.payload()) {
+                        // Nobody may be watching, which is fine
+                        Ok(question_uuid) => {
+                            let _ = notifications.send(question_uuid);
+                        }
+                        Err(err) => error!("Invalid question event notification: {:?}", err),
+                    },
+                    Err(err) => {
+                        error!("Error receiving question events: {:?}", err);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// The `Last-Event-ID` an `EventSource` sends when it reconnects, if it is a valid event ID.
+pub struct LastEventId(pub Option<i64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let last_event_id = request
+            .headers()
+            .get_one(LAST_EVENT_ID_HEADER)
+            .and_then(|value| value.trim().parse().ok());
+        Outcome::Success(LastEventId(last_event_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::http::{Header, Status};
+    use rocket::local::asynchronous::{Client, LocalResponse};
+    use tokio::io::AsyncReadExt;
+
+    use crate::handlers::question_events;
+    use crate::models::{Answer, AuditContext, Question};
+    use crate::persistance::answers_dao::{AnswersDao, AnswersDaoImpl};
+    use crate::persistance::question_events_dao::{QuestionEventsDao, QuestionEventsDaoImpl};
+    use crate::persistance::questions_dao::{QuestionsDao, QuestionsDaoImpl};
+
+    async fn client(pool: PgPool) -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![question_events])
+            .attach(QuestionEventsFairing::new(pool.clone()))
+            .manage(Box::new(QuestionEventsDaoImpl::new(pool)) as Box<dyn QuestionEventsDao + Send + Sync>);
+        Client::tracked(rocket).await.expect("valid rocket instance")
+    }
+
+    async fn answer(pool: &PgPool, question_uuid: &str, content: &str) -> String {
+        AnswersDaoImpl::new(pool.clone())
+            .create_answer(
+                Answer {
+                    question_uuid: question_uuid.to_owned(),
+                    content: content.to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .expect("Error creating answer")
+            .answer_uuid
+    }
+
+    /// Reads the event stream until it contains `expected`, or fails after a few seconds.
+    async fn read_until(response: &mut LocalResponse<'_>, received: &mut String, expected: &str) {
+        let read = async {
+            let mut buffer = [0; 1024];
+            while !received.contains(expected) {
+                let n = response.read(&mut buffer).await.expect("readable stream");
+                assert_ne!(n, 0, "stream ended before {:?}", expected);
+                received.push_str(std::str::from_utf8(&buffer[..n]).unwrap());
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), read)
+            .await
+            .unwrap_or_else(|_| panic!("no {:?} in {:?}", expected, received));
+    }
+
+    #[sqlx::test]
+    async fn should_replay_missed_events_and_push_new_ones(pool: PgPool) {
+        let question = QuestionsDaoImpl::new(pool.clone())
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .expect("Error creating question");
+        let missed = answer(&pool, &question.question_uuid, "missed").await;
+        let client = client(pool.clone()).await;
+
+        let mut response = client
+            .get(format!("/questions/{}/events", question.question_uuid))
+            .header(Header::new(LAST_EVENT_ID_HEADER, "0"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let mut received = String::new();
+        read_until(&mut response, &mut received, &missed).await;
+        assert!(received.contains("event:answer.created"));
+
+        let pushed = answer(&pool, &question.question_uuid, "pushed").await;
+        read_until(&mut response, &mut received, &pushed).await;
+    }
+
+    #[sqlx::test]
+    async fn should_not_stream_missing_questions(pool: PgPool) {
+        let client = client(pool).await;
+
+        let missing = client
+            .get("/questions/b068cd2f-edac-479e-a6ea-b4ad8bd6b2ac/events")
+            .dispatch()
+            .await;
+        let malformed = client.get("/questions/not-a-uuid/events").dispatch().await;
+
+        assert_eq!(missing.status(), Status::NotFound);
+        assert_eq!(malformed.status(), Status::BadRequest);
+    }
+}
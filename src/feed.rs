@@ -0,0 +1,424 @@
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::futures::{Sink, SinkExt, Stream, StreamExt};
+use rocket::{Build, Orbit, Rocket};
+use rocket_ws::frame::{CloseCode, CloseFrame};
+use rocket_ws::result::Error;
+use rocket_ws::Message;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+use tokio::time::Instant;
+
+use crate::drain::spawn_until_shutdown;
+use crate::events::QuestionEventsHub;
+use crate::models::QuestionEvent;
+use crate::persistance::question_events_dao::{QuestionEventsDao, QuestionEventsDaoImpl};
+
+/// How many events a connection may fall behind before it is dropped.
+const BUFFERED_EVENTS: usize = 256;
+const PAGE_SIZE: i64 = 100;
+const MAX_TOPICS: usize = 100;
+/// Connections are pinged this often, and dropped when they haven't sent anything for three periods.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a single frame may take to send before the connection counts as too slow.
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a live feed connection can subscribe to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Topic {
+    /// `questions`: every question and answer on the site.
+    Questions,
+    /// `question:<uuid>`: one question and its answers.
+    Question(Uuid),
+}
+
+impl Topic {
+    pub fn parse(topic: &str) -> Result<Self, String> {
+        if topic == "questions" {
+            return Ok(Topic::Questions);
+        }
+        if let Some(question_uuid) = topic.strip_prefix("question:") {
+            return Uuid::parse_str(question_uuid)
+                .map(Topic::Question)
+                .map_err(|_| format!("Invalid question ID in topic {}", topic));
+        }
+        if topic.starts_with("tag:") {
+            return Err("Tag topics are not available yet: questions have no tags".to_owned());
+        }
+        Err(format!("Unknown topic {}", topic))
+    }
+
+    pub fn matches(&self, event: &QuestionEvent) -> bool {
+        match self {
+            Topic::Questions => true,
+            Topic::Question(question_uuid) => question_uuid.to_string() == event.question_uuid,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Topic::Questions => "questions".to_owned(),
+            Topic::Question(question_uuid) => format!("question:{}", question_uuid),
+        }
+    }
+}
+
+/// A frame sent by the client.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientFrame {
+    Subscribe { topic: String },
+    Unsubscribe { topic: String },
+    Ping,
+}
+
+/// A frame sent to the client.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerFrame<'a> {
+    Subscribed { topic: &'a str },
+    Unsubscribed { topic: &'a str },
+    Pong,
+    Error { message: String },
+    /// `data` is the `QuestionDetail` or `AnswerDetail` the event is about.
+    Event {
+        topics: Vec<String>,
+        event: &'a str,
+        event_id: i64,
+        data: &'a serde_json::Value,
+    },
+}
+
+impl From<ServerFrame<'_>> for Message {
+    fn from(frame: ServerFrame<'_>) -> Self {
+        Message::Text(serde_json::to_string(&frame).expect("frames serialize to JSON"))
+    }
+}
+
+/// Fans out the events of every question to the live feed connections of this instance.
+pub struct LiveFeed {
+    events: Sender<Arc<QuestionEvent>>,
+}
+
+impl LiveFeed {
+    pub fn subscribe(&self) -> Receiver<Arc<QuestionEvent>> {
+        self.events.subscribe()
+    }
+}
+
+/// Manages the `LiveFeed` and, once launched, feeds it every new question event. Relies on the
+/// `QuestionEventsFairing` to be woken up when there are some.
+pub struct LiveFeedFairing {
+    db: PgPool,
+    events: Sender<Arc<QuestionEvent>>,
+}
+
+impl LiveFeedFairing {
+    pub fn new(db: PgPool) -> Self {
+        let (events, _) = broadcast::channel(BUFFERED_EVENTS);
+        Self { db, events }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for LiveFeedFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Live feed",
+            kind: Kind::Ignite | Kind::Liftoff,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        Ok(rocket.manage(LiveFeed {
+            events: self.events.clone(),
+        }))
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(hub) = rocket.state::<QuestionEventsHub>() else {
+            error!("QuestionEventsFairing is not attached, the live feed won't receive any events");
+            return;
+        };
+        let mut watch = hub.watch_all();
+        let dao = QuestionEventsDaoImpl::new(self.db.clone());
+        let events = self.events.clone();
+
+        spawn_until_shutdown(rocket, "live feed", async move {
+            // Events recorded before launch are not replayed to the feed
+            let mut cursor = None;
+            loop {
+                let after = match cursor {
+                    Some(after) => after,
+                    None => match dao.max_event_id().await {
+                        Ok(max) => *cursor.insert(max),
+                        Err(err) => {
+                            error!("Error starting the live feed: {:?}", err);
+                            watch.changed().await;
+                            continue;
+                        }
+                    },
+                };
+
+                match dao.get_all_events(after, PAGE_SIZE).await {
+                    Ok(page) => {
+                        let caught_up = (page.len() as i64) < PAGE_SIZE;
+                        for event in page {
+                            cursor = Some(event.event_id);
+                            // Nobody may be connected, which is fine
+                            let _ = events.send(Arc::new(event));
+                        }
+                        if caught_up {
+                            watch.changed().await;
+                        }
+                    }
+                    Err(err) => {
+                        error!("Error reading events for the live feed: {:?}", err);
+                        watch.changed().await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Runs a live feed connection until the client leaves, stops answering pings, falls more than
+/// `BUFFERED_EVENTS` behind, or `shutdown` completes: slow clients are disconnected rather than ever
+/// holding up writers.
+pub async fn serve<S>(
+    stream: S,
+    mut events: Receiver<Arc<QuestionEvent>>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Error>
+where
+    S: Stream<Item = Result<Message, Error>> + Sink<Message, Error = Error> + Unpin,
+{
+    tokio::pin!(shutdown);
+    let (mut sink, mut source) = stream.split();
+    let mut topics = BTreeSet::new();
+    let mut heartbeat = tokio::time::interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    loop {
+        tokio::select! {
+            message = source.next() => {
+                let Some(message) = message.transpose()? else {
+                    return Ok(());
+                };
+                last_seen = Instant::now();
+                match message {
+                    Message::Text(text) => send(&mut sink, handle_frame(&text, &mut topics)).await?,
+                    Message::Close(_) => return Ok(()),
+                    // Pings are answered by the WebSocket layer itself
+                    _ => {}
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let matching: Vec<String> = topics
+                        .iter()
+                        .filter(|topic| topic.matches(&event))
+                        .map(Topic::name)
+                        .collect();
+                    if !matching.is_empty() {
+                        let frame = ServerFrame::Event {
+                            topics: matching,
+                            event: &event.event_type,
+                            event_id: event.event_id,
+                            data: &event.payload,
+                        };
+                        send(&mut sink, frame.into()).await?;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => return close(&mut sink, CloseCode::Policy, "Too slow").await,
+                Err(RecvError::Closed) => return close(&mut sink, CloseCode::Away, "Shutting down").await,
+            },
+            _ = &mut shutdown => return close(&mut sink, CloseCode::Away, "Shutting down").await,
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > 3 * HEARTBEAT_INTERVAL {
+                    return close(&mut sink, CloseCode::Policy, "Heartbeat timeout").await;
+                }
+                send(&mut sink, Message::Ping(Vec::new())).await?;
+            }
+        }
+    }
+}
+
+/// Applies a client frame to the connection's subscriptions and returns the reply.
+fn handle_frame(text: &str, topics: &mut BTreeSet<Topic>) -> Message {
+    let frame = match serde_json::from_str::<ClientFrame>(text) {
+        Ok(frame) => frame,
+        Err(err) => return ServerFrame::Error { message: format!("Invalid frame: {}", err) }.into(),
+    };
+
+    let (ClientFrame::Subscribe { topic: name } | ClientFrame::Unsubscribe { topic: name }) = &frame else {
+        return ServerFrame::Pong.into();
+    };
+    let topic = match Topic::parse(name) {
+        Ok(topic) => topic,
+        Err(message) => return ServerFrame::Error { message }.into(),
+    };
+
+    if let ClientFrame::Unsubscribe { .. } = frame {
+        topics.remove(&topic);
+        return ServerFrame::Unsubscribed { topic: name }.into();
+    }
+    if topics.len() >= MAX_TOPICS && !topics.contains(&topic) {
+        return ServerFrame::Error {
+            message: format!("At most {} topics per connection", MAX_TOPICS),
+        }
+        .into();
+    }
+    topics.insert(topic);
+    ServerFrame::Subscribed { topic: name }.into()
+}
+
+async fn send<S>(sink: &mut S, message: Message) -> Result<(), Error>
+where
+    S: Sink<Message, Error = Error> + Unpin,
+{
+    tokio::time::timeout(SEND_TIMEOUT, sink.send(message))
+        .await
+        .map_err(|_| Error::Io(std::io::ErrorKind::TimedOut.into()))?
+}
+
+async fn close<S>(sink: &mut S, code: CloseCode, reason: &str) -> Result<(), Error>
+where
+    S: Sink<Message, Error = Error> + Unpin,
+{
+    let frame = CloseFrame {
+        code,
+        reason: reason.to_owned().into(),
+    };
+    send(sink, Message::Close(Some(frame))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::{duplex, DuplexStream};
+    use tokio_tungstenite::tungstenite::protocol::Role;
+    use tokio_tungstenite::WebSocketStream;
+
+    const QUESTION: &str = "b068cd2f-edac-479e-a6ea-b4ad8bd6b2ac";
+
+    fn event(event_id: i64, question_uuid: &str) -> Arc<QuestionEvent> {
+        Arc::new(QuestionEvent {
+            event_id,
+            question_uuid: question_uuid.to_owned(),
+            event_type: "answer.created".to_owned(),
+            payload: serde_json::json!({ "question_uuid": question_uuid }),
+        })
+    }
+
+    /// A client connected to `serve`, reading events from the returned sender.
+    async fn connect(buffered: usize) -> (WebSocketStream<DuplexStream>, Sender<Arc<QuestionEvent>>) {
+        let (client, server) = duplex(64 * 1024);
+        let (events, receiver) = broadcast::channel(buffered);
+        let server = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+        tokio::spawn(serve(server, receiver, std::future::pending()));
+        (WebSocketStream::from_raw_socket(client, Role::Client, None).await, events)
+    }
+
+    async fn request(client: &mut WebSocketStream<DuplexStream>, frame: serde_json::Value) -> serde_json::Value {
+        client.send(Message::Text(frame.to_string())).await.unwrap();
+        receive(client).await
+    }
+
+    async fn receive(client: &mut WebSocketStream<DuplexStream>) -> serde_json::Value {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("a frame in time")
+            .expect("an open connection")
+            .unwrap();
+        match message {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_parse_topics() {
+        let question = Uuid::parse_str(QUESTION).unwrap();
+
+        assert_eq!(Topic::parse("questions"), Ok(Topic::Questions));
+        assert_eq!(Topic::parse(&format!("question:{}", QUESTION)), Ok(Topic::Question(question)));
+        assert!(Topic::parse("question:123").is_err());
+        assert!(Topic::parse("tag:rust").is_err());
+        assert!(Topic::parse("answers").is_err());
+    }
+
+    #[tokio::test]
+    async fn should_send_events_of_subscribed_topics() {
+        let (mut client, events) = connect(16).await;
+
+        let subscribed = request(&mut client, serde_json::json!({"type": "subscribe", "topic": format!("question:{}", QUESTION)})).await;
+        let rejected = request(&mut client, serde_json::json!({"type": "subscribe", "topic": "tag:rust"})).await;
+        let pong = request(&mut client, serde_json::json!({"type": "ping"})).await;
+
+        events.send(event(1, "0b5c0f1e-70a2-4c8c-a9a6-6ff2e0b2f0c4")).unwrap();
+        events.send(event(2, QUESTION)).unwrap();
+        let received = receive(&mut client).await;
+
+        assert_eq!(subscribed, serde_json::json!({"type": "subscribed", "topic": format!("question:{}", QUESTION)}));
+        assert_eq!(rejected["type"], "error");
+        assert_eq!(pong, serde_json::json!({"type": "pong"}));
+        assert_eq!(
+            received,
+            serde_json::json!({
+                "type": "event",
+                "topics": [format!("question:{}", QUESTION)],
+                "event": "answer.created",
+                "event_id": 2,
+                "data": { "question_uuid": QUESTION },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn should_drop_slow_consumers() {
+        let (mut client, events) = connect(2).await;
+        request(&mut client, serde_json::json!({"type": "subscribe", "topic": "questions"})).await;
+
+        // The connection only gets to read these once all were sent, so it has fallen behind
+        for event_id in 1..=5 {
+            events.send(event(event_id, QUESTION)).unwrap();
+        }
+
+        let mut close = None;
+        while let Some(Ok(message)) = client.next().await {
+            if let Message::Close(frame) = message {
+                close = frame;
+                break;
+            }
+        }
+        assert_eq!(close.map(|frame| frame.code), Some(CloseCode::Policy));
+    }
+
+    #[tokio::test]
+    async fn should_close_connections_on_shutdown() {
+        let (client, server) = duplex(64 * 1024);
+        let (_events, receiver) = broadcast::channel(16);
+        let (shutdown, shutting_down) = tokio::sync::oneshot::channel::<()>();
+        let server = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+        tokio::spawn(serve(server, receiver, async move {
+            let _ = shutting_down.await;
+        }));
+        let mut client = WebSocketStream::from_raw_socket(client, Role::Client, None).await;
+
+        shutdown.send(()).unwrap();
+
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next()).await.unwrap();
+        let Some(Ok(Message::Close(Some(frame)))) = message else {
+            panic!("Expected a close frame, got {:?}", message);
+        };
+        assert_eq!(frame.code, CloseCode::Away);
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// The `[flags]` table of the Rocket configuration. Flagging needs `reputation.flag` reputation.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct FlagsConfig {
+    /// How many spam flags, pending or found helpful, hide a post until a moderator declines them.
+    pub hide_after_spam_flags: i64,
+    /// How long a moderator's claim on a flag keeps other moderators off it.
+    pub claim_minutes: u64,
+}
+
+impl Default for FlagsConfig {
+    fn default() -> Self {
+        Self {
+            hide_after_spam_flags: 3,
+            claim_minutes: 30,
+        }
+    }
+}
+
+impl FlagsConfig {
+    pub fn claim_timeout(&self) -> Duration {
+        Duration::from_secs(self.claim_minutes * 60)
+    }
+}
@@ -1,11 +1,79 @@
+use rocket::FromForm;
+use rocket::time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
 use crate::{
-    models::{Answer, AnswerDetail, AnswerId, DBError, Question, QuestionDetail, QuestionId},
-    persistance::{answers_dao::AnswersDao, questions_dao::QuestionsDao},
+    auth::{generate_api_key, hash_api_key},
+    badges::BadgeRules,
+    bounties::BountiesConfig,
+    closing::ClosingConfig,
+    flags::FlagsConfig,
+    models::{
+        Actor, Answer, AnswerDetail, AnswerEdit, AnswerId, AnswerVote, AuditContext, AuditEvent, AuditFilter,
+        Badge, Bounty, BountyDetail, CloseReason, CloseVote, ClosureVotes, DBError, DeliveryStatus, Flag,
+        FlagDetail, FlagReason, FlagResolution, FlagStats, FlagStatus, IfMatch, Job, JobId, PostType, Question,
+        QuestionDetail, QuestionEdit, QuestionEvent, QuestionFilter, QuestionId, QuestionVote, RelatedQuestions,
+        Reputation, Role, ScreeningDecision, SimilarQuestion, SuggestedEdit, SuggestedEditDetail, SuggestedEditReview, SuggestedEditStatus, User, UserBadge,
+        UserCredentials, Webhook, WebhookDelivery, WebhookDeliveryFilter, WebhookDetail,
+    },
+    persistance::{
+        answers_dao::AnswersDao, audit_dao::AuditDao, badges_dao::BadgesDao, bounties_dao::BountiesDao,
+        closing_dao::ClosingDao, flags_dao::FlagsDao, jobs_dao::{JobsDao, NewJob},
+        question_events_dao::QuestionEventsDao, question_links_dao::QuestionLinksDao, questions_dao::QuestionsDao, reputation_dao::ReputationDao,
+        suggested_edits_dao::SuggestedEditsDao, unit_of_work::TransactionManager, users_dao::UsersDao,
+        votes_dao::VotesDao, webhooks_dao::WebhooksDao,
+    },
+    reputation::ReputationConfig,
+    screening::{ContentScreener, Submission, Verdict},
+    similar::SimilarQuestionsConfig,
+    webhooks,
 };
 
+const DEFAULT_AUDIT_LIMIT: i64 = 100;
+const MAX_AUDIT_LIMIT: i64 = 1000;
+pub const QUESTION_EVENTS_PAGE_SIZE: i64 = 100;
+const DEFAULT_DELIVERIES_LIMIT: i64 = 100;
+const MAX_DELIVERIES_LIMIT: i64 = 1000;
+const DEFAULT_REPUTATION_LIMIT: i64 = 100;
+const MAX_REPUTATION_LIMIT: i64 = 1000;
+const DEFAULT_FLAGS_LIMIT: i64 = 100;
+const MAX_FLAGS_LIMIT: i64 = 1000;
+/// The longest explanation a flag can carry, in characters.
+const MAX_FLAG_DETAILS_LENGTH: usize = 500;
+const DEFAULT_SUGGESTED_EDITS_LIMIT: i64 = 100;
+const MAX_SUGGESTED_EDITS_LIMIT: i64 = 1000;
+/// The longest question title, in characters, as the column allows.
+const MAX_TITLE_LENGTH: usize = 255;
+/// The longest question description or answer, in characters. The columns are unbounded; this keeps
+/// a single post from growing beyond what a page can reasonably show.
+const MAX_BODY_LENGTH: usize = 30_000;
+/// The longest summary of a suggested edit or review comment, in characters.
+const MAX_EDIT_COMMENT_LENGTH: usize = 500;
+/// The most linked questions listed with a question.
+const LINKED_QUESTIONS_LIMIT: i64 = 50;
+/// The question events webhooks can subscribe to.
+pub const WEBHOOK_EVENT_TYPES: [&str; 13] = [
+    "question.created",
+    "question.edited",
+    "question.deleted",
+    "question.restored",
+    "question.closed",
+    "question.reopened",
+    "question.voted",
+    "answer.created",
+    "answer.edited",
+    "answer.deleted",
+    "answer.restored",
+    "answer.voted",
+    "answer.accepted",
+];
+
 #[derive(Debug, PartialEq)]
 pub enum HandlerError {
     BadRequest(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    PreconditionFailed(String),
     InternalError(String),
 }
 
@@ -15,15 +83,44 @@ impl HandlerError {
     }
 }
 
+fn require_moderator(actor: Option<&Actor>) -> Result<(), HandlerError> {
+    match actor {
+        Some(actor) if actor.role.is_moderator() => Ok(()),
+        _ => Err(HandlerError::Forbidden(
+            "Only moderators can see or restore deleted content".to_owned(),
+        )),
+    }
+}
+
+fn require_admin(actor: &Actor, message: &str) -> Result<(), HandlerError> {
+    if actor.role != Role::Admin {
+        return Err(HandlerError::Forbidden(message.to_owned()));
+    }
+    Ok(())
+}
+
+#[instrument(name = "handler", skip_all)]
 pub async fn create_question(
     question: Question,
+    actor: Option<&Actor>,
+    context: AuditContext,
+    screener: &ContentScreener,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
     // We are using a trait object here so that inner handlers do not depend on concrete DAO implementations
-    questions_dao: &Box<dyn QuestionsDao + Sync + Send>,
+    questions_dao: &(dyn QuestionsDao + Sync + Send),
 ) -> Result<QuestionDetail, HandlerError> {
-    let question = questions_dao.create_question(question).await; // create question using `questions_dao`
+    validate_post_field("title", Some(&question.title), MAX_TITLE_LENGTH)?;
+    validate_post_field("description", Some(&question.description), MAX_BODY_LENGTH)?;
+    let text = format!("{}\n{}", question.title, question.description);
+    let screened = screen_post(text, PostType::Question, actor, &context, screener, reputation_dao).await?;
+
+    let question = questions_dao.create_question(question, context.clone()).await; // create question using `questions_dao`
 
     match question {
-        Ok(question) => Ok(question), // return question
+        Ok(question) => {
+            record_screening(screened, question.question_uuid.clone(), context, screener).await;
+            Ok(question) // return question
+        }
         Err(err) => {
             // TODO: log err using error! macro
             error!("Error creating question: {:?}", err);
@@ -32,10 +129,24 @@ pub async fn create_question(
     }
 }
 
+#[instrument(name = "handler", skip_all)]
 pub async fn read_questions(
-    questions_dao: &Box<dyn QuestionsDao + Sync + Send>,
+    include_deleted: bool,
+    bounty: Option<String>,
+    actor: Option<&Actor>,
+    questions_dao: &(dyn QuestionsDao + Sync + Send),
 ) -> Result<Vec<QuestionDetail>, HandlerError> {
-    let questions = questions_dao.get_questions().await; // get questions using `questions_dao`
+    if include_deleted {
+        require_moderator(actor)?;
+    }
+    let open_bounty = match bounty.as_deref() {
+        None => false,
+        Some("open") => true,
+        Some(other) => return Err(HandlerError::BadRequest(format!("bounty must be open, got {}", other))),
+    };
+
+    let filter = QuestionFilter { include_deleted, open_bounty };
+    let questions = questions_dao.get_questions(filter).await; // get questions using `questions_dao`
 
     match questions {
         Ok(questions) => Ok(questions), // return questions
@@ -47,46 +158,143 @@ pub async fn read_questions(
     }
 }
 
+#[instrument(name = "handler", skip_all)]
+pub async fn find_similar_questions(
+    question: Question,
+    config: &SimilarQuestionsConfig,
+    questions_dao: &(dyn QuestionsDao + Sync + Send),
+) -> Result<Vec<SimilarQuestion>, HandlerError> {
+    if question.title.trim().is_empty() && question.description.trim().is_empty() {
+        return Err(HandlerError::BadRequest("A title or description is needed to find similar questions".to_owned()));
+    }
+
+    match questions_dao.find_similar(question, config.min_similarity, config.limit).await {
+        Ok(questions) => Ok(questions),
+        Err(err) => {
+            error!("Error finding similar questions: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all, fields(question_uuid = %question_uuid))]
+pub async fn read_related_questions(
+    question_uuid: String,
+    config: &SimilarQuestionsConfig,
+    questions_dao: &(dyn QuestionsDao + Sync + Send),
+    question_links_dao: &(dyn QuestionLinksDao + Sync + Send),
+) -> Result<RelatedQuestions, HandlerError> {
+    let related = match questions_dao.find_related(question_uuid.clone(), config.min_similarity, config.limit).await {
+        Ok(related) => related,
+        Err(err) => {
+            error!("Error finding related questions: {:?}", err);
+            return Err(match err {
+                DBError::InvalidUUID(s) => HandlerError::BadRequest(s),
+                DBError::NotFound(s) => HandlerError::NotFound(s),
+                _ => HandlerError::default_internal_error(),
+            });
+        }
+    };
+
+    match question_links_dao.get_linked(question_uuid, LINKED_QUESTIONS_LIMIT).await {
+        Ok(linked) => Ok(RelatedQuestions { related, linked }),
+        Err(err) => {
+            error!("Error reading linked questions: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all, fields(question_uuid = %question_uuid.question_uuid))]
 pub async fn delete_question(
     question_uuid: QuestionId,
-    questions_dao: &Box<dyn QuestionsDao + Sync + Send>,
+    actor: &Actor,
+    if_match: IfMatch,
+    context: AuditContext,
+    questions_dao: &(dyn QuestionsDao + Sync + Send),
 ) -> Result<(), HandlerError> {
     let result = questions_dao
-        .delete_question(question_uuid.question_uuid)
+        .delete_question(question_uuid.question_uuid, if_match, actor.role.is_moderator(), context)
         .await; // delete question using `questions_dao`
 
-    if result.is_err() {
-        return Err(HandlerError::default_internal_error());
+    match result {
+        Ok(()) => Ok(()),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::Forbidden(s)) => Err(HandlerError::Forbidden(s)),
+        Err(DBError::PreconditionFailed(s)) => Err(HandlerError::PreconditionFailed(s)),
+        Err(err) => {
+            error!("Error deleting question: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
     }
+}
 
-    Ok(())
+#[instrument(name = "handler", skip_all, fields(question_uuid = %question_uuid.question_uuid))]
+pub async fn restore_question(
+    question_uuid: QuestionId,
+    actor: &Actor,
+    if_match: IfMatch,
+    context: AuditContext,
+    questions_dao: &(dyn QuestionsDao + Sync + Send),
+) -> Result<(), HandlerError> {
+    require_moderator(Some(actor))?;
+
+    match questions_dao.restore_question(question_uuid.question_uuid, if_match, context).await {
+        Ok(()) => Ok(()),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(DBError::PreconditionFailed(s)) => Err(HandlerError::PreconditionFailed(s)),
+        Err(err) => {
+            error!("Error restoring question: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
 }
 
+#[instrument(name = "handler", skip_all, fields(question_uuid = %answer.question_uuid))]
 pub async fn create_answer(
     answer: Answer,
-    answers_dao: &Box<dyn AnswersDao + Send + Sync>,
+    actor: Option<&Actor>,
+    context: AuditContext,
+    screener: &ContentScreener,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    answers_dao: &(dyn AnswersDao + Send + Sync),
 ) -> Result<AnswerDetail, HandlerError> {
-    let answer = answers_dao.create_answer(answer).await; // create answer using `answers_dao`
+    validate_post_field("content", Some(&answer.content), MAX_BODY_LENGTH)?;
+    let screened = screen_post(answer.content.clone(), PostType::Answer, actor, &context, screener, reputation_dao).await?;
+
+    let answer = answers_dao.create_answer(answer, context.clone()).await; // create answer using `answers_dao`
 
     match answer {
-        Ok(answer) => Ok(answer), // return answer
+        Ok(answer) => {
+            record_screening(screened, answer.answer_uuid.clone(), context, screener).await;
+            Ok(answer) // return answer
+        }
         Err(err) => {
             error!("Error creating answer: {:?}", err);
 
             match err {
                 DBError::InvalidUUID(s) => Err(HandlerError::BadRequest(s)), // return a `HandlerError::BadRequest` error passing in s as the string
+                DBError::QuestionClosed(s) => Err(HandlerError::Conflict(s)),
                 _ => Err(HandlerError::default_internal_error()), // return a default internal error using the HandlerError type
             }
         }
     }
 }
 
+#[instrument(name = "handler", skip_all, fields(question_uuid = %question_uuid.question_uuid))]
 pub async fn read_answers(
     question_uuid: QuestionId,
-    answers_dao: &Box<dyn AnswersDao + Send + Sync>,
+    include_deleted: bool,
+    actor: Option<&Actor>,
+    answers_dao: &(dyn AnswersDao + Send + Sync),
 ) -> Result<Vec<AnswerDetail>, HandlerError> {
+    if include_deleted {
+        require_moderator(actor)?;
+    }
+
     let answers = answers_dao
-        .get_answers(question_uuid.question_uuid)
+        .get_answers(question_uuid.question_uuid, include_deleted)
         .await; // get answers using `answers_dao`
 
     match answers {
@@ -99,411 +307,2926 @@ pub async fn read_answers(
     }
 }
 
+#[instrument(name = "handler", skip_all, fields(answer_uuid = %answer_uuid.answer_uuid))]
 pub async fn delete_answer(
     answer_uuid: AnswerId,
-    answers_dao: &Box<dyn AnswersDao + Send + Sync>,
+    actor: &Actor,
+    if_match: IfMatch,
+    context: AuditContext,
+    answers_dao: &(dyn AnswersDao + Send + Sync),
 ) -> Result<(), HandlerError> {
     let result = answers_dao
-        .delete_answer(answer_uuid.answer_uuid)
+        .delete_answer(answer_uuid.answer_uuid, if_match, actor.role.is_moderator(), context)
         .await; // delete answer using `answers_dao`
 
-    if result.is_err() {
-        return Err(HandlerError::default_internal_error()); // return a default internal error using the HandlerError type
+    match result {
+        Ok(()) => Ok(()),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::Forbidden(s)) => Err(HandlerError::Forbidden(s)),
+        Err(DBError::PreconditionFailed(s)) => Err(HandlerError::PreconditionFailed(s)),
+        Err(err) => {
+            error!("Error deleting answer: {:?}", err);
+            Err(HandlerError::default_internal_error()) // return a default internal error using the HandlerError type
+        }
     }
-
-    Ok(())
 }
 
-// ***********************************************************
-//                           Tests
-// ***********************************************************
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[instrument(name = "handler", skip_all, fields(answer_uuid = %answer_uuid.answer_uuid))]
+pub async fn restore_answer(
+    answer_uuid: AnswerId,
+    actor: &Actor,
+    if_match: IfMatch,
+    context: AuditContext,
+    answers_dao: &(dyn AnswersDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    require_moderator(Some(actor))?;
 
-    use tokio::sync::Mutex;
+    match answers_dao.restore_answer(answer_uuid.answer_uuid, if_match, context).await {
+        Ok(()) => Ok(()),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(DBError::PreconditionFailed(s)) => Err(HandlerError::PreconditionFailed(s)),
+        Err(err) => {
+            error!("Error restoring answer: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
 
-    struct QuestionsDaoMock {
-        create_question_response: Mutex<Option<Result<QuestionDetail, DBError>>>,
-        delete_question_response: Mutex<Option<Result<(), DBError>>>,
-        get_questions_response: Mutex<Option<Result<Vec<QuestionDetail>, DBError>>>,
+/// Where to start streaming the events of a question: after `last_event_id` when resuming,
+/// otherwise after its newest event.
+#[instrument(name = "handler", skip_all)]
+pub async fn start_question_events(
+    question_uuid: String,
+    last_event_id: Option<i64>,
+    question_events_dao: &(dyn QuestionEventsDao + Send + Sync),
+) -> Result<i64, HandlerError> {
+    match question_events_dao.latest_event_id(question_uuid).await {
+        // A client can't have seen events that don't exist yet
+        Ok(latest) => Ok(last_event_id.map_or(latest, |last| last.min(latest))),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(err) => {
+            error!("Error starting question events: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
     }
+}
 
-    impl QuestionsDaoMock {
-        pub fn new() -> Self {
-            QuestionsDaoMock {
-                create_question_response: Mutex::new(None),
-                delete_question_response: Mutex::new(None),
-                get_questions_response: Mutex::new(None),
-            }
+#[instrument(name = "handler", skip_all)]
+pub async fn read_question_events(
+    question_uuid: String,
+    after_event_id: i64,
+    question_events_dao: &(dyn QuestionEventsDao + Send + Sync),
+) -> Result<Vec<QuestionEvent>, HandlerError> {
+    let events = question_events_dao
+        .get_events(question_uuid, after_event_id, QUESTION_EVENTS_PAGE_SIZE)
+        .await;
+
+    match events {
+        Ok(events) => Ok(events),
+        Err(err) => {
+            error!("Error reading question events: {:?}", err);
+            Err(HandlerError::default_internal_error())
         }
-        pub fn mock_create_question(&mut self, response: Result<QuestionDetail, DBError>) {
-            self.create_question_response = Mutex::new(Some(response));
+    }
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn create_user(
+    user: User,
+    users_dao: &(dyn UsersDao + Send + Sync),
+) -> Result<UserCredentials, HandlerError> {
+    let api_key = generate_api_key();
+
+    match users_dao.create_user(user, hash_api_key(&api_key)).await {
+        Ok(user) => Ok(UserCredentials { user, api_key }),
+        Err(DBError::Conflict(s)) => Err(HandlerError::Conflict(s)),
+        Err(err) => {
+            error!("Error creating user: {:?}", err);
+            Err(HandlerError::default_internal_error())
         }
-        pub fn mock_delete_question(&mut self, response: Result<(), DBError>) {
-            self.delete_question_response = Mutex::new(Some(response));
+    }
+}
+
+/// Query parameters of the audit log endpoint; see `AuditFilter`.
+#[derive(FromForm, Debug, Default)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target: Option<String>,
+    pub request_id: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+fn parse_timestamp(value: Option<String>, name: &str) -> Result<Option<OffsetDateTime>, HandlerError> {
+    value
+        .map(|value| {
+            OffsetDateTime::parse(&value, &Rfc3339).map_err(|_| {
+                HandlerError::BadRequest(format!("{} must be an RFC 3339 timestamp, got {}", name, value))
+            })
+        })
+        .transpose()
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn read_audit_events(
+    query: AuditQuery,
+    actor: &Actor,
+    audit_dao: &(dyn AuditDao + Send + Sync),
+) -> Result<Vec<AuditEvent>, HandlerError> {
+    require_admin(actor, "Only admins can read the audit log")?;
+
+    let filter = AuditFilter {
+        actor_uuid: query.actor,
+        action: query.action,
+        target_type: query.target_type,
+        target_uuid: query.target,
+        request_id: query.request_id,
+        since: parse_timestamp(query.since, "since")?,
+        until: parse_timestamp(query.until, "until")?,
+        before_event_id: query.before,
+        limit: query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).clamp(1, MAX_AUDIT_LIMIT),
+    };
+
+    match audit_dao.get_events(filter).await {
+        Ok(events) => Ok(events),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(err) => {
+            error!("Error reading audit events: {:?}", err);
+            Err(HandlerError::default_internal_error())
         }
-        pub fn mock_get_questions(&mut self, response: Result<Vec<QuestionDetail>, DBError>) {
-            self.get_questions_response = Mutex::new(Some(response));
+    }
+}
+
+/// Fails unless the actor is a moderator or has at least `required` reputation.
+async fn require_reputation(
+    actor: &Actor,
+    required: i32,
+    privilege: &str,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    match actor_reputation(actor, reputation_dao).await? {
+        Some(reputation) if reputation < required => Err(HandlerError::Forbidden(format!(
+            "You need {} reputation to {}, but have {}",
+            required, privilege, reputation
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the actor has `required` reputation, which moderators always do.
+async fn has_reputation(actor: &Actor, required: i32, reputation_dao: &(dyn ReputationDao + Send + Sync)) -> Result<bool, HandlerError> {
+    Ok(actor_reputation(actor, reputation_dao).await?.is_none_or(|reputation| reputation >= required))
+}
+
+/// The actor's reputation, or `None` for moderators, who need none.
+async fn actor_reputation(actor: &Actor, reputation_dao: &(dyn ReputationDao + Send + Sync)) -> Result<Option<i32>, HandlerError> {
+    if actor.role.is_moderator() {
+        return Ok(None);
+    }
+
+    reputation_dao.get_reputation(actor.user_uuid.clone()).await.map(Some).map_err(|err| {
+        error!("Error reading reputation: {:?}", err);
+        HandlerError::default_internal_error()
+    })
+}
+
+/// Screens a new post unless a moderator makes it, refusing it if a rule rejects it. The verdict
+/// on a post let through is recorded once it is created, see `record_screening`.
+async fn screen_post(
+    text: String,
+    target_type: PostType,
+    actor: Option<&Actor>,
+    context: &AuditContext,
+    screener: &ContentScreener,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+) -> Result<Option<(Submission, Verdict)>, HandlerError> {
+    let reputation = match actor {
+        None => 0,
+        Some(actor) => match actor_reputation(actor, reputation_dao).await? {
+            Some(reputation) => reputation,
+            None => return Ok(None),
+        },
+    };
+
+    let submission = Submission::new(actor.map(|actor| actor.user_uuid.clone()), reputation, target_type, text);
+    let verdict = screener.screen(&submission).await.map_err(|err| {
+        error!("Error screening {}: {:?}", target_type.as_str(), err);
+        HandlerError::default_internal_error()
+    })?;
+
+    if verdict.decision == ScreeningDecision::Rejected {
+        let reason = verdict.reason.clone().unwrap_or_default();
+        if let Err(err) = screener.record(submission, &verdict, None, context.clone()).await {
+            error!("Error recording screening decision: {:?}", err);
         }
+        return Err(HandlerError::BadRequest(format!("Your {} was rejected: {}", target_type.as_str(), reason)));
     }
+    Ok(Some((submission, verdict)))
+}
 
-    #[async_trait]
-    impl QuestionsDao for QuestionsDaoMock {
-        async fn create_question(&self, _: Question) -> Result<QuestionDetail, DBError> {
-            self.create_question_response
-                .lock()
-                .await
-                .take()
-                .expect("create_question_response should not be None.")
+/// Records the verdict on a post just created, quarantining it if need be. The post exists by
+/// now, so failures are only logged.
+async fn record_screening(screened: Option<(Submission, Verdict)>, target_uuid: String, context: AuditContext, screener: &ContentScreener) {
+    if let Some((submission, verdict)) = screened {
+        if let Err(err) = screener.record(submission, &verdict, Some(target_uuid), context).await {
+            error!("Error recording screening decision: {:?}", err);
         }
-        async fn delete_question(&self, _: String) -> Result<(), DBError> {
-            self.delete_question_response
-                .lock()
-                .await
-                .take()
-                .expect("delete_question_response should not be None.")
+    }
+}
+
+/// Checks that `value` is a valid vote the actor has the privilege to cast.
+async fn require_vote_privilege(
+    value: i16,
+    actor: &Actor,
+    config: &ReputationConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    match value {
+        1 => require_reputation(actor, config.upvote, "upvote", reputation_dao).await,
+        -1 => require_reputation(actor, config.downvote, "downvote", reputation_dao).await,
+        // Retracting a vote needs no privilege
+        0 => Ok(()),
+        _ => Err(HandlerError::BadRequest(format!("value must be 1, -1 or 0, got {}", value))),
+    }
+}
+
+async fn vote(
+    target_type: PostType,
+    target_uuid: String,
+    value: i16,
+    context: AuditContext,
+    votes_dao: &(dyn VotesDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    match votes_dao.vote(target_type, target_uuid, value, context).await {
+        Ok(()) => Ok(()),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(DBError::Forbidden(s)) => Err(HandlerError::Forbidden(s)),
+        Err(err) => {
+            error!("Error voting on {}: {:?}", target_type.as_str(), err);
+            Err(HandlerError::default_internal_error())
         }
-        async fn get_questions(&self) -> Result<Vec<QuestionDetail>, DBError> {
-            self.get_questions_response
-                .lock()
-                .await
-                .take()
-                .expect("get_questions_response should not be None.")
+    }
+}
+
+#[instrument(name = "handler", skip_all, fields(question_uuid = %vote.question_uuid))]
+pub async fn vote_question(
+    vote: QuestionVote,
+    actor: &Actor,
+    context: AuditContext,
+    config: &ReputationConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    votes_dao: &(dyn VotesDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    require_vote_privilege(vote.value, actor, config, reputation_dao).await?;
+    self::vote(PostType::Question, vote.question_uuid, vote.value, context, votes_dao).await
+}
+
+#[instrument(name = "handler", skip_all, fields(answer_uuid = %vote.answer_uuid))]
+pub async fn vote_answer(
+    vote: AnswerVote,
+    actor: &Actor,
+    context: AuditContext,
+    config: &ReputationConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    votes_dao: &(dyn VotesDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    require_vote_privilege(vote.value, actor, config, reputation_dao).await?;
+    self::vote(PostType::Answer, vote.answer_uuid, vote.value, context, votes_dao).await
+}
+
+#[instrument(name = "handler", skip_all, fields(answer_uuid = %answer_uuid.answer_uuid))]
+pub async fn accept_answer(
+    answer_uuid: AnswerId,
+    context: AuditContext,
+    votes_dao: &(dyn VotesDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    match votes_dao.accept_answer(answer_uuid.answer_uuid, context).await {
+        Ok(()) => Ok(()),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(DBError::Forbidden(s)) => Err(HandlerError::Forbidden(s)),
+        Err(err) => {
+            error!("Error accepting answer: {:?}", err);
+            Err(HandlerError::default_internal_error())
         }
     }
+}
 
-    struct AnswersDaoMock {
-        create_answer_response: Mutex<Option<Result<AnswerDetail, DBError>>>,
-        delete_answer_response: Mutex<Option<Result<(), DBError>>>,
-        get_answers_response: Mutex<Option<Result<Vec<AnswerDetail>, DBError>>>,
+fn closure_error(err: DBError) -> HandlerError {
+    match err {
+        DBError::InvalidUUID(s) => HandlerError::BadRequest(s),
+        DBError::NotFound(s) => HandlerError::NotFound(s),
+        DBError::Forbidden(s) => HandlerError::Forbidden(s),
+        DBError::Conflict(s) => HandlerError::Conflict(s),
+        err => {
+            error!("Error voting to close or reopen question: {:?}", err);
+            HandlerError::default_internal_error()
+        }
     }
+}
 
-    impl AnswersDaoMock {
-        pub fn new() -> Self {
-            AnswersDaoMock {
-                create_answer_response: Mutex::new(None),
-                delete_answer_response: Mutex::new(None),
-                get_answers_response: Mutex::new(None),
-            }
+#[instrument(name = "handler", skip_all, fields(question_uuid = %vote.question_uuid))]
+pub async fn vote_to_close(
+    vote: CloseVote,
+    actor: &Actor,
+    context: AuditContext,
+    reputation: &ReputationConfig,
+    closing: &ClosingConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    closing_dao: &(dyn ClosingDao + Send + Sync),
+) -> Result<ClosureVotes, HandlerError> {
+    match (vote.reason, vote.duplicate_of.as_deref()) {
+        (CloseReason::Duplicate, None) => {
+            return Err(HandlerError::BadRequest("duplicate_of is required to close a question as a duplicate".to_owned()))
         }
-        pub fn mock_create_answer(&mut self, response: Result<AnswerDetail, DBError>) {
-            self.create_answer_response = Mutex::new(Some(response));
+        (CloseReason::Duplicate, Some(duplicate_of)) if duplicate_of == vote.question_uuid => {
+            return Err(HandlerError::BadRequest("A question cannot duplicate itself".to_owned()))
         }
-        pub fn mock_delete_answer(&mut self, response: Result<(), DBError>) {
-            self.delete_answer_response = Mutex::new(Some(response));
+        (CloseReason::OffTopic | CloseReason::NeedsDetails, Some(_)) => {
+            return Err(HandlerError::BadRequest("duplicate_of is only allowed for duplicates".to_owned()))
         }
-        pub fn mock_get_answers(&mut self, response: Result<Vec<AnswerDetail>, DBError>) {
-            self.get_answers_response = Mutex::new(Some(response));
+        _ => {}
+    }
+    require_reputation(actor, reputation.close, "vote to close questions", reputation_dao).await?;
+
+    closing_dao
+        .vote_to_close(vote, closing.votes_needed, actor.role.is_moderator(), context)
+        .await
+        .map_err(closure_error)
+}
+
+#[instrument(name = "handler", skip_all, fields(question_uuid = %question_uuid.question_uuid))]
+pub async fn vote_to_reopen(
+    question_uuid: QuestionId,
+    actor: &Actor,
+    context: AuditContext,
+    reputation: &ReputationConfig,
+    closing: &ClosingConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    closing_dao: &(dyn ClosingDao + Send + Sync),
+) -> Result<ClosureVotes, HandlerError> {
+    require_reputation(actor, reputation.close, "vote to reopen questions", reputation_dao).await?;
+
+    closing_dao
+        .vote_to_reopen(question_uuid.question_uuid, closing.votes_needed, actor.role.is_moderator(), context)
+        .await
+        .map_err(closure_error)
+}
+
+fn require_flag_reviewer(actor: &Actor) -> Result<(), HandlerError> {
+    if !actor.role.is_moderator() {
+        return Err(HandlerError::Forbidden("Only moderators can review flags".to_owned()));
+    }
+    Ok(())
+}
+
+fn flag_error(err: DBError) -> HandlerError {
+    match err {
+        DBError::InvalidUUID(s) => HandlerError::BadRequest(s),
+        DBError::NotFound(s) => HandlerError::NotFound(s),
+        DBError::Forbidden(s) => HandlerError::Forbidden(s),
+        DBError::Conflict(s) => HandlerError::Conflict(s),
+        err => {
+            error!("Error handling flag: {:?}", err);
+            HandlerError::default_internal_error()
         }
     }
+}
 
-    #[async_trait]
-    impl AnswersDao for AnswersDaoMock {
-        async fn create_answer(&self, _: Answer) -> Result<AnswerDetail, DBError> {
-            self.create_answer_response
-                .lock()
-                .await
-                .take()
-                .expect("create_answer_response should not be None.")
+#[instrument(name = "handler", skip_all, fields(target_uuid = %flag.target_uuid))]
+pub async fn create_flag(
+    flag: Flag,
+    actor: &Actor,
+    context: AuditContext,
+    reputation: &ReputationConfig,
+    config: &FlagsConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    flags_dao: &(dyn FlagsDao + Send + Sync),
+) -> Result<FlagDetail, HandlerError> {
+    let details = flag.details.as_deref().map(str::trim).unwrap_or_default();
+    if flag.reason == FlagReason::Other && details.is_empty() {
+        return Err(HandlerError::BadRequest("details are required for flags with the other reason".to_owned()));
+    }
+    if details.chars().count() > MAX_FLAG_DETAILS_LENGTH {
+        return Err(HandlerError::BadRequest(format!("details must be at most {} characters long", MAX_FLAG_DETAILS_LENGTH)));
+    }
+    require_reputation(actor, reputation.flag, "flag posts", reputation_dao).await?;
+
+    flags_dao
+        .create_flag(flag, config.hide_after_spam_flags, context)
+        .await
+        .map_err(flag_error)
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn read_flag_queue(
+    actor: &Actor,
+    limit: Option<i64>,
+    config: &FlagsConfig,
+    flags_dao: &(dyn FlagsDao + Send + Sync),
+) -> Result<Vec<FlagDetail>, HandlerError> {
+    require_flag_reviewer(actor)?;
+    let limit = limit.unwrap_or(DEFAULT_FLAGS_LIMIT).clamp(1, MAX_FLAGS_LIMIT);
+
+    flags_dao
+        .get_queue(actor.user_uuid.clone(), config.claim_timeout(), limit)
+        .await
+        .map_err(flag_error)
+}
+
+#[instrument(name = "handler", skip_all, fields(flag_id))]
+pub async fn claim_flag(
+    flag_id: i64,
+    actor: &Actor,
+    context: AuditContext,
+    config: &FlagsConfig,
+    flags_dao: &(dyn FlagsDao + Send + Sync),
+) -> Result<FlagDetail, HandlerError> {
+    require_flag_reviewer(actor)?;
+
+    flags_dao
+        .claim_flag(flag_id, config.claim_timeout(), context)
+        .await
+        .map_err(flag_error)
+}
+
+#[instrument(name = "handler", skip_all, fields(flag_id))]
+pub async fn resolve_flag(
+    flag_id: i64,
+    resolution: FlagResolution,
+    actor: &Actor,
+    context: AuditContext,
+    config: &FlagsConfig,
+    flags_dao: &(dyn FlagsDao + Send + Sync),
+) -> Result<FlagDetail, HandlerError> {
+    require_flag_reviewer(actor)?;
+    if resolution.status == FlagStatus::Pending {
+        return Err(HandlerError::BadRequest("status must be helpful or declined".to_owned()));
+    }
+
+    flags_dao
+        .resolve_flag(flag_id, resolution.status, config.hide_after_spam_flags, config.claim_timeout(), context)
+        .await
+        .map_err(flag_error)
+}
+
+#[instrument(name = "handler", skip_all, fields(user_uuid = %user_uuid))]
+pub async fn read_flag_stats(
+    user_uuid: String,
+    actor: &Actor,
+    flags_dao: &(dyn FlagsDao + Send + Sync),
+) -> Result<FlagStats, HandlerError> {
+    if actor.user_uuid != user_uuid && !actor.role.is_moderator() {
+        return Err(HandlerError::Forbidden("Only moderators can see other users' flags".to_owned()));
+    }
+
+    flags_dao.get_stats(user_uuid).await.map_err(flag_error)
+}
+
+fn validate_post_field(name: &str, value: Option<&str>, max_length: usize) -> Result<(), HandlerError> {
+    match value {
+        Some(value) if value.trim().is_empty() => Err(HandlerError::BadRequest(format!("{} must not be empty", name))),
+        Some(value) if value.chars().count() > max_length => Err(HandlerError::BadRequest(format!(
+            "{} must be at most {} characters long",
+            name, max_length
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn validate_edit_comment(name: &str, value: Option<&str>) -> Result<(), HandlerError> {
+    if value.is_some_and(|value| value.chars().count() > MAX_EDIT_COMMENT_LENGTH) {
+        return Err(HandlerError::BadRequest(format!("{} must be at most {} characters long", name, MAX_EDIT_COMMENT_LENGTH)));
+    }
+    Ok(())
+}
+
+fn edit_error(err: DBError) -> HandlerError {
+    match err {
+        DBError::InvalidUUID(s) => HandlerError::BadRequest(s),
+        DBError::NotFound(s) => HandlerError::NotFound(s),
+        DBError::Forbidden(s) => HandlerError::Forbidden(s),
+        DBError::Conflict(s) => HandlerError::Conflict(s),
+        DBError::PreconditionFailed(s) => HandlerError::PreconditionFailed(s),
+        err => {
+            error!("Error handling edit: {:?}", err);
+            HandlerError::default_internal_error()
         }
-        async fn delete_answer(&self, _: String) -> Result<(), DBError> {
-            self.delete_answer_response
-                .lock()
-                .await
-                .take()
-                .expect("delete_answer_response should not be None.")
+    }
+}
+
+/// Edits a question: its author can, and so can users with the edit privilege.
+#[instrument(name = "handler", skip_all, fields(question_uuid = %edit.question_uuid))]
+pub async fn update_question(
+    edit: QuestionEdit,
+    if_match: IfMatch,
+    actor: &Actor,
+    context: AuditContext,
+    reputation: &ReputationConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    questions_dao: &(dyn QuestionsDao + Sync + Send),
+) -> Result<QuestionDetail, HandlerError> {
+    if edit.title.is_none() && edit.description.is_none() {
+        return Err(HandlerError::BadRequest("title or description is required".to_owned()));
+    }
+    validate_post_field("title", edit.title.as_deref(), MAX_TITLE_LENGTH)?;
+    validate_post_field("description", edit.description.as_deref(), MAX_BODY_LENGTH)?;
+    let privileged = has_reputation(actor, reputation.edit, reputation_dao).await?;
+
+    questions_dao
+        .update_question(edit, if_match, privileged, context)
+        .await
+        .map_err(edit_error)
+}
+
+/// Edits an answer: its author can, and so can users with the edit privilege.
+#[instrument(name = "handler", skip_all, fields(answer_uuid = %edit.answer_uuid))]
+pub async fn update_answer(
+    edit: AnswerEdit,
+    if_match: IfMatch,
+    actor: &Actor,
+    context: AuditContext,
+    reputation: &ReputationConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    answers_dao: &(dyn AnswersDao + Sync + Send),
+) -> Result<AnswerDetail, HandlerError> {
+    validate_post_field("content", Some(&edit.content), MAX_BODY_LENGTH)?;
+    let privileged = has_reputation(actor, reputation.edit, reputation_dao).await?;
+
+    answers_dao
+        .update_answer(edit, if_match, privileged, context)
+        .await
+        .map_err(edit_error)
+}
+
+#[instrument(name = "handler", skip_all, fields(target_uuid = %edit.target_uuid))]
+pub async fn create_suggested_edit(
+    edit: SuggestedEdit,
+    context: AuditContext,
+    suggested_edits_dao: &(dyn SuggestedEditsDao + Send + Sync),
+) -> Result<SuggestedEditDetail, HandlerError> {
+    match edit.target_type {
+        PostType::Question if edit.content.is_some() => {
+            return Err(HandlerError::BadRequest("Questions have a title and a description, not content".to_owned()));
         }
-        async fn get_answers(&self, _: String) -> Result<Vec<AnswerDetail>, DBError> {
-            self.get_answers_response
-                .lock()
-                .await
-                .take()
-                .expect("get_answers_response should not be None.")
+        PostType::Question if edit.title.is_none() && edit.description.is_none() => {
+            return Err(HandlerError::BadRequest("title or description is required".to_owned()));
+        }
+        PostType::Answer if edit.title.is_some() || edit.description.is_some() => {
+            return Err(HandlerError::BadRequest("Answers have content, not a title or description".to_owned()));
         }
+        PostType::Answer if edit.content.is_none() => {
+            return Err(HandlerError::BadRequest("content is required".to_owned()));
+        }
+        _ => {}
+    }
+    validate_post_field("title", edit.title.as_deref(), MAX_TITLE_LENGTH)?;
+    validate_post_field("description", edit.description.as_deref(), MAX_BODY_LENGTH)?;
+    validate_post_field("content", edit.content.as_deref(), MAX_BODY_LENGTH)?;
+    validate_edit_comment("summary", edit.summary.as_deref())?;
+
+    suggested_edits_dao
+        .create_suggested_edit(edit, context)
+        .await
+        .map_err(edit_error)
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn read_suggested_edit_queue(
+    actor: &Actor,
+    limit: Option<i64>,
+    reputation: &ReputationConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    suggested_edits_dao: &(dyn SuggestedEditsDao + Send + Sync),
+) -> Result<Vec<SuggestedEditDetail>, HandlerError> {
+    require_reputation(actor, reputation.edit, "review suggested edits", reputation_dao).await?;
+    let limit = limit.unwrap_or(DEFAULT_SUGGESTED_EDITS_LIMIT).clamp(1, MAX_SUGGESTED_EDITS_LIMIT);
+
+    suggested_edits_dao.get_queue(limit).await.map_err(edit_error)
+}
+
+/// Approves or rejects a suggested edit. Approving applies it through the questions or answers DAO,
+/// in the same transaction, unless the post changed since the edit was suggested.
+#[instrument(name = "handler", skip_all, fields(edit_id))]
+pub async fn review_suggested_edit(
+    edit_id: i64,
+    review: SuggestedEditReview,
+    actor: &Actor,
+    context: AuditContext,
+    reputation: &ReputationConfig,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+    transactions: &(dyn TransactionManager + Send + Sync),
+) -> Result<SuggestedEditDetail, HandlerError> {
+    if review.status == SuggestedEditStatus::Pending {
+        return Err(HandlerError::BadRequest("status must be approved or rejected".to_owned()));
+    }
+    validate_edit_comment("comment", review.comment.as_deref())?;
+    require_reputation(actor, reputation.edit, "review suggested edits", reputation_dao).await?;
+
+    let uow = transactions.begin().await.map_err(edit_error)?;
+    let edit = uow.suggested_edits().lock_pending(edit_id).await.map_err(edit_error)?;
+    if edit.suggested_by == actor.user_uuid {
+        return Err(HandlerError::Forbidden("You cannot review your own suggested edits".to_owned()));
+    }
+
+    if review.status == SuggestedEditStatus::Approved {
+        let if_match = IfMatch::Versions(vec![edit.base_version]);
+        let applied = match edit.target_type {
+            PostType::Question => {
+                let question = QuestionEdit {
+                    question_uuid: edit.target_uuid.clone(),
+                    title: edit.title.clone(),
+                    description: edit.description.clone(),
+                };
+                uow.questions().update_question(question, if_match, true, context.clone()).await.map(|_| ())
+            }
+            PostType::Answer => {
+                let answer = AnswerEdit {
+                    answer_uuid: edit.target_uuid.clone(),
+                    content: edit.content.clone().unwrap_or_default(),
+                };
+                uow.answers().update_answer(answer, if_match, true, context.clone()).await.map(|_| ())
+            }
+        };
+        applied.map_err(|err| match err {
+            DBError::PreconditionFailed(_) => HandlerError::Conflict(format!(
+                "The {} changed since edit {} was suggested",
+                edit.target_type.as_str(),
+                edit_id
+            )),
+            err => edit_error(err),
+        })?;
+    }
+
+    let reviewed = uow.suggested_edits().record_review(edit_id, review, context).await.map_err(edit_error)?;
+    uow.commit().await.map_err(edit_error)?;
+    Ok(reviewed)
+}
+
+#[instrument(name = "handler", skip_all, fields(question_uuid = %bounty.question_uuid))]
+pub async fn offer_bounty(
+    bounty: Bounty,
+    context: AuditContext,
+    config: &BountiesConfig,
+    bounties_dao: &(dyn BountiesDao + Send + Sync),
+) -> Result<BountyDetail, HandlerError> {
+    if !(config.min_amount..=config.max_amount).contains(&bounty.amount) {
+        return Err(HandlerError::BadRequest(format!(
+            "amount must be between {} and {}, got {}",
+            config.min_amount, config.max_amount, bounty.amount
+        )));
+    }
+
+    match bounties_dao.offer_bounty(bounty, config.duration(), context).await {
+        Ok(bounty) => Ok(bounty),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(DBError::Forbidden(s)) => Err(HandlerError::Forbidden(s)),
+        Err(DBError::Conflict(s)) => Err(HandlerError::Conflict(s)),
+        Err(err) => {
+            error!("Error offering bounty: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all, fields(answer_uuid = %answer_uuid.answer_uuid))]
+pub async fn award_bounty(
+    answer_uuid: AnswerId,
+    context: AuditContext,
+    bounties_dao: &(dyn BountiesDao + Send + Sync),
+) -> Result<BountyDetail, HandlerError> {
+    match bounties_dao.award_bounty(answer_uuid.answer_uuid, context).await {
+        Ok(bounty) => Ok(bounty),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(DBError::Forbidden(s)) => Err(HandlerError::Forbidden(s)),
+        Err(err) => {
+            error!("Error awarding bounty: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all, fields(user_uuid = %user_uuid))]
+pub async fn read_reputation(
+    user_uuid: String,
+    before: Option<i64>,
+    limit: Option<i64>,
+    reputation_dao: &(dyn ReputationDao + Send + Sync),
+) -> Result<Reputation, HandlerError> {
+    let limit = limit.unwrap_or(DEFAULT_REPUTATION_LIMIT).clamp(1, MAX_REPUTATION_LIMIT);
+    let read = async {
+        let reputation = reputation_dao.get_reputation(user_uuid.clone()).await?;
+        let history = reputation_dao.get_history(user_uuid.clone(), before, limit).await?;
+        Ok::<_, DBError>(Reputation { user_uuid: user_uuid.clone(), reputation, history })
+    };
+
+    match read.await {
+        Ok(reputation) => Ok(reputation),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(err) => {
+            error!("Error reading reputation: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn read_badges(
+    rules: &BadgeRules,
+    badges_dao: &(dyn BadgesDao + Send + Sync),
+) -> Result<Vec<Badge>, HandlerError> {
+    let awarded = match badges_dao.count_awards().await {
+        Ok(awarded) => awarded,
+        Err(err) => {
+            error!("Error counting badges: {:?}", err);
+            return Err(HandlerError::default_internal_error());
+        }
+    };
+
+    let badges = rules
+        .badges
+        .iter()
+        .map(|rule| Badge {
+            name: rule.name.clone(),
+            title: rule.title.clone(),
+            description: rule.description.clone(),
+            level: rule.level,
+            metric: rule.metric,
+            threshold: rule.threshold,
+            awarded: awarded.get(&rule.name).copied().unwrap_or(0),
+        })
+        .collect();
+    Ok(badges)
+}
+
+#[instrument(name = "handler", skip_all, fields(user_uuid = %user_uuid))]
+pub async fn read_user_badges(
+    user_uuid: String,
+    badges_dao: &(dyn BadgesDao + Send + Sync),
+) -> Result<Vec<UserBadge>, HandlerError> {
+    match badges_dao.get_user_badges(user_uuid).await {
+        Ok(badges) => Ok(badges),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(err) => {
+            error!("Error reading badges: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+fn validate_webhook(webhook: &mut Webhook) -> Result<(), HandlerError> {
+    let url = reqwest::Url::parse(&webhook.url)
+        .map_err(|err| HandlerError::BadRequest(format!("Invalid webhook URL {}: {}", webhook.url, err)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(HandlerError::BadRequest("Webhook URLs must use http or https".to_owned()));
+    }
+    webhooks::validate_url(&url).map_err(HandlerError::BadRequest)?;
+    if !(16..=255).contains(&webhook.secret.len()) {
+        return Err(HandlerError::BadRequest("Webhook secrets must be 16 to 255 bytes long".to_owned()));
+    }
+
+    webhook.event_types.sort();
+    webhook.event_types.dedup();
+    if webhook.event_types.is_empty() {
+        return Err(HandlerError::BadRequest("A webhook needs at least one event type".to_owned()));
+    }
+    if let Some(unknown) = webhook.event_types.iter().find(|event_type| !WEBHOOK_EVENT_TYPES.contains(&event_type.as_str())) {
+        return Err(HandlerError::BadRequest(format!(
+            "Unknown event type {}, expected one of {}",
+            unknown,
+            WEBHOOK_EVENT_TYPES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn create_webhook(
+    mut webhook: Webhook,
+    actor: &Actor,
+    webhooks_dao: &(dyn WebhooksDao + Send + Sync),
+) -> Result<WebhookDetail, HandlerError> {
+    require_admin(actor, "Only admins can manage webhooks")?;
+    validate_webhook(&mut webhook)?;
+
+    match webhooks_dao.create_subscription(webhook).await {
+        Ok(webhook) => Ok(webhook),
+        Err(err) => {
+            error!("Error creating webhook: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn read_webhooks(
+    actor: &Actor,
+    webhooks_dao: &(dyn WebhooksDao + Send + Sync),
+) -> Result<Vec<WebhookDetail>, HandlerError> {
+    require_admin(actor, "Only admins can manage webhooks")?;
+
+    match webhooks_dao.get_subscriptions().await {
+        Ok(webhooks) => Ok(webhooks),
+        Err(err) => {
+            error!("Error reading webhooks: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn delete_webhook(
+    subscription_uuid: String,
+    actor: &Actor,
+    webhooks_dao: &(dyn WebhooksDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    require_admin(actor, "Only admins can manage webhooks")?;
+
+    match webhooks_dao.delete_subscription(subscription_uuid).await {
+        Ok(()) => Ok(()),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(err) => {
+            error!("Error deleting webhook: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+/// Query parameters of the delivery log endpoint; see `WebhookDeliveryFilter`.
+#[derive(FromForm, Debug, Default)]
+pub struct DeliveryQuery {
+    /// `pending`, `delivered` or `dead` (the dead letters).
+    pub status: Option<String>,
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn read_webhook_deliveries(
+    subscription_uuid: String,
+    query: DeliveryQuery,
+    actor: &Actor,
+    webhooks_dao: &(dyn WebhooksDao + Send + Sync),
+) -> Result<Vec<WebhookDelivery>, HandlerError> {
+    require_admin(actor, "Only admins can manage webhooks")?;
+
+    let status = query
+        .status
+        .map(|status| {
+            DeliveryStatus::parse(&status).ok_or_else(|| {
+                HandlerError::BadRequest(format!("status must be pending, delivered or dead, got {}", status))
+            })
+        })
+        .transpose()?;
+    let filter = WebhookDeliveryFilter {
+        subscription_uuid,
+        status,
+        before_delivery_id: query.before,
+        limit: query.limit.unwrap_or(DEFAULT_DELIVERIES_LIMIT).clamp(1, MAX_DELIVERIES_LIMIT),
+    };
+
+    match webhooks_dao.get_deliveries(filter).await {
+        Ok(deliveries) => Ok(deliveries),
+        Err(DBError::InvalidUUID(s)) => Err(HandlerError::BadRequest(s)),
+        Err(err) => {
+            error!("Error reading webhook deliveries: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn retry_webhook_delivery(
+    delivery_id: i64,
+    actor: &Actor,
+    webhooks_dao: &(dyn WebhooksDao + Send + Sync),
+) -> Result<(), HandlerError> {
+    require_admin(actor, "Only admins can manage webhooks")?;
+
+    match webhooks_dao.retry_delivery(delivery_id).await {
+        Ok(()) => Ok(()),
+        Err(DBError::NotFound(s)) => Err(HandlerError::NotFound(s)),
+        Err(err) => {
+            error!("Error retrying webhook delivery: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+#[instrument(name = "handler", skip_all)]
+pub async fn create_job(
+    job: Job,
+    actor: &Actor,
+    registered_kinds: &[&str],
+    jobs_dao: &(dyn JobsDao + Send + Sync),
+) -> Result<JobId, HandlerError> {
+    require_admin(actor, "Only admins can enqueue jobs")?;
+
+    if !registered_kinds.contains(&job.kind.as_str()) {
+        return Err(HandlerError::BadRequest(format!(
+            "Unknown job kind {:?}; expected one of {}",
+            job.kind,
+            registered_kinds.join(", ")
+        )));
+    }
+    let job = NewJob {
+        kind: job.kind,
+        payload: job.payload.unwrap_or_else(|| serde_json::json!({})),
+        run_at: parse_timestamp(job.run_at, "run_at")?,
+        max_attempts: None,
+    };
+
+    match jobs_dao.enqueue(job).await {
+        Ok(job_id) => Ok(JobId { job_id }),
+        Err(err) => {
+            error!("Error enqueuing job: {:?}", err);
+            Err(HandlerError::default_internal_error())
+        }
+    }
+}
+
+// ***********************************************************
+//                           Tests
+// ***********************************************************
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::Mutex;
+
+    use crate::screening::{BannedPhrasesConfig, ScreeningConfig};
+
+    use crate::models::{BountyStatus, LinkedQuestion, RenderedBody, ReputationEntry, UserDetail};
+    use crate::persistance::jobs_dao::ClaimedJob;
+    use crate::persistance::screening_dao::{ScreeningDao, ScreeningRecord};
+    use crate::persistance::unit_of_work::UnitOfWork;
+    use crate::persistance::webhooks_dao::{DeliveryOutcome, PendingDelivery};
+
+    struct QuestionsDaoMock {
+        create_question_response: Mutex<Option<Result<QuestionDetail, DBError>>>,
+        delete_question_response: Mutex<Option<Result<(), DBError>>>,
+        /// Whether the last delete was made by a moderator.
+        delete_question_privileged: Mutex<Option<bool>>,
+        restore_question_response: Mutex<Option<Result<(), DBError>>>,
+        get_questions_response: Mutex<Option<Result<Vec<QuestionDetail>, DBError>>>,
+        find_similar_response: Mutex<Option<Result<Vec<SimilarQuestion>, DBError>>>,
+        find_related_response: Mutex<Option<Result<Vec<SimilarQuestion>, DBError>>>,
+        update_question_response: Mutex<Option<Result<QuestionDetail, DBError>>>,
+        /// Whether the last edit was made with the edit privilege.
+        update_question_privileged: Mutex<Option<bool>>,
+    }
+
+    impl QuestionsDaoMock {
+        pub fn new() -> Self {
+            QuestionsDaoMock {
+                create_question_response: Mutex::new(None),
+                delete_question_response: Mutex::new(None),
+                delete_question_privileged: Mutex::new(None),
+                restore_question_response: Mutex::new(None),
+                get_questions_response: Mutex::new(None),
+                find_similar_response: Mutex::new(None),
+                find_related_response: Mutex::new(None),
+                update_question_response: Mutex::new(None),
+                update_question_privileged: Mutex::new(None),
+            }
+        }
+        pub fn mock_create_question(&mut self, response: Result<QuestionDetail, DBError>) {
+            self.create_question_response = Mutex::new(Some(response));
+        }
+        pub fn mock_delete_question(&mut self, response: Result<(), DBError>) {
+            self.delete_question_response = Mutex::new(Some(response));
+        }
+        pub fn mock_restore_question(&mut self, response: Result<(), DBError>) {
+            self.restore_question_response = Mutex::new(Some(response));
+        }
+        pub fn mock_get_questions(&mut self, response: Result<Vec<QuestionDetail>, DBError>) {
+            self.get_questions_response = Mutex::new(Some(response));
+        }
+        pub fn mock_find_similar(&mut self, response: Result<Vec<SimilarQuestion>, DBError>) {
+            self.find_similar_response = Mutex::new(Some(response));
+        }
+        pub fn mock_find_related(&mut self, response: Result<Vec<SimilarQuestion>, DBError>) {
+            self.find_related_response = Mutex::new(Some(response));
+        }
+        pub fn mock_update_question(&mut self, response: Result<QuestionDetail, DBError>) {
+            self.update_question_response = Mutex::new(Some(response));
+        }
+    }
+
+    #[async_trait]
+    impl QuestionsDao for QuestionsDaoMock {
+        async fn create_question(&self, _: Question, _: AuditContext) -> Result<QuestionDetail, DBError> {
+            self.create_question_response
+                .lock()
+                .await
+                .take()
+                .expect("create_question_response should not be None.")
+        }
+        async fn delete_question(&self, _: String, _: IfMatch, privileged: bool, _: AuditContext) -> Result<(), DBError> {
+            *self.delete_question_privileged.lock().await = Some(privileged);
+            self.delete_question_response
+                .lock()
+                .await
+                .take()
+                .expect("delete_question_response should not be None.")
+        }
+        async fn restore_question(&self, _: String, _: IfMatch, _: AuditContext) -> Result<(), DBError> {
+            self.restore_question_response
+                .lock()
+                .await
+                .take()
+                .expect("restore_question_response should not be None.")
+        }
+        async fn get_questions(&self, _: QuestionFilter) -> Result<Vec<QuestionDetail>, DBError> {
+            self.get_questions_response
+                .lock()
+                .await
+                .take()
+                .expect("get_questions_response should not be None.")
+        }
+        async fn find_similar(&self, _: Question, _: f32, _: i64) -> Result<Vec<SimilarQuestion>, DBError> {
+            self.find_similar_response
+                .lock()
+                .await
+                .take()
+                .expect("find_similar_response should not be None.")
+        }
+        async fn find_related(&self, _: String, _: f32, _: i64) -> Result<Vec<SimilarQuestion>, DBError> {
+            self.find_related_response
+                .lock()
+                .await
+                .take()
+                .expect("find_related_response should not be None.")
+        }
+        async fn update_question(&self, _: QuestionEdit, _: IfMatch, privileged: bool, _: AuditContext) -> Result<QuestionDetail, DBError> {
+            *self.update_question_privileged.lock().await = Some(privileged);
+            self.update_question_response
+                .lock()
+                .await
+                .take()
+                .expect("update_question_response should not be None.")
+        }
+        async fn purge_deleted(&self, _: Duration) -> Result<u64, DBError> {
+            Ok(0)
+        }
+    }
+
+    /// Links every question to the same ones.
+    struct QuestionLinksDaoMock {
+        linked: Vec<LinkedQuestion>,
+    }
+
+    #[async_trait]
+    impl QuestionLinksDao for QuestionLinksDaoMock {
+        async fn get_linked(&self, _: String, _: i64) -> Result<Vec<LinkedQuestion>, DBError> {
+            Ok(self.linked.clone())
+        }
+    }
+
+    struct AnswersDaoMock {
+        create_answer_response: Mutex<Option<Result<AnswerDetail, DBError>>>,
+        delete_answer_response: Mutex<Option<Result<(), DBError>>>,
+        /// Whether the last delete was made by a moderator.
+        delete_answer_privileged: Mutex<Option<bool>>,
+        restore_answer_response: Mutex<Option<Result<(), DBError>>>,
+        get_answers_response: Mutex<Option<Result<Vec<AnswerDetail>, DBError>>>,
+        update_answer_response: Mutex<Option<Result<AnswerDetail, DBError>>>,
+    }
+
+    impl AnswersDaoMock {
+        pub fn new() -> Self {
+            AnswersDaoMock {
+                create_answer_response: Mutex::new(None),
+                delete_answer_response: Mutex::new(None),
+                delete_answer_privileged: Mutex::new(None),
+                restore_answer_response: Mutex::new(None),
+                get_answers_response: Mutex::new(None),
+                update_answer_response: Mutex::new(None),
+            }
+        }
+        pub fn mock_create_answer(&mut self, response: Result<AnswerDetail, DBError>) {
+            self.create_answer_response = Mutex::new(Some(response));
+        }
+        pub fn mock_delete_answer(&mut self, response: Result<(), DBError>) {
+            self.delete_answer_response = Mutex::new(Some(response));
+        }
+        pub fn mock_restore_answer(&mut self, response: Result<(), DBError>) {
+            self.restore_answer_response = Mutex::new(Some(response));
+        }
+        pub fn mock_get_answers(&mut self, response: Result<Vec<AnswerDetail>, DBError>) {
+            self.get_answers_response = Mutex::new(Some(response));
+        }
+        pub fn mock_update_answer(&mut self, response: Result<AnswerDetail, DBError>) {
+            self.update_answer_response = Mutex::new(Some(response));
+        }
+    }
+
+    #[async_trait]
+    impl AnswersDao for AnswersDaoMock {
+        async fn create_answer(&self, _: Answer, _: AuditContext) -> Result<AnswerDetail, DBError> {
+            self.create_answer_response
+                .lock()
+                .await
+                .take()
+                .expect("create_answer_response should not be None.")
+        }
+        async fn delete_answer(&self, _: String, _: IfMatch, privileged: bool, _: AuditContext) -> Result<(), DBError> {
+            *self.delete_answer_privileged.lock().await = Some(privileged);
+            self.delete_answer_response
+                .lock()
+                .await
+                .take()
+                .expect("delete_answer_response should not be None.")
+        }
+        async fn restore_answer(&self, _: String, _: IfMatch, _: AuditContext) -> Result<(), DBError> {
+            self.restore_answer_response
+                .lock()
+                .await
+                .take()
+                .expect("restore_answer_response should not be None.")
+        }
+        async fn get_answers(&self, _: String, _: bool) -> Result<Vec<AnswerDetail>, DBError> {
+            self.get_answers_response
+                .lock()
+                .await
+                .take()
+                .expect("get_answers_response should not be None.")
+        }
+        async fn update_answer(&self, _: AnswerEdit, _: IfMatch, _: bool, _: AuditContext) -> Result<AnswerDetail, DBError> {
+            self.update_answer_response
+                .lock()
+                .await
+                .take()
+                .expect("update_answer_response should not be None.")
+        }
+        async fn purge_deleted(&self, _: Duration) -> Result<u64, DBError> {
+            Ok(0)
+        }
+    }
+
+    struct UsersDaoMock {
+        create_user_response: Mutex<Option<Result<UserDetail, DBError>>>,
+    }
+
+    impl UsersDaoMock {
+        pub fn new() -> Self {
+            UsersDaoMock {
+                create_user_response: Mutex::new(None),
+            }
+        }
+        pub fn mock_create_user(&mut self, response: Result<UserDetail, DBError>) {
+            self.create_user_response = Mutex::new(Some(response));
+        }
+    }
+
+    #[async_trait]
+    impl UsersDao for UsersDaoMock {
+        async fn create_user(&self, _: User, _: String) -> Result<UserDetail, DBError> {
+            self.create_user_response
+                .lock()
+                .await
+                .take()
+                .expect("create_user_response should not be None.")
+        }
+        async fn get_user_by_api_key_hash(&self, _: String) -> Result<Option<UserDetail>, DBError> {
+            Ok(None)
+        }
+    }
+
+    fn user() -> Actor {
+        Actor {
+            user_uuid: "789".to_owned(),
+            role: Role::User,
+        }
+    }
+
+    /// Returns no events, remembering the filter it was asked for.
+    struct AuditDaoMock {
+        filter: Mutex<Option<AuditFilter>>,
+    }
+
+    impl AuditDaoMock {
+        pub fn new() -> Self {
+            AuditDaoMock {
+                filter: Mutex::new(None),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl AuditDao for AuditDaoMock {
+        async fn get_events(&self, filter: AuditFilter) -> Result<Vec<AuditEvent>, DBError> {
+            *self.filter.lock().await = Some(filter);
+            Ok(vec![])
+        }
+    }
+
+    /// Records the webhook it was asked to create; every other call fails.
+    struct WebhooksDaoMock {
+        created: Mutex<Option<Webhook>>,
+    }
+
+    impl WebhooksDaoMock {
+        pub fn new() -> Self {
+            WebhooksDaoMock {
+                created: Mutex::new(None),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl WebhooksDao for WebhooksDaoMock {
+        async fn create_subscription(&self, webhook: Webhook) -> Result<WebhookDetail, DBError> {
+            let detail = WebhookDetail {
+                subscription_uuid: "123".to_owned(),
+                url: webhook.url.clone(),
+                event_types: webhook.event_types.clone(),
+                created_at: "now".to_owned(),
+            };
+            *self.created.lock().await = Some(webhook);
+            Ok(detail)
+        }
+        async fn get_subscriptions(&self) -> Result<Vec<WebhookDetail>, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn delete_subscription(&self, _: String) -> Result<(), DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn get_deliveries(&self, _: WebhookDeliveryFilter) -> Result<Vec<WebhookDelivery>, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn retry_delivery(&self, _: i64) -> Result<(), DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn claim_due(&self, _: i64, _: Duration) -> Result<Vec<PendingDelivery>, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn record_attempt(&self, _: i64, _: DeliveryOutcome) -> Result<(), DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    struct JobsDaoMock {
+        enqueued: Mutex<Option<NewJob>>,
+    }
+
+    impl JobsDaoMock {
+        pub fn new() -> Self {
+            JobsDaoMock {
+                enqueued: Mutex::new(None),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl JobsDao for JobsDaoMock {
+        async fn enqueue(&self, job: NewJob) -> Result<i64, DBError> {
+            *self.enqueued.lock().await = Some(job);
+            Ok(1)
+        }
+        async fn claim_due(&self, _: i64, _: Duration) -> Result<Vec<ClaimedJob>, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn complete(&self, _: i64) -> Result<(), DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn fail(&self, _: i64, _: String, _: Option<Duration>) -> Result<(), DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn release(&self, _: i64) -> Result<(), DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn enqueue_scheduled(&self, _: &str, _: OffsetDateTime, _: NewJob) -> Result<Option<i64>, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn purge_finished(&self, _: Duration) -> Result<u64, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    /// Every user has the same reputation.
+    struct ReputationDaoMock {
+        reputation: i32,
+    }
+
+    #[async_trait]
+    impl ReputationDao for ReputationDaoMock {
+        async fn get_reputation(&self, _: String) -> Result<i32, DBError> {
+            Ok(self.reputation)
+        }
+        async fn get_history(&self, _: String, _: Option<i64>, _: i64) -> Result<Vec<ReputationEntry>, DBError> {
+            Ok(vec![])
+        }
+        async fn recompute(&self) -> Result<u64, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    /// Records the screening decisions it was asked to log; nothing was posted before.
+    #[derive(Clone, Default)]
+    struct ScreeningDaoMock {
+        records: Arc<Mutex<Vec<ScreeningRecord>>>,
+    }
+
+    #[async_trait]
+    impl ScreeningDao for ScreeningDaoMock {
+        async fn count_recent_posts(&self, _: String, _: Duration) -> Result<i64, DBError> {
+            Ok(0)
+        }
+        async fn count_repeats(&self, _: String, _: Duration) -> Result<i64, DBError> {
+            Ok(0)
+        }
+        async fn record_decision(&self, record: ScreeningRecord, _: i64, _: AuditContext) -> Result<(), DBError> {
+            self.records.lock().await.push(record);
+            Ok(())
+        }
+    }
+
+    /// A screener with the built-in rules, banning "cheap pills".
+    fn screener(screening_dao: &ScreeningDaoMock) -> ContentScreener {
+        let config = ScreeningConfig {
+            banned_phrases: BannedPhrasesConfig {
+                phrases: vec!["cheap pills".to_owned()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        ContentScreener::from_config(&config, Box::new(screening_dao.clone()), 3)
+    }
+
+    /// Records the votes it was asked to cast.
+    struct VotesDaoMock {
+        votes: Mutex<Vec<(PostType, i16)>>,
+        accept_answer_response: Mutex<Option<Result<(), DBError>>>,
+    }
+
+    impl VotesDaoMock {
+        pub fn new() -> Self {
+            VotesDaoMock {
+                votes: Mutex::new(vec![]),
+                accept_answer_response: Mutex::new(None),
+            }
+        }
+        pub fn mock_accept_answer(&mut self, response: Result<(), DBError>) {
+            self.accept_answer_response = Mutex::new(Some(response));
+        }
+    }
+
+    #[async_trait]
+    impl VotesDao for VotesDaoMock {
+        async fn vote(&self, target_type: PostType, _: String, value: i16, _: AuditContext) -> Result<(), DBError> {
+            self.votes.lock().await.push((target_type, value));
+            Ok(())
+        }
+        async fn accept_answer(&self, _: String, _: AuditContext) -> Result<(), DBError> {
+            self.accept_answer_response
+                .lock()
+                .await
+                .take()
+                .expect("accept_answer_response should not be None.")
+        }
+    }
+
+    /// Records the bounties it was asked to open and answers with the mocked response.
+    struct BountiesDaoMock {
+        offered: Mutex<Vec<(i32, Duration)>>,
+        response: Result<BountyDetail, DBError>,
+    }
+
+    impl BountiesDaoMock {
+        pub fn new(response: Result<BountyDetail, DBError>) -> Self {
+            BountiesDaoMock {
+                offered: Mutex::new(vec![]),
+                response,
+            }
+        }
+        fn response(&self) -> Result<BountyDetail, DBError> {
+            match &self.response {
+                Ok(bounty) => Ok(bounty.clone()),
+                Err(DBError::Conflict(s)) => Err(DBError::Conflict(s.clone())),
+                Err(DBError::Forbidden(s)) => Err(DBError::Forbidden(s.clone())),
+                Err(_) => Err(DBError::Other("not mocked".into())),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl BountiesDao for BountiesDaoMock {
+        async fn offer_bounty(&self, bounty: Bounty, duration: Duration, _: AuditContext) -> Result<BountyDetail, DBError> {
+            self.offered.lock().await.push((bounty.amount, duration));
+            self.response()
+        }
+        async fn award_bounty(&self, _: String, _: AuditContext) -> Result<BountyDetail, DBError> {
+            self.response()
+        }
+        async fn expire_due(&self, _: i64) -> Result<u64, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    /// Records the votes it was asked to cast, as `(votes_needed, binding)`, and closes nothing.
+    struct ClosingDaoMock {
+        votes: Mutex<Vec<(i64, bool)>>,
+    }
+
+    #[async_trait]
+    impl ClosingDao for ClosingDaoMock {
+        async fn vote_to_close(&self, vote: CloseVote, votes_needed: i64, binding: bool, _: AuditContext) -> Result<ClosureVotes, DBError> {
+            self.votes.lock().await.push((votes_needed, binding));
+            Ok(ClosureVotes {
+                question_uuid: vote.question_uuid,
+                votes: 1,
+                votes_needed,
+                closed: binding,
+            })
+        }
+        async fn vote_to_reopen(&self, _: String, _: i64, _: bool, _: AuditContext) -> Result<ClosureVotes, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    /// Counts the flags it was asked to create and resolve, and answers with a pending one.
+    struct FlagsDaoMock {
+        calls: Mutex<usize>,
+    }
+
+    impl FlagsDaoMock {
+        fn flag(flag_id: i64, status: FlagStatus) -> FlagDetail {
+            FlagDetail {
+                flag_id,
+                target_type: PostType::Question,
+                target_uuid: "123".to_owned(),
+                flagged_by: Some("456".to_owned()),
+                reason: FlagReason::Spam,
+                details: None,
+                status,
+                claimed_by: None,
+                claimed_at: None,
+                resolved_by: None,
+                resolved_at: None,
+                created_at: "now".to_owned(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl FlagsDao for FlagsDaoMock {
+        async fn create_flag(&self, _: Flag, _: i64, _: AuditContext) -> Result<FlagDetail, DBError> {
+            *self.calls.lock().await += 1;
+            Ok(Self::flag(1, FlagStatus::Pending))
+        }
+        async fn get_queue(&self, _: String, _: Duration, _: i64) -> Result<Vec<FlagDetail>, DBError> {
+            *self.calls.lock().await += 1;
+            Ok(vec![Self::flag(1, FlagStatus::Pending)])
+        }
+        async fn claim_flag(&self, _: i64, _: Duration, _: AuditContext) -> Result<FlagDetail, DBError> {
+            Err(DBError::Conflict("Flag 1 is claimed by another moderator".to_owned()))
+        }
+        async fn resolve_flag(&self, flag_id: i64, status: FlagStatus, _: i64, _: Duration, _: AuditContext) -> Result<FlagDetail, DBError> {
+            *self.calls.lock().await += 1;
+            Ok(Self::flag(flag_id, status))
+        }
+        async fn get_stats(&self, _: String) -> Result<FlagStats, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    /// Counts the suggested edits it was asked to create, answering with a pending one.
+    struct SuggestedEditsDaoMock {
+        calls: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl SuggestedEditsDao for SuggestedEditsDaoMock {
+        async fn create_suggested_edit(&self, edit: SuggestedEdit, _: AuditContext) -> Result<SuggestedEditDetail, DBError> {
+            *self.calls.lock().await += 1;
+            Ok(SuggestedEditDetail {
+                edit_id: 1,
+                target_type: edit.target_type,
+                target_uuid: edit.target_uuid,
+                suggested_by: "789".to_owned(),
+                title: edit.title,
+                description: edit.description,
+                content: edit.content,
+                summary: edit.summary,
+                base_version: 1,
+                status: SuggestedEditStatus::Pending,
+                reviewed_by: None,
+                review_comment: None,
+                reviewed_at: None,
+                created_at: "now".to_owned(),
+            })
+        }
+        async fn get_queue(&self, _: i64) -> Result<Vec<SuggestedEditDetail>, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn lock_pending(&self, _: i64) -> Result<SuggestedEditDetail, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+        async fn record_review(&self, _: i64, _: SuggestedEditReview, _: AuditContext) -> Result<SuggestedEditDetail, DBError> {
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    /// Counts the units of work it was asked to begin, without beginning any.
+    struct TransactionManagerMock {
+        calls: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl TransactionManager for TransactionManagerMock {
+        async fn begin(&self) -> Result<Box<dyn UnitOfWork + Send + Sync>, DBError> {
+            *self.calls.lock().await += 1;
+            Err(DBError::Other("not mocked".into()))
+        }
+    }
+
+    fn bounty_detail() -> BountyDetail {
+        BountyDetail {
+            bounty_id: 1,
+            question_uuid: "123".to_owned(),
+            offered_by: "456".to_owned(),
+            amount: 100,
+            status: BountyStatus::Open,
+            awarded_answer_uuid: None,
+            awarded_amount: None,
+            created_at: "now".to_owned(),
+            expires_at: "next week".to_owned(),
+            closed_at: None,
+        }
+    }
+
+    fn webhook(url: &str, event_types: &[&str]) -> Webhook {
+        Webhook {
+            url: url.to_owned(),
+            event_types: event_types.iter().map(|event_type| event_type.to_string()).collect(),
+            secret: "0123456789abcdef".to_owned(),
+        }
+    }
+
+    fn moderator() -> Actor {
+        Actor {
+            user_uuid: "790".to_owned(),
+            role: Role::Moderator,
+        }
+    }
+
+    fn admin() -> Actor {
+        Actor {
+            user_uuid: "791".to_owned(),
+            role: Role::Admin,
+        }
+    }
+
+    #[tokio::test]
+    async fn create_question_should_return_question() {
+        let question = Question {
+            title: "test title".to_owned(),
+            description: "test description".to_owned(),
+        };
+
+        let question_detail = QuestionDetail {
+            question_uuid: "123".to_owned(),
+            title: question.title.clone(),
+            description: question.description.clone(),
+            body: RenderedBody::new(&question.description),
+            created_at: "now".to_owned(),
+            deleted_at: None,
+            hidden_at: None,
+            accepted_answer_uuid: None,
+            bounty: None,
+            closed_reason: None,
+            duplicate_of: None,
+            version: 1,
+        };
+
+        let mut questions_dao = QuestionsDaoMock::new();
+
+        questions_dao.mock_create_question(Ok(question_detail.clone()));
+
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+
+        let result = create_question(
+            question,
+            None,
+            AuditContext::default(),
+            &screener(&ScreeningDaoMock::default()),
+            &ReputationDaoMock { reputation: 0 },
+            questions_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), question_detail);
+    }
+
+    #[tokio::test]
+    async fn create_question_should_validate_lengths() {
+        let questions_dao = QuestionsDaoMock::new();
+        let question = |title: String, description: String| Question { title, description };
+
+        for question in [
+            question("x".repeat(MAX_TITLE_LENGTH + 1), "test description".to_owned()),
+            question("test title".to_owned(), "x".repeat(MAX_BODY_LENGTH + 1)),
+        ] {
+            let result = create_question(
+                question,
+                None,
+                AuditContext::default(),
+                &screener(&ScreeningDaoMock::default()),
+                &ReputationDaoMock { reputation: 0 },
+                &questions_dao,
+            )
+            .await;
+            assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn create_question_should_return_error() {
+        let question = Question {
+            title: "test title".to_owned(),
+            description: "test description".to_owned(),
+        };
+
+        let mut questions_dao = QuestionsDaoMock::new();
+
+        questions_dao.mock_create_question(Err(DBError::InvalidUUID("test".to_owned())));
+
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+
+        let result = create_question(
+            question,
+            None,
+            AuditContext::default(),
+            &screener(&ScreeningDaoMock::default()),
+            &ReputationDaoMock { reputation: 0 },
+            questions_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(
+            std::mem::discriminant(&result.unwrap_err())
+                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn find_similar_questions_should_return_similar_questions() {
+        let similar = SimilarQuestion {
+            question_uuid: "123".to_owned(),
+            title: "How to parse JSON in Rust".to_owned(),
+            similarity: 0.6,
+            answer_count: 2,
+            accepted_answer_uuid: Some("456".to_owned()),
+            duplicate_of: None,
+        };
+
+        let mut questions_dao = QuestionsDaoMock::new();
+        questions_dao.mock_find_similar(Ok(vec![similar.clone()]));
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+
+        let question = Question {
+            title: "Parsing JSON with Rust".to_owned(),
+            description: "".to_owned(),
+        };
+        let result = find_similar_questions(question, &SimilarQuestionsConfig::default(), questions_dao.as_ref()).await;
+
+        assert_eq!(result, Ok(vec![similar]));
+    }
+
+    #[tokio::test]
+    async fn find_similar_questions_should_need_some_text() {
+        // Not mocked, so searching would panic
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(QuestionsDaoMock::new());
+
+        let question = Question {
+            title: " ".to_owned(),
+            description: "".to_owned(),
+        };
+        let result = find_similar_questions(question, &SimilarQuestionsConfig::default(), questions_dao.as_ref()).await;
+
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn read_related_questions_should_return_related_and_linked_questions() {
+        let related = SimilarQuestion {
+            question_uuid: "456".to_owned(),
+            title: "How to parse JSON in Rust".to_owned(),
+            similarity: 0.5,
+            answer_count: 0,
+            accepted_answer_uuid: None,
+            duplicate_of: None,
+        };
+        let linked = LinkedQuestion {
+            question_uuid: "789".to_owned(),
+            title: "What is serde".to_owned(),
+            answer_count: 1,
+            accepted_answer_uuid: None,
+            linked_at: "now".to_owned(),
+        };
+
+        let mut questions_dao = QuestionsDaoMock::new();
+        questions_dao.mock_find_related(Ok(vec![related.clone()]));
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+        let question_links_dao = QuestionLinksDaoMock { linked: vec![linked.clone()] };
+
+        let result = read_related_questions("123".to_owned(), &SimilarQuestionsConfig::default(), questions_dao.as_ref(), &question_links_dao).await;
+
+        assert_eq!(result, Ok(RelatedQuestions { related: vec![related], linked: vec![linked] }));
+    }
+
+    #[tokio::test]
+    async fn read_related_questions_should_return_not_found() {
+        let mut questions_dao = QuestionsDaoMock::new();
+        questions_dao.mock_find_related(Err(DBError::NotFound("No question with ID 123".to_owned())));
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+        let question_links_dao = QuestionLinksDaoMock { linked: vec![] };
+
+        let result = read_related_questions("123".to_owned(), &SimilarQuestionsConfig::default(), questions_dao.as_ref(), &question_links_dao).await;
+
+        assert_eq!(result, Err(HandlerError::NotFound("No question with ID 123".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn read_questions_should_return_questions() {
+        let question_detail = QuestionDetail {
+            question_uuid: "123".to_owned(),
+            title: "test title".to_owned(),
+            description: "test description".to_owned(),
+            body: RenderedBody::new("test description"),
+            created_at: "now".to_owned(),
+            deleted_at: None,
+            hidden_at: None,
+            accepted_answer_uuid: None,
+            bounty: None,
+            closed_reason: None,
+            duplicate_of: None,
+            version: 1,
+        };
+
+        let mut questions_dao = QuestionsDaoMock::new();
+
+        questions_dao.mock_get_questions(Ok(vec![question_detail.clone()]));
+
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+
+        let result = read_questions(false, None, None, questions_dao.as_ref()).await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), vec![question_detail]);
+    }
+
+    #[tokio::test]
+    async fn read_questions_should_return_error() {
+        let mut questions_dao = QuestionsDaoMock::new();
+
+        questions_dao.mock_get_questions(Err(DBError::InvalidUUID("test".to_owned())));
+
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+
+        let result = read_questions(false, None, None, questions_dao.as_ref()).await;
+
+        assert!(result.is_err());
+        assert!(
+            std::mem::discriminant(&result.unwrap_err())
+                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_question_should_succeed() {
+        let question_id = QuestionId {
+            question_uuid: "123".to_owned(),
+        };
+
+        let mut questions_dao = QuestionsDaoMock::new();
+
+        questions_dao.mock_delete_question(Ok(()));
+
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+
+        let result = delete_question(
+            question_id,
+            &user(),
+            IfMatch::Any,
+            AuditContext::default(),
+            questions_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), ());
+    }
+
+    #[tokio::test]
+    async fn delete_question_should_return_error() {
+        let question_id = QuestionId {
+            question_uuid: "123".to_owned(),
+        };
+
+        let mut questions_dao = QuestionsDaoMock::new();
+
+        questions_dao.mock_delete_question(Err(DBError::InvalidUUID("test".to_owned())));
+
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+
+        let result = delete_question(
+            question_id,
+            &user(),
+            IfMatch::Any,
+            AuditContext::default(),
+            questions_dao.as_ref(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn delete_question_should_forbid_other_users() {
+        let mut questions_dao = QuestionsDaoMock::new();
+        questions_dao.mock_delete_question(Err(DBError::Forbidden("Only the author or a moderator can delete this question".to_owned())));
+
+        let result = delete_question(QuestionId { question_uuid: "123".to_owned() }, &user(), IfMatch::Any, AuditContext::default(), &questions_dao).await;
+
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert_eq!(*questions_dao.delete_question_privileged.lock().await, Some(false));
+    }
+
+    #[tokio::test]
+    async fn delete_question_should_privilege_moderators() {
+        let mut questions_dao = QuestionsDaoMock::new();
+        questions_dao.mock_delete_question(Ok(()));
+
+        let result = delete_question(QuestionId { question_uuid: "123".to_owned() }, &moderator(), IfMatch::Any, AuditContext::default(), &questions_dao).await;
+
+        assert!(result.is_ok());
+        assert_eq!(*questions_dao.delete_question_privileged.lock().await, Some(true));
+    }
+
+    #[tokio::test]
+    async fn create_answer_should_return_answer() {
+        let answer = Answer {
+            question_uuid: "123".to_owned(),
+            content: "test content".to_owned(),
+        };
+
+        let answer_detail = AnswerDetail {
+            answer_uuid: "456".to_owned(),
+            question_uuid: answer.question_uuid.clone(),
+            content: answer.content.clone(),
+            body: RenderedBody::new(&answer.content),
+            created_at: "now".to_owned(),
+            deleted_at: None,
+            hidden_at: None,
+            version: 1,
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_create_answer(Ok(answer_detail.clone()));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = create_answer(
+            answer,
+            None,
+            AuditContext::default(),
+            &screener(&ScreeningDaoMock::default()),
+            &ReputationDaoMock { reputation: 0 },
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), answer_detail);
+    }
+
+    #[tokio::test]
+    async fn create_answer_should_return_bad_request_error() {
+        let answer = Answer {
+            question_uuid: "123".to_owned(),
+            content: "test content".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_create_answer(Err(DBError::InvalidUUID("test".to_owned())));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = create_answer(
+            answer,
+            None,
+            AuditContext::default(),
+            &screener(&ScreeningDaoMock::default()),
+            &ReputationDaoMock { reputation: 0 },
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(
+            std::mem::discriminant(&result.unwrap_err())
+                == std::mem::discriminant(&HandlerError::BadRequest("".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn create_answer_should_return_conflict_for_closed_questions() {
+        let answer = Answer {
+            question_uuid: "123".to_owned(),
+            content: "test content".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_create_answer(Err(DBError::QuestionClosed("Closed as off_topic".to_owned())));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = create_answer(
+            answer,
+            None,
+            AuditContext::default(),
+            &screener(&ScreeningDaoMock::default()),
+            &ReputationDaoMock { reputation: 0 },
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert_eq!(result, Err(HandlerError::Conflict("Closed as off_topic".to_owned())));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::io_other_error)]
+    async fn create_answer_should_return_internal_error() {
+        let answer = Answer {
+            question_uuid: "123".to_owned(),
+            content: "test content".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_create_answer(Err(DBError::Other(Box::new(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "oh no!",
+        )))));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = create_answer(
+            answer,
+            None,
+            AuditContext::default(),
+            &screener(&ScreeningDaoMock::default()),
+            &ReputationDaoMock { reputation: 0 },
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(
+            std::mem::discriminant(&result.unwrap_err())
+                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn create_question_should_refuse_rejected_posts() {
+        let question = Question {
+            title: "Where to buy".to_owned(),
+            description: "Cheap   PILLS for everyone".to_owned(),
+        };
+
+        // Not mocked, so creating the question would panic
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(QuestionsDaoMock::new());
+        let screening_dao = ScreeningDaoMock::default();
+
+        let result = create_question(
+            question,
+            Some(&user()),
+            AuditContext::default(),
+            &screener(&screening_dao),
+            &ReputationDaoMock { reputation: 100 },
+            questions_dao.as_ref(),
+        )
+        .await;
+
+        assert_eq!(
+            result,
+            Err(HandlerError::BadRequest("Your question was rejected: contains the banned phrase \"cheap pills\"".to_owned()))
+        );
+        let records = screening_dao.records.lock().await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].decision, ScreeningDecision::Rejected);
+        assert_eq!(records[0].rule.as_deref(), Some("banned_phrases"));
+        assert_eq!(records[0].target_uuid, None);
+    }
+
+    #[tokio::test]
+    async fn create_answer_should_quarantine_links_from_new_users() {
+        let answer = Answer {
+            question_uuid: "123".to_owned(),
+            content: "https://a.example https://b.example https://c.example".to_owned(),
+        };
+        let answer_detail = AnswerDetail {
+            answer_uuid: "456".to_owned(),
+            question_uuid: answer.question_uuid.clone(),
+            content: answer.content.clone(),
+            body: RenderedBody::new(&answer.content),
+            created_at: "now".to_owned(),
+            deleted_at: None,
+            hidden_at: None,
+            version: 1,
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+        answers_dao.mock_create_answer(Ok(answer_detail.clone()));
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+        let screening_dao = ScreeningDaoMock::default();
+
+        let result = create_answer(
+            answer,
+            Some(&user()),
+            AuditContext::default(),
+            &screener(&screening_dao),
+            &ReputationDaoMock { reputation: 1 },
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert_eq!(result, Ok(answer_detail));
+        let records = screening_dao.records.lock().await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].decision, ScreeningDecision::Quarantined);
+        assert_eq!(records[0].rule.as_deref(), Some("links"));
+        assert_eq!(records[0].target_uuid.as_deref(), Some("456"));
+        assert_eq!(records[0].author_uuid, Some(user().user_uuid));
+    }
+
+    #[tokio::test]
+    async fn create_answer_should_not_screen_moderators() {
+        let answer = Answer {
+            question_uuid: "123".to_owned(),
+            content: "cheap pills at https://a.example https://b.example https://c.example".to_owned(),
+        };
+        let answer_detail = AnswerDetail {
+            answer_uuid: "456".to_owned(),
+            question_uuid: answer.question_uuid.clone(),
+            content: answer.content.clone(),
+            body: RenderedBody::new(&answer.content),
+            created_at: "now".to_owned(),
+            deleted_at: None,
+            hidden_at: None,
+            version: 1,
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+        answers_dao.mock_create_answer(Ok(answer_detail.clone()));
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+        let screening_dao = ScreeningDaoMock::default();
+
+        let result = create_answer(
+            answer,
+            Some(&moderator()),
+            AuditContext::default(),
+            &screener(&screening_dao),
+            &ReputationDaoMock { reputation: 0 },
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert_eq!(result, Ok(answer_detail));
+        assert!(screening_dao.records.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_answers_should_return_answers() {
+        let answer_detail = AnswerDetail {
+            answer_uuid: "456".to_owned(),
+            question_uuid: "123".to_owned(),
+            content: "test content".to_owned(),
+            body: RenderedBody::new("test content"),
+            created_at: "now".to_owned(),
+            deleted_at: None,
+            hidden_at: None,
+            version: 1,
+        };
+
+        let question_id = QuestionId {
+            question_uuid: "123".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_get_answers(Ok(vec![answer_detail.clone()]));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = read_answers(question_id, false, None, answers_dao.as_ref()).await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), vec![answer_detail]);
+    }
+
+    #[tokio::test]
+    async fn read_answers_should_return_error() {
+        let question_id = QuestionId {
+            question_uuid: "123".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_get_answers(Err(DBError::InvalidUUID("test".to_owned())));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = read_answers(question_id, false, None, answers_dao.as_ref()).await;
+
+        assert!(result.is_err());
+        assert!(
+            std::mem::discriminant(&result.unwrap_err())
+                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_answer_should_succeed() {
+        let answer_id = AnswerId {
+            answer_uuid: "123".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_delete_answer(Ok(()));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = delete_answer(
+            answer_id,
+            &user(),
+            IfMatch::Any,
+            AuditContext::default(),
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), ());
+    }
+
+    #[tokio::test]
+    async fn delete_answer_should_return_error() {
+        let answer_id = AnswerId {
+            answer_uuid: "123".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_delete_answer(Err(DBError::InvalidUUID("test".to_owned())));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = delete_answer(
+            answer_id,
+            &user(),
+            IfMatch::Any,
+            AuditContext::default(),
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn delete_answer_should_forbid_other_users() {
+        let mut answers_dao = AnswersDaoMock::new();
+        answers_dao.mock_delete_answer(Err(DBError::Forbidden("Only the author or a moderator can delete this answer".to_owned())));
+
+        let result = delete_answer(AnswerId { answer_uuid: "123".to_owned() }, &user(), IfMatch::Any, AuditContext::default(), &answers_dao).await;
+
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert_eq!(*answers_dao.delete_answer_privileged.lock().await, Some(false));
+    }
+
+    #[tokio::test]
+    async fn delete_answer_should_privilege_moderators() {
+        let mut answers_dao = AnswersDaoMock::new();
+        answers_dao.mock_delete_answer(Ok(()));
+
+        let result = delete_answer(AnswerId { answer_uuid: "123".to_owned() }, &moderator(), IfMatch::Any, AuditContext::default(), &answers_dao).await;
+
+        assert!(result.is_ok());
+        assert_eq!(*answers_dao.delete_answer_privileged.lock().await, Some(true));
+    }
+
+    #[tokio::test]
+    async fn read_questions_should_forbid_deleted_for_non_moderators() {
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(QuestionsDaoMock::new());
+
+        let anonymous = read_questions(true, None, None, questions_dao.as_ref()).await;
+        let regular_user = read_questions(true, None, Some(&user()), questions_dao.as_ref()).await;
+
+        assert!(matches!(anonymous, Err(HandlerError::Forbidden(_))));
+        assert!(matches!(regular_user, Err(HandlerError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn read_questions_should_reject_unknown_bounty_filters() {
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(QuestionsDaoMock::new());
+
+        let result = read_questions(false, Some("closed".to_owned()), None, questions_dao.as_ref()).await;
+
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
     }
 
     #[tokio::test]
-    async fn create_question_should_return_question() {
-        let question = Question {
-            title: "test title".to_owned(),
-            description: "test description".to_owned(),
-        };
-
+    async fn read_questions_should_include_deleted_for_moderators() {
         let question_detail = QuestionDetail {
             question_uuid: "123".to_owned(),
-            title: question.title.clone(),
-            description: question.description.clone(),
+            title: "test title".to_owned(),
+            description: "test description".to_owned(),
+            body: RenderedBody::new("test description"),
             created_at: "now".to_owned(),
+            deleted_at: Some("yesterday".to_owned()),
+            hidden_at: None,
+            accepted_answer_uuid: None,
+            bounty: None,
+            closed_reason: None,
+            duplicate_of: None,
+            version: 1,
         };
 
         let mut questions_dao = QuestionsDaoMock::new();
 
-        questions_dao.mock_create_question(Ok(question_detail.clone()));
+        questions_dao.mock_get_questions(Ok(vec![question_detail.clone()]));
 
         let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
 
-        let result = create_question(question, &questions_dao).await;
+        let result = read_questions(true, None, Some(&moderator()), questions_dao.as_ref()).await;
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), question_detail);
+        assert_eq!(result, Ok(vec![question_detail]));
     }
 
     #[tokio::test]
-    async fn create_question_should_return_error() {
-        let question = Question {
-            title: "test title".to_owned(),
-            description: "test description".to_owned(),
+    async fn restore_question_should_require_moderator() {
+        let question_id = QuestionId {
+            question_uuid: "123".to_owned(),
+        };
+
+        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(QuestionsDaoMock::new());
+
+        let result = restore_question(
+            question_id,
+            &user(),
+            IfMatch::Any,
+            AuditContext::default(),
+            questions_dao.as_ref(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn restore_question_should_return_not_found() {
+        let question_id = QuestionId {
+            question_uuid: "123".to_owned(),
         };
 
         let mut questions_dao = QuestionsDaoMock::new();
 
-        questions_dao.mock_create_question(Err(DBError::InvalidUUID("test".to_owned())));
+        questions_dao.mock_restore_question(Err(DBError::NotFound("test".to_owned())));
 
         let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
 
-        let result = create_question(question, &questions_dao).await;
+        let result = restore_question(
+            question_id,
+            &moderator(),
+            IfMatch::Any,
+            AuditContext::default(),
+            questions_dao.as_ref(),
+        )
+        .await;
 
-        assert!(result.is_err());
-        assert!(
-            std::mem::discriminant(&result.unwrap_err())
-                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
-        );
+        assert_eq!(result, Err(HandlerError::NotFound("test".to_owned())));
     }
 
     #[tokio::test]
-    async fn read_questions_should_return_questions() {
-        let question_detail = QuestionDetail {
+    async fn restore_answer_should_succeed_for_moderators() {
+        let answer_id = AnswerId {
+            answer_uuid: "456".to_owned(),
+        };
+
+        let mut answers_dao = AnswersDaoMock::new();
+
+        answers_dao.mock_restore_answer(Ok(()));
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+
+        let result = restore_answer(
+            answer_id,
+            &moderator(),
+            IfMatch::Any,
+            AuditContext::default(),
+            answers_dao.as_ref(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn read_answers_should_forbid_deleted_for_non_moderators() {
+        let question_id = QuestionId {
             question_uuid: "123".to_owned(),
-            title: "test title".to_owned(),
-            description: "test description".to_owned(),
+        };
+
+        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(AnswersDaoMock::new());
+
+        let result = read_answers(question_id, true, Some(&user()), answers_dao.as_ref()).await;
+
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn create_user_should_return_api_key() {
+        let user_detail = UserDetail {
+            user_uuid: "789".to_owned(),
+            username: "alice".to_owned(),
+            role: Role::User,
             created_at: "now".to_owned(),
         };
 
-        let mut questions_dao = QuestionsDaoMock::new();
+        let mut users_dao = UsersDaoMock::new();
 
-        questions_dao.mock_get_questions(Ok(vec![question_detail.clone()]));
+        users_dao.mock_create_user(Ok(user_detail.clone()));
 
-        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+        let users_dao: Box<dyn UsersDao + Send + Sync> = Box::new(users_dao);
 
-        let result = read_questions(&questions_dao).await;
+        let result = create_user(User { username: "alice".to_owned() }, users_dao.as_ref())
+            .await
+            .unwrap();
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), vec![question_detail]);
+        assert_eq!(result.user, user_detail);
+        assert_eq!(result.api_key.len(), 64);
     }
 
     #[tokio::test]
-    async fn read_questions_should_return_error() {
-        let mut questions_dao = QuestionsDaoMock::new();
+    async fn create_user_should_return_conflict() {
+        let mut users_dao = UsersDaoMock::new();
 
-        questions_dao.mock_get_questions(Err(DBError::InvalidUUID("test".to_owned())));
+        users_dao.mock_create_user(Err(DBError::Conflict("taken".to_owned())));
 
-        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+        let users_dao: Box<dyn UsersDao + Send + Sync> = Box::new(users_dao);
 
-        let result = read_questions(&questions_dao).await;
+        let result = create_user(User { username: "alice".to_owned() }, users_dao.as_ref()).await;
 
-        assert!(result.is_err());
-        assert!(
-            std::mem::discriminant(&result.unwrap_err())
-                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
-        );
+        assert_eq!(result, Err(HandlerError::Conflict("taken".to_owned())));
     }
 
     #[tokio::test]
-    async fn delete_question_should_succeed() {
-        let question_id = QuestionId {
-            question_uuid: "123".to_owned(),
+    async fn read_audit_events_should_be_admin_only() {
+        let audit_dao = AuditDaoMock::new();
+
+        let result = read_audit_events(AuditQuery::default(), &moderator(), &audit_dao).await;
+
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert!(audit_dao.filter.lock().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn read_audit_events_should_build_filter() {
+        let audit_dao = AuditDaoMock::new();
+        let query = AuditQuery {
+            action: Some("question.deleted".to_owned()),
+            since: Some("2025-02-19T10:00:00Z".to_owned()),
+            limit: Some(1_000_000),
+            ..Default::default()
         };
 
-        let mut questions_dao = QuestionsDaoMock::new();
+        let result = read_audit_events(query, &admin(), &audit_dao).await;
 
-        questions_dao.mock_delete_question(Ok(()));
+        assert_eq!(result, Ok(vec![]));
+        let filter = audit_dao.filter.lock().await.take().unwrap();
+        assert_eq!(filter.action.as_deref(), Some("question.deleted"));
+        assert_eq!(filter.since.map(|since| since.unix_timestamp()), Some(1_739_959_200));
+        assert_eq!(filter.limit, MAX_AUDIT_LIMIT);
+    }
 
-        let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
+    #[tokio::test]
+    async fn read_audit_events_should_reject_malformed_timestamps() {
+        let audit_dao = AuditDaoMock::new();
+        let query = AuditQuery {
+            until: Some("yesterday".to_owned()),
+            ..Default::default()
+        };
 
-        let result = delete_question(question_id, &questions_dao).await;
+        let result = read_audit_events(query, &admin(), &audit_dao).await;
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), ());
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
     }
 
     #[tokio::test]
-    async fn delete_question_should_return_error() {
+    async fn delete_question_should_return_precondition_failed() {
         let question_id = QuestionId {
             question_uuid: "123".to_owned(),
         };
 
         let mut questions_dao = QuestionsDaoMock::new();
 
-        questions_dao.mock_delete_question(Err(DBError::InvalidUUID("test".to_owned())));
+        questions_dao.mock_delete_question(Err(DBError::PreconditionFailed("changed".to_owned())));
 
         let questions_dao: Box<dyn QuestionsDao + Send + Sync> = Box::new(questions_dao);
 
-        let result = delete_question(question_id, &questions_dao).await;
+        let result = delete_question(
+            question_id,
+            &user(),
+            IfMatch::Versions(vec![1]),
+            AuditContext::default(),
+            questions_dao.as_ref(),
+        )
+        .await;
 
-        assert!(result.is_err());
-        assert!(
-            std::mem::discriminant(&result.unwrap_err())
-                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
-        );
+        assert_eq!(result, Err(HandlerError::PreconditionFailed("changed".to_owned())));
     }
 
     #[tokio::test]
-    async fn create_answer_should_return_answer() {
-        let answer = Answer {
-            question_uuid: "123".to_owned(),
-            content: "test content".to_owned(),
-        };
+    async fn create_webhook_should_be_admin_only() {
+        let webhooks_dao = WebhooksDaoMock::new();
 
-        let answer_detail = AnswerDetail {
-            answer_uuid: "456".to_owned(),
-            question_uuid: answer.question_uuid.clone(),
-            content: answer.content.clone(),
-            created_at: "now".to_owned(),
+        let result = create_webhook(webhook("https://chat.example/hook", &["answer.created"]), &moderator(), &webhooks_dao).await;
+
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert!(webhooks_dao.created.lock().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn create_webhook_should_validate_subscription() {
+        let webhooks_dao = WebhooksDaoMock::new();
+        let mut short_secret = webhook("https://chat.example/hook", &["answer.created"]);
+        short_secret.secret = "secret".to_owned();
+
+        for invalid in [
+            webhook("chat.example/hook", &["answer.created"]),
+            webhook("ftp://chat.example/hook", &["answer.created"]),
+            webhook("http://127.0.0.1:8000/hook", &["answer.created"]),
+            webhook("http://169.254.169.254/latest/meta-data", &["answer.created"]),
+            webhook("https://10.0.0.5/hook", &["answer.created"]),
+            webhook("http://[::ffff:192.168.1.1]/hook", &["answer.created"]),
+            webhook("http://api.localhost/hook", &["answer.created"]),
+            webhook("https://chat.example/hook", &[]),
+            webhook("https://chat.example/hook", &["answer.commented"]),
+            short_secret,
+        ] {
+            let result = create_webhook(invalid, &admin(), &webhooks_dao).await;
+            assert!(matches!(result, Err(HandlerError::BadRequest(_))), "{:?}", result);
+        }
+
+        let result = create_webhook(
+            webhook("https://chat.example/hook", &["question.created", "answer.created", "question.created"]),
+            &admin(),
+            &webhooks_dao,
+        )
+        .await;
+
+        assert_eq!(result.map(|webhook| webhook.event_types), Ok(vec!["answer.created".to_owned(), "question.created".to_owned()]));
+    }
+
+    #[tokio::test]
+    async fn read_webhook_deliveries_should_reject_unknown_status() {
+        let webhooks_dao = WebhooksDaoMock::new();
+        let query = DeliveryQuery {
+            status: Some("failed".to_owned()),
+            ..Default::default()
         };
 
-        let mut answers_dao = AnswersDaoMock::new();
+        let result = read_webhook_deliveries("123".to_owned(), query, &admin(), &webhooks_dao).await;
 
-        answers_dao.mock_create_answer(Ok(answer_detail.clone()));
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+    }
 
-        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+    #[tokio::test]
+    async fn create_job_should_only_enqueue_registered_kinds() {
+        let jobs_dao = JobsDaoMock::new();
+        let job = |kind: &str| Job {
+            kind: kind.to_owned(),
+            payload: None,
+            run_at: None,
+        };
 
-        let result = create_answer(answer, &answers_dao).await;
+        let result = create_job(job("purge_deleted"), &moderator(), &["purge_deleted"], &jobs_dao).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), answer_detail);
+        let result = create_job(job("send_digests"), &admin(), &["purge_deleted"], &jobs_dao).await;
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        assert!(jobs_dao.enqueued.lock().await.is_none());
+
+        let result = create_job(job("purge_deleted"), &admin(), &["purge_deleted"], &jobs_dao).await;
+        assert_eq!(result, Ok(JobId { job_id: 1 }));
+        let enqueued = jobs_dao.enqueued.lock().await.take().unwrap();
+        assert_eq!(enqueued.payload, serde_json::json!({}));
+        assert_eq!(enqueued.run_at, None);
     }
 
     #[tokio::test]
-    async fn create_answer_should_return_bad_request_error() {
-        let answer = Answer {
+    async fn vote_should_require_reputation() {
+        let config = ReputationConfig { upvote: 15, downvote: 125, close: 250, flag: 15, edit: 2000 };
+        let votes_dao = VotesDaoMock::new();
+        let vote = |value| QuestionVote {
             question_uuid: "123".to_owned(),
-            content: "test content".to_owned(),
+            value,
         };
 
-        let mut answers_dao = AnswersDaoMock::new();
+        let newcomer = ReputationDaoMock { reputation: 1 };
+        let result = vote_question(vote(1), &user(), AuditContext::default(), &config, &newcomer, &votes_dao).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
 
-        answers_dao.mock_create_answer(Err(DBError::InvalidUUID("test".to_owned())));
+        let regular = ReputationDaoMock { reputation: 50 };
+        let result = vote_question(vote(-1), &user(), AuditContext::default(), &config, &regular, &votes_dao).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert!(votes_dao.votes.lock().await.is_empty());
 
-        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+        assert_eq!(vote_question(vote(1), &user(), AuditContext::default(), &config, &regular, &votes_dao).await, Ok(()));
+        // Retracting a vote needs no reputation, and moderators can always vote
+        assert_eq!(vote_question(vote(0), &user(), AuditContext::default(), &config, &newcomer, &votes_dao).await, Ok(()));
+        assert_eq!(vote_question(vote(-1), &moderator(), AuditContext::default(), &config, &newcomer, &votes_dao).await, Ok(()));
+        assert_eq!(*votes_dao.votes.lock().await, vec![(PostType::Question, 1), (PostType::Question, 0), (PostType::Question, -1)]);
+    }
 
-        let result = create_answer(answer, &answers_dao).await;
+    #[tokio::test]
+    async fn vote_answer_should_reject_invalid_values() {
+        let votes_dao = VotesDaoMock::new();
+        let vote = AnswerVote {
+            answer_uuid: "123".to_owned(),
+            value: 2,
+        };
 
-        assert!(result.is_err());
-        assert!(
-            std::mem::discriminant(&result.unwrap_err())
-                == std::mem::discriminant(&HandlerError::BadRequest("".to_owned()))
-        );
+        let result = vote_answer(
+            vote,
+            &moderator(),
+            AuditContext::default(),
+            &ReputationConfig::default(),
+            &ReputationDaoMock { reputation: 1000 },
+            &votes_dao,
+        )
+        .await;
+
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        assert!(votes_dao.votes.lock().await.is_empty());
     }
 
     #[tokio::test]
-    async fn create_answer_should_return_internal_error() {
-        let answer = Answer {
+    async fn accept_answer_should_return_forbidden_for_other_users_questions() {
+        let mut votes_dao = VotesDaoMock::new();
+        votes_dao.mock_accept_answer(Err(DBError::Forbidden("Not your question".to_owned())));
+
+        let answer = AnswerId {
+            answer_uuid: "123".to_owned(),
+        };
+        let result = accept_answer(answer, AuditContext::default(), &votes_dao).await;
+
+        assert_eq!(result, Err(HandlerError::Forbidden("Not your question".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn offer_bounty_should_reject_amounts_out_of_range() {
+        let config = BountiesConfig::default();
+        let bounties_dao = BountiesDaoMock::new(Ok(bounty_detail()));
+        let bounty = |amount| Bounty {
             question_uuid: "123".to_owned(),
-            content: "test content".to_owned(),
+            amount,
         };
 
-        let mut answers_dao = AnswersDaoMock::new();
+        for amount in [0, 49, 501] {
+            let result = offer_bounty(bounty(amount), AuditContext::default(), &config, &bounties_dao).await;
+            assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        }
+        assert!(bounties_dao.offered.lock().await.is_empty());
 
-        answers_dao.mock_create_answer(Err(DBError::Other(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "oh no!",
-        )))));
+        let result = offer_bounty(bounty(100), AuditContext::default(), &config, &bounties_dao).await;
+        assert_eq!(result, Ok(bounty_detail()));
+        assert_eq!(*bounties_dao.offered.lock().await, vec![(100, Duration::from_secs(7 * 24 * 60 * 60))]);
+    }
 
-        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+    #[tokio::test]
+    async fn offer_bounty_should_return_conflict_for_an_open_bounty() {
+        let bounties_dao = BountiesDaoMock::new(Err(DBError::Conflict("Already open".to_owned())));
+        let bounty = Bounty {
+            question_uuid: "123".to_owned(),
+            amount: 50,
+        };
 
-        let result = create_answer(answer, &answers_dao).await;
+        let result = offer_bounty(bounty, AuditContext::default(), &BountiesConfig::default(), &bounties_dao).await;
 
-        assert!(result.is_err());
-        assert!(
-            std::mem::discriminant(&result.unwrap_err())
-                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
-        );
+        assert_eq!(result, Err(HandlerError::Conflict("Already open".to_owned())));
     }
 
     #[tokio::test]
-    async fn read_answers_should_return_answers() {
-        let answer_detail = AnswerDetail {
-            answer_uuid: "456".to_owned(),
+    async fn award_bounty_should_return_forbidden_for_other_users_bounties() {
+        let bounties_dao = BountiesDaoMock::new(Err(DBError::Forbidden("Not your bounty".to_owned())));
+        let answer = AnswerId {
+            answer_uuid: "123".to_owned(),
+        };
+
+        let result = award_bounty(answer, AuditContext::default(), &bounties_dao).await;
+
+        assert_eq!(result, Err(HandlerError::Forbidden("Not your bounty".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn vote_to_close_should_validate_duplicate_of() {
+        let closing_dao = ClosingDaoMock { votes: Mutex::new(vec![]) };
+        let reputation_dao = ReputationDaoMock { reputation: 1000 };
+        let vote = |reason, duplicate_of: Option<&str>| CloseVote {
             question_uuid: "123".to_owned(),
-            content: "test content".to_owned(),
-            created_at: "now".to_owned(),
+            reason,
+            duplicate_of: duplicate_of.map(str::to_owned),
         };
 
-        let question_id = QuestionId {
+        for vote in [
+            vote(CloseReason::Duplicate, None),
+            vote(CloseReason::Duplicate, Some("123")),
+            vote(CloseReason::OffTopic, Some("456")),
+        ] {
+            let result = vote_to_close(
+                vote,
+                &user(),
+                AuditContext::default(),
+                &ReputationConfig::default(),
+                &ClosingConfig::default(),
+                &reputation_dao,
+                &closing_dao,
+            )
+            .await;
+            assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        }
+        assert!(closing_dao.votes.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn vote_to_close_should_require_reputation_unless_binding() {
+        let closing_dao = ClosingDaoMock { votes: Mutex::new(vec![]) };
+        let newcomer = ReputationDaoMock { reputation: 1 };
+        let vote = || CloseVote {
             question_uuid: "123".to_owned(),
+            reason: CloseReason::Duplicate,
+            duplicate_of: Some("456".to_owned()),
+        };
+        let (reputation_config, closing_config) = (ReputationConfig::default(), ClosingConfig::default());
+        let (regular, moderator) = (user(), moderator());
+        let close = |actor| {
+            vote_to_close(vote(), actor, AuditContext::default(), &reputation_config, &closing_config, &newcomer, &closing_dao)
         };
 
-        let mut answers_dao = AnswersDaoMock::new();
+        let result = close(&regular).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
 
-        answers_dao.mock_get_answers(Ok(vec![answer_detail.clone()]));
+        let result = close(&moderator).await.unwrap();
+        assert!(result.closed);
+        assert_eq!(*closing_dao.votes.lock().await, vec![(3, true)]);
+    }
 
-        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+    #[tokio::test]
+    async fn create_flag_should_validate_details_and_reputation() {
+        let flags_dao = FlagsDaoMock { calls: Mutex::new(0) };
+        let (reputation_config, flags_config) = (ReputationConfig::default(), FlagsConfig::default());
+        let regular = user();
+        let flag = |reason, details: Option<String>| Flag {
+            target_type: PostType::Answer,
+            target_uuid: "123".to_owned(),
+            reason,
+            details,
+        };
+        let create = |flag, reputation_dao| {
+            create_flag(flag, &regular, AuditContext::default(), &reputation_config, &flags_config, reputation_dao, &flags_dao)
+        };
+        let regular_reputation = ReputationDaoMock { reputation: 50 };
+
+        for flag in [
+            flag(FlagReason::Other, None),
+            flag(FlagReason::Other, Some("  ".to_owned())),
+            flag(FlagReason::Rude, Some("x".repeat(MAX_FLAG_DETAILS_LENGTH + 1))),
+        ] {
+            let result = create(flag, &regular_reputation).await;
+            assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        }
 
-        let result = read_answers(question_id, &answers_dao).await;
+        let result = create(flag(FlagReason::Spam, None), &ReputationDaoMock { reputation: 1 }).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert_eq!(*flags_dao.calls.lock().await, 0);
 
+        let result = create(flag(FlagReason::Other, Some("Copied from elsewhere".to_owned())), &regular_reputation).await;
+        assert!(result.is_ok());
+        assert_eq!(*flags_dao.calls.lock().await, 1);
+
+        // Details are limited in characters, not bytes
+        let result = create(flag(FlagReason::Rude, Some("é".repeat(MAX_FLAG_DETAILS_LENGTH))), &regular_reputation).await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), vec![answer_detail]);
     }
 
     #[tokio::test]
-    async fn read_answers_should_return_error() {
-        let question_id = QuestionId {
-            question_uuid: "123".to_owned(),
-        };
+    async fn flag_review_should_be_limited_to_moderators() {
+        let flags_dao = FlagsDaoMock { calls: Mutex::new(0) };
+        let config = FlagsConfig::default();
+        let helpful = || FlagResolution { status: FlagStatus::Helpful };
+
+        let result = read_flag_queue(&user(), None, &config, &flags_dao).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        let result = resolve_flag(1, helpful(), &user(), AuditContext::default(), &config, &flags_dao).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        let result = read_flag_stats("someone-else".to_owned(), &user(), &flags_dao).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert_eq!(*flags_dao.calls.lock().await, 0);
+
+        let result = read_flag_queue(&moderator(), Some(5000), &config, &flags_dao).await.unwrap();
+        assert_eq!(result.len(), 1);
+        let result = resolve_flag(1, helpful(), &moderator(), AuditContext::default(), &config, &flags_dao).await.unwrap();
+        assert_eq!(result.status, FlagStatus::Helpful);
+    }
 
-        let mut answers_dao = AnswersDaoMock::new();
+    #[tokio::test]
+    async fn resolve_flag_should_reject_pending_status() {
+        let flags_dao = FlagsDaoMock { calls: Mutex::new(0) };
+        let resolution = FlagResolution { status: FlagStatus::Pending };
 
-        answers_dao.mock_get_answers(Err(DBError::InvalidUUID("test".to_owned())));
+        let result = resolve_flag(1, resolution, &moderator(), AuditContext::default(), &FlagsConfig::default(), &flags_dao).await;
 
-        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        assert_eq!(*flags_dao.calls.lock().await, 0);
+    }
 
-        let result = read_answers(question_id, &answers_dao).await;
+    #[tokio::test]
+    async fn claim_flag_should_return_conflict_for_claimed_flags() {
+        let flags_dao = FlagsDaoMock { calls: Mutex::new(0) };
 
-        assert!(result.is_err());
-        assert!(
-            std::mem::discriminant(&result.unwrap_err())
-                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
-        );
+        let result = claim_flag(1, &moderator(), AuditContext::default(), &FlagsConfig::default(), &flags_dao).await;
+
+        assert!(matches!(result, Err(HandlerError::Conflict(_))));
     }
 
     #[tokio::test]
-    async fn delete_answer_should_succeed() {
-        let answer_id = AnswerId {
-            answer_uuid: "123".to_owned(),
+    async fn update_question_should_validate_fields() {
+        let questions_dao = QuestionsDaoMock::new();
+        let reputation_dao = ReputationDaoMock { reputation: 1 };
+        let edit = |title: Option<&str>, description: Option<&str>| QuestionEdit {
+            question_uuid: "123".to_owned(),
+            title: title.map(str::to_owned),
+            description: description.map(str::to_owned),
         };
+        let too_long = "x".repeat(MAX_BODY_LENGTH + 1);
+        let long_title = "x".repeat(MAX_TITLE_LENGTH + 1);
+
+        for edit in [edit(None, None), edit(Some(" "), None), edit(Some("title"), Some(&too_long)), edit(Some(&long_title), None)] {
+            let result = update_question(
+                edit,
+                IfMatch::Any,
+                &user(),
+                AuditContext::default(),
+                &ReputationConfig::default(),
+                &reputation_dao,
+                &questions_dao,
+            )
+            .await;
+            assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        }
+        assert_eq!(*questions_dao.update_question_privileged.lock().await, None);
+    }
 
-        let mut answers_dao = AnswersDaoMock::new();
-
-        answers_dao.mock_delete_answer(Ok(()));
-
-        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+    #[tokio::test]
+    async fn update_question_should_only_privilege_users_with_the_edit_reputation() {
+        let config = ReputationConfig::default();
+        let edit = || QuestionEdit {
+            question_uuid: "123".to_owned(),
+            title: Some("new title".to_owned()),
+            description: None,
+        };
 
-        let result = delete_answer(answer_id, &answers_dao).await;
+        let mut questions_dao = QuestionsDaoMock::new();
+        questions_dao.mock_update_question(Err(DBError::Forbidden("Only the author can edit this question".to_owned())));
+        let newcomer = ReputationDaoMock { reputation: 1 };
+        let result = update_question(edit(), IfMatch::Any, &user(), AuditContext::default(), &config, &newcomer, &questions_dao).await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert_eq!(*questions_dao.update_question_privileged.lock().await, Some(false));
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), ());
+        let mut questions_dao = QuestionsDaoMock::new();
+        questions_dao.mock_update_question(Err(DBError::PreconditionFailed("Question 123 has changed".to_owned())));
+        let editor = ReputationDaoMock { reputation: config.edit };
+        let result = update_question(edit(), IfMatch::Any, &user(), AuditContext::default(), &config, &editor, &questions_dao).await;
+        assert!(matches!(result, Err(HandlerError::PreconditionFailed(_))));
+        assert_eq!(*questions_dao.update_question_privileged.lock().await, Some(true));
     }
 
     #[tokio::test]
-    async fn delete_answer_should_return_error() {
-        let answer_id = AnswerId {
-            answer_uuid: "123".to_owned(),
+    async fn create_suggested_edit_should_validate_fields_for_the_target_type() {
+        let suggested_edits_dao = SuggestedEditsDaoMock { calls: Mutex::new(0) };
+        let edit = |target_type, title: Option<&str>, content: Option<&str>| SuggestedEdit {
+            target_type,
+            target_uuid: "123".to_owned(),
+            title: title.map(str::to_owned),
+            description: None,
+            content: content.map(str::to_owned),
+            summary: None,
         };
 
-        let mut answers_dao = AnswersDaoMock::new();
+        for edit in [
+            edit(PostType::Question, None, None),
+            edit(PostType::Question, Some("title"), Some("content")),
+            edit(PostType::Answer, None, None),
+            edit(PostType::Answer, Some("title"), Some("content")),
+            edit(PostType::Answer, None, Some("")),
+        ] {
+            let result = create_suggested_edit(edit, AuditContext::default(), &suggested_edits_dao).await;
+            assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+        }
+        assert_eq!(*suggested_edits_dao.calls.lock().await, 0);
 
-        answers_dao.mock_delete_answer(Err(DBError::InvalidUUID("test".to_owned())));
+        let result = create_suggested_edit(edit(PostType::Answer, None, Some("fixed typo")), AuditContext::default(), &suggested_edits_dao).await;
+        assert!(result.is_ok());
+        assert_eq!(*suggested_edits_dao.calls.lock().await, 1);
 
-        let answers_dao: Box<dyn AnswersDao + Send + Sync> = Box::new(answers_dao);
+        // Summaries are limited in characters, not bytes
+        let summarized = |length| SuggestedEdit { summary: Some("é".repeat(length)), ..edit(PostType::Answer, None, Some("fixed typo")) };
+        let result = create_suggested_edit(summarized(MAX_EDIT_COMMENT_LENGTH), AuditContext::default(), &suggested_edits_dao).await;
+        assert!(result.is_ok());
+        let result = create_suggested_edit(summarized(MAX_EDIT_COMMENT_LENGTH + 1), AuditContext::default(), &suggested_edits_dao).await;
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+    }
 
-        let result = delete_answer(answer_id, &answers_dao).await;
+    #[tokio::test]
+    async fn review_suggested_edit_should_require_a_decision_and_reputation() {
+        let transactions = TransactionManagerMock { calls: Mutex::new(0) };
+        let config = ReputationConfig::default();
+        let reputation_dao = ReputationDaoMock { reputation: 1 };
+        let review = |status| SuggestedEditReview { status, comment: None };
+
+        let result = review_suggested_edit(
+            1,
+            review(SuggestedEditStatus::Pending),
+            &moderator(),
+            AuditContext::default(),
+            &config,
+            &reputation_dao,
+            &transactions,
+        )
+        .await;
+        assert!(matches!(result, Err(HandlerError::BadRequest(_))));
+
+        let result = review_suggested_edit(
+            1,
+            review(SuggestedEditStatus::Approved),
+            &user(),
+            AuditContext::default(),
+            &config,
+            &reputation_dao,
+            &transactions,
+        )
+        .await;
+        assert!(matches!(result, Err(HandlerError::Forbidden(_))));
+        assert_eq!(*transactions.calls.lock().await, 0);
+    }
 
-        assert!(result.is_err());
-        assert!(
-            std::mem::discriminant(&result.unwrap_err())
-                == std::mem::discriminant(&HandlerError::InternalError("".to_owned()))
-        );
+    #[tokio::test]
+    async fn update_answer_should_return_not_found_for_missing_answers() {
+        let mut answers_dao = AnswersDaoMock::new();
+        answers_dao.mock_update_answer(Err(DBError::NotFound("No answer with ID 123".to_owned())));
+        let edit = AnswerEdit {
+            answer_uuid: "123".to_owned(),
+            content: "new content".to_owned(),
+        };
+
+        let result = update_answer(
+            edit,
+            IfMatch::Any,
+            &user(),
+            AuditContext::default(),
+            &ReputationConfig::default(),
+            &ReputationDaoMock { reputation: 1 },
+            &answers_dao,
+        )
+        .await;
+
+        assert_eq!(result, Err(HandlerError::NotFound("No answer with ID 123".to_owned())));
     }
-}
\ No newline at end of file
+}
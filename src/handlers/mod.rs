@@ -1,108 +1,974 @@
 mod handlers_inner;
 
-use rocket::{serde::json::Json, State};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rocket::{
+    http::Status,
+    response::{self, status, stream::{Event, EventStream}, Responder},
+    serde::json::Json,
+    Request, Response, Shutdown, State,
+};
+use rocket_ws::{Channel, WebSocket};
+use serde::Serialize;
+use tracing::Instrument;
 use crate::{
+    badges::BadgeRules,
+    bounties::BountiesConfig,
+    closing::ClosingConfig,
+    drain::Readiness,
+    etag::Tagged,
+    events::{LastEventId, QuestionEventsHub},
+    feed::{self, LiveFeed},
+    flags::FlagsConfig,
+    idempotency::{Idempotency, Idempotent},
+    jobs::JobKinds,
     models::*,
     persistance::{
         answers_dao::AnswersDao,
-        questions_dao::QuestionsDao
+        audit_dao::AuditDao,
+        badges_dao::BadgesDao,
+        bounties_dao::BountiesDao,
+        closing_dao::ClosingDao,
+        flags_dao::FlagsDao,
+        jobs_dao::JobsDao,
+        question_events_dao::QuestionEventsDao,
+        question_links_dao::QuestionLinksDao,
+        questions_dao::QuestionsDao,
+        reputation_dao::ReputationDao,
+        suggested_edits_dao::SuggestedEditsDao,
+        unit_of_work::TransactionManager,
+        users_dao::UsersDao,
+        votes_dao::VotesDao,
+        webhooks_dao::WebhooksDao,
     },
+    rate_limit::RateLimit,
+    reputation::ReputationConfig,
+    request_id::RequestId,
+    screening::ContentScreener,
+    similar::SimilarQuestionsConfig,
+    telemetry::RequestSpan,
 };
 use handlers_inner::*;
 
-#[derive(Responder)]
+#[derive(Debug)]
 pub enum APIError {
-    #[response(status = 400)]
     BadRequest(String),
-    #[response(status = 500)]
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    PreconditionFailed(String),
     InternalServerError(String),
 }
 
+impl APIError {
+    fn status(&self) -> Status {
+        match self {
+            APIError::BadRequest(_) => Status::BadRequest,
+            APIError::Forbidden(_) => Status::Forbidden,
+            APIError::NotFound(_) => Status::NotFound,
+            APIError::Conflict(_) => Status::Conflict,
+            APIError::PreconditionFailed(_) => Status::PreconditionFailed,
+            APIError::InternalServerError(_) => Status::InternalServerError,
+        }
+    }
+}
+
 impl From<HandlerError> for APIError {
     fn from(value: HandlerError) -> Self {
         match value {
             HandlerError::BadRequest(message) => Self::BadRequest(message),
+            HandlerError::Forbidden(message) => Self::Forbidden(message),
+            HandlerError::NotFound(message) => Self::NotFound(message),
+            HandlerError::Conflict(message) => Self::Conflict(message),
+            HandlerError::PreconditionFailed(message) => Self::PreconditionFailed(message),
             HandlerError::InternalError(s) => Self::InternalServerError(s),
         }
     }
 }
 
+/// JSON body of every error response, so clients can quote the request ID when reporting problems.
+#[derive(Serialize, Debug)]
+pub struct ErrorBody {
+    pub error: String,
+    pub request_id: String,
+}
+
+impl ErrorBody {
+    pub fn new(error: impl Into<String>, request: &Request<'_>) -> Self {
+        ErrorBody {
+            error: error.into(),
+            request_id: RequestId::of(request).0.clone(),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for APIError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        let message = match self {
+            APIError::BadRequest(message)
+            | APIError::Forbidden(message)
+            | APIError::NotFound(message)
+            | APIError::Conflict(message)
+            | APIError::PreconditionFailed(message)
+            | APIError::InternalServerError(message) => message,
+        };
+        let body = ErrorBody::new(message, request);
+
+        Response::build_from(Json(body).respond_to(request)?)
+            .status(status)
+            .ok()
+    }
+}
+
+#[catch(default)]
+pub fn default_catcher(status: Status, request: &Request) -> (Status, Json<ErrorBody>) {
+    let body = ErrorBody::new(status.reason().unwrap_or("Unknown error"), request);
+    (status, Json(body))
+}
+
 // ---- CRUD for Questions ----
 
 #[post("/question", data = "<question>")]
-#[allow(unused_variables)]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question"))]
+#[allow(clippy::too_many_arguments)]
 pub async fn create_question(
     question: Json<Question>,
+    actor: Option<Actor>,
+    screener: &State<ContentScreener>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
     questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>,
-) -> Result<Json<QuestionDetail>, APIError> {
-    match handlers_inner::create_question(question.0, questions_dao.inner()).await {
-        Ok(details) => Ok(Json(details)),
-        Err(err) => Err(err.into()),
-    }
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    idempotency: Idempotency<'_>,
+    _rate_limit: RateLimit,
+) -> Result<Idempotent<QuestionDetail>, APIError> {
+    let context = AuditContext::new(actor.as_ref(), request_id.0.clone());
+    idempotency
+        .run(question.0, |question| async {
+            handlers_inner::create_question(
+                question,
+                actor.as_ref(),
+                context,
+                screener.inner(),
+                reputation_dao.inner().as_ref(),
+                questions_dao.inner().as_ref(),
+            )
+            .await
+            .map_err(Into::<APIError>::into)
+        })
+        .await
+        .map(Idempotent::tagged)
 }
 
-#[get("/questions")]
-pub async fn read_questions(questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>) -> Result<Json<Vec<QuestionDetail>>, APIError> {
-    /**
+#[get("/questions?<include_deleted>&<bounty>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /questions"))]
+pub async fn read_questions(
+    include_deleted: bool,
+    bounty: Option<String>,
+    actor: Option<Actor>,
+    questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Tagged<Vec<QuestionDetail>>, APIError> {
+    /*
      *  I know it's not recommended to leave comments in code, but it's just an important note.
      *  There's another way, without using `match`. We can do as following code, but in `map_err`'s
      *  closure, we must use Into::<T>::into(err), or we'll face some strange casting error messages.
      */
-    let vec = handlers_inner::read_questions(questions_dao.inner())
+    let vec = handlers_inner::read_questions(include_deleted, bounty, actor.as_ref(), questions_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Tagged::list(vec))
+}
+
+#[post("/questions/similar", data = "<question>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /questions/similar"))]
+pub async fn find_similar_questions(
+    question: Json<Question>,
+    config: &State<SimilarQuestionsConfig>,
+    questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<SimilarQuestion>>, APIError> {
+    let questions = handlers_inner::find_similar_questions(question.0, config, questions_dao.inner().as_ref())
         .await
-        .map_err(|err| Into::<APIError>::into(err))?;
-    Ok(Json(vec))
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(questions))
+}
+
+#[get("/questions/<question_uuid>/related")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /questions/<uuid>/related"))]
+pub async fn read_related_questions(
+    question_uuid: String,
+    config: &State<SimilarQuestionsConfig>,
+    questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>,
+    question_links_dao: &State<Box<dyn QuestionLinksDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<RelatedQuestions>, APIError> {
+    let related = handlers_inner::read_related_questions(
+        question_uuid,
+        config,
+        questions_dao.inner().as_ref(),
+        question_links_dao.inner().as_ref(),
+    )
+    .await
+    .map_err(Into::<APIError>::into)?;
+    Ok(Json(related))
 }
 
 #[delete("/question", data = "<question_uuid>")]
-#[allow(unused_variables)]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "DELETE /question"))]
 pub async fn delete_question(
     question_uuid: Json<QuestionId>,
+    actor: Actor,
+    if_match: IfMatch,
     questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
 ) -> Result<(), APIError> {
     let uuid = question_uuid.0;
-    match handlers_inner::delete_question(uuid, questions_dao.inner()).await {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    match handlers_inner::delete_question(uuid, &actor, if_match, context, questions_dao.inner().as_ref()).await {
         Ok(_) => Ok(()),
         Err(err) => Err(err.into()),
     }
 }
 
+#[post("/question/restore", data = "<question_uuid>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question/restore"))]
+pub async fn restore_question(
+    question_uuid: Json<QuestionId>,
+    actor: Actor,
+    if_match: IfMatch,
+    questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<(), APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    handlers_inner::restore_question(question_uuid.0, &actor, if_match, context, questions_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)
+}
+
+#[post("/question/edit", data = "<edit>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question/edit"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_question(
+    edit: Json<QuestionEdit>,
+    actor: Actor,
+    if_match: IfMatch,
+    reputation_config: &State<ReputationConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    questions_dao: &State<Box<dyn QuestionsDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Tagged<QuestionDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let question = handlers_inner::update_question(
+        edit.0,
+        if_match,
+        &actor,
+        context,
+        reputation_config,
+        reputation_dao.inner().as_ref(),
+        questions_dao.inner().as_ref(),
+    )
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Tagged::row(question))
+}
+
 // ---- CRUD for Answers ----
 
 #[post("/answer", data = "<answer>")]
-#[allow(unused_variables)]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /answer"))]
+#[allow(clippy::too_many_arguments)]
 pub async fn create_answer(
     answer: Json<Answer>,
+    actor: Option<Actor>,
+    screener: &State<ContentScreener>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
     answers_dao: &State<Box<dyn AnswersDao + Sync + Send>>,
-) -> Result<Json<AnswerDetail>, APIError> {
-    let detail = handlers_inner::create_answer(answer.0, answers_dao.inner())
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    idempotency: Idempotency<'_>,
+    _rate_limit: RateLimit,
+) -> Result<Idempotent<AnswerDetail>, APIError> {
+    let context = AuditContext::new(actor.as_ref(), request_id.0.clone());
+    idempotency
+        .run(answer.0, |answer| async {
+            handlers_inner::create_answer(
+                answer,
+                actor.as_ref(),
+                context,
+                screener.inner(),
+                reputation_dao.inner().as_ref(),
+                answers_dao.inner().as_ref(),
+            )
+            .await
+            .map_err(Into::<APIError>::into)
+        })
         .await
-        .map_err(|err| Into::<APIError>::into(err))?;
-    Ok(Json(detail))
+        .map(Idempotent::tagged)
 }
 
-#[get("/answers", data = "<question_id>")]
-#[allow(unused_variables)]
+#[get("/answers?<include_deleted>", data = "<question_id>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /answers"))]
 pub async fn read_answers(
     question_id: Json<QuestionId>,
+    include_deleted: bool,
+    actor: Option<Actor>,
     answers_dao: &State<Box<dyn AnswersDao + Sync + Send>>,
-) -> Result<Json<Vec<AnswerDetail>>, APIError> {
-    let vec = handlers_inner::read_answers(question_id.0, answers_dao.inner())
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Tagged<Vec<AnswerDetail>>, APIError> {
+    let vec = handlers_inner::read_answers(question_id.0, include_deleted, actor.as_ref(), answers_dao.inner().as_ref())
         .await;
     match vec {
-        Ok(answers) => Ok(Json(answers)),
+        Ok(answers) => Ok(Tagged::list(answers)),
         Err(err) => Err(err.into()),
     }
 }
 
 #[delete("/answer", data="<answer_id>")]
-#[allow(unused_variables)]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "DELETE /answer"))]
 pub async fn delete_answer(
     answer_id: Json<AnswerId>,
+    actor: Actor,
+    if_match: IfMatch,
     answers_dao: &State<Box<dyn AnswersDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<(), APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    handlers_inner::delete_answer(answer_id.0, &actor, if_match, context, answers_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(())
+}
+
+#[post("/answer/restore", data = "<answer_id>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /answer/restore"))]
+pub async fn restore_answer(
+    answer_id: Json<AnswerId>,
+    actor: Actor,
+    if_match: IfMatch,
+    answers_dao: &State<Box<dyn AnswersDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<(), APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    handlers_inner::restore_answer(answer_id.0, &actor, if_match, context, answers_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)
+}
+
+#[post("/answer/edit", data = "<edit>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /answer/edit"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_answer(
+    edit: Json<AnswerEdit>,
+    actor: Actor,
+    if_match: IfMatch,
+    reputation_config: &State<ReputationConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    answers_dao: &State<Box<dyn AnswersDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Tagged<AnswerDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let answer = handlers_inner::update_answer(
+        edit.0,
+        if_match,
+        &actor,
+        context,
+        reputation_config,
+        reputation_dao.inner().as_ref(),
+        answers_dao.inner().as_ref(),
+    )
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Tagged::row(answer))
+}
+
+// ---- Votes and reputation ----
+
+#[post("/question/vote", data = "<vote>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question/vote"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn vote_question(
+    vote: Json<QuestionVote>,
+    actor: Actor,
+    config: &State<ReputationConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    votes_dao: &State<Box<dyn VotesDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<(), APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    handlers_inner::vote_question(vote.0, &actor, context, config, reputation_dao.inner().as_ref(), votes_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)
+}
+
+#[post("/answer/vote", data = "<vote>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /answer/vote"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn vote_answer(
+    vote: Json<AnswerVote>,
+    actor: Actor,
+    config: &State<ReputationConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    votes_dao: &State<Box<dyn VotesDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<(), APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    handlers_inner::vote_answer(vote.0, &actor, context, config, reputation_dao.inner().as_ref(), votes_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)
+}
+
+#[post("/answer/accept", data = "<answer_uuid>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /answer/accept"))]
+pub async fn accept_answer(
+    answer_uuid: Json<AnswerId>,
+    actor: Actor,
+    votes_dao: &State<Box<dyn VotesDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<(), APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    handlers_inner::accept_answer(answer_uuid.0, context, votes_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)
+}
+
+#[get("/users/<user_uuid>/reputation?<before>&<limit>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /users/<uuid>/reputation"))]
+pub async fn read_reputation(
+    user_uuid: String,
+    before: Option<i64>,
+    limit: Option<i64>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Reputation>, APIError> {
+    let reputation = handlers_inner::read_reputation(user_uuid, before, limit, reputation_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(reputation))
+}
+
+#[get("/badges")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /badges"))]
+pub async fn read_badges(
+    rules: &State<Arc<BadgeRules>>,
+    badges_dao: &State<Box<dyn BadgesDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<Badge>>, APIError> {
+    let badges = handlers_inner::read_badges(rules, badges_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(badges))
+}
+
+#[get("/users/<user_uuid>/badges")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /users/<uuid>/badges"))]
+pub async fn read_user_badges(
+    user_uuid: String,
+    badges_dao: &State<Box<dyn BadgesDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<UserBadge>>, APIError> {
+    let badges = handlers_inner::read_user_badges(user_uuid, badges_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(badges))
+}
+
+// ---- Bounties ----
+
+#[post("/question/bounty", data = "<bounty>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question/bounty"))]
+pub async fn offer_bounty(
+    bounty: Json<Bounty>,
+    actor: Actor,
+    config: &State<BountiesConfig>,
+    bounties_dao: &State<Box<dyn BountiesDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<BountyDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let bounty = handlers_inner::offer_bounty(bounty.0, context, config, bounties_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(bounty))
+}
+
+#[post("/question/bounty/award", data = "<answer_uuid>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question/bounty/award"))]
+pub async fn award_bounty(
+    answer_uuid: Json<AnswerId>,
+    actor: Actor,
+    bounties_dao: &State<Box<dyn BountiesDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<BountyDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let bounty = handlers_inner::award_bounty(answer_uuid.0, context, bounties_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(bounty))
+}
+
+// ---- Closing ----
+
+#[post("/question/close", data = "<vote>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question/close"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn vote_to_close(
+    vote: Json<CloseVote>,
+    actor: Actor,
+    reputation_config: &State<ReputationConfig>,
+    closing_config: &State<ClosingConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    closing_dao: &State<Box<dyn ClosingDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<ClosureVotes>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let votes = handlers_inner::vote_to_close(
+        vote.0,
+        &actor,
+        context,
+        reputation_config,
+        closing_config,
+        reputation_dao.inner().as_ref(),
+        closing_dao.inner().as_ref(),
+    )
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(votes))
+}
+
+#[post("/question/reopen", data = "<question_uuid>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /question/reopen"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn vote_to_reopen(
+    question_uuid: Json<QuestionId>,
+    actor: Actor,
+    reputation_config: &State<ReputationConfig>,
+    closing_config: &State<ClosingConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    closing_dao: &State<Box<dyn ClosingDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<ClosureVotes>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let votes = handlers_inner::vote_to_reopen(
+        question_uuid.0,
+        &actor,
+        context,
+        reputation_config,
+        closing_config,
+        reputation_dao.inner().as_ref(),
+        closing_dao.inner().as_ref(),
+    )
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(votes))
+}
+
+// ---- Flags ----
+
+#[post("/flag", data = "<flag>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /flag"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_flag(
+    flag: Json<Flag>,
+    actor: Actor,
+    reputation_config: &State<ReputationConfig>,
+    flags_config: &State<FlagsConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    flags_dao: &State<Box<dyn FlagsDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<FlagDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let flag = handlers_inner::create_flag(
+        flag.0,
+        &actor,
+        context,
+        reputation_config,
+        flags_config,
+        reputation_dao.inner().as_ref(),
+        flags_dao.inner().as_ref(),
+    )
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(flag))
+}
+
+#[get("/flags?<limit>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /flags"))]
+pub async fn read_flag_queue(
+    limit: Option<i64>,
+    actor: Actor,
+    flags_config: &State<FlagsConfig>,
+    flags_dao: &State<Box<dyn FlagsDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<FlagDetail>>, APIError> {
+    let flags = handlers_inner::read_flag_queue(&actor, limit, flags_config, flags_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(flags))
+}
+
+#[post("/flags/<flag_id>/claim")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /flags/<id>/claim"))]
+pub async fn claim_flag(
+    flag_id: i64,
+    actor: Actor,
+    flags_config: &State<FlagsConfig>,
+    flags_dao: &State<Box<dyn FlagsDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<FlagDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let flag = handlers_inner::claim_flag(flag_id, &actor, context, flags_config, flags_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(flag))
+}
+
+#[post("/flags/<flag_id>/resolve", data = "<resolution>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /flags/<id>/resolve"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn resolve_flag(
+    flag_id: i64,
+    resolution: Json<FlagResolution>,
+    actor: Actor,
+    flags_config: &State<FlagsConfig>,
+    flags_dao: &State<Box<dyn FlagsDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<FlagDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let flag = handlers_inner::resolve_flag(flag_id, resolution.0, &actor, context, flags_config, flags_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(flag))
+}
+
+#[get("/users/<user_uuid>/flag_stats")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /users/<uuid>/flag_stats"))]
+pub async fn read_flag_stats(
+    user_uuid: String,
+    actor: Actor,
+    flags_dao: &State<Box<dyn FlagsDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<FlagStats>, APIError> {
+    let stats = handlers_inner::read_flag_stats(user_uuid, &actor, flags_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(stats))
+}
+
+// ---- Suggested edits ----
+
+#[post("/suggested_edit", data = "<edit>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /suggested_edit"))]
+pub async fn create_suggested_edit(
+    edit: Json<SuggestedEdit>,
+    actor: Actor,
+    suggested_edits_dao: &State<Box<dyn SuggestedEditsDao + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<SuggestedEditDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let edit = handlers_inner::create_suggested_edit(edit.0, context, suggested_edits_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(edit))
+}
+
+#[get("/suggested_edits?<limit>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /suggested_edits"))]
+pub async fn read_suggested_edit_queue(
+    limit: Option<i64>,
+    actor: Actor,
+    reputation_config: &State<ReputationConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    suggested_edits_dao: &State<Box<dyn SuggestedEditsDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<SuggestedEditDetail>>, APIError> {
+    let edits = handlers_inner::read_suggested_edit_queue(
+        &actor,
+        limit,
+        reputation_config,
+        reputation_dao.inner().as_ref(),
+        suggested_edits_dao.inner().as_ref(),
+    )
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(edits))
+}
+
+#[post("/suggested_edits/<edit_id>/review", data = "<review>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /suggested_edits/<id>/review"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn review_suggested_edit(
+    edit_id: i64,
+    review: Json<SuggestedEditReview>,
+    actor: Actor,
+    reputation_config: &State<ReputationConfig>,
+    reputation_dao: &State<Box<dyn ReputationDao + Sync + Send>>,
+    transactions: &State<Box<dyn TransactionManager + Sync + Send>>,
+    request_id: &RequestId,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<SuggestedEditDetail>, APIError> {
+    let context = AuditContext::new(Some(&actor), request_id.0.clone());
+    let edit = handlers_inner::review_suggested_edit(
+        edit_id,
+        review.0,
+        &actor,
+        context,
+        reputation_config,
+        reputation_dao.inner().as_ref(),
+        transactions.inner().as_ref(),
+    )
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(edit))
+}
+
+// ---- Live updates ----
+
+/// Streams the changes to a question's answers as server-sent events, starting after the
+/// `Last-Event-ID` the client reconnects with, or from now on.
+/// Not `#[instrument]`ed like the other routes, since that can't wrap a streamed response: the
+/// span is entered around each database call instead.
+#[get("/questions/<question_uuid>/events")]
+pub async fn question_events<'r>(
+    question_uuid: String,
+    last_event_id: LastEventId,
+    hub: &'r State<QuestionEventsHub>,
+    question_events_dao: &'r State<Box<dyn QuestionEventsDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    mut shutdown: Shutdown,
+    _rate_limit: RateLimit,
+) -> Result<EventStream![Event + 'r], APIError> {
+    let span = info_span!(parent: &request_span.0, "route", route = "GET /questions/<uuid>/events");
+    let dao = question_events_dao.inner().as_ref();
+    let mut watch = hub.watch(&question_uuid);
+    let mut cursor = handlers_inner::start_question_events(question_uuid.clone(), last_event_id.0, dao)
+        .instrument(span.clone())
+        .await
+        .map_err(Into::<APIError>::into)?;
+
+    let stream = EventStream! {
+        loop {
+            let events = handlers_inner::read_question_events(question_uuid.clone(), cursor, dao)
+                .instrument(span.clone())
+                .await;
+            let Ok(events) = events else {
+                break;
+            };
+            let caught_up = (events.len() as i64) < QUESTION_EVENTS_PAGE_SIZE;
+            for event in events {
+                cursor = event.event_id;
+                yield Event::json(&event.payload)
+                    .id(event.event_id.to_string())
+                    .event(event.event_type);
+            }
+            if caught_up {
+                tokio::select! {
+                    _ = watch.changed() => {},
+                    _ = &mut shutdown => break,
+                }
+            }
+        }
+    };
+    Ok(stream.heartbeat(Duration::from_secs(15)))
+}
+
+/// A WebSocket of the new questions and answers of the subscribed topics, for dashboards.
+#[get("/feed")]
+pub fn live_feed(ws: WebSocket, feed: &State<LiveFeed>, shutdown: Shutdown, _rate_limit: RateLimit) -> Channel<'static> {
+    // Subscribe right away so nothing is missed while the connection is upgraded
+    let events = feed.subscribe();
+    ws.channel(move |stream| Box::pin(feed::serve(stream, events, shutdown)))
+}
+
+// ---- Health ----
+
+/// Whether the process is up; probes don't count against the rate limits.
+#[get("/health/live")]
+pub fn liveness() -> Json<HealthStatus> {
+    Json(HealthStatus {
+        status: "ok".to_owned(),
+    })
+}
+
+/// Whether the instance should get new requests: fails with `503` once it starts draining.
+#[get("/health/ready")]
+pub fn readiness(readiness: &State<Readiness>) -> (Status, Json<HealthStatus>) {
+    match readiness.is_draining() {
+        true => (Status::ServiceUnavailable, Json(HealthStatus { status: "draining".to_owned() })),
+        false => (Status::Ok, Json(HealthStatus { status: "ok".to_owned() })),
+    }
+}
+
+// ---- Users ----
+
+#[post("/user", data = "<user>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /user"))]
+pub async fn create_user(
+    user: Json<User>,
+    users_dao: &State<Box<dyn UsersDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<UserCredentials>, APIError> {
+    let credentials = handlers_inner::create_user(user.0, users_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(credentials))
+}
+
+// ---- Audit log ----
+
+#[get("/admin/audit_events?<query..>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /admin/audit_events"))]
+pub async fn read_audit_events(
+    query: AuditQuery,
+    actor: Actor,
+    audit_dao: &State<Box<dyn AuditDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<AuditEvent>>, APIError> {
+    let events = handlers_inner::read_audit_events(query, &actor, audit_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(events))
+}
+
+// ---- Jobs ----
+
+#[post("/admin/jobs", data = "<job>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /admin/jobs"))]
+pub async fn create_job(
+    job: Json<Job>,
+    actor: Actor,
+    job_kinds: &State<JobKinds>,
+    jobs_dao: &State<Box<dyn JobsDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<status::Accepted<Json<JobId>>, APIError> {
+    let job_id = handlers_inner::create_job(job.0, &actor, &job_kinds.0, jobs_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(status::Accepted(Json(job_id)))
+}
+
+// ---- Webhooks ----
+
+#[post("/webhooks", data = "<webhook>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /webhooks"))]
+pub async fn create_webhook(
+    webhook: Json<Webhook>,
+    actor: Actor,
+    webhooks_dao: &State<Box<dyn WebhooksDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<WebhookDetail>, APIError> {
+    let webhook = handlers_inner::create_webhook(webhook.0, &actor, webhooks_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(webhook))
+}
+
+#[get("/webhooks")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /webhooks"))]
+pub async fn read_webhooks(
+    actor: Actor,
+    webhooks_dao: &State<Box<dyn WebhooksDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<WebhookDetail>>, APIError> {
+    let webhooks = handlers_inner::read_webhooks(&actor, webhooks_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(webhooks))
+}
+
+#[delete("/webhooks/<subscription_uuid>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "DELETE /webhooks/<uuid>"))]
+pub async fn delete_webhook(
+    subscription_uuid: String,
+    actor: Actor,
+    webhooks_dao: &State<Box<dyn WebhooksDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<(), APIError> {
+    handlers_inner::delete_webhook(subscription_uuid, &actor, webhooks_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(())
+}
+
+#[get("/webhooks/<subscription_uuid>/deliveries?<query..>")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "GET /webhooks/<uuid>/deliveries"))]
+pub async fn read_webhook_deliveries(
+    subscription_uuid: String,
+    query: DeliveryQuery,
+    actor: Actor,
+    webhooks_dao: &State<Box<dyn WebhooksDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
+) -> Result<Json<Vec<WebhookDelivery>>, APIError> {
+    let deliveries = handlers_inner::read_webhook_deliveries(subscription_uuid, query, &actor, webhooks_dao.inner().as_ref())
+        .await
+        .map_err(Into::<APIError>::into)?;
+    Ok(Json(deliveries))
+}
+
+#[post("/webhooks/deliveries/<delivery_id>/retry")]
+#[instrument(name = "route", parent = &request_span.0, skip_all, fields(route = "POST /webhooks/deliveries/<id>/retry"))]
+pub async fn retry_webhook_delivery(
+    delivery_id: i64,
+    actor: Actor,
+    webhooks_dao: &State<Box<dyn WebhooksDao + Sync + Send>>,
+    request_span: &RequestSpan,
+    _rate_limit: RateLimit,
 ) -> Result<(), APIError> {
-    handlers_inner::delete_answer(answer_id.0, answers_dao.inner())
+    handlers_inner::retry_webhook_delivery(delivery_id, &actor, webhooks_dao.inner().as_ref())
         .await
-        .map_err(|err| Into::<APIError>::into(err))?;
+        .map_err(Into::<APIError>::into)?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,360 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{Build, Orbit, Request, Rocket};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::drain::spawn_until_shutdown;
+use crate::etag::{row_etag, Versioned};
+use crate::handlers::APIError;
+use crate::models::DBError;
+use crate::persistance::idempotency_dao::{IdempotencyDao, IdempotencyDaoImpl};
+use crate::rate_limit::client_key;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// Set on responses replayed from an earlier request with the same idempotency key.
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// The `[idempotency]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    /// How long a response is kept for replay.
+    pub ttl_hours: u32,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self { ttl_hours: 24 }
+    }
+}
+
+impl IdempotencyConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(u64::from(self.ttl_hours) * 60 * 60)
+    }
+}
+
+struct IdempotencyStore {
+    config: IdempotencyConfig,
+    dao: Box<dyn IdempotencyDao + Send + Sync>,
+}
+
+/// Stores idempotent responses and removes them once they expire.
+pub struct IdempotencyFairing {
+    db: PgPool,
+}
+
+impl IdempotencyFairing {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for IdempotencyFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Idempotency keys",
+            kind: Kind::Ignite | Kind::Liftoff,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = if rocket.figment().contains("idempotency") {
+            match rocket.figment().extract_inner::<IdempotencyConfig>("idempotency") {
+                Ok(config) => config,
+                Err(err) => {
+                    error!("Invalid idempotency configuration: {}", err);
+                    return Err(rocket);
+                }
+            }
+        } else {
+            IdempotencyConfig::default()
+        };
+
+        Ok(rocket.manage(IdempotencyStore {
+            config,
+            dao: Box::new(IdempotencyDaoImpl::new(self.db.clone())),
+        }))
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let dao = IdempotencyDaoImpl::new(self.db.clone());
+        spawn_until_shutdown(rocket, "idempotency key purge", async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+            loop {
+                interval.tick().await;
+                match dao.purge_expired().await {
+                    Ok(purged) if purged > 0 => info!(purged, "Purged expired idempotency keys"),
+                    Ok(_) => {}
+                    Err(err) => error!("Error purging expired idempotency keys: {:?}", err),
+                }
+            }
+        });
+    }
+}
+
+/// The `Idempotency-Key` of the current request, if it sent one. Wrap the handler of a `POST`
+/// route in `run` to replay the first response to every retry with the same key.
+pub struct Idempotency<'r> {
+    key: Option<String>,
+    scope: String,
+    route: String,
+    store: &'r IdempotencyStore,
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 255 && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Idempotency<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(store) = request.rocket().state::<IdempotencyStore>() else {
+            error!("IdempotencyFairing is not attached");
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+
+        let key = request.headers().get_one(IDEMPOTENCY_KEY_HEADER);
+        if key.is_some_and(|key| !is_valid_key(key)) {
+            return Outcome::Error((Status::BadRequest, ()));
+        }
+
+        Outcome::Success(Idempotency {
+            key: key.map(String::from),
+            scope: client_key(request).await,
+            route: format!("{} {}", request.method(), request.uri().path()),
+            store,
+        })
+    }
+}
+
+impl Idempotency<'_> {
+    /// Runs `handler` on `body`, unless the key was already used: then the stored response is
+    /// returned instead, or a conflict if the key came with a different request or is still in use.
+    /// Failed requests are not stored, so they can be retried with the same key.
+    pub async fn run<B, T, F, Fut>(&self, body: B, handler: F) -> Result<Idempotent<T>, APIError>
+    where
+        B: Serialize,
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(B) -> Fut,
+        Fut: Future<Output = Result<T, APIError>>,
+    {
+        let Some(key) = &self.key else {
+            return handler(body).await.map(Idempotent::fresh);
+        };
+
+        let request_hash = self.request_hash(&body)?;
+        let dao = self.store.dao.as_ref();
+
+        let earlier = dao
+            .claim(self.scope.clone(), key.clone(), request_hash.clone(), self.store.config.ttl())
+            .await
+            .map_err(|err| {
+                error!("Error claiming idempotency key: {:?}", err);
+                APIError::InternalServerError("Something went wrong! Please try again.".to_owned())
+            })?;
+
+        if let Some(earlier) = earlier {
+            if earlier.request_hash != request_hash {
+                return Err(APIError::Conflict(format!(
+                    "{} was already used for a different request",
+                    IDEMPOTENCY_KEY_HEADER
+                )));
+            }
+            let Some(response) = earlier.response else {
+                return Err(APIError::Conflict(format!(
+                    "A request with this {} is still in progress",
+                    IDEMPOTENCY_KEY_HEADER
+                )));
+            };
+            return serde_json::from_value(response)
+                .map(Idempotent::replayed)
+                .map_err(|err| {
+                    error!("Error reading stored idempotent response: {:?}", err);
+                    APIError::InternalServerError("Something went wrong! Please try again.".to_owned())
+                });
+        }
+
+        let result = handler(body).await;
+
+        let stored = match &result {
+            Ok(value) => match serde_json::to_value(value) {
+                Ok(response) => dao.complete(self.scope.clone(), key.clone(), response).await,
+                Err(err) => Err(DBError::Other(Box::new(err))),
+            },
+            Err(_) => dao.release(self.scope.clone(), key.clone()).await,
+        };
+        // The request itself went through; at worst a retry runs it again once the key frees up.
+        if let Err(err) = stored {
+            error!("Error storing idempotent response: {:?}", err);
+        }
+
+        result.map(Idempotent::fresh)
+    }
+
+    fn request_hash(&self, body: &impl Serialize) -> Result<String, APIError> {
+        let body = serde_json::to_vec(body).map_err(|err| {
+            error!("Error serializing request body: {:?}", err);
+            APIError::InternalServerError("Something went wrong! Please try again.".to_owned())
+        })?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.route.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(&body);
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// A JSON response that may have been replayed for a repeated idempotency key.
+pub struct Idempotent<T> {
+    value: T,
+    replayed: bool,
+    etag: Option<String>,
+}
+
+impl<T> Idempotent<T> {
+    fn fresh(value: T) -> Self {
+        Self { value, replayed: false, etag: None }
+    }
+
+    fn replayed(value: T) -> Self {
+        Self { value, replayed: true, etag: None }
+    }
+}
+
+impl<T: Versioned> Idempotent<T> {
+    /// Sends the row's `ETag`, so it can be edited with `If-Match` right away.
+    pub fn tagged(self) -> Self {
+        Self {
+            etag: Some(row_etag(&self.value)),
+            ..self
+        }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Idempotent<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Json(self.value).respond_to(request)?;
+        if self.replayed {
+            response.set_header(Header::new(REPLAYED_HEADER, "true"));
+        }
+        if let Some(etag) = self.etag {
+            response.set_header(Header::new("ETag", etag));
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use rocket::http::ContentType;
+    use rocket::local::asynchronous::Client;
+    use rocket::State;
+
+    #[derive(Serialize, Deserialize)]
+    struct Echo {
+        message: String,
+    }
+
+    /// Counts how often the wrapped handler actually runs.
+    struct Calls(AtomicU32);
+
+    #[post("/echo", data = "<echo>")]
+    async fn echo(echo: Json<Echo>, calls: &State<Calls>, idempotency: Idempotency<'_>) -> Result<Idempotent<Echo>, APIError> {
+        idempotency
+            .run(echo.0, |echo| async move {
+                let call = calls.0.fetch_add(1, Ordering::SeqCst) + 1;
+                if echo.message == "fail" {
+                    return Err(APIError::BadRequest("failed".to_owned()));
+                }
+                Ok(Echo {
+                    message: format!("{} #{}", echo.message, call),
+                })
+            })
+            .await
+    }
+
+    async fn client(pool: PgPool) -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![echo])
+            .manage(Calls(AtomicU32::new(0)))
+            .attach(IdempotencyFairing::new(pool));
+        Client::tracked(rocket).await.expect("valid rocket instance")
+    }
+
+    async fn post(client: &Client, key: Option<&str>, message: &str) -> (Status, Option<String>, String) {
+        let mut request = client
+            .post("/echo")
+            .header(ContentType::JSON)
+            .body(format!(r#"{{"message":"{}"}}"#, message));
+        if let Some(key) = key {
+            request = request.header(Header::new(IDEMPOTENCY_KEY_HEADER, key.to_owned()));
+        }
+        let response = request.dispatch().await;
+        let replayed = response.headers().get_one(REPLAYED_HEADER).map(String::from);
+        (response.status(), replayed, response.into_string().await.unwrap_or_default())
+    }
+
+    #[sqlx::test]
+    async fn should_replay_the_first_response(pool: PgPool) {
+        let client = client(pool).await;
+
+        let first = post(&client, Some("key-1"), "hello").await;
+        let retry = post(&client, Some("key-1"), "hello").await;
+
+        assert_eq!(first, (Status::Ok, None, r#"{"message":"hello #1"}"#.to_owned()));
+        assert_eq!(retry, (Status::Ok, Some("true".to_owned()), first.2));
+    }
+
+    #[sqlx::test]
+    async fn should_reject_a_reused_key_with_a_different_body(pool: PgPool) {
+        let client = client(pool).await;
+
+        post(&client, Some("key-1"), "hello").await;
+        let (status, _, _) = post(&client, Some("key-1"), "goodbye").await;
+
+        assert_eq!(status, Status::Conflict);
+    }
+
+    #[sqlx::test]
+    async fn should_run_every_request_without_a_key(pool: PgPool) {
+        let client = client(pool).await;
+
+        post(&client, None, "hello").await;
+        let (_, replayed, body) = post(&client, None, "hello").await;
+
+        assert_eq!(replayed, None);
+        assert_eq!(body, r#"{"message":"hello #2"}"#);
+    }
+
+    #[sqlx::test]
+    async fn should_let_failed_requests_be_retried(pool: PgPool) {
+        let client = client(pool).await;
+
+        let (first, _, _) = post(&client, Some("key-1"), "fail").await;
+        let (retry, replayed, _) = post(&client, Some("key-1"), "fail").await;
+        let (malformed, _, _) = post(&client, Some("bad key"), "hello").await;
+
+        assert_eq!(first, Status::BadRequest);
+        assert_eq!((retry, replayed), (Status::BadRequest, None));
+        assert_eq!(client.rocket().state::<Calls>().unwrap().0.load(Ordering::SeqCst), 2);
+        assert_eq!(malformed, Status::BadRequest);
+    }
+}
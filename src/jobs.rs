@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::{Build, Orbit, Rocket};
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+use tokio::sync::watch;
+use tokio::task::{JoinError, JoinHandle, JoinSet};
+
+use crate::persistance::jobs_dao::{ClaimedJob, JobsDao, JobsDaoImpl, NewJob};
+
+/// How often jobs finished more than `retention_days` ago are removed.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The `[jobs]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct JobsConfig {
+    /// Jobs run at the same time by this instance.
+    pub concurrency: usize,
+    /// How often due jobs are looked for.
+    pub poll_interval_seconds: u64,
+    /// How long a job may run before other instances consider it abandoned and run it again.
+    pub lease_seconds: u64,
+    /// Runs after which a failing job is given up on.
+    pub max_attempts: i32,
+    /// Delay before the first retry, doubled after every further failure up to `max_delay_seconds`.
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+    /// How long finished jobs are kept.
+    pub retention_days: u64,
+    /// How long running jobs may take to finish on shutdown before they are handed back.
+    pub shutdown_grace_seconds: u64,
+    /// Cron expressions (with seconds: `sec min hour day month weekday`, in UTC) by job kind.
+    pub schedules: HashMap<String, String>,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            poll_interval_seconds: 5,
+            lease_seconds: 5 * 60,
+            max_attempts: 5,
+            base_delay_seconds: 10,
+            max_delay_seconds: 60 * 60,
+            retention_days: 7,
+            shutdown_grace_seconds: 20,
+            schedules: HashMap::from([
+                ("purge_deleted".to_owned(), "0 0 * * * *".to_owned()),
+                ("recompute_reputation".to_owned(), "0 30 3 * * *".to_owned()),
+                ("expire_bounties".to_owned(), "0 */15 * * * *".to_owned()),
+            ]),
+        }
+    }
+}
+
+impl JobsConfig {
+    /// How long to wait after the `attempts`th failed run, or `None` to give up.
+    pub fn retry_delay(&self, attempts: i32, max_attempts: i32) -> Option<Duration> {
+        if attempts >= max_attempts {
+            return None;
+        }
+        let delay = self
+            .base_delay_seconds
+            .saturating_mul(1 << attempts.saturating_sub(1).clamp(0, 32))
+            .min(self.max_delay_seconds);
+        Some(Duration::from_secs(delay))
+    }
+}
+
+/// Runs the jobs of one kind. Jobs are run at least once, and again after failures, so handlers
+/// must be idempotent.
+#[rocket::async_trait]
+pub trait JobHandler {
+    fn kind(&self) -> &'static str;
+    async fn run(&self, payload: &JsonValue) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// A job kind enqueued on a cron-like schedule.
+pub struct Schedule {
+    pub kind: String,
+    pub cron: cron::Schedule,
+}
+
+impl Schedule {
+    pub fn parse(kind: &str, expression: &str) -> Result<Self, cron::error::Error> {
+        Ok(Self {
+            kind: kind.to_owned(),
+            cron: cron::Schedule::from_str(expression)?,
+        })
+    }
+
+    /// The first run strictly after now.
+    fn upcoming(&self) -> Option<OffsetDateTime> {
+        let next = self.cron.upcoming(chrono::Utc).next()?;
+        OffsetDateTime::from_unix_timestamp(next.timestamp()).ok()
+    }
+}
+
+/// Claims and runs jobs until told to shut down.
+pub struct JobRunner {
+    config: JobsConfig,
+    handlers: HashMap<&'static str, Arc<dyn JobHandler + Send + Sync>>,
+    schedules: Vec<Schedule>,
+    jobs_dao: Arc<dyn JobsDao + Send + Sync>,
+}
+
+impl JobRunner {
+    pub fn new(
+        config: JobsConfig,
+        handlers: &[Arc<dyn JobHandler + Send + Sync>],
+        schedules: Vec<Schedule>,
+        jobs_dao: Arc<dyn JobsDao + Send + Sync>,
+    ) -> Self {
+        let handlers = handlers
+            .iter()
+            .map(|handler| (handler.kind(), handler.clone()))
+            .collect();
+        Self { config, handlers, schedules, jobs_dao }
+    }
+
+    fn lease(&self) -> Duration {
+        Duration::from_secs(self.config.lease_seconds)
+    }
+
+    /// Enqueues the jobs of the schedules that are due.
+    pub async fn enqueue_scheduled(&self) {
+        for schedule in &self.schedules {
+            let Some(next_run_at) = schedule.upcoming() else {
+                continue;
+            };
+            let job = NewJob {
+                kind: schedule.kind.clone(),
+                payload: JsonValue::Object(Default::default()),
+                run_at: None,
+                max_attempts: None,
+            };
+            if let Err(err) = self.jobs_dao.enqueue_scheduled(&schedule.kind, next_run_at, job).await {
+                error!("Error enqueuing scheduled {} job: {:?}", schedule.kind, err);
+            }
+        }
+    }
+
+    /// Runs a claimed job and records how it went.
+    pub async fn run_job(&self, job: ClaimedJob) {
+        let max_attempts = job.max_attempts.unwrap_or(self.config.max_attempts);
+        let result = match self.handlers.get(job.kind.as_str()) {
+            // Also covers jobs abandoned by instances that went down while running them
+            _ if job.attempts > max_attempts => Err(format!("Gave up after {} attempts", max_attempts)),
+            Some(handler) => match tokio::time::timeout(self.lease(), handler.run(&job.payload)).await {
+                Ok(result) => result.map_err(|err| err.to_string()),
+                Err(_) => Err("Timed out".to_owned()),
+            },
+            None => Err(format!("No handler for {} jobs", job.kind)),
+        };
+
+        let recorded = match result {
+            Ok(()) => {
+                info!(job_id = job.job_id, attempts = job.attempts, "Ran {} job", job.kind);
+                self.jobs_dao.complete(job.job_id).await
+            }
+            Err(error) => {
+                let retry_in = match self.handlers.contains_key(job.kind.as_str()) {
+                    true => self.config.retry_delay(job.attempts, max_attempts),
+                    false => None,
+                };
+                match retry_in {
+                    Some(_) => warn!(job_id = job.job_id, attempts = job.attempts, "Error running {} job: {}", job.kind, error),
+                    None => error!(job_id = job.job_id, attempts = job.attempts, "Giving up on {} job: {}", job.kind, error),
+                }
+                self.jobs_dao.fail(job.job_id, error, retry_in).await
+            }
+        };
+        if let Err(err) = recorded {
+            error!("Error recording the outcome of job {}: {:?}", job.job_id, err);
+        }
+    }
+
+    /// Runs jobs until `shutdown` turns true, then waits for the running ones for up to
+    /// `shutdown_grace_seconds` and hands the others back.
+    pub async fn run(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) {
+        let poll_interval = Duration::from_secs(self.config.poll_interval_seconds);
+        let retention = Duration::from_secs(self.config.retention_days * 24 * 60 * 60);
+        let mut purged_at: Option<Instant> = None;
+        let mut running = JoinSet::new();
+        let mut running_ids = HashMap::new();
+
+        while !*shutdown.borrow() {
+            if purged_at.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL) {
+                match self.jobs_dao.purge_finished(retention).await {
+                    Ok(purged) if purged > 0 => info!("Removed {} finished jobs", purged),
+                    Ok(_) => {}
+                    Err(err) => error!("Error removing finished jobs: {:?}", err),
+                }
+                purged_at = Some(Instant::now());
+            }
+
+            self.enqueue_scheduled().await;
+
+            let free = self.config.concurrency.saturating_sub(running.len());
+            if free > 0 {
+                match self.jobs_dao.claim_due(free as i64, self.lease()).await {
+                    Ok(jobs) => {
+                        for job in jobs {
+                            let runner = self.clone();
+                            let job_id = job.job_id;
+                            let task = running.spawn(async move { runner.run_job(job).await });
+                            running_ids.insert(task.id(), job_id);
+                        }
+                    }
+                    Err(err) => error!("Error claiming jobs: {:?}", err),
+                }
+            }
+
+            tokio::select! {
+                _ = shutdown.changed() => {}
+                Some(finished) = running.join_next_with_id(), if !running.is_empty() => forget(&mut running_ids, finished),
+                _ = tokio::time::sleep(poll_interval) => {}
+            }
+            while let Some(finished) = running.try_join_next_with_id() {
+                forget(&mut running_ids, finished);
+            }
+        }
+
+        if running.is_empty() {
+            return;
+        }
+        info!("Waiting for {} running jobs", running.len());
+        let grace = Duration::from_secs(self.config.shutdown_grace_seconds);
+        let drained = tokio::time::timeout(grace, async {
+            while let Some(finished) = running.join_next_with_id().await {
+                forget(&mut running_ids, finished);
+            }
+        })
+        .await;
+        if drained.is_err() {
+            running.abort_all();
+            for job_id in running_ids.into_values() {
+                warn!(job_id, "Handing back unfinished job");
+                if let Err(err) = self.jobs_dao.release(job_id).await {
+                    error!("Error handing back job {}: {:?}", job_id, err);
+                }
+            }
+        }
+    }
+}
+
+/// Forgets the ID of a job whose task finished.
+fn forget(running_ids: &mut HashMap<tokio::task::Id, i64>, finished: Result<(tokio::task::Id, ()), JoinError>) {
+    let id = match finished {
+        Ok((id, ())) => id,
+        Err(err) => err.id(),
+    };
+    running_ids.remove(&id);
+}
+
+/// The kinds of jobs with a registered handler, managed by `JobsFairing`.
+pub struct JobKinds(pub Vec<&'static str>);
+
+/// Runs background jobs once Rocket has launched, and stops them gracefully when it shuts down.
+pub struct JobsFairing {
+    db: PgPool,
+    handlers: Vec<Arc<dyn JobHandler + Send + Sync>>,
+    runner: Mutex<Option<(watch::Sender<bool>, JoinHandle<()>)>>,
+}
+
+impl JobsFairing {
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            handlers: Vec::new(),
+            runner: Mutex::new(None),
+        }
+    }
+
+    /// Adds the handler of a job kind.
+    pub fn register(mut self, handler: impl JobHandler + Send + Sync + 'static) -> Self {
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for JobsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Background jobs",
+            kind: Kind::Ignite | Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let kinds = self.handlers.iter().map(|handler| handler.kind()).collect();
+        Ok(rocket.manage(JobKinds(kinds)))
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let config = rocket
+            .figment()
+            .extract_inner::<JobsConfig>("jobs")
+            .unwrap_or_default();
+
+        let mut schedules = Vec::new();
+        for (kind, expression) in &config.schedules {
+            if !self.handlers.iter().any(|handler| handler.kind() == kind) {
+                warn!("Ignoring the schedule of unknown {} jobs", kind);
+                continue;
+            }
+            match Schedule::parse(kind, expression) {
+                Ok(schedule) => schedules.push(schedule),
+                Err(err) => error!("Invalid schedule {:?} for {} jobs: {}", expression, kind, err),
+            }
+        }
+
+        let dao = Arc::new(JobsDaoImpl::new(self.db.clone()));
+        let runner = Arc::new(JobRunner::new(config, &self.handlers, schedules, dao));
+        let (shutdown, watch) = watch::channel(false);
+        let task = tokio::spawn(runner.run(watch));
+        *self.runner.lock().unwrap() = Some((shutdown, task));
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        let runner = self.runner.lock().unwrap().take();
+        if let Some((shutdown, task)) = runner {
+            let _ = shutdown.send(true);
+            if let Err(err) = task.await {
+                error!("Error stopping background jobs: {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts its runs, failing the first `failures` and sleeping `duration` in each.
+    #[derive(Default)]
+    struct Counter {
+        failures: AtomicUsize,
+        runs: AtomicUsize,
+        duration: Duration,
+    }
+
+    #[rocket::async_trait]
+    impl JobHandler for Arc<Counter> {
+        fn kind(&self) -> &'static str {
+            "count"
+        }
+
+        async fn run(&self, _payload: &JsonValue) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.duration).await;
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err("unavailable".into());
+            }
+            Ok(())
+        }
+    }
+
+    fn runner(pool: &PgPool, counter: &Arc<Counter>, config: JobsConfig) -> Arc<JobRunner> {
+        let handlers: Vec<Arc<dyn JobHandler + Send + Sync>> = vec![Arc::new(counter.clone())];
+        Arc::new(JobRunner::new(config, &handlers, Vec::new(), Arc::new(JobsDaoImpl::new(pool.clone()))))
+    }
+
+    async fn enqueue(pool: &PgPool, kind: &str) -> i64 {
+        JobsDaoImpl::new(pool.clone())
+            .enqueue(NewJob {
+                kind: kind.to_owned(),
+                payload: JsonValue::Null,
+                run_at: None,
+                max_attempts: Some(2),
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn status(pool: &PgPool, job_id: i64) -> (String, i32) {
+        let record = sqlx::query!("SELECT status, attempts FROM jobs WHERE job_id = $1", job_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        (record.status, record.attempts)
+    }
+
+    async fn run_due(runner: &JobRunner) {
+        for job in runner.jobs_dao.claim_due(10, runner.lease()).await.unwrap() {
+            runner.run_job(job).await;
+        }
+    }
+
+    #[test]
+    fn retry_delay_should_back_off_exponentially() {
+        let config = JobsConfig {
+            base_delay_seconds: 10,
+            max_delay_seconds: 30,
+            ..JobsConfig::default()
+        };
+
+        let delays: Vec<_> = (1..=4).map(|attempts| config.retry_delay(attempts, 4)).collect();
+
+        assert_eq!(delays, [Some(10), Some(20), Some(30), None].map(|delay| delay.map(Duration::from_secs)));
+    }
+
+    #[sqlx::test]
+    async fn failed_jobs_should_be_retried_then_given_up_on(pool: PgPool) {
+        let counter = Arc::new(Counter {
+            failures: AtomicUsize::new(3),
+            ..Counter::default()
+        });
+        let runner = runner(&pool, &counter, JobsConfig {
+            base_delay_seconds: 0,
+            ..JobsConfig::default()
+        });
+        let retried = enqueue(&pool, "count").await;
+        let unknown = enqueue(&pool, "unknown").await;
+
+        run_due(&runner).await;
+        assert_eq!(status(&pool, retried).await, ("pending".to_owned(), 1));
+        assert_eq!(status(&pool, unknown).await, ("failed".to_owned(), 1));
+
+        run_due(&runner).await;
+        assert_eq!(status(&pool, retried).await, ("failed".to_owned(), 2));
+        assert_eq!(counter.runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[sqlx::test]
+    async fn shutdown_should_hand_back_jobs_still_running_after_the_grace_period(pool: PgPool) {
+        let counter = Arc::new(Counter {
+            duration: Duration::from_secs(60),
+            ..Counter::default()
+        });
+        let runner = runner(&pool, &counter, JobsConfig {
+            shutdown_grace_seconds: 0,
+            schedules: HashMap::new(),
+            ..JobsConfig::default()
+        });
+        let job_id = enqueue(&pool, "count").await;
+
+        let (shutdown, watch) = watch::channel(false);
+        let task = tokio::spawn(runner.run(watch));
+        while counter.runs.load(Ordering::SeqCst) == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap();
+
+        assert_eq!(status(&pool, job_id).await, ("pending".to_owned(), 0));
+    }
+}
@@ -2,26 +2,82 @@
 extern crate rocket;
 
 #[macro_use]
-extern crate log;
-
-extern crate pretty_env_logger;
+extern crate tracing;
 
+mod auth;
+mod badges;
+mod bounties;
+mod closing;
+mod flags;
 mod cors;
+mod drain;
+mod etag;
+mod events;
+mod feed;
 mod handlers;
+mod idempotency;
+mod jobs;
+mod markdown;
 mod models;
+mod outbox;
 mod persistance;
+mod purge;
+mod rate_limit;
+mod reputation;
+mod request_id;
+mod screening;
+mod similar;
+mod telemetry;
+mod webhooks;
+
+use std::sync::Arc;
 
 use dotenvy::dotenv;
 use sqlx::postgres::PgPoolOptions;
+use badges::{BadgeEngine, BadgeRules, BadgesConfig};
+use bounties::{BountiesConfig, ExpireBountiesJob};
+use closing::ClosingConfig;
 use cors::*;
+use drain::DrainFairing;
 use handlers::*;
+use events::QuestionEventsFairing;
+use feed::LiveFeedFairing;
+use flags::FlagsConfig;
+use idempotency::IdempotencyFairing;
+use jobs::JobsFairing;
+use outbox::{LoggingHandler, OutboxFairing};
+use purge::{PurgeDeletedJob, SoftDeleteConfig};
+use rate_limit::{PruneRateLimitBucketsJob, RateLimitConfig, RateLimitFairing};
+use reputation::{RecomputeReputationJob, ReputationConfig};
+use request_id::RequestIdFairing;
+use screening::{ContentScreener, ScreeningConfig};
+use similar::SimilarQuestionsConfig;
+use telemetry::{TelemetryConfig, TracingFairing};
+use webhooks::WebhooksFairing;
 use crate::persistance::answers_dao::{AnswersDao, AnswersDaoImpl};
+use crate::persistance::audit_dao::{AuditDao, AuditDaoImpl};
+use crate::persistance::badges_dao::{BadgesDao, BadgesDaoImpl};
+use crate::persistance::bounties_dao::{BountiesDao, BountiesDaoImpl};
+use crate::persistance::closing_dao::{ClosingDao, ClosingDaoImpl};
+use crate::persistance::flags_dao::{FlagsDao, FlagsDaoImpl};
+use crate::persistance::jobs_dao::{JobsDao, JobsDaoImpl};
+use crate::persistance::question_events_dao::{QuestionEventsDao, QuestionEventsDaoImpl};
+use crate::persistance::question_links_dao::{QuestionLinksDao, QuestionLinksDaoImpl};
 use crate::persistance::questions_dao::{QuestionsDao, QuestionsDaoImpl};
+use crate::persistance::reputation_dao::{ReputationDao, ReputationDaoImpl};
+use crate::persistance::screening_dao::ScreeningDaoImpl;
+use crate::persistance::suggested_edits_dao::{SuggestedEditsDao, SuggestedEditsDaoImpl};
+use crate::persistance::unit_of_work::{TransactionManager, TransactionManagerImpl};
+use crate::persistance::users_dao::{UsersDao, UsersDaoImpl};
+use crate::persistance::votes_dao::{VotesDao, VotesDaoImpl};
+use crate::persistance::webhooks_dao::{WebhooksDao, WebhooksDaoImpl};
 
-#[launch]
-async fn rocket() -> _ {
-    pretty_env_logger::init();
+#[rocket::main]
+async fn main() {
     dotenv().ok();
+    // SIGTERM is left to `DrainFairing`, which fails readiness before asking Rocket to shut down
+    let figment = rocket::Config::figment().merge(("shutdown.signals", Vec::<String>::new()));
+    let telemetry = telemetry::init(&TelemetryConfig::from_figment(&figment));
 
     let pool = PgPoolOptions::new()
         .max_connections(5)
@@ -29,22 +85,161 @@ async fn rocket() -> _ {
         .await
         .expect("Unable to connect to database");
 
+    let rate_limit = RateLimitFairing::new(pool.clone());
+    let idempotency = IdempotencyFairing::new(pool.clone());
+    let events = QuestionEventsFairing::new(pool.clone());
+    let feed = LiveFeedFairing::new(pool.clone());
+    let webhooks = WebhooksFairing::new(pool.clone());
+    let badges: BadgesConfig = figment
+        .extract_inner("badges")
+        .unwrap_or_default();
+    let badge_rules = Arc::new(BadgeRules::load(&badges.rules_file).expect("Unable to load badge rules"));
+    let outbox = OutboxFairing::new(pool.clone())
+        .register(LoggingHandler)
+        .register(BadgeEngine::new(badge_rules.clone(), Box::new(BadgesDaoImpl::new(pool.clone()))));
+    let soft_delete: SoftDeleteConfig = figment
+        .extract_inner("soft_delete")
+        .unwrap_or_default();
+    let reputation: ReputationConfig = figment
+        .extract_inner("reputation")
+        .unwrap_or_default();
+    let bounties: BountiesConfig = figment
+        .extract_inner("bounties")
+        .unwrap_or_default();
+    let closing: ClosingConfig = figment
+        .extract_inner("closing")
+        .unwrap_or_default();
+    let flags: FlagsConfig = figment
+        .extract_inner("flags")
+        .unwrap_or_default();
+    let screening: ScreeningConfig = figment
+        .extract_inner("screening")
+        .unwrap_or_default();
+    let similar_questions: SimilarQuestionsConfig = figment
+        .extract_inner("similar_questions")
+        .unwrap_or_default();
+    let screener = ContentScreener::from_config(&screening, Box::new(ScreeningDaoImpl::new(pool.clone())), flags.hide_after_spam_flags);
+    let rate_limits: RateLimitConfig = figment
+        .extract_inner("rate_limit")
+        .unwrap_or_default();
+    let jobs = JobsFairing::new(pool.clone())
+        .register(PurgeDeletedJob::new(pool.clone(), &soft_delete))
+        .register(PruneRateLimitBucketsJob::new(pool.clone(), &rate_limits))
+        .register(RecomputeReputationJob::new(pool.clone()))
+        .register(ExpireBountiesJob::new(pool.clone(), &bounties));
     let questions_dao = QuestionsDaoImpl::new(pool.clone());
-    let answers_dao = AnswersDaoImpl::new(pool);
+    let answers_dao = AnswersDaoImpl::new(pool.clone());
+    let users_dao = UsersDaoImpl::new(pool.clone());
+    let audit_dao = AuditDaoImpl::new(pool.clone());
+    let question_events_dao = QuestionEventsDaoImpl::new(pool.clone());
+    let question_links_dao = QuestionLinksDaoImpl::new(pool.clone());
+    let webhooks_dao = WebhooksDaoImpl::new(pool.clone());
+    let jobs_dao = JobsDaoImpl::new(pool.clone());
+    let votes_dao = VotesDaoImpl::new(pool.clone());
+    let reputation_dao = ReputationDaoImpl::new(pool.clone());
+    let badges_dao = BadgesDaoImpl::new(pool.clone());
+    let bounties_dao = BountiesDaoImpl::new(pool.clone());
+    let closing_dao = ClosingDaoImpl::new(pool.clone());
+    let flags_dao = FlagsDaoImpl::new(pool.clone());
+    let suggested_edits_dao = SuggestedEditsDaoImpl::new(pool.clone());
+    let transactions = TransactionManagerImpl::new(pool.clone());
 
-    rocket::build()
+    let result = rocket::custom(figment)
         .mount(
             "/",
             routes![
+                liveness,
+                readiness,
                 create_question,
                 read_questions,
+                find_similar_questions,
+                read_related_questions,
                 delete_question,
+                restore_question,
+                update_question,
+                vote_to_close,
+                vote_to_reopen,
                 create_answer,
                 read_answers,
-                delete_answer
+                delete_answer,
+                restore_answer,
+                update_answer,
+                vote_question,
+                vote_answer,
+                accept_answer,
+                read_reputation,
+                offer_bounty,
+                award_bounty,
+                read_badges,
+                read_user_badges,
+                create_flag,
+                read_flag_queue,
+                claim_flag,
+                resolve_flag,
+                read_flag_stats,
+                create_suggested_edit,
+                read_suggested_edit_queue,
+                review_suggested_edit,
+                question_events,
+                live_feed,
+                create_user,
+                read_audit_events,
+                create_job,
+                create_webhook,
+                read_webhooks,
+                delete_webhook,
+                read_webhook_deliveries,
+                retry_webhook_delivery
             ],
         )
+        .register("/", catchers![default_catcher])
+        .attach(RequestIdFairing)
+        .attach(TracingFairing)
+        .attach(DrainFairing::default())
         .attach(CORS)
+        .attach(rate_limit)
+        .attach(idempotency)
+        .attach(events)
+        .attach(feed)
+        .attach(webhooks)
+        .attach(outbox)
+        .attach(jobs)
+        .manage(reputation)
+        .manage(bounties)
+        .manage(closing)
+        .manage(flags)
+        .manage(screener)
+        .manage(similar_questions)
+        .manage(badge_rules)
         .manage(Box::new(questions_dao) as Box<dyn QuestionsDao + Send + Sync>)
         .manage(Box::new(answers_dao) as Box<dyn AnswersDao + Send + Sync>)
-}
\ No newline at end of file
+        .manage(Box::new(users_dao) as Box<dyn UsersDao + Send + Sync>)
+        .manage(Box::new(audit_dao) as Box<dyn AuditDao + Send + Sync>)
+        .manage(Box::new(question_events_dao) as Box<dyn QuestionEventsDao + Send + Sync>)
+        .manage(Box::new(question_links_dao) as Box<dyn QuestionLinksDao + Send + Sync>)
+        .manage(Box::new(webhooks_dao) as Box<dyn WebhooksDao + Send + Sync>)
+        .manage(Box::new(jobs_dao) as Box<dyn JobsDao + Send + Sync>)
+        .manage(Box::new(votes_dao) as Box<dyn VotesDao + Send + Sync>)
+        .manage(Box::new(reputation_dao) as Box<dyn ReputationDao + Send + Sync>)
+        .manage(Box::new(badges_dao) as Box<dyn BadgesDao + Send + Sync>)
+        .manage(Box::new(bounties_dao) as Box<dyn BountiesDao + Send + Sync>)
+        .manage(Box::new(closing_dao) as Box<dyn ClosingDao + Send + Sync>)
+        .manage(Box::new(flags_dao) as Box<dyn FlagsDao + Send + Sync>)
+        .manage(Box::new(suggested_edits_dao) as Box<dyn SuggestedEditsDao + Send + Sync>)
+        .manage(Box::new(transactions) as Box<dyn TransactionManager + Send + Sync>)
+        .launch()
+        .await;
+
+    if let Err(err) = &result {
+        error!("Rocket failed: {}", err);
+    }
+
+    // Rocket returns once requests have drained and background tasks have stopped
+    pool.close().await;
+    info!("Closed the database pool");
+    telemetry.shutdown();
+
+    if result.is_err() {
+        std::process::exit(1);
+    }
+}
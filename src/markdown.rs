@@ -0,0 +1,146 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use ammonia::Builder;
+use pulldown_cmark::{html, Options, Parser};
+
+/// The tags rendered Markdown can keep, along with their allowed attributes. Anything else,
+/// including raw HTML written in the Markdown, is stripped.
+const ALLOWED_TAGS: [(&str, &[&str]); 27] = [
+    ("p", &[]),
+    ("br", &[]),
+    ("hr", &[]),
+    ("h1", &[]),
+    ("h2", &[]),
+    ("h3", &[]),
+    ("h4", &[]),
+    ("h5", &[]),
+    ("h6", &[]),
+    ("strong", &[]),
+    ("em", &[]),
+    ("del", &[]),
+    ("blockquote", &[]),
+    ("ul", &[]),
+    ("ol", &["start"]),
+    ("li", &[]),
+    ("pre", &[]),
+    // The language of fenced code, as a `language-*` class for syntax highlighters
+    ("code", &["class"]),
+    ("a", &["href", "title"]),
+    ("img", &["src", "alt", "title"]),
+    ("table", &[]),
+    ("thead", &[]),
+    ("tbody", &[]),
+    ("tr", &[]),
+    ("th", &[]),
+    ("td", &[]),
+    // Task list checkboxes
+    ("input", &["type", "checked", "disabled"]),
+];
+
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::empty();
+    for (tag, attributes) in ALLOWED_TAGS {
+        builder.add_tags([tag]).add_tag_attributes(tag, attributes.iter().copied());
+    }
+    builder
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("nofollow noopener noreferrer"))
+        .attribute_filter(filter_attribute);
+    builder
+});
+
+/// Narrows down the values of the attributes `ALLOWED_TAGS` lets through.
+fn filter_attribute<'u>(element: &str, attribute: &str, value: &'u str) -> Option<Cow<'u, str>> {
+    match (element, attribute) {
+        ("code", "class") => value.split_whitespace().find(|class| is_language_class(class)).map(Cow::Borrowed),
+        ("input", "type") => (value == "checkbox").then_some(Cow::Borrowed(value)),
+        _ => Some(Cow::Borrowed(value)),
+    }
+}
+
+fn is_language_class(class: &str) -> bool {
+    class
+        .strip_prefix("language-")
+        .is_some_and(|language| !language.is_empty() && language.chars().all(|c| c.is_ascii_alphanumeric() || "+#_-.".contains(c)))
+}
+
+/// Renders CommonMark, with GitHub's tables, strikethrough and task lists, to HTML that is safe to
+/// embed in a page.
+pub fn render(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+    SANITIZER.clean(&unsafe_html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_should_support_commonmark_and_gfm() {
+        let html = render("# Title\n\n**bold** ~~gone~~ [link](https://example.com)\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n");
+
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains("<strong>bold</strong> <del>gone</del>"));
+        assert!(html.contains(r#"<a href="https://example.com" rel="nofollow noopener noreferrer">link</a>"#));
+        assert!(html.contains("<table><thead><tr><th>a</th><th>b</th></tr></thead><tbody>"));
+        assert!(html.contains(r#"<li><input disabled="" type="checkbox" checked="">"#), "{}", html);
+    }
+
+    #[test]
+    fn render_should_keep_the_language_of_fenced_code() {
+        let html = render("```rust\nfn main() { println!(\"<hi>\"); }\n```\n");
+
+        assert_eq!(html, "<pre><code class=\"language-rust\">fn main() { println!(\"&lt;hi&gt;\"); }\n</code></pre>\n");
+    }
+
+    #[test]
+    fn render_should_strip_scripts_and_raw_html() {
+        let html = render("<script>alert(1)</script>\n\nhi <iframe src=\"https://evil.example\"></iframe><style>p{}</style>");
+
+        assert!(!html.contains("script") && !html.contains("alert") && !html.contains("iframe") && !html.contains("style"), "{}", html);
+        assert!(html.contains("hi"));
+    }
+
+    #[test]
+    fn render_should_strip_event_handlers_and_styles() {
+        let html = render("<img src=\"https://example.com/a.png\" onerror=\"alert(1)\" style=\"position:fixed\">\n\n<a href=\"https://example.com\" onclick=\"alert(1)\">x</a>");
+
+        assert!(!html.contains("onerror") && !html.contains("onclick") && !html.contains("style") && !html.contains("alert"), "{}", html);
+        assert!(html.contains(r#"<img src="https://example.com/a.png">"#), "{}", html);
+    }
+
+    #[test]
+    fn render_should_drop_dangerous_urls() {
+        for markdown in [
+            "[x](javascript:alert(1))",
+            "[x](JaVaScRiPt:alert(1))",
+            "[x](jav&#x61;script:alert(1))",
+            "[x](data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==)",
+            "![x](vbscript:msgbox(1))",
+            "<a href=\"javascript:alert(1)\">x</a>",
+            "[x]: javascript:alert(1)\n\n[y][x]",
+        ] {
+            let html = render(markdown);
+            assert!(!html.to_lowercase().contains("script:") && !html.contains("data:"), "{} rendered as {}", markdown, html);
+        }
+    }
+
+    #[test]
+    fn render_should_not_let_code_fences_inject_attributes() {
+        let html = render("```rust\" onmouseover=\"alert(1)\nx\n```\n\n<code class=\"hljs language-js evil\">y</code>");
+
+        assert!(!html.contains("onmouseover"), "{}", html);
+        assert!(html.contains(r#"<code class="language-js">y</code>"#), "{}", html);
+    }
+
+    #[test]
+    fn render_should_escape_text() {
+        let html = render("1 < 2 && \"quotes\" > 'single'");
+
+        assert_eq!(html, "<p>1 &lt; 2 &amp;&amp; \"quotes\" &gt; 'single'</p>\n");
+    }
+}
@@ -1,18 +1,61 @@
 use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
 use thiserror::Error;
 
+use crate::markdown;
+
 #[derive(Serialize, Deserialize)]
 pub struct Question {
     pub title: String,
     pub description: String,
 }
 
+/// A question description or answer content, which are Markdown, along with its HTML rendering.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct RenderedBody {
+    pub body_markdown: String,
+    /// Sanitized, so it is safe to embed in a page as is.
+    pub body_html: String,
+}
+
+impl RenderedBody {
+    pub fn new(markdown: &str) -> Self {
+        Self {
+            body_markdown: markdown.to_owned(),
+            body_html: markdown::render(markdown),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct QuestionDetail {
     pub question_uuid: String,
     pub title: String,
     pub description: String,
+    /// The description, rendered.
+    #[serde(flatten)]
+    pub body: RenderedBody,
     pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// Set once enough users flagged the question as spam; hidden questions are listed like deleted ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hidden_at: Option<String>,
+    /// Chosen by the question's author; see `POST /answer/accept`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_answer_uuid: Option<String>,
+    /// The open bounty on the question, if any; only filled in when listing questions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounty: Option<OpenBounty>,
+    /// Why the question was closed; closed questions take no new answers until they are reopened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed_reason: Option<CloseReason>,
+    /// The question this one duplicates, when closed as a duplicate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+    /// Incremented on every change; `"<version>"` is the question's ETag.
+    pub version: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -20,6 +63,159 @@ pub struct QuestionId {
     pub question_uuid: String,
 }
 
+/// A new title and/or description for a question; what is left out stays as it is.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuestionEdit {
+    pub question_uuid: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    Duplicate,
+    OffTopic,
+    NeedsDetails,
+}
+
+impl CloseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::Duplicate => "duplicate",
+            CloseReason::OffTopic => "off_topic",
+            CloseReason::NeedsDetails => "needs_details",
+        }
+    }
+
+    pub fn parse(reason: &str) -> Option<Self> {
+        match reason {
+            "duplicate" => Some(CloseReason::Duplicate),
+            "off_topic" => Some(CloseReason::OffTopic),
+            "needs_details" => Some(CloseReason::NeedsDetails),
+            _ => None,
+        }
+    }
+}
+
+/// A vote to close a question; `duplicate_of` is required for duplicates and not allowed otherwise.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CloseVote {
+    pub question_uuid: String,
+    pub reason: CloseReason,
+    #[serde(default)]
+    pub duplicate_of: Option<String>,
+}
+
+/// Where the votes to close (or reopen) a question stand after a vote.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClosureVotes {
+    pub question_uuid: String,
+    /// Pending votes; cleared when the question is closed or reopened.
+    pub votes: i64,
+    pub votes_needed: i64,
+    pub closed: bool,
+}
+
+/// Criteria for listing questions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuestionFilter {
+    /// Also list soft-deleted questions.
+    pub include_deleted: bool,
+    /// Only list questions with an open bounty.
+    pub open_bounty: bool,
+}
+
+/// An existing question that may be what the user is about to ask.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SimilarQuestion {
+    pub question_uuid: String,
+    pub title: String,
+    /// Trigram similarity to the question asked, from 0 to 1, the title weighing twice the description.
+    pub similarity: f32,
+    pub answer_count: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_answer_uuid: Option<String>,
+    /// Set when the question itself was closed as a duplicate of another one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+}
+
+/// A question linked from or to another one, by mentioning its ID or URL.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkedQuestion {
+    pub question_uuid: String,
+    pub title: String,
+    pub answer_count: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_answer_uuid: Option<String>,
+    /// When the most recent link between the two questions was made.
+    pub linked_at: String,
+}
+
+/// The sidebar of a question page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RelatedQuestions {
+    /// Questions similar to this one, the most similar first.
+    pub related: Vec<SimilarQuestion>,
+    pub linked: Vec<LinkedQuestion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenBounty {
+    pub bounty_id: i64,
+    pub amount: i32,
+    pub expires_at: String,
+}
+
+/// An offer of `amount` reputation for answering a question.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Bounty {
+    pub question_uuid: String,
+    pub amount: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BountyStatus {
+    Open,
+    /// Awarded by the offerer, or automatically on expiry.
+    Awarded,
+    /// Given back because the question was deleted.
+    Refunded,
+    /// Lost: expired without an answer good enough to be awarded automatically.
+    Expired,
+}
+
+impl BountyStatus {
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "open" => Some(BountyStatus::Open),
+            "awarded" => Some(BountyStatus::Awarded),
+            "refunded" => Some(BountyStatus::Refunded),
+            "expired" => Some(BountyStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BountyDetail {
+    pub bounty_id: i64,
+    pub question_uuid: String,
+    pub offered_by: String,
+    pub amount: i32,
+    pub status: BountyStatus,
+    pub awarded_answer_uuid: Option<String>,
+    /// Half the amount when awarded automatically.
+    pub awarded_amount: Option<i32>,
+    pub created_at: String,
+    pub expires_at: String,
+    pub closed_at: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Answer {
     pub question_uuid: String,
@@ -31,7 +227,17 @@ pub struct AnswerDetail {
     pub answer_uuid: String,
     pub question_uuid: String,
     pub content: String,
+    /// The content, rendered.
+    #[serde(flatten)]
+    pub body: RenderedBody,
     pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// Set once enough users flagged the answer as spam; hidden answers are listed like deleted ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hidden_at: Option<String>,
+    /// Incremented on every change; `"<version>"` is the answer's ETag.
+    pub version: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -39,10 +245,592 @@ pub struct AnswerId {
     pub answer_uuid: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnswerEdit {
+    pub answer_uuid: String,
+    pub content: String,
+}
+
+/// What can be voted on, flagged or edited.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PostType {
+    Question,
+    Answer,
+}
+
+impl PostType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostType::Question => "question",
+            PostType::Answer => "answer",
+        }
+    }
+
+    pub fn parse(post_type: &str) -> Option<Self> {
+        match post_type {
+            "question" => Some(PostType::Question),
+            "answer" => Some(PostType::Answer),
+            _ => None,
+        }
+    }
+}
+
+/// `value` is 1 to upvote, -1 to downvote, or 0 to retract the actor's vote.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QuestionVote {
+    pub question_uuid: String,
+    pub value: i16,
+}
+
+/// `value` is 1 to upvote, -1 to downvote, or 0 to retract the actor's vote.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnswerVote {
+    pub answer_uuid: String,
+    pub value: i16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagReason {
+    Spam,
+    Rude,
+    LowQuality,
+    Other,
+}
+
+impl FlagReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlagReason::Spam => "spam",
+            FlagReason::Rude => "rude",
+            FlagReason::LowQuality => "low_quality",
+            FlagReason::Other => "other",
+        }
+    }
+
+    pub fn parse(reason: &str) -> Option<Self> {
+        match reason {
+            "spam" => Some(FlagReason::Spam),
+            "rude" => Some(FlagReason::Rude),
+            "low_quality" => Some(FlagReason::LowQuality),
+            "other" => Some(FlagReason::Other),
+            _ => None,
+        }
+    }
+}
+
+/// A report of a question or an answer for moderators to review.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Flag {
+    pub target_type: PostType,
+    pub target_uuid: String,
+    pub reason: FlagReason,
+    #[serde(default)]
+    pub details: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagStatus {
+    Pending,
+    Helpful,
+    Declined,
+}
+
+impl FlagStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlagStatus::Pending => "pending",
+            FlagStatus::Helpful => "helpful",
+            FlagStatus::Declined => "declined",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "pending" => Some(FlagStatus::Pending),
+            "helpful" => Some(FlagStatus::Helpful),
+            "declined" => Some(FlagStatus::Declined),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FlagDetail {
+    pub flag_id: i64,
+    pub target_type: PostType,
+    pub target_uuid: String,
+    /// `None` for posts quarantined by content screening.
+    pub flagged_by: Option<String>,
+    pub reason: FlagReason,
+    pub details: Option<String>,
+    pub status: FlagStatus,
+    pub claimed_by: Option<String>,
+    pub claimed_at: Option<String>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<String>,
+    pub created_at: String,
+}
+
+/// How a moderator resolves a flag: `helpful` or `declined`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FlagResolution {
+    pub status: FlagStatus,
+}
+
+/// How a user's flags were resolved.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FlagStats {
+    pub user_uuid: String,
+    pub pending: i64,
+    pub helpful: i64,
+    pub declined: i64,
+    /// The share of resolved flags found helpful, once any were resolved.
+    pub accuracy: Option<f64>,
+}
+
+/// A change to someone else's question or answer for reviewers to approve: a new `title` and/or
+/// `description` for a question, or `content` for an answer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SuggestedEdit {
+    pub target_type: PostType,
+    pub target_uuid: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Why the change is needed.
+    #[serde(default)]
+    pub summary: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestedEditStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl SuggestedEditStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuggestedEditStatus::Pending => "pending",
+            SuggestedEditStatus::Approved => "approved",
+            SuggestedEditStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "pending" => Some(SuggestedEditStatus::Pending),
+            "approved" => Some(SuggestedEditStatus::Approved),
+            "rejected" => Some(SuggestedEditStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SuggestedEditDetail {
+    pub edit_id: i64,
+    pub target_type: PostType,
+    pub target_uuid: String,
+    pub suggested_by: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub content: Option<String>,
+    pub summary: Option<String>,
+    /// The version of the post the edit was suggested against.
+    pub base_version: i64,
+    pub status: SuggestedEditStatus,
+    pub reviewed_by: Option<String>,
+    pub review_comment: Option<String>,
+    pub reviewed_at: Option<String>,
+    pub created_at: String,
+}
+
+/// A reviewer's decision on a suggested edit: `approved` or `rejected`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SuggestedEditReview {
+    pub status: SuggestedEditStatus,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// What content screening did with a new post.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreeningDecision {
+    Accepted,
+    /// Created, but hidden until a moderator resolves the flag raised for it.
+    Quarantined,
+    Rejected,
+}
+
+impl ScreeningDecision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScreeningDecision::Accepted => "accepted",
+            ScreeningDecision::Quarantined => "quarantined",
+            ScreeningDecision::Rejected => "rejected",
+        }
+    }
+}
+
+/// A change to a user's reputation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReputationEntry {
+    pub entry_id: i64,
+    /// E.g. `answer_upvoted`, `downvote_cast`, `answer_accepted`, or `vote_retracted` when a vote
+    /// is undone.
+    pub reason: String,
+    pub amount: i32,
+    pub target_type: String,
+    pub target_uuid: String,
+    /// Who caused the change, e.g. the voter.
+    pub actor_uuid: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reputation {
+    pub user_uuid: String,
+    pub reputation: i32,
+    /// Newest first.
+    pub history: Vec<ReputationEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    pub fn is_moderator(&self) -> bool {
+        matches!(self, Role::Moderator | Role::Admin)
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "user" => Some(Role::User),
+            "moderator" => Some(Role::Moderator),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct User {
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserDetail {
+    pub user_uuid: String,
+    pub username: String,
+    pub role: Role,
+    pub created_at: String,
+}
+
+/// Returned once, when a user is created; the API key cannot be retrieved afterwards.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserCredentials {
+    #[serde(flatten)]
+    pub user: UserDetail,
+    pub api_key: String,
+}
+
+/// The authenticated user performing a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Actor {
+    pub user_uuid: String,
+    pub role: Role,
+}
+
+/// Who performs a mutation, and for which request, as recorded in the audit log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditContext {
+    pub actor_uuid: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl AuditContext {
+    pub fn new(actor: Option<&Actor>, request_id: impl Into<String>) -> Self {
+        Self {
+            actor_uuid: actor.map(|actor| actor.user_uuid.clone()),
+            request_id: Some(request_id.into()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub event_id: i64,
+    pub occurred_at: String,
+    pub actor_uuid: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_uuid: String,
+    /// Snapshot of the target row before the mutation; absent for creations.
+    pub before: Option<serde_json::Value>,
+    /// Snapshot of the target row after the mutation; absent for purges.
+    pub after: Option<serde_json::Value>,
+    pub request_id: Option<String>,
+}
+
+/// Criteria for listing audit events, newest first. Unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditFilter {
+    pub actor_uuid: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_uuid: Option<String>,
+    pub request_id: Option<String>,
+    /// Bounds on `occurred_at`, inclusive and exclusive respectively.
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+    /// Only return events older than this one, for paging backwards.
+    pub before_event_id: Option<i64>,
+    pub limit: i64,
+}
+
+/// A change to a question or its answers, as streamed to the clients watching it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuestionEvent {
+    /// Increases with every event; sent as the SSE event ID so clients can resume with `Last-Event-ID`.
+    pub event_id: i64,
+    pub question_uuid: String,
+    /// E.g. `answer.created`; the SSE event name.
+    pub event_type: String,
+    /// The changed row: a `QuestionDetail` or an `AnswerDetail`.
+    pub payload: serde_json::Value,
+}
+
+/// A change to a question or an answer, published to the in-process outbox handlers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DomainEvent {
+    pub event_id: i64,
+    /// `question` or `answer`; events of the same aggregate are published in order.
+    pub aggregate_type: String,
+    pub aggregate_uuid: String,
+    /// E.g. `answer.created`.
+    pub event_type: String,
+    /// The changed row: a `QuestionDetail`, an `AnswerDetail`, or a snapshot of a purged row.
+    pub payload: serde_json::Value,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BadgeLevel {
+    Bronze,
+    Silver,
+    Gold,
+}
+
+impl BadgeLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BadgeLevel::Bronze => "bronze",
+            BadgeLevel::Silver => "silver",
+            BadgeLevel::Gold => "gold",
+        }
+    }
+
+    pub fn parse(level: &str) -> Option<Self> {
+        match level {
+            "bronze" => Some(BadgeLevel::Bronze),
+            "silver" => Some(BadgeLevel::Silver),
+            "gold" => Some(BadgeLevel::Gold),
+            _ => None,
+        }
+    }
+}
+
+/// What a badge rule counts for a user.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BadgeMetric {
+    /// Questions asked, not counting deleted ones.
+    Questions,
+    /// Answers given, not counting deleted ones.
+    Answers,
+    /// Answers given that were accepted.
+    AcceptedAnswers,
+    /// Questions asked with a positive score.
+    UpvotedQuestions,
+    /// Answers given with a positive score.
+    UpvotedAnswers,
+    /// Votes cast on other users' posts.
+    VotesCast,
+}
+
+/// A badge users can earn, as defined in the badge rules.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Badge {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    pub level: BadgeLevel,
+    /// Earned once `metric` reaches `threshold`.
+    pub metric: BadgeMetric,
+    pub threshold: i64,
+    /// How many users have earned it.
+    pub awarded: i64,
+}
+
+/// A badge earned by a user.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserBadge {
+    pub name: String,
+    pub title: String,
+    pub level: BadgeLevel,
+    pub awarded_at: String,
+}
+
+/// A request to notify `url` of the question events of the given types.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Webhook {
+    pub url: String,
+    pub event_types: Vec<String>,
+    /// Key of the HMAC-SHA256 signature of every delivery; never returned.
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookDetail {
+    pub subscription_uuid: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Not delivered yet, but will be (re)tried.
+    Pending,
+    Delivered,
+    /// Given up on after too many failed attempts.
+    Dead,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Dead => "dead",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "pending" => Some(DeliveryStatus::Pending),
+            "delivered" => Some(DeliveryStatus::Delivered),
+            "dead" => Some(DeliveryStatus::Dead),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookDelivery {
+    pub delivery_id: i64,
+    pub subscription_uuid: String,
+    /// The `QuestionEvent` delivered.
+    pub event_id: i64,
+    pub event_type: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    /// When the next attempt is due, for pending deliveries.
+    pub next_attempt_at: Option<String>,
+    pub last_attempt_at: Option<String>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+/// Criteria for listing the deliveries of a webhook, newest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookDeliveryFilter {
+    pub subscription_uuid: String,
+    pub status: Option<DeliveryStatus>,
+    /// Only return deliveries older than this one, for paging backwards.
+    pub before_delivery_id: Option<i64>,
+    pub limit: i64,
+}
+
+/// A request to run a background job once, e.g. `{"kind": "purge_deleted"}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Job {
+    pub kind: String,
+    /// Handed to the job's handler; `{}` when absent.
+    #[serde(default)]
+    pub payload: Option<serde_json::Value>,
+    /// RFC 3339 time before which the job isn't run; now when absent.
+    #[serde(default)]
+    pub run_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobId {
+    pub job_id: i64,
+}
+
+/// The body of the health checks: `ok`, or why the check fails.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HealthStatus {
+    pub status: String,
+}
+
+/// An `If-Match` precondition on the version of the row being changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum IfMatch {
+    /// No precondition.
+    #[default]
+    Any,
+    /// `If-Match: *`: the row must exist.
+    Exists,
+    /// The row must be at one of these versions.
+    Versions(Vec<i64>),
+}
+
+impl IfMatch {
+    /// Whether a row at `version` (`None` if there is no such row) satisfies the precondition.
+    pub fn allows(&self, version: Option<i64>) -> bool {
+        match self {
+            IfMatch::Any => true,
+            IfMatch::Exists => version.is_some(),
+            IfMatch::Versions(versions) => version.is_some_and(|version| versions.contains(&version)),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum DBError {
     #[error("Invalid UUID provided: {0}")]
     InvalidUUID(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+    #[error("Question closed: {0}")]
+    QuestionClosed(String),
     #[error("Unexpected database error")]
     Other(
         #[from] Box<dyn std::error::Error + Send + Sync>,
@@ -50,5 +838,5 @@ pub enum DBError {
 }
 
 pub mod postgres_error_codes {
-    pub const FOREIGN_KEY_VIOLATION: &str = "23503";
+    pub const UNIQUE_VIOLATION: &str = "23505";
 }
\ No newline at end of file
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::futures::future::join_all;
+use rocket::{Orbit, Rocket};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::drain::spawn_until_shutdown;
+use crate::events::QuestionEventsHub;
+use crate::models::{DBError, DomainEvent};
+use crate::persistance::outbox_dao::{OutboxDao, OutboxDaoImpl, PendingEvent};
+
+/// How often published events older than `retention_hours` are removed.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The `[outbox]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct OutboxConfig {
+    pub batch_size: i64,
+    /// How often due events are looked for when no new events wake the dispatcher up.
+    pub poll_interval_seconds: u64,
+    /// How long all handlers together may take to handle one event.
+    pub timeout_seconds: u64,
+    /// Delay before the first retry, doubled after every further failure up to `max_delay_seconds`.
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+    /// How long published events are kept.
+    pub retention_hours: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 50,
+            poll_interval_seconds: 5,
+            timeout_seconds: 30,
+            base_delay_seconds: 1,
+            max_delay_seconds: 5 * 60,
+            retention_hours: 24,
+        }
+    }
+}
+
+impl OutboxConfig {
+    /// How long to wait after the `attempts`th failed attempt. Events are never given up on: that
+    /// would publish the later events of their aggregate out of order.
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let delay = self
+            .base_delay_seconds
+            .saturating_mul(1 << attempts.saturating_sub(1).min(32))
+            .min(self.max_delay_seconds);
+        Duration::from_secs(delay)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+}
+
+/// Reacts to domain events. Events are published at least once, so handlers must be idempotent:
+/// when any handler fails, every handler gets the event again.
+#[rocket::async_trait]
+pub trait OutboxHandler {
+    fn name(&self) -> &'static str;
+    async fn handle(&self, event: &DomainEvent) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Logs every event published.
+pub struct LoggingHandler;
+
+#[rocket::async_trait]
+impl OutboxHandler for LoggingHandler {
+    fn name(&self) -> &'static str {
+        "logging"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        info!(
+            event_id = event.event_id,
+            aggregate_uuid = %event.aggregate_uuid,
+            "Published {}",
+            event.event_type
+        );
+        Ok(())
+    }
+}
+
+pub type Handlers = [Arc<dyn OutboxHandler + Send + Sync>];
+
+async fn publish(handlers: &Handlers, event: &DomainEvent) -> Result<(), String> {
+    for handler in handlers.iter() {
+        handler
+            .handle(event)
+            .await
+            .map_err(|err| format!("{}: {}", handler.name(), err))?;
+    }
+    Ok(())
+}
+
+/// Publishes one batch of due events, one per aggregate and concurrently, and returns how many
+/// were claimed.
+pub async fn dispatch_due(
+    config: &OutboxConfig,
+    handlers: &Handlers,
+    outbox_dao: &(dyn OutboxDao + Send + Sync),
+) -> Result<usize, DBError> {
+    // Leave each event enough time to be handled before another instance may claim it again
+    let lease = config.timeout() * 2;
+    let events = outbox_dao.claim_due(config.batch_size, lease).await?;
+
+    let publications = events.iter().map(|PendingEvent { event, attempts }| async move {
+        let result = match tokio::time::timeout(config.timeout(), publish(handlers, event)).await {
+            Ok(result) => result,
+            Err(_) => Err("Timed out".to_owned()),
+        };
+
+        let recorded = match result {
+            Ok(()) => outbox_dao.mark_published(event.event_id).await,
+            Err(error) => {
+                let attempts = u32::try_from(*attempts).unwrap_or(0) + 1;
+                warn!(event_id = event.event_id, attempts, "Error publishing {}: {}", event.event_type, error);
+                outbox_dao.mark_failed(event.event_id, error, config.retry_delay(attempts)).await
+            }
+        };
+        if let Err(err) = recorded {
+            error!("Error recording outbox publication: {:?}", err);
+        }
+    });
+    join_all(publications).await;
+
+    Ok(events.len())
+}
+
+/// Publishes the outbox to the registered handlers in the background once Rocket has launched.
+pub struct OutboxFairing {
+    db: PgPool,
+    handlers: Vec<Arc<dyn OutboxHandler + Send + Sync>>,
+}
+
+impl OutboxFairing {
+    pub fn new(db: PgPool) -> Self {
+        Self { db, handlers: Vec::new() }
+    }
+
+    /// Adds a handler; handlers are called in the order they are registered.
+    pub fn register(mut self, handler: impl OutboxHandler + Send + Sync + 'static) -> Self {
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for OutboxFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Outbox dispatcher",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let config = rocket
+            .figment()
+            .extract_inner::<OutboxConfig>("outbox")
+            .unwrap_or_default();
+
+        let handlers = self.handlers.clone();
+        // Every change records a question event along with its domain event
+        let mut watch = rocket.state::<QuestionEventsHub>().map(QuestionEventsHub::watch_all);
+        let dao = OutboxDaoImpl::new(self.db.clone());
+
+        spawn_until_shutdown(rocket, "outbox dispatcher", async move {
+            let poll_interval = Duration::from_secs(config.poll_interval_seconds);
+            let retention = Duration::from_secs(config.retention_hours * 60 * 60);
+            let mut purged_at: Option<Instant> = None;
+
+            loop {
+                if purged_at.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL) {
+                    match dao.purge_published(retention).await {
+                        Ok(purged) if purged > 0 => info!("Removed {} published outbox events", purged),
+                        Ok(_) => {}
+                        Err(err) => error!("Error removing published outbox events: {:?}", err),
+                    }
+                    purged_at = Some(Instant::now());
+                }
+
+                match dispatch_due(&config, &handlers, &dao).await {
+                    // A full batch: there may be more
+                    Ok(claimed) if claimed as i64 >= config.batch_size => continue,
+                    Ok(_) => {}
+                    Err(err) => error!("Error claiming outbox events: {:?}", err),
+                }
+
+                match &mut watch {
+                    Some(watch) => {
+                        let _ = tokio::time::timeout(poll_interval, watch.changed()).await;
+                    }
+                    None => tokio::time::sleep(poll_interval).await,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use crate::models::{AuditContext, IfMatch, Question};
+    use crate::persistance::questions_dao::{QuestionsDao, QuestionsDaoImpl};
+
+    /// Records the events it handles, after failing the first `failures` times.
+    #[derive(Default)]
+    struct Recorder {
+        failures: AtomicUsize,
+        handled: Mutex<Vec<String>>,
+    }
+
+    #[rocket::async_trait]
+    impl OutboxHandler for Arc<Recorder> {
+        fn name(&self) -> &'static str {
+            "recorder"
+        }
+
+        async fn handle(&self, event: &DomainEvent) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err("unavailable".into());
+            }
+            self.handled.lock().unwrap().push(event.event_type.clone());
+            Ok(())
+        }
+    }
+
+    fn config() -> OutboxConfig {
+        OutboxConfig {
+            base_delay_seconds: 0,
+            ..OutboxConfig::default()
+        }
+    }
+
+    #[sqlx::test]
+    async fn should_publish_events_of_an_aggregate_in_order_despite_failures(pool: PgPool) {
+        let questions_dao = QuestionsDaoImpl::new(pool.clone());
+        let question_uuid = questions_dao
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .unwrap()
+            .question_uuid;
+        questions_dao
+            .delete_question(question_uuid, IfMatch::Any, true, AuditContext::default())
+            .await
+            .unwrap();
+
+        let recorder = Arc::new(Recorder {
+            failures: AtomicUsize::new(1),
+            ..Recorder::default()
+        });
+        let handlers: Vec<Arc<dyn OutboxHandler + Send + Sync>> = vec![Arc::new(recorder.clone())];
+        let dao = OutboxDaoImpl::new(pool);
+
+        // The deletion waits for the creation, which fails once and is retried
+        let mut claimed = Vec::new();
+        for _ in 0..4 {
+            claimed.push(dispatch_due(&config(), &handlers, &dao).await.unwrap());
+        }
+
+        assert_eq!(claimed, [1, 1, 1, 0]);
+        assert_eq!(*recorder.handled.lock().unwrap(), ["question.created", "question.deleted"]);
+    }
+
+    #[test]
+    fn retry_delay_should_back_off_exponentially() {
+        let config = OutboxConfig {
+            base_delay_seconds: 1,
+            max_delay_seconds: 5,
+            ..OutboxConfig::default()
+        };
+
+        let delays: Vec<_> = (1..=5).map(|attempts| config.retry_delay(attempts).as_secs()).collect();
+
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+    }
+}
@@ -1,28 +1,50 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
-use sqlx::PgPool;
+use sqlx::{Connection, PgPool};
+
+use crate::models::{Answer, AnswerDetail, AnswerEdit, AuditContext, DBError, IfMatch, PostType, RenderedBody};
 
-use crate::models::{postgres_error_codes, Answer, AnswerDetail, DBError};
+use super::audit_dao::{record_event, AuditRecord};
+use super::outbox_dao::append_event;
+use super::parse_uuid;
+use super::question_events_dao::record_question_event;
+use super::question_links_dao::record_links;
+use super::unit_of_work::Executor;
 
 #[async_trait]
 pub trait AnswersDao {
-    async fn create_answer(&self, answer: Answer) -> Result<AnswerDetail, DBError>;
-    async fn delete_answer(&self, answer_uuid: String) -> Result<(), DBError>;
-    async fn get_answers(&self, question_uuid: String) -> Result<Vec<AnswerDetail>, DBError>;
+    async fn create_answer(&self, answer: Answer, context: AuditContext) -> Result<AnswerDetail, DBError>;
+    /// Soft-deletes the answer. Unless `privileged`, only its author can delete it.
+    async fn delete_answer(&self, answer_uuid: String, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<(), DBError>;
+    async fn restore_answer(&self, answer_uuid: String, if_match: IfMatch, context: AuditContext) -> Result<(), DBError>;
+    /// Changes the content of an answer that isn't deleted, nor its question. Unless `privileged`,
+    /// only its author can edit it.
+    async fn update_answer(&self, edit: AnswerEdit, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<AnswerDetail, DBError>;
+    /// Answers of a soft-deleted question count as deleted too.
+    async fn get_answers(&self, question_uuid: String, include_deleted: bool) -> Result<Vec<AnswerDetail>, DBError>;
+    /// Permanently removes answers soft-deleted more than `retention` ago, returning how many were removed.
+    async fn purge_deleted(&self, retention: Duration) -> Result<u64, DBError>;
 }
 
 pub struct AnswersDaoImpl {
-    db: PgPool,
+    db: Executor,
 }
 
 impl AnswersDaoImpl {
     pub fn new(db: PgPool) -> Self {
+        Self { db: Executor::Pool(db) }
+    }
+
+    pub(crate) fn with_executor(db: Executor) -> Self {
         Self { db }
     }
 }
 
 #[async_trait]
 impl AnswersDao for AnswersDaoImpl {
-    async fn create_answer(&self, answer: Answer) -> Result<AnswerDetail, DBError> {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO answers", question_uuid = %answer.question_uuid))]
+    async fn create_answer(&self, answer: Answer, context: AuditContext) -> Result<AnswerDetail, DBError> {
         // Use the `sqlx::types::Uuid::parse_str` method to parse the `question_uuid` field
         // in `Answer` into a `Uuid` type.
         // parse_str docs: https://docs.rs/sqlx/latest/sqlx/types/struct.Uuid.html#method.parse_str
@@ -32,60 +54,262 @@ impl AnswersDao for AnswersDaoImpl {
         let uuid = sqlx::types::Uuid::parse_str(&answer.question_uuid).map_err(|e| {
             DBError::InvalidUUID(format!("Error parsing question ID: {} due to follow error: {:?}", answer.question_uuid, e))
         })?;
+        let author = context.actor_uuid.as_deref().map(|actor| parse_uuid(actor, "user ID")).transpose()?;
 
-        // Make a database query to insert a new answer.
+        // Make a database query to insert a new answer, unless the question is soft-deleted or closed.
+        // The question is locked first, so it cannot be closed while the answer is inserted.
         // Here is the SQL query:
         // ```
-        // INSERT INTO answers ( question_uuid, content )
-        // VALUES ( $1, $2 )
+        // SELECT deleted_at, closed_reason FROM questions WHERE question_uuid = $1 FOR SHARE
+        // INSERT INTO answers ( question_uuid, content, author_uuid ) VALUES ( $1, $2, $3 )
         // RETURNING *
         // ```
-        // If executing the query results in an error, check to see if
-        // the error code matches `postgres_error_codes::FOREIGN_KEY_VIOLATION`.
-        // If so early return the `DBError::InvalidUUID` error. Otherwise, early return
-        // the `DBError::Other` error.
+        // A missing or deleted question is reported as a `DBError::InvalidUUID` error, and a closed one
+        // as a `DBError::QuestionClosed` error. If executing a query results in an error, map that error
+        // to a `DBError::Other` error and early return from this function.
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let question = sqlx::query!(
+                "SELECT deleted_at, closed_reason FROM public.questions WHERE question_uuid = $1 FOR SHARE",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .filter(|question| question.deleted_at.is_none())
+            .ok_or_else(|| DBError::InvalidUUID(format!("Invalid question ID: {}", answer.question_uuid)))?;
+        if let Some(reason) = question.closed_reason {
+            return Err(DBError::QuestionClosed(format!("Question {} is closed as {}", answer.question_uuid, reason)));
+        }
+
         let record = sqlx::query!(
-                "INSERT INTO public.answers (question_uuid, content) VALUES ($1, $2) RETURNING *",
+                "INSERT INTO public.answers (question_uuid, content, author_uuid) VALUES ($1, $2, $3)
+                 RETURNING answer_uuid, created_at, version, to_jsonb(answers.*) AS snapshot",
                 uuid,
-                answer.content
+                answer.content,
+                author
             )
-            .fetch_one(&self.db)
+            .fetch_one(&mut *tx)
             .await
-            .map_err(|e: sqlx::Error| match e {
-                sqlx::Error::Database(e) => {
-                    if let Some(code) = e.code() {
-                        if code.eq(postgres_error_codes::FOREIGN_KEY_VIOLATION) {
-                            return DBError::InvalidUUID(format!("Invalid question ID: {}", answer.question_uuid));
-                        }
-                    }
-                    DBError::Other(Box::new(e))
-                }
-                e => DBError::Other(Box::new(e)),
-            })?;
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        record_event(&mut tx, &context, AuditRecord {
+            action: "answer.created",
+            target_type: "answer",
+            target_uuid: record.answer_uuid,
+            before: None,
+            after: record.snapshot,
+        }).await?;
 
         // Populate the AnswerDetail fields using `record`.
-        Ok(AnswerDetail {
+        let detail = AnswerDetail {
             answer_uuid: record.answer_uuid.to_string(),
             question_uuid: answer.question_uuid.to_string(),
+            body: RenderedBody::new(&answer.content),
             content: answer.content.to_string(),
-            created_at: "now".to_string(),
-        })
+            created_at: record.created_at.to_string(),
+            deleted_at: None,
+            hidden_at: None,
+            version: record.version,
+        };
+        record_links(&mut tx, PostType::Answer, record.answer_uuid, uuid, &detail.content).await?;
+        record_question_event(&mut tx, uuid, "answer.created", &detail).await?;
+        append_event(&mut tx, "answer", record.answer_uuid, "answer.created", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
     }
 
-    async fn delete_answer(&self, answer_uuid: String) -> Result<(), DBError> {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE answers", answer_uuid = %answer_uuid))]
+    async fn delete_answer(&self, answer_uuid: String, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<(), DBError> {
         let uuid = sqlx::types::Uuid::parse_str(&answer_uuid).map_err(|e| {
             DBError::InvalidUUID(format!("Error parsing question ID: {} due to follow error: {:?}", answer_uuid, e))
         })?;
+        let deleted_by = context.actor_uuid.as_deref().map(|actor| parse_uuid(actor, "user ID")).transpose()?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let before = sqlx::query!(
+                "SELECT version, author_uuid, to_jsonb(answers.*) AS snapshot FROM public.answers WHERE answer_uuid = $1 AND deleted_at IS NULL FOR UPDATE",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if let Some(before) = &before {
+            if !privileged && (deleted_by.is_none() || before.author_uuid != deleted_by) {
+                return Err(DBError::Forbidden("Only the author or a moderator can delete this answer".to_owned()));
+            }
+        }
+        if !if_match.allows(before.as_ref().map(|before| before.version)) {
+            return Err(DBError::PreconditionFailed(format!("Answer {} has changed", answer_uuid)));
+        }
+
+        // Deleting an already deleted (or missing) answer is a no-op, and so not audited.
+        let Some(before) = before else {
+            return Ok(());
+        };
+
+        let after = sqlx::query!(
+                "UPDATE public.answers SET deleted_at = CURRENT_TIMESTAMP, deleted_by = $2 WHERE answer_uuid = $1
+                 RETURNING question_uuid, content, created_at, deleted_at, hidden_at, version, to_jsonb(answers.*) AS snapshot",
+                uuid,
+                deleted_by
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        record_event(&mut tx, &context, AuditRecord {
+            action: "answer.deleted",
+            target_type: "answer",
+            target_uuid: uuid,
+            before: before.snapshot,
+            after: after.snapshot,
+        }).await?;
+
+        let detail = AnswerDetail {
+            answer_uuid: answer_uuid.clone(),
+            question_uuid: after.question_uuid.to_string(),
+            body: RenderedBody::new(&after.content),
+            content: after.content,
+            created_at: after.created_at.to_string(),
+            deleted_at: after.deleted_at.map(|deleted_at| deleted_at.to_string()),
+            hidden_at: after.hidden_at.map(|hidden_at| hidden_at.to_string()),
+            version: after.version,
+        };
+        record_question_event(&mut tx, after.question_uuid, "answer.deleted", &detail).await?;
+        append_event(&mut tx, "answer", uuid, "answer.deleted", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE answers", answer_uuid = %answer_uuid))]
+    async fn restore_answer(&self, answer_uuid: String, if_match: IfMatch, context: AuditContext) -> Result<(), DBError> {
+        let uuid = parse_uuid(&answer_uuid, "answer ID")?;
 
-        sqlx::query!("DELETE FROM public.answers WHERE answer_uuid = $1", uuid)
-            .execute(&self.db)
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let before = sqlx::query!(
+                "SELECT version, to_jsonb(answers.*) AS snapshot FROM public.answers WHERE answer_uuid = $1 AND deleted_at IS NOT NULL FOR UPDATE",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No deleted answer with ID {}", answer_uuid)))?;
+
+        if !if_match.allows(Some(before.version)) {
+            return Err(DBError::PreconditionFailed(format!("Answer {} has changed", answer_uuid)));
+        }
+
+        let after = sqlx::query!(
+                "UPDATE public.answers SET deleted_at = NULL, deleted_by = NULL WHERE answer_uuid = $1
+                 RETURNING question_uuid, content, created_at, hidden_at, version, to_jsonb(answers.*) AS snapshot",
+                uuid
+            )
+            .fetch_one(&mut *tx)
             .await
             .map_err(|e| DBError::Other(Box::new(e)))?;
 
+        record_event(&mut tx, &context, AuditRecord {
+            action: "answer.restored",
+            target_type: "answer",
+            target_uuid: uuid,
+            before: before.snapshot,
+            after: after.snapshot,
+        }).await?;
+
+        let detail = AnswerDetail {
+            answer_uuid: answer_uuid.clone(),
+            question_uuid: after.question_uuid.to_string(),
+            body: RenderedBody::new(&after.content),
+            content: after.content,
+            created_at: after.created_at.to_string(),
+            deleted_at: None,
+            hidden_at: after.hidden_at.map(|hidden_at| hidden_at.to_string()),
+            version: after.version,
+        };
+        record_question_event(&mut tx, after.question_uuid, "answer.restored", &detail).await?;
+        append_event(&mut tx, "answer", uuid, "answer.restored", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
         Ok(())
     }
 
-    async fn get_answers(&self, question_uuid: String) -> Result<Vec<AnswerDetail>, DBError> {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE answers", answer_uuid = %edit.answer_uuid))]
+    async fn update_answer(&self, edit: AnswerEdit, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<AnswerDetail, DBError> {
+        let uuid = parse_uuid(&edit.answer_uuid, "answer ID")?;
+        let editor = context.actor_uuid.as_deref().map(|actor| parse_uuid(actor, "user ID")).transpose()?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let before = sqlx::query!(
+                "SELECT a.version, a.author_uuid, to_jsonb(a.*) AS snapshot
+                 FROM public.answers a JOIN public.questions q ON q.question_uuid = a.question_uuid
+                 WHERE a.answer_uuid = $1 AND a.deleted_at IS NULL AND q.deleted_at IS NULL FOR UPDATE OF a",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No answer with ID {}", edit.answer_uuid)))?;
+
+        if !privileged && (editor.is_none() || before.author_uuid != editor) {
+            return Err(DBError::Forbidden("Only the author can edit this answer; suggest an edit instead".to_owned()));
+        }
+        if !if_match.allows(Some(before.version)) {
+            return Err(DBError::PreconditionFailed(format!("Answer {} has changed", edit.answer_uuid)));
+        }
+
+        let after = sqlx::query!(
+                "UPDATE public.answers SET content = $2 WHERE answer_uuid = $1
+                 RETURNING question_uuid, content, created_at, hidden_at, version, to_jsonb(answers.*) AS snapshot",
+                uuid,
+                edit.content
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        record_event(&mut tx, &context, AuditRecord {
+            action: "answer.edited",
+            target_type: "answer",
+            target_uuid: uuid,
+            before: before.snapshot,
+            after: after.snapshot,
+        }).await?;
+
+        let detail = AnswerDetail {
+            answer_uuid: edit.answer_uuid,
+            question_uuid: after.question_uuid.to_string(),
+            body: RenderedBody::new(&after.content),
+            content: after.content,
+            created_at: after.created_at.to_string(),
+            deleted_at: None,
+            hidden_at: after.hidden_at.map(|hidden_at| hidden_at.to_string()),
+            version: after.version,
+        };
+        record_links(&mut tx, PostType::Answer, uuid, after.question_uuid, &detail.content).await?;
+        record_question_event(&mut tx, after.question_uuid, "answer.edited", &detail).await?;
+        append_event(&mut tx, "answer", uuid, "answer.edited", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM answers", question_uuid = %question_uuid))]
+    async fn get_answers(&self, question_uuid: String, include_deleted: bool) -> Result<Vec<AnswerDetail>, DBError> {
         // Use the `sqlx::types::Uuid::parse_str` method to parse `question_uuid` into a `Uuid` type.
         // parse_str docs: https://docs.rs/sqlx/latest/sqlx/types/struct.Uuid.html#method.parse_str
         //
@@ -95,15 +319,23 @@ impl AnswersDao for AnswersDaoImpl {
             DBError::InvalidUUID(format!("Error parsing question ID: {} due to follow error: {:?}", question_uuid, e))
         })?;
 
-        // Make a database query to get all answers associated with a question uuid.
+        // Make a database query to get all answers associated with a question uuid, skipping
+        // soft-deleted or hidden answers (and every answer of a soft-deleted question) unless asked for.
         // Here is the SQL query:
         // ```
-        // SELECT * FROM answers WHERE question_uuid = $1
+        // SELECT a.* FROM answers a JOIN questions q USING (question_uuid)
+        // WHERE a.question_uuid = $1 AND ($2 OR (a.deleted_at IS NULL AND a.hidden_at IS NULL AND q.deleted_at IS NULL))
         // ```
         // If executing the query results in an error, map that error
         // to a `DBError::Other` error and early return from this function.
-        let records = sqlx::query!("SELECT * FROM public.answers WHERE question_uuid = $1", uuid)
-            .fetch_all(&self.db)
+        let records = sqlx::query!(
+                "SELECT a.answer_uuid, a.content, a.created_at, a.deleted_at, a.hidden_at, a.version FROM public.answers a
+                 JOIN public.questions q ON q.question_uuid = a.question_uuid
+                 WHERE a.question_uuid = $1 AND ($2 OR (a.deleted_at IS NULL AND a.hidden_at IS NULL AND q.deleted_at IS NULL))",
+                uuid,
+                include_deleted
+            )
+            .fetch_all(&mut *self.db.acquire().await?)
             .await
             .map_err(|e| DBError::Other(Box::new(e)))?;
 
@@ -115,15 +347,42 @@ impl AnswersDao for AnswersDaoImpl {
                 let content = r.content.to_string();
                 let answer_uuid = r.answer_uuid.to_string();
                 let created_at = r.created_at.to_string();
+                let deleted_at = r.deleted_at.map(|deleted_at| deleted_at.to_string());
                 AnswerDetail{
                     question_uuid,
                     answer_uuid,
+                    body: RenderedBody::new(&content),
                     content,
                     created_at,
+                    deleted_at,
+                    hidden_at: r.hidden_at.map(|hidden_at| hidden_at.to_string()),
+                    version: r.version,
                 }
             })
             .collect();
 
         Ok(answers)
     }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM answers"))]
+    async fn purge_deleted(&self, retention: Duration) -> Result<u64, DBError> {
+        // A single statement, so every purged answer is audited and published atomically with its removal.
+        let result = sqlx::query!(
+                "WITH purged AS (
+                     DELETE FROM public.answers WHERE deleted_at < LOCALTIMESTAMP - make_interval(secs => $1) RETURNING *
+                 ),
+                 outbox AS (
+                     INSERT INTO outbox_events (aggregate_type, aggregate_uuid, event_type, payload)
+                     SELECT 'answer', answer_uuid, 'answer.purged', to_jsonb(purged.*) FROM purged
+                 )
+                 INSERT INTO audit_events (action, target_type, target_uuid, before)
+                 SELECT 'answer.purged', 'answer', answer_uuid, to_jsonb(purged.*) FROM purged",
+                retention.as_secs_f64()
+            )
+            .execute(&mut *self.db.acquire().await?)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(result.rows_affected())
+    }
 }
\ No newline at end of file
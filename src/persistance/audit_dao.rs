@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use sqlx::types::{JsonValue, Uuid};
+use sqlx::{PgConnection, PgPool};
+
+use crate::models::{AuditContext, AuditEvent, AuditFilter, DBError};
+
+use super::parse_uuid;
+
+/// A mutation to record, written by the DAO performing it inside its own transaction.
+pub(crate) struct AuditRecord<'a> {
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_uuid: Uuid,
+    pub before: Option<JsonValue>,
+    pub after: Option<JsonValue>,
+}
+
+/// Appends an audit event using `conn`, so it commits or rolls back together with the mutation.
+pub(crate) async fn record_event(conn: &mut PgConnection, context: &AuditContext, record: AuditRecord<'_>) -> Result<(), DBError> {
+    let actor_uuid = context
+        .actor_uuid
+        .as_deref()
+        .map(|actor_uuid| parse_uuid(actor_uuid, "user ID"))
+        .transpose()?;
+
+    sqlx::query!(
+            "INSERT INTO audit_events (actor_uuid, action, target_type, target_uuid, before, after, request_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            actor_uuid,
+            record.action,
+            record.target_type,
+            record.target_uuid,
+            record.before,
+            record.after,
+            context.request_id
+        )
+        .execute(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    Ok(())
+}
+
+#[async_trait]
+pub trait AuditDao {
+    async fn get_events(&self, filter: AuditFilter) -> Result<Vec<AuditEvent>, DBError>;
+}
+
+pub struct AuditDaoImpl {
+    db: PgPool,
+}
+
+impl AuditDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl AuditDao for AuditDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM audit_events"))]
+    async fn get_events(&self, filter: AuditFilter) -> Result<Vec<AuditEvent>, DBError> {
+        let actor_uuid = filter.actor_uuid.as_deref().map(|uuid| parse_uuid(uuid, "user ID")).transpose()?;
+        let target_uuid = filter.target_uuid.as_deref().map(|uuid| parse_uuid(uuid, "target ID")).transpose()?;
+
+        let records = sqlx::query!(
+                "SELECT * FROM audit_events
+                 WHERE ($1::uuid IS NULL OR actor_uuid = $1)
+                   AND ($2::text IS NULL OR action = $2)
+                   AND ($3::text IS NULL OR target_type = $3)
+                   AND ($4::uuid IS NULL OR target_uuid = $4)
+                   AND ($5::text IS NULL OR request_id = $5)
+                   AND ($6::timestamptz IS NULL OR occurred_at >= $6)
+                   AND ($7::timestamptz IS NULL OR occurred_at < $7)
+                   AND ($8::bigint IS NULL OR event_id < $8)
+                 ORDER BY event_id DESC
+                 LIMIT $9",
+                actor_uuid,
+                filter.action,
+                filter.target_type,
+                target_uuid,
+                filter.request_id,
+                filter.since,
+                filter.until,
+                filter.before_event_id,
+                filter.limit
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let events = records
+            .into_iter()
+            .map(|r| AuditEvent {
+                event_id: r.event_id,
+                occurred_at: r.occurred_at.to_string(),
+                actor_uuid: r.actor_uuid.map(|uuid| uuid.to_string()),
+                action: r.action,
+                target_type: r.target_type,
+                target_uuid: r.target_uuid.to_string(),
+                before: r.before,
+                after: r.after,
+                request_id: r.request_id,
+            })
+            .collect();
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::models::{BadgeLevel, BadgeMetric, DBError, PostType, UserBadge};
+
+use super::parse_uuid;
+
+/// A user's count for every badge metric.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BadgeProgress {
+    pub questions: i64,
+    pub answers: i64,
+    pub accepted_answers: i64,
+    pub upvoted_questions: i64,
+    pub upvoted_answers: i64,
+    pub votes_cast: i64,
+}
+
+impl BadgeProgress {
+    pub fn get(&self, metric: BadgeMetric) -> i64 {
+        match metric {
+            BadgeMetric::Questions => self.questions,
+            BadgeMetric::Answers => self.answers,
+            BadgeMetric::AcceptedAnswers => self.accepted_answers,
+            BadgeMetric::UpvotedQuestions => self.upvoted_questions,
+            BadgeMetric::UpvotedAnswers => self.upvoted_answers,
+            BadgeMetric::VotesCast => self.votes_cast,
+        }
+    }
+}
+
+#[async_trait]
+pub trait BadgesDao {
+    /// The author of a question or an answer, if it still exists and has one.
+    async fn get_author(&self, post_type: PostType, post_uuid: String) -> Result<Option<String>, DBError>;
+    async fn get_progress(&self, user_uuid: String) -> Result<BadgeProgress, DBError>;
+    /// Awards a badge unless the user already has it, returning whether it was awarded.
+    async fn award(&self, user_uuid: String, name: &str, title: &str, level: BadgeLevel) -> Result<bool, DBError>;
+    /// The user's badges, oldest first; `NotFound` if there is no such user.
+    async fn get_user_badges(&self, user_uuid: String) -> Result<Vec<UserBadge>, DBError>;
+    /// How many users have earned each badge.
+    async fn count_awards(&self) -> Result<HashMap<String, i64>, DBError>;
+}
+
+pub struct BadgesDaoImpl {
+    db: PgPool,
+}
+
+impl BadgesDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl BadgesDao for BadgesDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM questions"))]
+    async fn get_author(&self, post_type: PostType, post_uuid: String) -> Result<Option<String>, DBError> {
+        let uuid = parse_uuid(&post_uuid, &format!("{} ID", post_type.as_str()))?;
+
+        let author = match post_type {
+            PostType::Question => sqlx::query_scalar!("SELECT author_uuid FROM questions WHERE question_uuid = $1", uuid)
+                .fetch_optional(&self.db)
+                .await,
+            PostType::Answer => sqlx::query_scalar!("SELECT author_uuid FROM answers WHERE answer_uuid = $1", uuid)
+                .fetch_optional(&self.db)
+                .await,
+        }
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(author.flatten().map(|author| author.to_string()))
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM questions, answers, votes"))]
+    async fn get_progress(&self, user_uuid: String) -> Result<BadgeProgress, DBError> {
+        let uuid = parse_uuid(&user_uuid, "user ID")?;
+
+        let record = sqlx::query!(
+                r#"SELECT
+                     (SELECT COUNT(*) FROM questions WHERE author_uuid = $1 AND deleted_at IS NULL) AS "questions!",
+                     (SELECT COUNT(*) FROM answers WHERE author_uuid = $1 AND deleted_at IS NULL) AS "answers!",
+                     (SELECT COUNT(*) FROM answers JOIN questions ON questions.accepted_answer_uuid = answers.answer_uuid
+                      WHERE answers.author_uuid = $1 AND answers.deleted_at IS NULL) AS "accepted_answers!",
+                     (SELECT COUNT(*) FROM questions
+                      WHERE author_uuid = $1 AND deleted_at IS NULL
+                        AND (SELECT SUM(value) FROM votes WHERE target_uuid = question_uuid) > 0) AS "upvoted_questions!",
+                     (SELECT COUNT(*) FROM answers
+                      WHERE author_uuid = $1 AND deleted_at IS NULL
+                        AND (SELECT SUM(value) FROM votes WHERE target_uuid = answer_uuid) > 0) AS "upvoted_answers!",
+                     (SELECT COUNT(*) FROM votes WHERE voter_uuid = $1) AS "votes_cast!""#,
+                uuid
+            )
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(BadgeProgress {
+            questions: record.questions,
+            answers: record.answers,
+            accepted_answers: record.accepted_answers,
+            upvoted_questions: record.upvoted_questions,
+            upvoted_answers: record.upvoted_answers,
+            votes_cast: record.votes_cast,
+        })
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO user_badges", badge = %name))]
+    async fn award(&self, user_uuid: String, name: &str, title: &str, level: BadgeLevel) -> Result<bool, DBError> {
+        let uuid = parse_uuid(&user_uuid, "user ID")?;
+
+        let result = sqlx::query!(
+                "INSERT INTO user_badges (user_uuid, badge, title, level) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (user_uuid, badge) DO NOTHING",
+                uuid,
+                name,
+                title,
+                level.as_str()
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM user_badges"))]
+    async fn get_user_badges(&self, user_uuid: String) -> Result<Vec<UserBadge>, DBError> {
+        let uuid = parse_uuid(&user_uuid, "user ID")?;
+
+        let records = sqlx::query!("SELECT * FROM user_badges WHERE user_uuid = $1 ORDER BY awarded_at, badge", uuid)
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if records.is_empty() {
+            let exists = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM users WHERE user_uuid = $1) AS "exists!""#, uuid)
+                .fetch_one(&self.db)
+                .await
+                .map_err(|e| DBError::Other(Box::new(e)))?;
+            if !exists {
+                return Err(DBError::NotFound(format!("No user with ID {}", user_uuid)));
+            }
+        }
+
+        records
+            .into_iter()
+            .map(|r| {
+                Ok(UserBadge {
+                    level: BadgeLevel::parse(&r.level)
+                        .ok_or_else(|| DBError::Other(format!("Unknown badge level: {}", r.level).into()))?,
+                    name: r.badge,
+                    title: r.title,
+                    awarded_at: r.awarded_at.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM user_badges"))]
+    async fn count_awards(&self) -> Result<HashMap<String, i64>, DBError> {
+        let records = sqlx::query!(r#"SELECT badge, COUNT(*) AS "awarded!" FROM user_badges GROUP BY badge"#)
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(records.into_iter().map(|r| (r.badge, r.awarded)).collect())
+    }
+}
@@ -0,0 +1,329 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::types::Uuid;
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::models::{postgres_error_codes, AuditContext, Bounty, BountyDetail, BountyStatus, DBError};
+
+use super::outbox_dao::append_event;
+use super::parse_uuid;
+use super::reputation_dao::{record_reputation, ReputationChange};
+
+/// How many expired bounties `expire_due` closes at most per call.
+const EXPIRE_BATCH_SIZE: i64 = 100;
+
+struct BountyRow {
+    bounty_id: i64,
+    question_uuid: Uuid,
+    offered_by: Uuid,
+    amount: i32,
+    status: String,
+    awarded_answer_uuid: Option<Uuid>,
+    awarded_amount: Option<i32>,
+    created_at: OffsetDateTime,
+    expires_at: OffsetDateTime,
+    closed_at: Option<OffsetDateTime>,
+}
+
+impl TryFrom<BountyRow> for BountyDetail {
+    type Error = DBError;
+
+    fn try_from(row: BountyRow) -> Result<Self, DBError> {
+        Ok(BountyDetail {
+            bounty_id: row.bounty_id,
+            question_uuid: row.question_uuid.to_string(),
+            offered_by: row.offered_by.to_string(),
+            amount: row.amount,
+            status: BountyStatus::parse(&row.status)
+                .ok_or_else(|| DBError::Other(format!("Unknown bounty status: {}", row.status).into()))?,
+            awarded_answer_uuid: row.awarded_answer_uuid.map(|uuid| uuid.to_string()),
+            awarded_amount: row.awarded_amount,
+            created_at: row.created_at.to_string(),
+            expires_at: row.expires_at.to_string(),
+            closed_at: row.closed_at.map(|closed_at| closed_at.to_string()),
+        })
+    }
+}
+
+/// Changes the question's version, and so its ETag, when its bounty opens or closes.
+async fn touch_question(conn: &mut PgConnection, question_uuid: Uuid) -> Result<(), DBError> {
+    sqlx::query!("UPDATE questions SET version = version WHERE question_uuid = $1", question_uuid)
+        .execute(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+    Ok(())
+}
+
+/// Closes the open bounty on the question, if any, giving the amount back to the offerer. Called by
+/// the question DAO inside the transaction deleting the question.
+pub(crate) async fn refund_open_bounty(conn: &mut PgConnection, question_uuid: Uuid, actor_uuid: Option<Uuid>) -> Result<(), DBError> {
+    let refunded = sqlx::query_as!(
+            BountyRow,
+            "UPDATE bounties SET status = 'refunded', closed_at = CURRENT_TIMESTAMP
+             WHERE question_uuid = $1 AND status = 'open'
+             RETURNING *",
+            question_uuid
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    let Some(refunded) = refunded else {
+        return Ok(());
+    };
+    record_reputation(&mut *conn, ReputationChange {
+        user_uuid: refunded.offered_by,
+        reason: "bounty_refunded",
+        amount: refunded.amount,
+        target_type: "question",
+        target_uuid: question_uuid,
+        actor_uuid,
+    }).await?;
+    let detail = BountyDetail::try_from(refunded)?;
+    append_event(conn, "question", question_uuid, "question.bounty_refunded", &detail).await
+}
+
+#[async_trait]
+pub trait BountiesDao {
+    /// Opens a bounty on a question for `duration`, taking the amount from the actor, who must be the
+    /// question's author and have that much reputation.
+    async fn offer_bounty(&self, bounty: Bounty, duration: Duration, context: AuditContext) -> Result<BountyDetail, DBError>;
+    /// Awards the open bounty on the answer's question to the answer, which only the offerer may do.
+    async fn award_bounty(&self, answer_uuid: String, context: AuditContext) -> Result<BountyDetail, DBError>;
+    /// Closes the open bounties that have expired. Half of each goes to the highest-scored answer
+    /// posted since the bounty opened, if it has a score of at least `min_score`; otherwise the
+    /// bounty is lost. Returns how many bounties were closed.
+    async fn expire_due(&self, min_score: i64) -> Result<u64, DBError>;
+}
+
+pub struct BountiesDaoImpl {
+    db: PgPool,
+}
+
+impl BountiesDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+fn require_actor(context: &AuditContext) -> Result<Uuid, DBError> {
+    let actor = context
+        .actor_uuid
+        .as_deref()
+        .ok_or_else(|| DBError::Forbidden("Only signed-in users can offer or award bounties".to_owned()))?;
+    parse_uuid(actor, "user ID")
+}
+
+#[async_trait]
+impl BountiesDao for BountiesDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO bounties", question_uuid = %bounty.question_uuid))]
+    async fn offer_bounty(&self, bounty: Bounty, duration: Duration, context: AuditContext) -> Result<BountyDetail, DBError> {
+        let uuid = parse_uuid(&bounty.question_uuid, "question ID")?;
+        let offerer = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let author = sqlx::query_scalar!(
+                "SELECT author_uuid FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No question with ID {}", bounty.question_uuid)))?;
+        if author != Some(offerer) {
+            return Err(DBError::Forbidden("Only the author of a question can offer a bounty on it".to_owned()));
+        }
+
+        let reputation = sqlx::query_scalar!("SELECT reputation FROM users WHERE user_uuid = $1 FOR UPDATE", offerer)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+        if reputation < bounty.amount {
+            return Err(DBError::Forbidden(format!(
+                "A bounty of {} needs that much reputation, but you have {}",
+                bounty.amount, reputation
+            )));
+        }
+
+        let row = sqlx::query_as!(
+                BountyRow,
+                "INSERT INTO bounties (question_uuid, offered_by, amount, expires_at)
+                 VALUES ($1, $2, $3, CURRENT_TIMESTAMP + make_interval(secs => $4))
+                 RETURNING *",
+                uuid,
+                offerer,
+                bounty.amount,
+                duration.as_secs_f64()
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e: sqlx::Error| match e {
+                sqlx::Error::Database(e) => {
+                    if let Some(code) = e.code() {
+                        if code.eq(postgres_error_codes::UNIQUE_VIOLATION) {
+                            return DBError::Conflict(format!("Question {} already has an open bounty", bounty.question_uuid));
+                        }
+                    }
+                    DBError::Other(Box::new(e))
+                }
+                e => DBError::Other(Box::new(e)),
+            })?;
+
+        record_reputation(&mut tx, ReputationChange {
+            user_uuid: offerer,
+            reason: "bounty_offered",
+            amount: -bounty.amount,
+            target_type: "question",
+            target_uuid: uuid,
+            actor_uuid: Some(offerer),
+        }).await?;
+        touch_question(&mut tx, uuid).await?;
+        let detail = BountyDetail::try_from(row)?;
+        append_event(&mut tx, "question", uuid, "question.bounty_offered", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE bounties", answer_uuid = %answer_uuid))]
+    async fn award_bounty(&self, answer_uuid: String, context: AuditContext) -> Result<BountyDetail, DBError> {
+        let uuid = parse_uuid(&answer_uuid, "answer ID")?;
+        let awarder = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let answer = sqlx::query!(
+                "SELECT answers.question_uuid, answers.author_uuid FROM answers JOIN questions USING (question_uuid)
+                 WHERE answer_uuid = $1 AND answers.deleted_at IS NULL AND questions.deleted_at IS NULL",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No answer with ID {}", answer_uuid)))?;
+
+        let offered_by = sqlx::query_scalar!(
+                "SELECT offered_by FROM bounties WHERE question_uuid = $1 AND status = 'open' FOR UPDATE",
+                answer.question_uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No open bounty on question {}", answer.question_uuid)))?;
+        if offered_by != awarder {
+            return Err(DBError::Forbidden("Only the user who offered a bounty can award it".to_owned()));
+        }
+        let Some(author) = answer.author_uuid.filter(|author| *author != awarder) else {
+            return Err(DBError::Forbidden("Bounties can only be awarded to other users' answers".to_owned()));
+        };
+
+        let row = sqlx::query_as!(
+                BountyRow,
+                "UPDATE bounties SET status = 'awarded', awarded_answer_uuid = $2, awarded_amount = amount, closed_at = CURRENT_TIMESTAMP
+                 WHERE question_uuid = $1 AND status = 'open'
+                 RETURNING *",
+                answer.question_uuid,
+                uuid
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        record_reputation(&mut tx, ReputationChange {
+            user_uuid: author,
+            reason: "bounty_awarded",
+            amount: row.amount,
+            target_type: "answer",
+            target_uuid: uuid,
+            actor_uuid: Some(awarder),
+        }).await?;
+        touch_question(&mut tx, answer.question_uuid).await?;
+        let detail = BountyDetail::try_from(row)?;
+        append_event(&mut tx, "question", answer.question_uuid, "question.bounty_awarded", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE bounties"))]
+    async fn expire_due(&self, min_score: i64) -> Result<u64, DBError> {
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let expired = sqlx::query!(
+                "SELECT bounty_id, question_uuid, offered_by, created_at FROM bounties
+                 WHERE status = 'open' AND expires_at <= CURRENT_TIMESTAMP
+                 ORDER BY expires_at
+                 LIMIT $1
+                 FOR UPDATE SKIP LOCKED",
+                EXPIRE_BATCH_SIZE
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        for bounty in &expired {
+            let best = sqlx::query!(
+                    r#"SELECT answers.answer_uuid, answers.author_uuid AS "author_uuid!" FROM answers
+                       LEFT JOIN votes ON votes.target_uuid = answers.answer_uuid
+                       WHERE answers.question_uuid = $1 AND answers.deleted_at IS NULL AND answers.created_at >= $2::timestamptz
+                         AND answers.author_uuid IS NOT NULL AND answers.author_uuid <> $3
+                       GROUP BY answers.answer_uuid
+                       HAVING COALESCE(SUM(votes.value), 0) >= $4
+                       ORDER BY COALESCE(SUM(votes.value), 0) DESC, answers.created_at
+                       LIMIT 1"#,
+                    bounty.question_uuid,
+                    bounty.created_at,
+                    bounty.offered_by,
+                    min_score
+                )
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| DBError::Other(Box::new(e)))?;
+
+            let row = sqlx::query_as!(
+                    BountyRow,
+                    "UPDATE bounties
+                     SET status = CASE WHEN $2::uuid IS NULL THEN 'expired' ELSE 'awarded' END,
+                         awarded_answer_uuid = $2,
+                         awarded_amount = CASE WHEN $2::uuid IS NULL THEN NULL ELSE amount / 2 END,
+                         closed_at = CURRENT_TIMESTAMP
+                     WHERE bounty_id = $1
+                     RETURNING *",
+                    bounty.bounty_id,
+                    best.as_ref().map(|best| best.answer_uuid)
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| DBError::Other(Box::new(e)))?;
+
+            let event_type = if let (Some(best), Some(awarded_amount)) = (&best, row.awarded_amount) {
+                record_reputation(&mut tx, ReputationChange {
+                    user_uuid: best.author_uuid,
+                    reason: "bounty_awarded",
+                    amount: awarded_amount,
+                    target_type: "answer",
+                    target_uuid: best.answer_uuid,
+                    actor_uuid: None,
+                }).await?;
+                "question.bounty_awarded"
+            } else {
+                "question.bounty_expired"
+            };
+            touch_question(&mut tx, bounty.question_uuid).await?;
+            let detail = BountyDetail::try_from(row)?;
+            append_event(&mut tx, "question", bounty.question_uuid, event_type, &detail).await?;
+        }
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(expired.len() as u64)
+    }
+}
@@ -0,0 +1,237 @@
+use async_trait::async_trait;
+use sqlx::types::{JsonValue, Uuid};
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::models::{AuditContext, CloseReason, CloseVote, ClosureVotes, DBError, QuestionDetail, RenderedBody};
+
+use super::audit_dao::{record_event, AuditRecord};
+use super::outbox_dao::append_event;
+use super::parse_uuid;
+use super::question_events_dao::record_question_event;
+
+#[async_trait]
+pub trait ClosingDao {
+    /// Records the actor's vote to close an open question, replacing their earlier one. The question
+    /// is closed once `votes_needed` users voted, for the most common reason, or right away with the
+    /// actor's reason if the vote is `binding`.
+    async fn vote_to_close(&self, vote: CloseVote, votes_needed: i64, binding: bool, context: AuditContext) -> Result<ClosureVotes, DBError>;
+    /// Records the actor's vote to reopen a closed question, which is reopened once `votes_needed`
+    /// users voted, or right away if the vote is `binding`.
+    async fn vote_to_reopen(&self, question_uuid: String, votes_needed: i64, binding: bool, context: AuditContext) -> Result<ClosureVotes, DBError>;
+}
+
+pub struct ClosingDaoImpl {
+    db: PgPool,
+}
+
+impl ClosingDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+fn require_actor(context: &AuditContext) -> Result<Uuid, DBError> {
+    let actor = context
+        .actor_uuid
+        .as_deref()
+        .ok_or_else(|| DBError::Forbidden("Only signed-in users can vote to close or reopen questions".to_owned()))?;
+    parse_uuid(actor, "user ID")
+}
+
+/// Locks the question, returning whether it is closed and its snapshot for the audit log.
+async fn lock_question(conn: &mut PgConnection, question_uuid: Uuid) -> Result<(bool, Option<JsonValue>), DBError> {
+    let record = sqlx::query!(
+            "SELECT closed_at IS NOT NULL AS \"closed!\", to_jsonb(questions.*) AS snapshot FROM questions
+             WHERE question_uuid = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+            question_uuid
+        )
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?
+        .ok_or_else(|| DBError::NotFound(format!("No question with ID {}", question_uuid)))?;
+    Ok((record.closed, record.snapshot))
+}
+
+/// Records a vote, replacing the voter's earlier one, and returns how many votes are pending.
+async fn cast(conn: &mut PgConnection, question_uuid: Uuid, voter: Uuid, reason: Option<CloseReason>, duplicate_of: Option<Uuid>) -> Result<i64, DBError> {
+    sqlx::query!(
+            "INSERT INTO closure_votes (question_uuid, voter_uuid, reason, duplicate_of) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (question_uuid, voter_uuid)
+             DO UPDATE SET reason = EXCLUDED.reason, duplicate_of = EXCLUDED.duplicate_of, created_at = CURRENT_TIMESTAMP",
+            question_uuid,
+            voter,
+            reason.map(|reason| reason.as_str()),
+            duplicate_of
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    sqlx::query_scalar!(r#"SELECT COUNT(*) AS "votes!" FROM closure_votes WHERE question_uuid = $1"#, question_uuid)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))
+}
+
+/// Closes the question with `reason`, or reopens it if `None`, clearing the pending votes.
+async fn set_closure(
+    conn: &mut PgConnection,
+    context: &AuditContext,
+    question_uuid: Uuid,
+    before: Option<JsonValue>,
+    reason: Option<CloseReason>,
+    duplicate_of: Option<Uuid>,
+) -> Result<(), DBError> {
+    let after = sqlx::query!(
+            "UPDATE questions
+             SET closed_at = CASE WHEN $2::text IS NULL THEN NULL ELSE CURRENT_TIMESTAMP END, closed_reason = $2, duplicate_of = $3
+             WHERE question_uuid = $1
+             RETURNING title, description, created_at, hidden_at, accepted_answer_uuid, version, to_jsonb(questions.*) AS snapshot",
+            question_uuid,
+            reason.map(|reason| reason.as_str()),
+            duplicate_of
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    sqlx::query!("DELETE FROM closure_votes WHERE question_uuid = $1", question_uuid)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    let action = if reason.is_some() { "question.closed" } else { "question.reopened" };
+    record_event(&mut *conn, context, AuditRecord {
+        action,
+        target_type: "question",
+        target_uuid: question_uuid,
+        before,
+        after: after.snapshot,
+    }).await?;
+
+    let detail = QuestionDetail {
+        question_uuid: question_uuid.to_string(),
+        title: after.title,
+        body: RenderedBody::new(&after.description),
+        description: after.description,
+        created_at: after.created_at.to_string(),
+        deleted_at: None,
+        hidden_at: after.hidden_at.map(|hidden_at| hidden_at.to_string()),
+        accepted_answer_uuid: after.accepted_answer_uuid.map(|answer_uuid| answer_uuid.to_string()),
+        bounty: None,
+        closed_reason: reason,
+        duplicate_of: duplicate_of.map(|duplicate_of| duplicate_of.to_string()),
+        version: after.version,
+    };
+    record_question_event(&mut *conn, question_uuid, action, &detail).await?;
+    append_event(conn, "question", question_uuid, action, &detail).await
+}
+
+#[async_trait]
+impl ClosingDao for ClosingDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO closure_votes", question_uuid = %vote.question_uuid))]
+    async fn vote_to_close(&self, vote: CloseVote, votes_needed: i64, binding: bool, context: AuditContext) -> Result<ClosureVotes, DBError> {
+        let uuid = parse_uuid(&vote.question_uuid, "question ID")?;
+        let duplicate_of = vote.duplicate_of.as_deref().map(|duplicate_of| parse_uuid(duplicate_of, "question ID")).transpose()?;
+        let voter = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let (closed, before) = lock_question(&mut tx, uuid).await?;
+        if closed {
+            return Err(DBError::Conflict(format!("Question {} is already closed", vote.question_uuid)));
+        }
+        let open_bounty = sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM bounties WHERE question_uuid = $1 AND status = 'open') AS "exists!""#,
+                uuid
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+        if open_bounty {
+            return Err(DBError::Conflict(format!("Question {} has an open bounty", vote.question_uuid)));
+        }
+        if let Some(duplicate_of) = duplicate_of {
+            let exists = sqlx::query_scalar!(
+                    r#"SELECT EXISTS (SELECT 1 FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL) AS "exists!""#,
+                    duplicate_of
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| DBError::Other(Box::new(e)))?;
+            if !exists {
+                return Err(DBError::NotFound(format!("No question with ID {}", duplicate_of)));
+            }
+        }
+
+        let votes = cast(&mut tx, uuid, voter, Some(vote.reason), duplicate_of).await?;
+        let close = binding || votes >= votes_needed;
+        if close {
+            let (reason, duplicate_of) = if binding {
+                (vote.reason, duplicate_of)
+            } else {
+                // The most common reason wins, and then the most common duplicate; ties go to the earliest vote
+                let reason = sqlx::query_scalar!(
+                        r#"SELECT reason AS "reason!" FROM closure_votes WHERE question_uuid = $1
+                           GROUP BY reason ORDER BY COUNT(*) DESC, MIN(created_at) LIMIT 1"#,
+                        uuid
+                    )
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(|e| DBError::Other(Box::new(e)))?;
+                let reason = CloseReason::parse(&reason)
+                    .ok_or_else(|| DBError::Other(format!("Unknown close reason: {}", reason).into()))?;
+                let duplicate_of = sqlx::query_scalar!(
+                        r#"SELECT duplicate_of AS "duplicate_of!" FROM closure_votes
+                           WHERE question_uuid = $1 AND reason = 'duplicate' AND duplicate_of IS NOT NULL
+                           GROUP BY duplicate_of ORDER BY COUNT(*) DESC, MIN(created_at) LIMIT 1"#,
+                        uuid
+                    )
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(|e| DBError::Other(Box::new(e)))?;
+                (reason, duplicate_of.filter(|_| reason == CloseReason::Duplicate))
+            };
+            set_closure(&mut tx, &context, uuid, before, Some(reason), duplicate_of).await?;
+        }
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(ClosureVotes {
+            question_uuid: vote.question_uuid,
+            votes,
+            votes_needed,
+            closed: close,
+        })
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO closure_votes", question_uuid = %question_uuid))]
+    async fn vote_to_reopen(&self, question_uuid: String, votes_needed: i64, binding: bool, context: AuditContext) -> Result<ClosureVotes, DBError> {
+        let uuid = parse_uuid(&question_uuid, "question ID")?;
+        let voter = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let (closed, before) = lock_question(&mut tx, uuid).await?;
+        if !closed {
+            return Err(DBError::Conflict(format!("Question {} is not closed", question_uuid)));
+        }
+
+        let votes = cast(&mut tx, uuid, voter, None, None).await?;
+        let reopen = binding || votes >= votes_needed;
+        if reopen {
+            set_closure(&mut tx, &context, uuid, before, None, None).await?;
+        }
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(ClosureVotes {
+            question_uuid,
+            votes,
+            votes_needed,
+            closed: !reopen,
+        })
+    }
+}
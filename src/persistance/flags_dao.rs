@@ -0,0 +1,395 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::types::Uuid;
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::models::{postgres_error_codes, AuditContext, DBError, Flag, FlagDetail, FlagReason, FlagStats, FlagStatus, PostType};
+
+use super::audit_dao::{record_event, AuditRecord};
+use super::outbox_dao::append_event;
+use super::parse_uuid;
+
+/// Payload of the `question.hidden`, `question.unhidden`, `answer.hidden` and `answer.unhidden` events.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HidingEvent {
+    pub target_type: PostType,
+    pub target_uuid: String,
+    /// Spam flags that are pending or were found helpful.
+    pub spam_flags: i64,
+}
+
+struct FlagRow {
+    flag_id: i64,
+    target_type: String,
+    target_uuid: Uuid,
+    flagged_by: Option<Uuid>,
+    reason: String,
+    details: Option<String>,
+    status: String,
+    claimed_by: Option<Uuid>,
+    claimed_at: Option<OffsetDateTime>,
+    resolved_by: Option<Uuid>,
+    resolved_at: Option<OffsetDateTime>,
+    created_at: OffsetDateTime,
+}
+
+impl TryFrom<FlagRow> for FlagDetail {
+    type Error = DBError;
+
+    fn try_from(row: FlagRow) -> Result<Self, DBError> {
+        Ok(FlagDetail {
+            flag_id: row.flag_id,
+            target_type: PostType::parse(&row.target_type)
+                .ok_or_else(|| DBError::Other(format!("Unknown flag target type: {}", row.target_type).into()))?,
+            target_uuid: row.target_uuid.to_string(),
+            flagged_by: row.flagged_by.map(|uuid| uuid.to_string()),
+            reason: FlagReason::parse(&row.reason)
+                .ok_or_else(|| DBError::Other(format!("Unknown flag reason: {}", row.reason).into()))?,
+            details: row.details,
+            status: FlagStatus::parse(&row.status)
+                .ok_or_else(|| DBError::Other(format!("Unknown flag status: {}", row.status).into()))?,
+            claimed_by: row.claimed_by.map(|uuid| uuid.to_string()),
+            claimed_at: row.claimed_at.map(|claimed_at| claimed_at.to_string()),
+            resolved_by: row.resolved_by.map(|uuid| uuid.to_string()),
+            resolved_at: row.resolved_at.map(|resolved_at| resolved_at.to_string()),
+            created_at: row.created_at.to_string(),
+        })
+    }
+}
+
+/// Locks the post, returning whether it exists (and isn't deleted) and its author.
+async fn lock_post(conn: &mut PgConnection, target_type: PostType, target_uuid: Uuid) -> Result<Option<Option<Uuid>>, DBError> {
+    match target_type {
+        PostType::Question => sqlx::query_scalar!(
+                "SELECT author_uuid FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+                target_uuid
+            )
+            .fetch_optional(conn)
+            .await,
+        PostType::Answer => sqlx::query_scalar!(
+                "SELECT author_uuid FROM answers WHERE answer_uuid = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+                target_uuid
+            )
+            .fetch_optional(conn)
+            .await,
+    }
+        .map_err(|e| DBError::Other(Box::new(e)))
+}
+
+/// Hides the post once it has `hide_after` spam flags that weren't declined, or one raised by
+/// content screening, and shows it again when declined flags bring it back under. The post must be
+/// locked.
+async fn update_hiding(conn: &mut PgConnection, context: &AuditContext, target_type: PostType, target_uuid: Uuid, hide_after: i64) -> Result<(), DBError> {
+    let flags = sqlx::query!(
+            r#"SELECT COUNT(*) AS "spam_flags!", COUNT(*) FILTER (WHERE flagged_by IS NULL) AS "screening_flags!"
+               FROM flags WHERE target_uuid = $1 AND reason = 'spam' AND status <> 'declined'"#,
+            target_uuid
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+    let spam_flags = flags.spam_flags;
+    let hide = spam_flags >= hide_after || flags.screening_flags > 0;
+
+    // Only changes the post if it isn't in the right state already
+    let changed = match target_type {
+        PostType::Question => sqlx::query!(
+                "UPDATE questions SET hidden_at = CASE WHEN $2 THEN CURRENT_TIMESTAMP END
+                 FROM (SELECT to_jsonb(questions.*) AS snapshot FROM questions WHERE question_uuid = $1) AS before
+                 WHERE question_uuid = $1 AND (hidden_at IS NOT NULL) <> $2
+                 RETURNING before.snapshot AS before, to_jsonb(questions.*) AS after",
+                target_uuid,
+                hide
+            )
+            .fetch_optional(&mut *conn)
+            .await
+            .map(|record| record.map(|record| (record.before, record.after))),
+        PostType::Answer => sqlx::query!(
+                "UPDATE answers SET hidden_at = CASE WHEN $2 THEN CURRENT_TIMESTAMP END
+                 FROM (SELECT to_jsonb(answers.*) AS snapshot FROM answers WHERE answer_uuid = $1) AS before
+                 WHERE answer_uuid = $1 AND (hidden_at IS NOT NULL) <> $2
+                 RETURNING before.snapshot AS before, to_jsonb(answers.*) AS after",
+                target_uuid,
+                hide
+            )
+            .fetch_optional(&mut *conn)
+            .await
+            .map(|record| record.map(|record| (record.before, record.after))),
+    }
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    let Some((before, after)) = changed else {
+        return Ok(());
+    };
+    let action = format!("{}.{}", target_type.as_str(), if hide { "hidden" } else { "unhidden" });
+    record_event(&mut *conn, context, AuditRecord {
+        action: &action,
+        target_type: target_type.as_str(),
+        target_uuid,
+        before,
+        after,
+    }).await?;
+    let event = HidingEvent {
+        target_type,
+        target_uuid: target_uuid.to_string(),
+        spam_flags,
+    };
+    append_event(conn, target_type.as_str(), target_uuid, &action, &event).await
+}
+
+/// Flags the post as spam on behalf of content screening, which hides it until a moderator
+/// declines the flag. Does nothing if the post is gone.
+pub(crate) async fn quarantine_post(
+    conn: &mut PgConnection,
+    context: &AuditContext,
+    target_type: PostType,
+    target_uuid: Uuid,
+    details: String,
+    hide_after: i64,
+) -> Result<(), DBError> {
+    if lock_post(&mut *conn, target_type, target_uuid).await?.is_none() {
+        return Ok(());
+    }
+
+    let row = sqlx::query_as!(
+            FlagRow,
+            "INSERT INTO flags (target_type, target_uuid, reason, details) VALUES ($1, $2, 'spam', $3) RETURNING *",
+            target_type.as_str(),
+            target_uuid,
+            details
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    update_hiding(&mut *conn, context, target_type, target_uuid, hide_after).await?;
+    let detail = FlagDetail::try_from(row)?;
+    append_event(conn, target_type.as_str(), target_uuid, "flag.created", &detail).await
+}
+
+fn require_actor(context: &AuditContext) -> Result<Uuid, DBError> {
+    let actor = context
+        .actor_uuid
+        .as_deref()
+        .ok_or_else(|| DBError::Forbidden("Only signed-in users can flag posts or review flags".to_owned()))?;
+    parse_uuid(actor, "user ID")
+}
+
+#[async_trait]
+pub trait FlagsDao {
+    /// Flags a question or an answer as the actor, who can't flag their own posts or flag a post
+    /// twice. The post is hidden once it has `hide_after` spam flags.
+    async fn create_flag(&self, flag: Flag, hide_after: i64, context: AuditContext) -> Result<FlagDetail, DBError>;
+    /// Up to `limit` pending flags the moderator can review, oldest first: unclaimed ones, their own,
+    /// and ones whose claim is older than `claim_timeout`.
+    async fn get_queue(&self, moderator_uuid: String, claim_timeout: Duration, limit: i64) -> Result<Vec<FlagDetail>, DBError>;
+    /// Claims a pending flag for the actor, so other moderators leave it alone for `claim_timeout`.
+    async fn claim_flag(&self, flag_id: i64, claim_timeout: Duration, context: AuditContext) -> Result<FlagDetail, DBError>;
+    /// Resolves a pending flag as helpful or declined, updating whether its post is hidden.
+    async fn resolve_flag(&self, flag_id: i64, status: FlagStatus, hide_after: i64, claim_timeout: Duration, context: AuditContext) -> Result<FlagDetail, DBError>;
+    /// How many of the user's flags are pending, helpful and declined, and the share of the resolved
+    /// ones found helpful. `NotFound` if there is no such user.
+    async fn get_stats(&self, user_uuid: String) -> Result<FlagStats, DBError>;
+}
+
+pub struct FlagsDaoImpl {
+    db: PgPool,
+}
+
+impl FlagsDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl FlagsDao for FlagsDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO flags", target_uuid = %flag.target_uuid))]
+    async fn create_flag(&self, flag: Flag, hide_after: i64, context: AuditContext) -> Result<FlagDetail, DBError> {
+        let uuid = parse_uuid(&flag.target_uuid, &format!("{} ID", flag.target_type.as_str()))?;
+        let flagger = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let author = lock_post(&mut tx, flag.target_type, uuid)
+            .await?
+            .ok_or_else(|| DBError::NotFound(format!("No {} with ID {}", flag.target_type.as_str(), flag.target_uuid)))?;
+        if author == Some(flagger) {
+            return Err(DBError::Forbidden("You cannot flag your own posts".to_owned()));
+        }
+
+        let row = sqlx::query_as!(
+                FlagRow,
+                "INSERT INTO flags (target_type, target_uuid, flagged_by, reason, details) VALUES ($1, $2, $3, $4, $5)
+                 RETURNING *",
+                flag.target_type.as_str(),
+                uuid,
+                flagger,
+                flag.reason.as_str(),
+                flag.details
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e: sqlx::Error| match e {
+                sqlx::Error::Database(e) => {
+                    if let Some(code) = e.code() {
+                        if code.eq(postgres_error_codes::UNIQUE_VIOLATION) {
+                            return DBError::Conflict(format!("You already flagged {} {}", flag.target_type.as_str(), flag.target_uuid));
+                        }
+                    }
+                    DBError::Other(Box::new(e))
+                }
+                e => DBError::Other(Box::new(e)),
+            })?;
+
+        if flag.reason == FlagReason::Spam {
+            update_hiding(&mut tx, &context, flag.target_type, uuid, hide_after).await?;
+        }
+        let detail = FlagDetail::try_from(row)?;
+        append_event(&mut tx, flag.target_type.as_str(), uuid, "flag.created", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM flags"))]
+    async fn get_queue(&self, moderator_uuid: String, claim_timeout: Duration, limit: i64) -> Result<Vec<FlagDetail>, DBError> {
+        let moderator = parse_uuid(&moderator_uuid, "user ID")?;
+
+        let rows = sqlx::query_as!(
+                FlagRow,
+                "SELECT * FROM flags
+                 WHERE status = 'pending'
+                   AND (claimed_by IS NULL OR claimed_by = $1 OR claimed_at < CURRENT_TIMESTAMP - make_interval(secs => $2))
+                 ORDER BY created_at, flag_id
+                 LIMIT $3",
+                moderator,
+                claim_timeout.as_secs_f64(),
+                limit
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        rows.into_iter().map(FlagDetail::try_from).collect()
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE flags", flag_id))]
+    async fn claim_flag(&self, flag_id: i64, claim_timeout: Duration, context: AuditContext) -> Result<FlagDetail, DBError> {
+        let moderator = require_actor(&context)?;
+
+        let row = sqlx::query_as!(
+                FlagRow,
+                "UPDATE flags SET claimed_by = $2, claimed_at = CURRENT_TIMESTAMP
+                 WHERE flag_id = $1 AND status = 'pending'
+                   AND (claimed_by IS NULL OR claimed_by = $2 OR claimed_at < CURRENT_TIMESTAMP - make_interval(secs => $3))
+                 RETURNING *",
+                flag_id,
+                moderator,
+                claim_timeout.as_secs_f64()
+            )
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if let Some(row) = row {
+            return FlagDetail::try_from(row);
+        }
+        let status = sqlx::query_scalar!("SELECT status FROM flags WHERE flag_id = $1", flag_id)
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+        match status.as_deref() {
+            None => Err(DBError::NotFound(format!("No flag with ID {}", flag_id))),
+            Some("pending") => Err(DBError::Conflict(format!("Flag {} is claimed by another moderator", flag_id))),
+            Some(_) => Err(DBError::Conflict(format!("Flag {} is already resolved", flag_id))),
+        }
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE flags", flag_id))]
+    async fn resolve_flag(&self, flag_id: i64, status: FlagStatus, hide_after: i64, claim_timeout: Duration, context: AuditContext) -> Result<FlagDetail, DBError> {
+        let moderator = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let flag = sqlx::query!(
+                r#"SELECT target_type, target_uuid, reason, status,
+                          (claimed_by IS NOT NULL AND claimed_by <> $2 AND claimed_at >= CURRENT_TIMESTAMP - make_interval(secs => $3)) AS "claimed_by_other!"
+                   FROM flags WHERE flag_id = $1 FOR UPDATE"#,
+                flag_id,
+                moderator,
+                claim_timeout.as_secs_f64()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No flag with ID {}", flag_id)))?;
+        if flag.status != "pending" {
+            return Err(DBError::Conflict(format!("Flag {} is already resolved", flag_id)));
+        }
+        if flag.claimed_by_other {
+            return Err(DBError::Conflict(format!("Flag {} is claimed by another moderator", flag_id)));
+        }
+        let target_type = PostType::parse(&flag.target_type)
+            .ok_or_else(|| DBError::Other(format!("Unknown flag target type: {}", flag.target_type).into()))?;
+
+        // The post may have been deleted since, which leaves nothing to hide or show
+        let post = lock_post(&mut tx, target_type, flag.target_uuid).await?;
+
+        let row = sqlx::query_as!(
+                FlagRow,
+                "UPDATE flags SET status = $2, resolved_by = $3, resolved_at = CURRENT_TIMESTAMP WHERE flag_id = $1 RETURNING *",
+                flag_id,
+                status.as_str(),
+                moderator
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if post.is_some() && flag.reason == FlagReason::Spam.as_str() {
+            update_hiding(&mut tx, &context, target_type, flag.target_uuid, hide_after).await?;
+        }
+        let detail = FlagDetail::try_from(row)?;
+        append_event(&mut tx, target_type.as_str(), flag.target_uuid, "flag.resolved", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM flags"))]
+    async fn get_stats(&self, user_uuid: String) -> Result<FlagStats, DBError> {
+        let uuid = parse_uuid(&user_uuid, "user ID")?;
+
+        let record = sqlx::query!(
+                r#"SELECT
+                     COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+                     COUNT(*) FILTER (WHERE status = 'helpful') AS "helpful!",
+                     COUNT(*) FILTER (WHERE status = 'declined') AS "declined!"
+                   FROM users LEFT JOIN flags ON flags.flagged_by = users.user_uuid
+                   WHERE users.user_uuid = $1
+                   GROUP BY users.user_uuid"#,
+                uuid
+            )
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No user with ID {}", user_uuid)))?;
+
+        let resolved = record.helpful + record.declined;
+        Ok(FlagStats {
+            user_uuid,
+            pending: record.pending,
+            helpful: record.helpful,
+            declined: record.declined,
+            accuracy: (resolved > 0).then(|| record.helpful as f64 / resolved as f64),
+        })
+    }
+}
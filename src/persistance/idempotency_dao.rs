@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+
+use crate::models::DBError;
+
+/// A request already made with the same idempotency key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotentRequest {
+    pub request_hash: String,
+    /// The stored response, or `None` while the request is still being handled.
+    pub response: Option<JsonValue>,
+}
+
+#[async_trait]
+pub trait IdempotencyDao {
+    /// Reserves `key` for a request with the given hash, for `ttl`. Returns `None` when the key was
+    /// free (or expired), and the earlier request otherwise.
+    async fn claim(&self, scope: String, key: String, request_hash: String, ttl: Duration) -> Result<Option<IdempotentRequest>, DBError>;
+    /// Stores the response to replay for a claimed key.
+    async fn complete(&self, scope: String, key: String, response: JsonValue) -> Result<(), DBError>;
+    /// Frees a claimed key whose request failed, so it can be retried.
+    async fn release(&self, scope: String, key: String) -> Result<(), DBError>;
+    async fn purge_expired(&self) -> Result<u64, DBError>;
+}
+
+pub struct IdempotencyDaoImpl {
+    db: PgPool,
+}
+
+impl IdempotencyDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl IdempotencyDao for IdempotencyDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO idempotency_keys"))]
+    async fn claim(&self, scope: String, key: String, request_hash: String, ttl: Duration) -> Result<Option<IdempotentRequest>, DBError> {
+        // Expired keys can be claimed again, and so can keys whose first request never completed
+        // (e.g. because the instance handling it went down).
+        let claimed = sqlx::query_scalar!(
+                "INSERT INTO idempotency_keys (scope, idempotency_key, request_hash, expires_at)
+                 VALUES ($1, $2, $3, CURRENT_TIMESTAMP + make_interval(secs => $4))
+                 ON CONFLICT (scope, idempotency_key) DO UPDATE
+                 SET request_hash = EXCLUDED.request_hash, response = NULL,
+                     created_at = CURRENT_TIMESTAMP, expires_at = EXCLUDED.expires_at
+                 WHERE idempotency_keys.expires_at <= CURRENT_TIMESTAMP
+                    OR (idempotency_keys.response IS NULL AND idempotency_keys.created_at < CURRENT_TIMESTAMP - INTERVAL '1 minute')
+                 RETURNING true",
+                scope,
+                key,
+                request_hash,
+                ttl.as_secs_f64()
+            )
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let record = sqlx::query!(
+                "SELECT request_hash, response FROM idempotency_keys WHERE scope = $1 AND idempotency_key = $2",
+                scope,
+                key
+            )
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(Some(IdempotentRequest {
+            request_hash: record.request_hash,
+            response: record.response,
+        }))
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE idempotency_keys"))]
+    async fn complete(&self, scope: String, key: String, response: JsonValue) -> Result<(), DBError> {
+        sqlx::query!(
+                "UPDATE idempotency_keys SET response = $3 WHERE scope = $1 AND idempotency_key = $2",
+                scope,
+                key,
+                response
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM idempotency_keys"))]
+    async fn release(&self, scope: String, key: String) -> Result<(), DBError> {
+        sqlx::query!(
+                "DELETE FROM idempotency_keys WHERE scope = $1 AND idempotency_key = $2 AND response IS NULL",
+                scope,
+                key
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM idempotency_keys"))]
+    async fn purge_expired(&self) -> Result<u64, DBError> {
+        let result = sqlx::query!("DELETE FROM idempotency_keys WHERE expires_at <= CURRENT_TIMESTAMP")
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+
+use crate::models::DBError;
+
+/// A job to enqueue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewJob {
+    pub kind: String,
+    pub payload: JsonValue,
+    /// When the job is due; now when unset.
+    pub run_at: Option<OffsetDateTime>,
+    /// Runs after which a failing job is given up on; the configured default when unset.
+    pub max_attempts: Option<i32>,
+}
+
+/// A job claimed for a run, with what is needed to make it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimedJob {
+    pub job_id: i64,
+    pub kind: String,
+    pub payload: JsonValue,
+    /// Attempts so far, including this one.
+    pub attempts: i32,
+    pub max_attempts: Option<i32>,
+}
+
+#[async_trait]
+pub trait JobsDao {
+    async fn enqueue(&self, job: NewJob) -> Result<i64, DBError>;
+    /// Claims up to `limit` due jobs for `lease`: other instances skip them until it runs out, so a
+    /// job whose instance went down is picked up again.
+    async fn claim_due(&self, limit: i64, lease: Duration) -> Result<Vec<ClaimedJob>, DBError>;
+    async fn complete(&self, job_id: i64) -> Result<(), DBError>;
+    /// Records a failed run; the job is retried in `retry_in`, or given up on when `None`.
+    async fn fail(&self, job_id: i64, error: String, retry_in: Option<Duration>) -> Result<(), DBError>;
+    /// Hands a claimed job back without counting the attempt, e.g. when shutting down.
+    async fn release(&self, job_id: i64) -> Result<(), DBError>;
+    /// Enqueues `job` if the schedule `name` is due, and moves the schedule to `next_run_at` in the
+    /// same transaction, so that each run is enqueued by a single instance. A new schedule is first
+    /// due at `next_run_at`. Returns the ID of the job enqueued, if any.
+    async fn enqueue_scheduled(&self, name: &str, next_run_at: OffsetDateTime, job: NewJob) -> Result<Option<i64>, DBError>;
+    /// Removes jobs finished more than `retention` ago, returning how many were removed.
+    async fn purge_finished(&self, retention: Duration) -> Result<u64, DBError>;
+}
+
+pub struct JobsDaoImpl {
+    db: PgPool,
+}
+
+impl JobsDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl JobsDao for JobsDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO jobs"))]
+    async fn enqueue(&self, job: NewJob) -> Result<i64, DBError> {
+        let record = sqlx::query!(
+                "INSERT INTO jobs (kind, payload, run_at, max_attempts)
+                 VALUES ($1, $2, COALESCE($3, CURRENT_TIMESTAMP), $4)
+                 RETURNING job_id",
+                job.kind,
+                job.payload,
+                job.run_at,
+                job.max_attempts
+            )
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(record.job_id)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE jobs"))]
+    async fn claim_due(&self, limit: i64, lease: Duration) -> Result<Vec<ClaimedJob>, DBError> {
+        let records = sqlx::query!(
+                "UPDATE jobs
+                 SET status = 'running', attempts = attempts + 1,
+                     locked_until = CURRENT_TIMESTAMP + make_interval(secs => $2)
+                 WHERE job_id IN (
+                     SELECT job_id FROM jobs
+                     WHERE (status = 'pending' AND run_at <= CURRENT_TIMESTAMP)
+                        OR (status = 'running' AND locked_until < CURRENT_TIMESTAMP)
+                     ORDER BY run_at, job_id
+                     LIMIT $1
+                     FOR UPDATE SKIP LOCKED
+                 )
+                 RETURNING job_id, kind, payload, attempts, max_attempts",
+                limit,
+                lease.as_secs_f64()
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let mut jobs: Vec<ClaimedJob> = records
+            .into_iter()
+            .map(|r| ClaimedJob {
+                job_id: r.job_id,
+                kind: r.kind,
+                payload: r.payload,
+                attempts: r.attempts,
+                max_attempts: r.max_attempts,
+            })
+            .collect();
+        jobs.sort_by_key(|job| job.job_id);
+
+        Ok(jobs)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE jobs"))]
+    async fn complete(&self, job_id: i64) -> Result<(), DBError> {
+        sqlx::query!(
+                "UPDATE jobs SET status = 'succeeded', locked_until = NULL, last_error = NULL,
+                                 finished_at = CURRENT_TIMESTAMP
+                 WHERE job_id = $1",
+                job_id
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE jobs"))]
+    async fn fail(&self, job_id: i64, error: String, retry_in: Option<Duration>) -> Result<(), DBError> {
+        sqlx::query!(
+                "UPDATE jobs
+                 SET status = CASE WHEN $3::float8 IS NULL THEN 'failed' ELSE 'pending' END,
+                     run_at = CURRENT_TIMESTAMP + make_interval(secs => COALESCE($3, 0)),
+                     finished_at = CASE WHEN $3::float8 IS NULL THEN CURRENT_TIMESTAMP END,
+                     locked_until = NULL, last_error = $2
+                 WHERE job_id = $1",
+                job_id,
+                error,
+                retry_in.map(|retry_in| retry_in.as_secs_f64())
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE jobs"))]
+    async fn release(&self, job_id: i64) -> Result<(), DBError> {
+        sqlx::query!(
+                "UPDATE jobs SET status = 'pending', attempts = attempts - 1, locked_until = NULL
+                 WHERE job_id = $1 AND status = 'running'",
+                job_id
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE job_schedules"))]
+    async fn enqueue_scheduled(&self, name: &str, next_run_at: OffsetDateTime, job: NewJob) -> Result<Option<i64>, DBError> {
+        sqlx::query!(
+                "INSERT INTO job_schedules (name, next_run_at) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING",
+                name,
+                next_run_at
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        // A single statement, so the schedule only moves on if the job is enqueued.
+        let record = sqlx::query!(
+                "WITH due AS (
+                     UPDATE job_schedules SET next_run_at = $2
+                     WHERE name = $1 AND next_run_at <= CURRENT_TIMESTAMP
+                     RETURNING name
+                 )
+                 INSERT INTO jobs (kind, payload, run_at, max_attempts)
+                 SELECT $3, $4, COALESCE($5, CURRENT_TIMESTAMP), $6 FROM due
+                 RETURNING job_id",
+                name,
+                next_run_at,
+                job.kind,
+                job.payload,
+                job.run_at,
+                job.max_attempts
+            )
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(record.map(|r| r.job_id))
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM jobs"))]
+    async fn purge_finished(&self, retention: Duration) -> Result<u64, DBError> {
+        let result = sqlx::query!(
+                "DELETE FROM jobs WHERE finished_at < CURRENT_TIMESTAMP - make_interval(secs => $1)",
+                retention.as_secs_f64()
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,5 +1,30 @@
 pub mod answers_dao;
+pub mod audit_dao;
+pub mod badges_dao;
+pub mod bounties_dao;
+pub mod closing_dao;
+pub mod flags_dao;
+pub mod idempotency_dao;
+pub mod jobs_dao;
+pub mod outbox_dao;
+pub mod question_events_dao;
+pub mod question_links_dao;
 pub mod questions_dao;
+pub mod reputation_dao;
+pub mod screening_dao;
+pub mod suggested_edits_dao;
+pub mod unit_of_work;
+pub mod users_dao;
+pub mod votes_dao;
+pub mod webhooks_dao;
+
+use crate::models::DBError;
+
+pub(crate) fn parse_uuid(uuid: &str, what: &str) -> Result<sqlx::types::Uuid, DBError> {
+    sqlx::types::Uuid::parse_str(uuid).map_err(|err| {
+        DBError::InvalidUUID(format!("Unable to parse given {} ({}) due to error: {:?}", what, uuid, err))
+    })
+}
 
 #[cfg(test)]
-mod tests;
\ No newline at end of file
+mod tests;
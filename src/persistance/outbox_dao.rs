@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+
+use crate::models::{DBError, DomainEvent};
+
+/// Appends a domain event to the outbox using `conn`, so it is published if and only if the
+/// mutation commits.
+pub(crate) async fn append_event(
+    conn: &mut PgConnection,
+    aggregate_type: &str,
+    aggregate_uuid: Uuid,
+    event_type: &str,
+    payload: &impl Serialize,
+) -> Result<(), DBError> {
+    let payload = serde_json::to_value(payload).map_err(|e| DBError::Other(Box::new(e)))?;
+
+    sqlx::query!(
+            "INSERT INTO outbox_events (aggregate_type, aggregate_uuid, event_type, payload) VALUES ($1, $2, $3, $4)",
+            aggregate_type,
+            aggregate_uuid,
+            event_type,
+            payload
+        )
+        .execute(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    Ok(())
+}
+
+/// An event claimed for publishing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEvent {
+    pub event: DomainEvent,
+    /// Failed attempts so far.
+    pub attempts: i32,
+}
+
+#[async_trait]
+pub trait OutboxDao {
+    /// Claims up to `limit` due events for `lease`, at most one per aggregate: an event is only
+    /// claimable once every earlier event of its aggregate has been published.
+    async fn claim_due(&self, limit: i64, lease: Duration) -> Result<Vec<PendingEvent>, DBError>;
+    async fn mark_published(&self, event_id: i64) -> Result<(), DBError>;
+    /// Records a failed attempt; the event (and so the rest of its aggregate) waits `retry_in`.
+    async fn mark_failed(&self, event_id: i64, error: String, retry_in: Duration) -> Result<(), DBError>;
+    /// Removes events published more than `retention` ago, returning how many were removed.
+    async fn purge_published(&self, retention: Duration) -> Result<u64, DBError>;
+}
+
+pub struct OutboxDaoImpl {
+    db: PgPool,
+}
+
+impl OutboxDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl OutboxDao for OutboxDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE outbox_events"))]
+    async fn claim_due(&self, limit: i64, lease: Duration) -> Result<Vec<PendingEvent>, DBError> {
+        let records = sqlx::query!(
+                "UPDATE outbox_events
+                 SET next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $2)
+                 WHERE event_id IN (
+                     SELECT event_id FROM outbox_events AS e
+                     WHERE published_at IS NULL AND next_attempt_at <= CURRENT_TIMESTAMP
+                       AND NOT EXISTS (
+                           SELECT 1 FROM outbox_events AS earlier
+                           WHERE earlier.aggregate_uuid = e.aggregate_uuid
+                             AND earlier.published_at IS NULL
+                             AND earlier.event_id < e.event_id
+                       )
+                     ORDER BY event_id
+                     LIMIT $1
+                     FOR UPDATE SKIP LOCKED
+                 )
+                 RETURNING event_id, aggregate_type, aggregate_uuid, event_type, payload, created_at, attempts",
+                limit,
+                lease.as_secs_f64()
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let mut events: Vec<PendingEvent> = records
+            .into_iter()
+            .map(|r| PendingEvent {
+                event: DomainEvent {
+                    event_id: r.event_id,
+                    aggregate_type: r.aggregate_type,
+                    aggregate_uuid: r.aggregate_uuid.to_string(),
+                    event_type: r.event_type,
+                    payload: r.payload,
+                    created_at: r.created_at.to_string(),
+                },
+                attempts: r.attempts,
+            })
+            .collect();
+        events.sort_by_key(|pending| pending.event.event_id);
+
+        Ok(events)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE outbox_events"))]
+    async fn mark_published(&self, event_id: i64) -> Result<(), DBError> {
+        sqlx::query!(
+                "UPDATE outbox_events SET published_at = CURRENT_TIMESTAMP, attempts = attempts + 1, last_error = NULL
+                 WHERE event_id = $1",
+                event_id
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE outbox_events"))]
+    async fn mark_failed(&self, event_id: i64, error: String, retry_in: Duration) -> Result<(), DBError> {
+        sqlx::query!(
+                "UPDATE outbox_events
+                 SET attempts = attempts + 1, last_error = $2,
+                     next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $3)
+                 WHERE event_id = $1",
+                event_id,
+                error,
+                retry_in.as_secs_f64()
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM outbox_events"))]
+    async fn purge_published(&self, retention: Duration) -> Result<u64, DBError> {
+        let result = sqlx::query!(
+                "DELETE FROM outbox_events WHERE published_at < CURRENT_TIMESTAMP - make_interval(secs => $1)",
+                retention.as_secs_f64()
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(result.rows_affected())
+    }
+}
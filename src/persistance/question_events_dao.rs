@@ -0,0 +1,150 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+
+use crate::models::{DBError, QuestionEvent};
+
+use super::parse_uuid;
+
+/// The Postgres channel notified with the question UUID whenever one of its events is recorded.
+pub const QUESTION_EVENTS_CHANNEL: &str = "question_events";
+
+/// Appends an event to the stream of `question_uuid` using `conn`, so watchers only hear about it
+/// once the mutation commits: `NOTIFY` is delivered on commit, and dropped on rollback. The event is
+/// queued for delivery to the webhooks subscribed to its type in the same statement.
+pub(crate) async fn record_question_event(conn: &mut PgConnection, question_uuid: Uuid, event_type: &str, payload: &impl Serialize) -> Result<(), DBError> {
+    let payload = serde_json::to_value(payload).map_err(|e| DBError::Other(Box::new(e)))?;
+
+    sqlx::query_scalar!(
+            "WITH event AS (
+                 INSERT INTO question_events (question_uuid, event_type, payload) VALUES ($1, $2, $3)
+                 RETURNING event_id, question_uuid, event_type, payload
+             ), deliveries AS (
+                 INSERT INTO webhook_deliveries (subscription_uuid, event_id, event_type, payload)
+                 SELECT subscription_uuid, event_id, event.event_type, payload
+                 FROM webhook_subscriptions, event
+                 WHERE event.event_type = ANY(webhook_subscriptions.event_types)
+             )
+             SELECT pg_notify('question_events', question_uuid::text)::text FROM event",
+            question_uuid,
+            event_type,
+            payload
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    Ok(())
+}
+
+#[async_trait]
+pub trait QuestionEventsDao {
+    /// The ID of the newest event of a question (0 if it has none), to start streaming after.
+    async fn latest_event_id(&self, question_uuid: String) -> Result<i64, DBError>;
+    /// Up to `limit` events of a question recorded after `after_event_id`, oldest first.
+    async fn get_events(&self, question_uuid: String, after_event_id: i64, limit: i64) -> Result<Vec<QuestionEvent>, DBError>;
+    /// The ID of the newest event of any question (0 if there are none).
+    async fn max_event_id(&self) -> Result<i64, DBError>;
+    /// Up to `limit` events of all questions recorded after `after_event_id`, oldest first.
+    async fn get_all_events(&self, after_event_id: i64, limit: i64) -> Result<Vec<QuestionEvent>, DBError>;
+}
+
+pub struct QuestionEventsDaoImpl {
+    db: PgPool,
+}
+
+impl QuestionEventsDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl QuestionEventsDao for QuestionEventsDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM question_events"))]
+    async fn latest_event_id(&self, question_uuid: String) -> Result<i64, DBError> {
+        let uuid = parse_uuid(&question_uuid, "question ID")?;
+
+        let latest = sqlx::query_scalar!(
+                r#"SELECT (SELECT MAX(event_id) FROM question_events WHERE question_uuid = $1) AS "latest"
+                   FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL"#,
+                uuid
+            )
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        match latest {
+            Some(latest) => Ok(latest.unwrap_or(0)),
+            None => Err(DBError::NotFound(format!("Question {} not found", question_uuid))),
+        }
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM question_events"))]
+    async fn get_events(&self, question_uuid: String, after_event_id: i64, limit: i64) -> Result<Vec<QuestionEvent>, DBError> {
+        let uuid = parse_uuid(&question_uuid, "question ID")?;
+
+        let records = sqlx::query!(
+                "SELECT event_id, question_uuid, event_type, payload FROM question_events
+                 WHERE question_uuid = $1 AND event_id > $2
+                 ORDER BY event_id
+                 LIMIT $3",
+                uuid,
+                after_event_id,
+                limit
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let events = records
+            .into_iter()
+            .map(|r| QuestionEvent {
+                event_id: r.event_id,
+                question_uuid: r.question_uuid.to_string(),
+                event_type: r.event_type,
+                payload: r.payload,
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM question_events"))]
+    async fn max_event_id(&self) -> Result<i64, DBError> {
+        let max = sqlx::query_scalar!("SELECT MAX(event_id) FROM question_events")
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(max.unwrap_or(0))
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM question_events"))]
+    async fn get_all_events(&self, after_event_id: i64, limit: i64) -> Result<Vec<QuestionEvent>, DBError> {
+        let records = sqlx::query!(
+                "SELECT event_id, question_uuid, event_type, payload FROM question_events
+                 WHERE event_id > $1
+                 ORDER BY event_id
+                 LIMIT $2",
+                after_event_id,
+                limit
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let events = records
+            .into_iter()
+            .map(|r| QuestionEvent {
+                event_id: r.event_id,
+                question_uuid: r.question_uuid.to_string(),
+                event_type: r.event_type,
+                payload: r.payload,
+            })
+            .collect();
+
+        Ok(events)
+    }
+}
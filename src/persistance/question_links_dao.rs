@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+
+use crate::models::{DBError, LinkedQuestion, PostType};
+
+use super::parse_uuid;
+
+/// The UUIDs mentioned in a text, on their own or in a URL, in order and without repeats.
+fn mentioned_uuids(text: &str) -> Vec<Uuid> {
+    let mut uuids = Vec::new();
+    for word in text.split(|c: char| !c.is_ascii_hexdigit() && c != '-') {
+        // Only the hyphenated form, as in URLs
+        if word.len() != 36 {
+            continue;
+        }
+        if let Ok(uuid) = Uuid::parse_str(word) {
+            if !uuids.contains(&uuid) {
+                uuids.push(uuid);
+            }
+        }
+    }
+    uuids
+}
+
+/// Replaces the links from a question or answer of `question_uuid` with the questions its text
+/// mentions using `conn`, so they change along with the post. Mentioning an answer links its question.
+pub(crate) async fn record_links(conn: &mut PgConnection, source_type: PostType, source_uuid: Uuid, question_uuid: Uuid, text: &str) -> Result<(), DBError> {
+    sqlx::query!("DELETE FROM question_links WHERE source_uuid = $1", source_uuid)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    let mentioned = mentioned_uuids(text);
+    if mentioned.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+            "INSERT INTO question_links (source_type, source_uuid, question_uuid, linked_question_uuid)
+             SELECT $1, $2, $3, q.question_uuid FROM questions q
+             WHERE (q.question_uuid = ANY($4) OR q.question_uuid IN (SELECT a.question_uuid FROM answers a WHERE a.answer_uuid = ANY($4)))
+               AND q.question_uuid <> $3
+             ON CONFLICT DO NOTHING",
+            source_type.as_str(),
+            source_uuid,
+            question_uuid,
+            &mentioned
+        )
+        .execute(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    Ok(())
+}
+
+#[async_trait]
+pub trait QuestionLinksDao {
+    /// Up to `limit` visible questions linking to the question or linked from it, in either its
+    /// text or its visible answers, the most recently linked first.
+    async fn get_linked(&self, question_uuid: String, limit: i64) -> Result<Vec<LinkedQuestion>, DBError>;
+}
+
+pub struct QuestionLinksDaoImpl {
+    db: PgPool,
+}
+
+impl QuestionLinksDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl QuestionLinksDao for QuestionLinksDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM question_links", question_uuid = %question_uuid))]
+    async fn get_linked(&self, question_uuid: String, limit: i64) -> Result<Vec<LinkedQuestion>, DBError> {
+        let uuid = parse_uuid(&question_uuid, "question ID")?;
+
+        let records = sqlx::query!(
+                r#"SELECT q.question_uuid, q.title, q.accepted_answer_uuid,
+                          (SELECT COUNT(*) FROM answers a
+                           WHERE a.question_uuid = q.question_uuid AND a.deleted_at IS NULL AND a.hidden_at IS NULL) AS "answer_count!",
+                          MAX(l.created_at) AS "linked_at!"
+                   FROM question_links l
+                   JOIN questions q ON q.question_uuid = CASE WHEN l.question_uuid = $1 THEN l.linked_question_uuid ELSE l.question_uuid END
+                   WHERE (l.question_uuid = $1 OR l.linked_question_uuid = $1)
+                     AND q.deleted_at IS NULL AND q.hidden_at IS NULL
+                     AND NOT EXISTS (SELECT 1 FROM answers a
+                                     WHERE a.answer_uuid = l.source_uuid AND (a.deleted_at IS NOT NULL OR a.hidden_at IS NOT NULL))
+                   GROUP BY q.question_uuid
+                   ORDER BY 5 DESC
+                   LIMIT $2"#,
+                uuid,
+                limit
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| LinkedQuestion {
+                question_uuid: record.question_uuid.to_string(),
+                title: record.title,
+                answer_count: record.answer_count,
+                accepted_answer_uuid: record.accepted_answer_uuid.map(|uuid| uuid.to_string()),
+                linked_at: record.linked_at.to_string(),
+            })
+            .collect())
+    }
+}
+
@@ -1,67 +1,366 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
-use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::models::{AuditContext, CloseReason, DBError, IfMatch, OpenBounty, PostType, Question, QuestionDetail, QuestionEdit, QuestionFilter, RenderedBody, SimilarQuestion};
+
+use super::audit_dao::{record_event, AuditRecord};
+use super::bounties_dao::refund_open_bounty;
+use super::outbox_dao::append_event;
+use super::parse_uuid;
+use super::question_events_dao::record_question_event;
+use super::question_links_dao::record_links;
+use super::unit_of_work::Executor;
+
+/// Up to `limit` visible questions other than `exclude` similar to `question`, the most similar
+/// first. `conn` must be in a transaction, which the similarity threshold is set for.
+async fn similar_questions(
+    conn: &mut PgConnection,
+    question: &Question,
+    exclude: Option<Uuid>,
+    min_similarity: f32,
+    limit: i64,
+) -> Result<Vec<SimilarQuestion>, DBError> {
+    // The `%` operators use the trigram indexes, with this threshold until the end of the transaction
+    sqlx::query!("SELECT set_config('pg_trgm.similarity_threshold', $1, true)", min_similarity.to_string())
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|err| DBError::Other(Box::new(err)))?;
+
+    let records = sqlx::query!(
+            r#"SELECT q.question_uuid, q.title, q.accepted_answer_uuid, q.duplicate_of,
+                      ((2 * similarity(q.title, $1) + similarity(q.description, $2)) / 3)::REAL AS "similarity!",
+                      (SELECT COUNT(*) FROM answers a
+                       WHERE a.question_uuid = q.question_uuid AND a.deleted_at IS NULL AND a.hidden_at IS NULL) AS "answer_count!"
+               FROM questions q
+               WHERE (q.title % $1 OR q.description % $2) AND q.deleted_at IS NULL AND q.hidden_at IS NULL
+                 AND q.question_uuid IS DISTINCT FROM $3
+               ORDER BY 5 DESC, q.created_at
+               LIMIT $4"#,
+            question.title,
+            question.description,
+            exclude,
+            limit
+        )
+        .fetch_all(conn)
+        .await
+        .map_err(|err| DBError::Other(Box::new(err)))?;
 
-use crate::models::{DBError, Question, QuestionDetail};
+    Ok(records
+        .into_iter()
+        .map(|record| SimilarQuestion {
+            question_uuid: record.question_uuid.to_string(),
+            title: record.title,
+            similarity: record.similarity,
+            answer_count: record.answer_count,
+            accepted_answer_uuid: record.accepted_answer_uuid.map(|uuid| uuid.to_string()),
+            duplicate_of: record.duplicate_of.map(|uuid| uuid.to_string()),
+        })
+        .collect())
+}
 
 #[async_trait]
 pub trait QuestionsDao {
-    async fn create_question(&self, question: Question) -> Result<QuestionDetail, DBError>;
-    async fn delete_question(&self, question_uuid: String) -> Result<(), DBError>;
-    async fn get_questions(&self) -> Result<Vec<QuestionDetail>, DBError>;
+    async fn create_question(&self, question: Question, context: AuditContext) -> Result<QuestionDetail, DBError>;
+    /// Soft-deletes the question; its answers are hidden along with it until it is restored or purged.
+    /// Unless `privileged`, only its author can delete it.
+    async fn delete_question(&self, question_uuid: String, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<(), DBError>;
+    async fn restore_question(&self, question_uuid: String, if_match: IfMatch, context: AuditContext) -> Result<(), DBError>;
+    /// Changes the title and/or description of a question that isn't deleted. Unless `privileged`,
+    /// only its author can edit it.
+    async fn update_question(&self, edit: QuestionEdit, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<QuestionDetail, DBError>;
+    async fn get_questions(&self, filter: QuestionFilter) -> Result<Vec<QuestionDetail>, DBError>;
+    /// Up to `limit` visible questions whose title or description is at least `min_similarity`
+    /// similar to the given ones, the most similar first.
+    async fn find_similar(&self, question: Question, min_similarity: f32, limit: i64) -> Result<Vec<SimilarQuestion>, DBError>;
+    /// Like `find_similar`, for the title and description of a visible question, leaving it out.
+    async fn find_related(&self, question_uuid: String, min_similarity: f32, limit: i64) -> Result<Vec<SimilarQuestion>, DBError>;
+    /// Permanently removes questions soft-deleted more than `retention` ago, returning how many were removed.
+    async fn purge_deleted(&self, retention: Duration) -> Result<u64, DBError>;
 }
 
 pub struct QuestionsDaoImpl {
-    db: PgPool,
+    db: Executor,
 }
 
 impl QuestionsDaoImpl {
     pub fn new(db: PgPool) -> Self {
+        Self { db: Executor::Pool(db) }
+    }
+
+    pub(crate) fn with_executor(db: Executor) -> Self {
         Self { db }
     }
 }
 
 #[async_trait]
 impl QuestionsDao for QuestionsDaoImpl {
-    async fn create_question(&self, question: Question) -> Result<QuestionDetail, DBError> {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO questions"))]
+    async fn create_question(&self, question: Question, context: AuditContext) -> Result<QuestionDetail, DBError> {
+        let author = context.actor_uuid.as_deref().map(|actor| parse_uuid(actor, "user ID")).transpose()?;
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
         let record = sqlx::query!(
-            "INSERT INTO questions ( title, description) VALUES ($1, $2) RETURNING *"
-            , question.title, question.description
+            "INSERT INTO questions ( title, description, author_uuid) VALUES ($1, $2, $3) RETURNING question_uuid, created_at, version, to_jsonb(questions.*) AS snapshot"
+            , question.title, question.description, author
         )
-            .fetch_one(&self.db)
+            .fetch_one(&mut *tx)
             .await
             .map_err(|err| DBError::Other(Box::new(err)))?;
 
-        Ok(QuestionDetail {
+        record_event(&mut tx, &context, AuditRecord {
+            action: "question.created",
+            target_type: "question",
+            target_uuid: record.question_uuid,
+            before: None,
+            after: record.snapshot,
+        }).await?;
+
+        let detail = QuestionDetail {
             question_uuid: record.question_uuid.to_string(),
             title: question.title,
+            body: RenderedBody::new(&question.description),
             description: question.description,
-            created_at: "now".to_string(),
-        })
+            created_at: record.created_at.to_string(),
+            deleted_at: None,
+            hidden_at: None,
+            accepted_answer_uuid: None,
+            bounty: None,
+            closed_reason: None,
+            duplicate_of: None,
+            version: record.version,
+        };
+        let text = format!("{}\n{}", detail.title, detail.description);
+        record_links(&mut tx, PostType::Question, record.question_uuid, record.question_uuid, &text).await?;
+        record_question_event(&mut tx, record.question_uuid, "question.created", &detail).await?;
+        append_event(&mut tx, "question", record.question_uuid, "question.created", &detail).await?;
+
+        tx.commit().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        Ok(detail)
     }
 
-    async fn delete_question(&self, question_uuid: String) -> Result<(), DBError> {
-        let uuid = sqlx::types::Uuid::parse_str(&question_uuid).map_err(|err| {
-            DBError::InvalidUUID(format!("Unable to parse given question ID ({}) due to error: {:?}", question_uuid, err))
-        })?;
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE questions", question_uuid = %question_uuid))]
+    async fn delete_question(&self, question_uuid: String, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<(), DBError> {
+        let uuid = parse_uuid(&question_uuid, "question ID")?;
+        let deleted_by = context.actor_uuid.as_deref().map(|actor| parse_uuid(actor, "user ID")).transpose()?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|err| DBError::Other(Box::new(err)))?;
 
-        sqlx::query!("DELETE FROM questions WHERE question_uuid = $1", uuid)
-            .execute(&self.db)
+        let before = sqlx::query!(
+            "SELECT version, author_uuid, to_jsonb(questions.*) AS snapshot FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL FOR UPDATE",
+            uuid
+        )
+            .fetch_optional(&mut *tx)
             .await
             .map_err(|err| { DBError::Other(Box::new(err)) })?;
 
+        if let Some(before) = &before {
+            if !privileged && (deleted_by.is_none() || before.author_uuid != deleted_by) {
+                return Err(DBError::Forbidden("Only the author or a moderator can delete this question".to_owned()));
+            }
+        }
+        if !if_match.allows(before.as_ref().map(|before| before.version)) {
+            return Err(DBError::PreconditionFailed(format!("Question {} has changed", question_uuid)));
+        }
+
+        // Deleting an already deleted (or missing) question is a no-op, and so not audited.
+        let Some(before) = before else {
+            return Ok(());
+        };
+
+        let after = sqlx::query!(
+            "UPDATE questions SET deleted_at = CURRENT_TIMESTAMP, deleted_by = $2 WHERE question_uuid = $1
+             RETURNING title, description, created_at, deleted_at, hidden_at, accepted_answer_uuid, closed_reason, duplicate_of, version, to_jsonb(questions.*) AS snapshot",
+            uuid,
+            deleted_by
+        )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|err| { DBError::Other(Box::new(err)) })?;
+
+        refund_open_bounty(&mut tx, uuid, deleted_by).await?;
+
+        record_event(&mut tx, &context, AuditRecord {
+            action: "question.deleted",
+            target_type: "question",
+            target_uuid: uuid,
+            before: before.snapshot,
+            after: after.snapshot,
+        }).await?;
+
+        let detail = QuestionDetail {
+            question_uuid: question_uuid.clone(),
+            title: after.title,
+            body: RenderedBody::new(&after.description),
+            description: after.description,
+            created_at: after.created_at.to_string(),
+            deleted_at: after.deleted_at.map(|deleted_at| deleted_at.to_string()),
+            hidden_at: after.hidden_at.map(|hidden_at| hidden_at.to_string()),
+            accepted_answer_uuid: after.accepted_answer_uuid.map(|answer_uuid| answer_uuid.to_string()),
+            bounty: None,
+            closed_reason: after.closed_reason.as_deref().and_then(CloseReason::parse),
+            duplicate_of: after.duplicate_of.map(|duplicate_of| duplicate_of.to_string()),
+            version: after.version,
+        };
+        record_question_event(&mut tx, uuid, "question.deleted", &detail).await?;
+        append_event(&mut tx, "question", uuid, "question.deleted", &detail).await?;
+
+        tx.commit().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
         Ok(())
     }
 
-    async fn get_questions(&self) -> Result<Vec<QuestionDetail>, DBError> {
-        // Make a database query to get all questions.
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE questions", question_uuid = %question_uuid))]
+    async fn restore_question(&self, question_uuid: String, if_match: IfMatch, context: AuditContext) -> Result<(), DBError> {
+        let uuid = parse_uuid(&question_uuid, "question ID")?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        let before = sqlx::query!(
+            "SELECT version, to_jsonb(questions.*) AS snapshot FROM questions WHERE question_uuid = $1 AND deleted_at IS NOT NULL FOR UPDATE",
+            uuid
+        )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|err| { DBError::Other(Box::new(err)) })?
+            .ok_or_else(|| DBError::NotFound(format!("No deleted question with ID {}", question_uuid)))?;
+
+        if !if_match.allows(Some(before.version)) {
+            return Err(DBError::PreconditionFailed(format!("Question {} has changed", question_uuid)));
+        }
+
+        let after = sqlx::query!(
+            "UPDATE questions SET deleted_at = NULL, deleted_by = NULL WHERE question_uuid = $1
+             RETURNING title, description, created_at, hidden_at, accepted_answer_uuid, closed_reason, duplicate_of, version, to_jsonb(questions.*) AS snapshot",
+            uuid
+        )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|err| { DBError::Other(Box::new(err)) })?;
+
+        record_event(&mut tx, &context, AuditRecord {
+            action: "question.restored",
+            target_type: "question",
+            target_uuid: uuid,
+            before: before.snapshot,
+            after: after.snapshot,
+        }).await?;
+
+        let detail = QuestionDetail {
+            question_uuid: question_uuid.clone(),
+            title: after.title,
+            body: RenderedBody::new(&after.description),
+            description: after.description,
+            created_at: after.created_at.to_string(),
+            deleted_at: None,
+            hidden_at: after.hidden_at.map(|hidden_at| hidden_at.to_string()),
+            accepted_answer_uuid: after.accepted_answer_uuid.map(|answer_uuid| answer_uuid.to_string()),
+            bounty: None,
+            closed_reason: after.closed_reason.as_deref().and_then(CloseReason::parse),
+            duplicate_of: after.duplicate_of.map(|duplicate_of| duplicate_of.to_string()),
+            version: after.version,
+        };
+        record_question_event(&mut tx, uuid, "question.restored", &detail).await?;
+        append_event(&mut tx, "question", uuid, "question.restored", &detail).await?;
+
+        tx.commit().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE questions", question_uuid = %edit.question_uuid))]
+    async fn update_question(&self, edit: QuestionEdit, if_match: IfMatch, privileged: bool, context: AuditContext) -> Result<QuestionDetail, DBError> {
+        let uuid = parse_uuid(&edit.question_uuid, "question ID")?;
+        let editor = context.actor_uuid.as_deref().map(|actor| parse_uuid(actor, "user ID")).transpose()?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        let before = sqlx::query!(
+            "SELECT version, author_uuid, to_jsonb(questions.*) AS snapshot FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL FOR UPDATE",
+            uuid
+        )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|err| { DBError::Other(Box::new(err)) })?
+            .ok_or_else(|| DBError::NotFound(format!("No question with ID {}", edit.question_uuid)))?;
+
+        if !privileged && (editor.is_none() || before.author_uuid != editor) {
+            return Err(DBError::Forbidden("Only the author can edit this question; suggest an edit instead".to_owned()));
+        }
+        if !if_match.allows(Some(before.version)) {
+            return Err(DBError::PreconditionFailed(format!("Question {} has changed", edit.question_uuid)));
+        }
+
+        let after = sqlx::query!(
+            "UPDATE questions SET title = COALESCE($2, title), description = COALESCE($3, description) WHERE question_uuid = $1
+             RETURNING title, description, created_at, hidden_at, accepted_answer_uuid, closed_reason, duplicate_of, version, to_jsonb(questions.*) AS snapshot",
+            uuid,
+            edit.title,
+            edit.description
+        )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|err| { DBError::Other(Box::new(err)) })?;
+
+        record_event(&mut tx, &context, AuditRecord {
+            action: "question.edited",
+            target_type: "question",
+            target_uuid: uuid,
+            before: before.snapshot,
+            after: after.snapshot,
+        }).await?;
+
+        let detail = QuestionDetail {
+            question_uuid: edit.question_uuid,
+            title: after.title,
+            body: RenderedBody::new(&after.description),
+            description: after.description,
+            created_at: after.created_at.to_string(),
+            deleted_at: None,
+            hidden_at: after.hidden_at.map(|hidden_at| hidden_at.to_string()),
+            accepted_answer_uuid: after.accepted_answer_uuid.map(|answer_uuid| answer_uuid.to_string()),
+            bounty: None,
+            closed_reason: after.closed_reason.as_deref().and_then(CloseReason::parse),
+            duplicate_of: after.duplicate_of.map(|duplicate_of| duplicate_of.to_string()),
+            version: after.version,
+        };
+        let text = format!("{}\n{}", detail.title, detail.description);
+        record_links(&mut tx, PostType::Question, uuid, uuid, &text).await?;
+        record_question_event(&mut tx, uuid, "question.edited", &detail).await?;
+        append_event(&mut tx, "question", uuid, "question.edited", &detail).await?;
+
+        tx.commit().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        Ok(detail)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM questions"))]
+    async fn get_questions(&self, filter: QuestionFilter) -> Result<Vec<QuestionDetail>, DBError> {
+        // Make a database query to get all questions, skipping soft-deleted or hidden ones unless asked for,
+        // along with their open bounty.
         // Here is the SQL query:
         // ```
-        // SELECT * FROM questions
+        // SELECT questions.*, <open bounty> FROM questions LEFT JOIN bounties
+        // WHERE ($1 OR (deleted_at IS NULL AND hidden_at IS NULL)) AND (NOT $2 OR <has an open bounty>)
         // ```
         // If executing the query results in an error, map that error
         // to a `DBError::Other` error and early return from this function.
-        let records = sqlx::query!("SELECT * FROM questions")
-            .fetch_all(&self.db)
+        let records = sqlx::query!(
+                r#"SELECT questions.*, bounties.bounty_id AS "bounty_id?", bounties.amount AS "bounty_amount?",
+                          bounties.expires_at AS "bounty_expires_at?"
+                   FROM questions LEFT JOIN bounties ON bounties.question_uuid = questions.question_uuid AND bounties.status = 'open'
+                   WHERE ($1 OR (questions.deleted_at IS NULL AND questions.hidden_at IS NULL)) AND (NOT $2 OR bounties.bounty_id IS NOT NULL)"#,
+                filter.include_deleted,
+                filter.open_bounty
+            )
+            .fetch_all(&mut *self.db.acquire().await?)
             .await
             .map_err(|err| { DBError::Other(Box::new(err)) })?;
 
@@ -72,10 +371,97 @@ impl QuestionsDao for QuestionsDaoImpl {
                 question_uuid: record.question_uuid.to_string(),
                 title: record.title.to_string(),
                 description: record.description.to_string(),
+                body: RenderedBody::new(&record.description),
                 created_at: record.created_at.to_string(),
+                deleted_at: record.deleted_at.map(|deleted_at| deleted_at.to_string()),
+                hidden_at: record.hidden_at.map(|hidden_at| hidden_at.to_string()),
+                accepted_answer_uuid: record.accepted_answer_uuid.map(|answer_uuid| answer_uuid.to_string()),
+                bounty: match (record.bounty_id, record.bounty_amount, record.bounty_expires_at) {
+                    (Some(bounty_id), Some(amount), Some(expires_at)) => Some(OpenBounty {
+                        bounty_id,
+                        amount,
+                        expires_at: expires_at.to_string(),
+                    }),
+                    _ => None,
+                },
+                closed_reason: record.closed_reason.as_deref().and_then(CloseReason::parse),
+                duplicate_of: record.duplicate_of.map(|duplicate_of| duplicate_of.to_string()),
+                version: record.version,
             })
             .collect();
 
         Ok(questions)
     }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM questions"))]
+    async fn find_similar(&self, question: Question, min_similarity: f32, limit: i64) -> Result<Vec<SimilarQuestion>, DBError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        let similar = similar_questions(&mut tx, &question, None, min_similarity, limit).await?;
+
+        tx.commit().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        Ok(similar)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM questions", question_uuid = %question_uuid))]
+    async fn find_related(&self, question_uuid: String, min_similarity: f32, limit: i64) -> Result<Vec<SimilarQuestion>, DBError> {
+        let uuid = parse_uuid(&question_uuid, "question ID")?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        let question = sqlx::query_as!(
+                Question,
+                "SELECT title, description FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL AND hidden_at IS NULL",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|err| DBError::Other(Box::new(err)))?
+            .ok_or_else(|| DBError::NotFound(format!("No question with ID {}", question_uuid)))?;
+        let related = similar_questions(&mut tx, &question, Some(uuid), min_similarity, limit).await?;
+
+        tx.commit().await.map_err(|err| DBError::Other(Box::new(err)))?;
+
+        Ok(related)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM questions"))]
+    async fn purge_deleted(&self, retention: Duration) -> Result<u64, DBError> {
+        // A single statement, so every purged question is audited and published atomically with its removal.
+        // Its answers, live or not, are deleted here rather than left to the cascade so they are audited too.
+        let result = sqlx::query!(
+            "WITH expired AS (
+                 SELECT question_uuid FROM questions WHERE deleted_at < LOCALTIMESTAMP - make_interval(secs => $1)
+             ),
+             purged_answers AS (
+                 DELETE FROM answers WHERE question_uuid IN (SELECT question_uuid FROM expired) RETURNING *
+             ),
+             answers_outbox AS (
+                 INSERT INTO outbox_events (aggregate_type, aggregate_uuid, event_type, payload)
+                 SELECT 'answer', answer_uuid, 'answer.purged', to_jsonb(purged_answers.*) FROM purged_answers
+             ),
+             answers_audit AS (
+                 INSERT INTO audit_events (action, target_type, target_uuid, before)
+                 SELECT 'answer.purged', 'answer', answer_uuid, to_jsonb(purged_answers.*) FROM purged_answers
+             ),
+             purged AS (
+                 DELETE FROM questions WHERE question_uuid IN (SELECT question_uuid FROM expired) RETURNING *
+             ),
+             outbox AS (
+                 INSERT INTO outbox_events (aggregate_type, aggregate_uuid, event_type, payload)
+                 SELECT 'question', question_uuid, 'question.purged', to_jsonb(purged.*) FROM purged
+             )
+             INSERT INTO audit_events (action, target_type, target_uuid, before)
+             SELECT 'question.purged', 'question', question_uuid, to_jsonb(purged.*) FROM purged",
+            retention.as_secs_f64()
+        )
+            .execute(&mut *self.db.acquire().await?)
+            .await
+            .map_err(|err| { DBError::Other(Box::new(err)) })?;
+
+        Ok(result.rows_affected())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+
+use crate::models::{DBError, ReputationEntry};
+
+use super::parse_uuid;
+
+/// A change to a user's reputation, written by the DAO causing it inside its own transaction.
+pub(crate) struct ReputationChange<'a> {
+    pub user_uuid: Uuid,
+    pub reason: &'a str,
+    pub amount: i32,
+    pub target_type: &'a str,
+    pub target_uuid: Uuid,
+    pub actor_uuid: Option<Uuid>,
+}
+
+/// Appends an entry to the reputation ledger and updates the user's cached total, using `conn`
+/// so both commit or roll back together with the cause.
+pub(crate) async fn record_reputation(conn: &mut PgConnection, change: ReputationChange<'_>) -> Result<(), DBError> {
+    sqlx::query!(
+            "WITH entry AS (
+                 INSERT INTO reputation_entries (user_uuid, reason, amount, target_type, target_uuid, actor_uuid)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 RETURNING user_uuid, amount
+             )
+             UPDATE users SET reputation = users.reputation + entry.amount FROM entry WHERE users.user_uuid = entry.user_uuid",
+            change.user_uuid,
+            change.reason,
+            change.amount,
+            change.target_type,
+            change.target_uuid,
+            change.actor_uuid
+        )
+        .execute(conn)
+        .await
+        .map_err(|e| DBError::Other(Box::new(e)))?;
+
+    Ok(())
+}
+
+#[async_trait]
+pub trait ReputationDao {
+    /// The user's cached total: 1 plus the sum of their ledger entries.
+    async fn get_reputation(&self, user_uuid: String) -> Result<i32, DBError>;
+    /// The user's ledger entries, newest first, older than `before_entry_id` if given.
+    async fn get_history(&self, user_uuid: String, before_entry_id: Option<i64>, limit: i64) -> Result<Vec<ReputationEntry>, DBError>;
+    /// Recomputes every cached total from the ledger, returning how many were wrong.
+    async fn recompute(&self) -> Result<u64, DBError>;
+}
+
+pub struct ReputationDaoImpl {
+    db: PgPool,
+}
+
+impl ReputationDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl ReputationDao for ReputationDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM users"))]
+    async fn get_reputation(&self, user_uuid: String) -> Result<i32, DBError> {
+        let uuid = parse_uuid(&user_uuid, "user ID")?;
+
+        sqlx::query_scalar!("SELECT reputation FROM users WHERE user_uuid = $1", uuid)
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No user with ID {}", user_uuid)))
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM reputation_entries"))]
+    async fn get_history(&self, user_uuid: String, before_entry_id: Option<i64>, limit: i64) -> Result<Vec<ReputationEntry>, DBError> {
+        let uuid = parse_uuid(&user_uuid, "user ID")?;
+
+        let records = sqlx::query!(
+                "SELECT * FROM reputation_entries
+                 WHERE user_uuid = $1 AND ($2::bigint IS NULL OR entry_id < $2)
+                 ORDER BY entry_id DESC
+                 LIMIT $3",
+                uuid,
+                before_entry_id,
+                limit
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let entries = records
+            .into_iter()
+            .map(|r| ReputationEntry {
+                entry_id: r.entry_id,
+                reason: r.reason,
+                amount: r.amount,
+                target_type: r.target_type,
+                target_uuid: r.target_uuid.to_string(),
+                actor_uuid: r.actor_uuid.map(|uuid| uuid.to_string()),
+                created_at: r.created_at.to_string(),
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE users"))]
+    async fn recompute(&self) -> Result<u64, DBError> {
+        let mut tx = self.db.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        // Entries and the totals they change are written together, so once no entries can be
+        // added, every committed total is consistent with the ledger read below
+        sqlx::query!("LOCK TABLE reputation_entries IN SHARE MODE")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let result = sqlx::query!(
+                "WITH totals AS (
+                     SELECT users.user_uuid, 1 + COALESCE(SUM(reputation_entries.amount), 0)::int AS reputation
+                     FROM users LEFT JOIN reputation_entries USING (user_uuid)
+                     GROUP BY users.user_uuid
+                 )
+                 UPDATE users SET reputation = totals.reputation FROM totals
+                 WHERE users.user_uuid = totals.user_uuid AND users.reputation <> totals.reputation"
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(result.rows_affected())
+    }
+}
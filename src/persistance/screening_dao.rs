@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::{Connection, PgPool};
+
+use crate::models::{AuditContext, DBError, PostType, ScreeningDecision};
+
+use super::flags_dao::quarantine_post;
+use super::parse_uuid;
+
+/// A screening decision to log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreeningRecord {
+    pub author_uuid: Option<String>,
+    pub target_type: PostType,
+    /// The post created, unless it was rejected.
+    pub target_uuid: Option<String>,
+    pub decision: ScreeningDecision,
+    pub rule: Option<String>,
+    pub reason: Option<String>,
+    pub content_hash: String,
+}
+
+#[async_trait]
+pub trait ScreeningDao {
+    /// How many posts the author made within `window`, quarantined ones included.
+    async fn count_recent_posts(&self, author_uuid: String, window: Duration) -> Result<i64, DBError>;
+    /// How many posts with this content hash were made within `window`, by anyone.
+    async fn count_repeats(&self, content_hash: String, window: Duration) -> Result<i64, DBError>;
+    /// Logs a decision. A quarantined post is also flagged as spam on behalf of screening, which
+    /// hides it until a moderator declines the flag; `hide_after` is the usual spam flag threshold.
+    async fn record_decision(&self, record: ScreeningRecord, hide_after: i64, context: AuditContext) -> Result<(), DBError>;
+}
+
+pub struct ScreeningDaoImpl {
+    db: PgPool,
+}
+
+impl ScreeningDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl ScreeningDao for ScreeningDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM screening_decisions"))]
+    async fn count_recent_posts(&self, author_uuid: String, window: Duration) -> Result<i64, DBError> {
+        let author = parse_uuid(&author_uuid, "user ID")?;
+
+        sqlx::query_scalar!(
+                r#"SELECT COUNT(*) AS "count!" FROM screening_decisions
+                   WHERE author_uuid = $1 AND decision <> 'rejected' AND created_at > CURRENT_TIMESTAMP - make_interval(secs => $2)"#,
+                author,
+                window.as_secs_f64()
+            )
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM screening_decisions"))]
+    async fn count_repeats(&self, content_hash: String, window: Duration) -> Result<i64, DBError> {
+        sqlx::query_scalar!(
+                r#"SELECT COUNT(*) AS "count!" FROM screening_decisions
+                   WHERE content_hash = $1 AND decision <> 'rejected' AND created_at > CURRENT_TIMESTAMP - make_interval(secs => $2)"#,
+                content_hash,
+                window.as_secs_f64()
+            )
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO screening_decisions", decision = record.decision.as_str()))]
+    async fn record_decision(&self, record: ScreeningRecord, hide_after: i64, context: AuditContext) -> Result<(), DBError> {
+        let author = record.author_uuid.as_deref().map(|author| parse_uuid(author, "user ID")).transpose()?;
+        let target = record.target_uuid.as_deref().map(|target| parse_uuid(target, "post ID")).transpose()?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        sqlx::query!(
+                "INSERT INTO screening_decisions (author_uuid, target_type, target_uuid, decision, rule, reason, content_hash)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                author,
+                record.target_type.as_str(),
+                target,
+                record.decision.as_str(),
+                record.rule,
+                record.reason,
+                record.content_hash
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if let (ScreeningDecision::Quarantined, Some(target)) = (record.decision, target) {
+            let details = format!(
+                "Quarantined by {}: {}",
+                record.rule.as_deref().unwrap_or("screening"),
+                record.reason.as_deref().unwrap_or("no reason given")
+            );
+            quarantine_post(&mut tx, &context, record.target_type, target, details, hide_after).await?;
+        }
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))
+    }
+}
@@ -0,0 +1,230 @@
+use async_trait::async_trait;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::types::Uuid;
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::models::{
+    postgres_error_codes, AuditContext, DBError, PostType, SuggestedEdit, SuggestedEditDetail, SuggestedEditReview, SuggestedEditStatus,
+};
+
+use super::outbox_dao::append_event;
+use super::parse_uuid;
+use super::unit_of_work::Executor;
+
+struct SuggestedEditRow {
+    edit_id: i64,
+    target_type: String,
+    target_uuid: Uuid,
+    suggested_by: Uuid,
+    title: Option<String>,
+    description: Option<String>,
+    content: Option<String>,
+    summary: Option<String>,
+    base_version: i64,
+    status: String,
+    reviewed_by: Option<Uuid>,
+    review_comment: Option<String>,
+    reviewed_at: Option<OffsetDateTime>,
+    created_at: OffsetDateTime,
+}
+
+impl TryFrom<SuggestedEditRow> for SuggestedEditDetail {
+    type Error = DBError;
+
+    fn try_from(row: SuggestedEditRow) -> Result<Self, DBError> {
+        Ok(SuggestedEditDetail {
+            edit_id: row.edit_id,
+            target_type: PostType::parse(&row.target_type)
+                .ok_or_else(|| DBError::Other(format!("Unknown suggested edit target type: {}", row.target_type).into()))?,
+            target_uuid: row.target_uuid.to_string(),
+            suggested_by: row.suggested_by.to_string(),
+            title: row.title,
+            description: row.description,
+            content: row.content,
+            summary: row.summary,
+            base_version: row.base_version,
+            status: SuggestedEditStatus::parse(&row.status)
+                .ok_or_else(|| DBError::Other(format!("Unknown suggested edit status: {}", row.status).into()))?,
+            reviewed_by: row.reviewed_by.map(|uuid| uuid.to_string()),
+            review_comment: row.review_comment,
+            reviewed_at: row.reviewed_at.map(|reviewed_at| reviewed_at.to_string()),
+            created_at: row.created_at.to_string(),
+        })
+    }
+}
+
+fn require_actor(context: &AuditContext) -> Result<Uuid, DBError> {
+    let actor = context
+        .actor_uuid
+        .as_deref()
+        .ok_or_else(|| DBError::Forbidden("Only signed-in users can suggest or review edits".to_owned()))?;
+    parse_uuid(actor, "user ID")
+}
+
+/// The author and version of a post that isn't deleted, or `None`.
+async fn find_post(conn: &mut PgConnection, target_type: PostType, target_uuid: Uuid) -> Result<Option<(Option<Uuid>, i64)>, DBError> {
+    let post = match target_type {
+        PostType::Question => sqlx::query!(
+                "SELECT author_uuid, version FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL",
+                target_uuid
+            )
+            .fetch_optional(conn)
+            .await
+            .map(|post| post.map(|post| (post.author_uuid, post.version))),
+        PostType::Answer => sqlx::query!(
+                "SELECT a.author_uuid, a.version FROM answers a JOIN questions q ON q.question_uuid = a.question_uuid
+                 WHERE a.answer_uuid = $1 AND a.deleted_at IS NULL AND q.deleted_at IS NULL",
+                target_uuid
+            )
+            .fetch_optional(conn)
+            .await
+            .map(|post| post.map(|post| (post.author_uuid, post.version))),
+    };
+    post.map_err(|e| DBError::Other(Box::new(e)))
+}
+
+#[async_trait]
+pub trait SuggestedEditsDao {
+    /// Suggests an edit to someone else's question or answer as the actor, against its current
+    /// version. A post has at most one pending suggestion at a time.
+    async fn create_suggested_edit(&self, edit: SuggestedEdit, context: AuditContext) -> Result<SuggestedEditDetail, DBError>;
+    /// Up to `limit` pending suggested edits, oldest first.
+    async fn get_queue(&self, limit: i64) -> Result<Vec<SuggestedEditDetail>, DBError>;
+    /// Locks a pending suggested edit until the end of the unit of work, so it is reviewed once.
+    async fn lock_pending(&self, edit_id: i64) -> Result<SuggestedEditDetail, DBError>;
+    /// Records the actor's review of a pending suggested edit. Approving one does not apply it: that
+    /// is up to the caller, in the same unit of work.
+    async fn record_review(&self, edit_id: i64, review: SuggestedEditReview, context: AuditContext) -> Result<SuggestedEditDetail, DBError>;
+}
+
+pub struct SuggestedEditsDaoImpl {
+    db: Executor,
+}
+
+impl SuggestedEditsDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db: Executor::Pool(db) }
+    }
+
+    pub(crate) fn with_executor(db: Executor) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl SuggestedEditsDao for SuggestedEditsDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO suggested_edits", target_uuid = %edit.target_uuid))]
+    async fn create_suggested_edit(&self, edit: SuggestedEdit, context: AuditContext) -> Result<SuggestedEditDetail, DBError> {
+        let uuid = parse_uuid(&edit.target_uuid, &format!("{} ID", edit.target_type.as_str()))?;
+        let editor = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let (author, version) = find_post(&mut tx, edit.target_type, uuid)
+            .await?
+            .ok_or_else(|| DBError::NotFound(format!("No {} with ID {}", edit.target_type.as_str(), edit.target_uuid)))?;
+        if author == Some(editor) {
+            return Err(DBError::Forbidden("Edit your own posts directly instead".to_owned()));
+        }
+
+        let row = sqlx::query_as!(
+                SuggestedEditRow,
+                "INSERT INTO suggested_edits (target_type, target_uuid, suggested_by, title, description, content, summary, base_version)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                 RETURNING *",
+                edit.target_type.as_str(),
+                uuid,
+                editor,
+                edit.title,
+                edit.description,
+                edit.content,
+                edit.summary,
+                version
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e: sqlx::Error| match e {
+                sqlx::Error::Database(e) => {
+                    if let Some(code) = e.code() {
+                        if code.eq(postgres_error_codes::UNIQUE_VIOLATION) {
+                            return DBError::Conflict(format!("{} {} already has a pending suggested edit", edit.target_type.as_str(), edit.target_uuid));
+                        }
+                    }
+                    DBError::Other(Box::new(e))
+                }
+                e => DBError::Other(Box::new(e)),
+            })?;
+
+        let detail = SuggestedEditDetail::try_from(row)?;
+        append_event(&mut tx, edit.target_type.as_str(), uuid, "suggested_edit.created", &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM suggested_edits"))]
+    async fn get_queue(&self, limit: i64) -> Result<Vec<SuggestedEditDetail>, DBError> {
+        let mut conn = self.db.acquire().await?;
+
+        let rows = sqlx::query_as!(
+                SuggestedEditRow,
+                "SELECT * FROM suggested_edits WHERE status = 'pending' ORDER BY created_at, edit_id LIMIT $1",
+                limit
+            )
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        rows.into_iter().map(SuggestedEditDetail::try_from).collect()
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM suggested_edits", edit_id))]
+    async fn lock_pending(&self, edit_id: i64) -> Result<SuggestedEditDetail, DBError> {
+        let mut conn = self.db.acquire().await?;
+
+        let row = sqlx::query_as!(SuggestedEditRow, "SELECT * FROM suggested_edits WHERE edit_id = $1 FOR UPDATE", edit_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No suggested edit with ID {}", edit_id)))?;
+        if row.status != SuggestedEditStatus::Pending.as_str() {
+            return Err(DBError::Conflict(format!("Suggested edit {} was already reviewed", edit_id)));
+        }
+
+        SuggestedEditDetail::try_from(row)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE suggested_edits", edit_id))]
+    async fn record_review(&self, edit_id: i64, review: SuggestedEditReview, context: AuditContext) -> Result<SuggestedEditDetail, DBError> {
+        let reviewer = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let row = sqlx::query_as!(
+                SuggestedEditRow,
+                "UPDATE suggested_edits SET status = $2, reviewed_by = $3, review_comment = $4, reviewed_at = CURRENT_TIMESTAMP
+                 WHERE edit_id = $1 AND status = 'pending'
+                 RETURNING *",
+                edit_id,
+                review.status.as_str(),
+                reviewer,
+                review.comment
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::Conflict(format!("Suggested edit {} was already reviewed", edit_id)))?;
+
+        let target_uuid = row.target_uuid;
+        let detail = SuggestedEditDetail::try_from(row)?;
+        let event_type = format!("suggested_edit.{}", detail.status.as_str());
+        append_event(&mut tx, detail.target_type.as_str(), target_uuid, &event_type, &detail).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(detail)
+    }
+}
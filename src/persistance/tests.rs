@@ -1,8 +1,41 @@
+use sqlx::PgPool;
+
+use crate::{
+    models::{AuditContext, User},
+    persistance::users_dao::{UsersDao, UsersDaoImpl},
+};
+
+/// A well-formed user ID for tests that fail before reaching the database.
+const TEST_USER: &str = "b33bcde3-33bc-3333-b33c-3bcd3b3c33dd";
+
+/// Creates a user to act as `deleted_by` and returns its ID.
+async fn test_user(pool: &PgPool) -> String {
+    UsersDaoImpl::new(pool.clone())
+        .create_user(
+            User {
+                username: "test user".to_owned(),
+            },
+            "0".repeat(64),
+        )
+        .await
+        .expect("Error creating test user")
+        .user_uuid
+}
+
+fn as_user(user_uuid: impl Into<String>) -> AuditContext {
+    AuditContext {
+        actor_uuid: Some(user_uuid.into()),
+        request_id: None,
+    }
+}
+
+#[allow(clippy::cmp_owned, clippy::len_zero, clippy::get_first)]
 mod answers_tests {
     use sqlx::PgPool;
 
+    use super::{as_user, test_user, TEST_USER};
     use crate::{
-        models::{Answer, DBError, Question},
+        models::{Answer, AuditContext, DBError, IfMatch, Question},
         persistance::{
             answers_dao::{AnswersDao, AnswersDaoImpl},
             questions_dao::{QuestionsDao, QuestionsDaoImpl},
@@ -14,10 +47,13 @@ mod answers_tests {
         let answer_doa = AnswersDaoImpl::new(pool);
 
         let result = answer_doa
-            .create_answer(Answer {
-                question_uuid: "malformed".to_owned(),
-                content: "test content".to_owned(),
-            })
+            .create_answer(
+                Answer {
+                    question_uuid: "malformed".to_owned(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await;
 
         if result.is_ok() {
@@ -42,10 +78,13 @@ mod answers_tests {
         let answer_doa = AnswersDaoImpl::new(pool);
 
         let result = answer_doa
-            .create_answer(Answer {
-                question_uuid: "a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned(),
-                content: "test content".to_owned(),
-            })
+            .create_answer(
+                Answer {
+                    question_uuid: "a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await;
 
         if result.is_ok() {
@@ -74,10 +113,13 @@ mod answers_tests {
         pool.close().await;
 
         let result = answer_doa
-            .create_answer(Answer {
-                question_uuid: "a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned(),
-                content: "test content".to_owned(),
-            })
+            .create_answer(
+                Answer {
+                    question_uuid: "a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await;
 
         if result.is_ok() {
@@ -103,18 +145,24 @@ mod answers_tests {
         let answer_doa = AnswersDaoImpl::new(pool);
 
         let result = question_doa
-            .create_question(Question {
-                title: "test title".to_owned(),
-                description: "test description".to_owned(),
-            })
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("{:?}", e))?;
 
         let result = answer_doa
-            .create_answer(Answer {
-                question_uuid: result.question_uuid,
-                content: "test content".to_owned(),
-            })
+            .create_answer(
+                Answer {
+                    question_uuid: result.question_uuid,
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("{:?}", e))?;
 
@@ -129,7 +177,7 @@ mod answers_tests {
     async fn delete_answer_should_fail_with_malformed_uuid(pool: PgPool) -> Result<(), String> {
         let answer_doa = AnswersDaoImpl::new(pool);
 
-        let result = answer_doa.delete_answer("malformed".to_owned()).await;
+        let result = answer_doa.delete_answer("malformed".to_owned(), IfMatch::Any, true, as_user(TEST_USER)).await;
 
         if result.is_ok() {
             return Err(format!(
@@ -157,7 +205,7 @@ mod answers_tests {
         pool.close().await;
 
         let result = answer_doa
-            .delete_answer("a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned())
+            .delete_answer("a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned(), IfMatch::Any, true, as_user(TEST_USER))
             .await;
 
         if result.is_ok() {
@@ -183,28 +231,34 @@ mod answers_tests {
         let answer_doa = AnswersDaoImpl::new(pool.clone());
 
         let question = question_doa
-            .create_question(Question {
-                title: "test title".to_owned(),
-                description: "test description".to_owned(),
-            })
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("Error creating question:\n\t{:?}", e))?;
 
         let result = answer_doa
-            .create_answer(Answer {
-                question_uuid: question.question_uuid.clone(),
-                content: "test content".to_owned(),
-            })
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("Error creating answer:\n\t{:?}", e))?;
 
         answer_doa
-            .delete_answer(result.answer_uuid)
+            .delete_answer(result.answer_uuid, IfMatch::Any, true, as_user(test_user(&pool).await))
             .await
             .map_err(|e| format!("Error deleting answer:\n\t{:?}", e))?;
 
         let results = answer_doa
-            .get_answers(question.question_uuid.clone())
+            .get_answers(question.question_uuid.clone(), false)
             .await
             .map_err(|e| format!("Error getting answers:\n\t{:?}", e))?;
 
@@ -219,7 +273,7 @@ mod answers_tests {
     async fn get_answers_should_fail_with_malformed_uuid(pool: PgPool) -> Result<(), String> {
         let answer_doa = AnswersDaoImpl::new(pool);
 
-        let result = answer_doa.get_answers("malformed".to_owned()).await;
+        let result = answer_doa.get_answers("malformed".to_owned(), false).await;
 
         if result.is_ok() {
             return Err(format!(
@@ -245,7 +299,7 @@ mod answers_tests {
         pool.close().await;
 
         let result = answer_doa
-            .get_answers("a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned())
+            .get_answers("a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned(), false)
             .await;
 
         if result.is_ok() {
@@ -271,23 +325,29 @@ mod answers_tests {
         let answer_doa = AnswersDaoImpl::new(pool);
 
         let question = question_doa
-            .create_question(Question {
-                title: "test title".to_owned(),
-                description: "test description".to_owned(),
-            })
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("{:?}", e))?;
 
         let result = answer_doa
-            .create_answer(Answer {
-                question_uuid: question.question_uuid.clone(),
-                content: "test content".to_owned(),
-            })
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("{:?}", e))?;
 
         let results = answer_doa
-            .get_answers(question.question_uuid.clone())
+            .get_answers(question.question_uuid.clone(), false)
             .await
             .map_err(|e| format!("{:?}", e))?;
 
@@ -301,13 +361,128 @@ mod answers_tests {
 
         Ok(())
     }
+
+    #[sqlx::test]
+    async fn answers_of_deleted_question_should_be_hidden(pool: PgPool) -> Result<(), String> {
+        let question_doa = QuestionsDaoImpl::new(pool.clone());
+        let answer_doa = AnswersDaoImpl::new(pool.clone());
+
+        let question = question_doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        answer_doa
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        question_doa
+            .delete_question(question.question_uuid.clone(), IfMatch::Any, true, as_user(test_user(&pool).await))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let visible = answer_doa
+            .get_answers(question.question_uuid.clone(), false)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let all = answer_doa
+            .get_answers(question.question_uuid.clone(), true)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if !visible.is_empty() || all.len() != 1 {
+            return Err(format!("Unexpected answers: {:?} / {:?}", visible, all));
+        }
+
+        let result = answer_doa
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid,
+                    content: "late answer".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await;
+
+        if let Err(DBError::InvalidUUID(_)) = result {
+            Ok(())
+        } else {
+            Err(format!("Expected answering a deleted question to fail but got: {:?}", result))
+        }
+    }
+
+    #[sqlx::test]
+    async fn restore_answer_should_succeed(pool: PgPool) -> Result<(), String> {
+        let question_doa = QuestionsDaoImpl::new(pool.clone());
+        let answer_doa = AnswersDaoImpl::new(pool.clone());
+
+        let question = question_doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let answer = answer_doa
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        answer_doa
+            .delete_answer(answer.answer_uuid.clone(), IfMatch::Any, true, as_user(test_user(&pool).await))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        answer_doa
+            .restore_answer(answer.answer_uuid, IfMatch::Any, AuditContext::default())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let results = answer_doa
+            .get_answers(question.question_uuid, false)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if results.len() != 1 {
+            return Err("Answer was not restored".to_owned());
+        }
+
+        Ok(())
+    }
 }
 
+
+#[allow(clippy::cmp_owned, clippy::len_zero, clippy::get_first)]
 mod questions_tests {
+    use std::time::Duration;
+
     use sqlx::PgPool;
 
+    use super::{as_user, test_user, TEST_USER};
     use crate::{
-        models::{DBError, Question},
+        models::{AuditContext, DBError, IfMatch, Question, QuestionFilter},
         persistance::questions_dao::{QuestionsDao, QuestionsDaoImpl},
     };
 
@@ -320,10 +495,13 @@ mod questions_tests {
         pool.close().await;
 
         let result = doa
-            .create_question(Question {
-                title: "test title".to_owned(),
-                description: "test description".to_owned(),
-            })
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await;
 
         if result.is_ok() {
@@ -348,10 +526,13 @@ mod questions_tests {
         let doa = QuestionsDaoImpl::new(pool);
 
         let result = doa
-            .create_question(Question {
-                title: "test title".to_owned(),
-                description: "test description".to_owned(),
-            })
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("{:?}", e))?;
 
@@ -368,7 +549,7 @@ mod questions_tests {
     async fn delete_question_should_fail_with_malformed_uuid(pool: PgPool) -> Result<(), String> {
         let doa = QuestionsDaoImpl::new(pool);
 
-        let result = doa.delete_question("malformed".to_owned()).await;
+        let result = doa.delete_question("malformed".to_owned(), IfMatch::Any, true, as_user(TEST_USER)).await;
 
         if result.is_ok() {
             return Err(format!(
@@ -396,7 +577,7 @@ mod questions_tests {
         pool.close().await;
 
         let result = doa
-            .delete_question("a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned())
+            .delete_question("a22abcd2-22ab-2222-a22b-2abc2a2b22cc".to_owned(), IfMatch::Any, true, as_user(TEST_USER))
             .await;
 
         if result.is_ok() {
@@ -418,21 +599,24 @@ mod questions_tests {
 
     #[sqlx::test]
     async fn delete_question_should_succeed(pool: PgPool) -> Result<(), String> {
-        let doa = QuestionsDaoImpl::new(pool);
+        let doa = QuestionsDaoImpl::new(pool.clone());
 
         let result = doa
-            .create_question(Question {
-                title: "test title".to_owned(),
-                description: "test description".to_owned(),
-            })
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("Error creating question:\n\t{:?}", e))?;
 
-        doa.delete_question(result.question_uuid)
+        doa.delete_question(result.question_uuid, IfMatch::Any, true, as_user(test_user(&pool).await))
             .await
             .map_err(|e| format!("Error deleting question:\n\t{:?}", e))?;
 
-        let results = doa.get_questions().await.map_err(|e| format!("{:?}", e))?;
+        let results = doa.get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
 
         if results.len() != 0 {
             return Err("Question was not deleted".to_owned());
@@ -449,7 +633,7 @@ mod questions_tests {
 
         pool.close().await;
 
-        let result = doa.get_questions().await;
+        let result = doa.get_questions(QuestionFilter::default()).await;
 
         if result.is_ok() {
             return Err(format!(
@@ -473,14 +657,17 @@ mod questions_tests {
         let doa = QuestionsDaoImpl::new(pool);
 
         let result = doa
-            .create_question(Question {
-                title: "test title".to_owned(),
-                description: "test description".to_owned(),
-            })
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
             .await
             .map_err(|e| format!("{:?}", e))?;
 
-        let results = doa.get_questions().await.map_err(|e| format!("{:?}", e))?;
+        let results = doa.get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
 
         if results.len() != 1 {
             return Err("Incorrect number of results returned.".to_owned());
@@ -492,4 +679,2096 @@ mod questions_tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[sqlx::test]
+    async fn deleted_question_should_be_listed_only_when_requested(pool: PgPool) -> Result<(), String> {
+        let doa = QuestionsDaoImpl::new(pool.clone());
+
+        let result = doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        doa.delete_question(result.question_uuid.clone(), IfMatch::Any, true, as_user(test_user(&pool).await))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let results = doa.get_questions(QuestionFilter { include_deleted: true, ..QuestionFilter::default() }).await.map_err(|e| format!("{:?}", e))?;
+
+        if results.len() != 1 || results[0].deleted_at.is_none() {
+            return Err("Deleted question should be listed with its deletion time.".to_owned());
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn restore_question_should_succeed(pool: PgPool) -> Result<(), String> {
+        let doa = QuestionsDaoImpl::new(pool.clone());
+
+        let result = doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        doa.delete_question(result.question_uuid.clone(), IfMatch::Any, true, as_user(test_user(&pool).await))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        doa.restore_question(result.question_uuid.clone(), IfMatch::Any, AuditContext::default())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let results = doa.get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+
+        if results.len() != 1 || results[0].deleted_at.is_some() {
+            return Err("Question was not restored".to_owned());
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn create_question_should_store_long_descriptions(pool: PgPool) -> Result<(), String> {
+        let description = "x".repeat(30_000);
+
+        let question = QuestionsDaoImpl::new(pool)
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: description.clone(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if question.description != description {
+            return Err(format!("Expected the description to be kept whole, got {} characters", question.description.len()));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn delete_question_should_check_if_match(pool: PgPool) -> Result<(), String> {
+        let doa = QuestionsDaoImpl::new(pool.clone());
+        let user = test_user(&pool).await;
+
+        let question = doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let stale = doa
+            .delete_question(question.question_uuid.clone(), IfMatch::Versions(vec![question.version + 1]), true, as_user(user.clone()))
+            .await;
+        if !matches!(stale, Err(DBError::PreconditionFailed(_))) {
+            return Err(format!("Expected a precondition failure but got: {:?}", stale));
+        }
+
+        doa.delete_question(question.question_uuid.clone(), IfMatch::Versions(vec![question.version]), true, as_user(user.clone()))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        // The delete was a change, so the old version no longer matches, and nothing exists to delete
+        let repeated = doa
+            .delete_question(question.question_uuid.clone(), IfMatch::Exists, true, as_user(user))
+            .await;
+        if !matches!(repeated, Err(DBError::PreconditionFailed(_))) {
+            return Err(format!("Expected a precondition failure but got: {:?}", repeated));
+        }
+
+        let questions = doa.get_questions(QuestionFilter { include_deleted: true, ..QuestionFilter::default() }).await.map_err(|e| format!("{:?}", e))?;
+        if questions.len() != 1 || questions[0].version != question.version + 1 {
+            return Err(format!("Expected the version to be bumped: {:?}", questions));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn restore_question_should_fail_if_not_deleted(pool: PgPool) -> Result<(), String> {
+        let doa = QuestionsDaoImpl::new(pool);
+
+        let result = doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let result = doa.restore_question(result.question_uuid, IfMatch::Any, AuditContext::default()).await;
+
+        if let Err(DBError::NotFound(_)) = result {
+            Ok(())
+        } else {
+            Err(format!("Expected a not found error but got: {:?}", result))
+        }
+    }
+
+    #[sqlx::test]
+    async fn purge_deleted_should_only_remove_expired_questions(pool: PgPool) -> Result<(), String> {
+        let doa = QuestionsDaoImpl::new(pool.clone());
+        let user = test_user(&pool).await;
+
+        for title in ["expired", "recent"] {
+            let result = doa
+                .create_question(
+                    Question {
+                        title: title.to_owned(),
+                        description: "test description".to_owned(),
+                    },
+                    AuditContext::default(),
+                )
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+            doa.delete_question(result.question_uuid, IfMatch::Any, true, as_user(user.clone()))
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+        }
+
+        sqlx::query("UPDATE questions SET deleted_at = deleted_at - INTERVAL '2 days' WHERE title = 'expired'")
+            .execute(&pool)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let purged = doa
+            .purge_deleted(Duration::from_secs(24 * 60 * 60))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let remaining = doa.get_questions(QuestionFilter { include_deleted: true, ..QuestionFilter::default() }).await.map_err(|e| format!("{:?}", e))?;
+
+        if purged != 1 || remaining.len() != 1 || remaining[0].title != "recent" {
+            return Err(format!("Unexpected purge result: {} purged, {:?} remaining", purged, remaining));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn find_similar_should_rank_visible_questions_by_similarity(pool: PgPool) -> Result<(), String> {
+        let doa = QuestionsDaoImpl::new(pool.clone());
+        let user = test_user(&pool).await;
+
+        let mut uuids = vec![];
+        for (title, description) in [
+            ("How do I parse JSON in Rust", "serde_json fails on nested objects"),
+            ("How do I parse JSON in Rust quickly", "Looking for the fastest crate"),
+            ("Parse JSON in Rust with serde", "serde_json fails on nested objects"),
+            ("Why is my Postgres query slow", "It scans the whole table"),
+        ] {
+            let question = Question {
+                title: title.to_owned(),
+                description: description.to_owned(),
+            };
+            let detail = doa.create_question(question, AuditContext::default()).await.map_err(|e| format!("{:?}", e))?;
+            uuids.push(detail.question_uuid);
+        }
+        doa.delete_question(uuids[1].clone(), IfMatch::Any, true, as_user(user.clone())).await.map_err(|e| format!("{:?}", e))?;
+
+        let question = Question {
+            title: "How to parse JSON in Rust".to_owned(),
+            description: "serde_json fails on nested objects".to_owned(),
+        };
+        let similar = doa.find_similar(question, 0.3, 5).await.map_err(|e| format!("{:?}", e))?;
+
+        let found: Vec<&str> = similar.iter().map(|q| q.question_uuid.as_str()).collect();
+        if found != [uuids[0].as_str(), uuids[2].as_str()] || similar[0].similarity <= similar[1].similarity {
+            return Err(format!("Expected the two visible JSON questions, most similar first, got {:?}", similar));
+        }
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn questions_should_be_returned_with_their_rendered_description(pool: PgPool) -> Result<(), String> {
+        let doa = QuestionsDaoImpl::new(pool.clone());
+        let question = Question {
+            title: "test title".to_owned(),
+            description: "**Why** <img src=x onerror=alert(1)>".to_owned(),
+        };
+
+        let created = doa.create_question(question, AuditContext::default()).await.map_err(|e| format!("{:?}", e))?;
+        let listed = doa.get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+
+        for detail in [&created, &listed[0]] {
+            if detail.body.body_markdown != detail.description || detail.body.body_html != "<p><strong>Why</strong> <img src=\"x\"></p>\n" {
+                return Err(format!("Unexpected rendering: {:?}", detail.body));
+            }
+        }
+        Ok(())
+    }
+}
+
+mod audit_tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use super::test_user;
+    use crate::{
+        models::{Answer, AuditContext, AuditFilter, IfMatch, Question},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            audit_dao::{AuditDao, AuditDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+        },
+    };
+
+    fn filter() -> AuditFilter {
+        AuditFilter {
+            limit: 100,
+            ..Default::default()
+        }
+    }
+
+    #[sqlx::test]
+    async fn mutations_should_be_audited(pool: PgPool) -> Result<(), String> {
+        let questions_doa = QuestionsDaoImpl::new(pool.clone());
+        let audit_doa = AuditDaoImpl::new(pool.clone());
+        let context = AuditContext {
+            actor_uuid: Some(test_user(&pool).await),
+            request_id: Some("req-1".to_owned()),
+        };
+
+        let question = questions_doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                context.clone(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        questions_doa
+            .delete_question(question.question_uuid.clone(), IfMatch::Any, true, context.clone())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        // Already deleted: nothing changes, so nothing is audited
+        questions_doa
+            .delete_question(question.question_uuid.clone(), IfMatch::Any, true, context.clone())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let events = audit_doa.get_events(filter()).await.map_err(|e| format!("{:?}", e))?;
+        let actions: Vec<&str> = events.iter().map(|event| event.action.as_str()).collect();
+        if actions != ["question.deleted", "question.created"] {
+            return Err(format!("Unexpected audit events: {:?}", events));
+        }
+
+        let deleted = &events[0];
+        if deleted.actor_uuid != context.actor_uuid
+            || deleted.request_id.as_deref() != Some("req-1")
+            || deleted.target_uuid != question.question_uuid
+            || deleted.before.as_ref().map(|before| before["deleted_at"].is_null()) != Some(true)
+            || deleted.after.as_ref().map(|after| after["deleted_at"].is_null()) != Some(false)
+        {
+            return Err(format!("Unexpected delete event: {:?}", deleted));
+        }
+
+        if events[1].before.is_some() || events[1].after.as_ref().map(|after| after["title"].clone()) != Some("test title".into()) {
+            return Err(format!("Unexpected create event: {:?}", events[1]));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn get_events_should_filter(pool: PgPool) -> Result<(), String> {
+        let questions_doa = QuestionsDaoImpl::new(pool.clone());
+        let audit_doa = AuditDaoImpl::new(pool.clone());
+        let user = test_user(&pool).await;
+
+        for actor_uuid in [None, Some(user.clone())] {
+            questions_doa
+                .create_question(
+                    Question {
+                        title: "test title".to_owned(),
+                        description: "test description".to_owned(),
+                    },
+                    AuditContext {
+                        actor_uuid,
+                        request_id: None,
+                    },
+                )
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+        }
+
+        let by_actor = audit_doa
+            .get_events(AuditFilter {
+                actor_uuid: Some(user.clone()),
+                ..filter()
+            })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let newest = audit_doa
+            .get_events(AuditFilter { limit: 1, ..filter() })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let older = audit_doa
+            .get_events(AuditFilter {
+                before_event_id: Some(newest[0].event_id),
+                ..filter()
+            })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if by_actor.len() != 1 || by_actor[0].actor_uuid != Some(user) {
+            return Err(format!("Unexpected events by actor: {:?}", by_actor));
+        }
+        if newest.len() != 1 || older.len() != 1 || older[0].actor_uuid.is_some() {
+            return Err(format!("Unexpected paging: {:?} then {:?}", newest, older));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn purges_should_be_audited(pool: PgPool) -> Result<(), String> {
+        let questions_doa = QuestionsDaoImpl::new(pool.clone());
+        let audit_doa = AuditDaoImpl::new(pool.clone());
+
+        let question = questions_doa
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        // Still live when its question is purged
+        let answer = AnswersDaoImpl::new(pool.clone())
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        questions_doa
+            .delete_question(question.question_uuid.clone(), IfMatch::Any, true, AuditContext::default())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        questions_doa
+            .purge_deleted(Duration::ZERO)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let events = audit_doa
+            .get_events(AuditFilter {
+                action: Some("question.purged".to_owned()),
+                ..filter()
+            })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if events.len() != 1 || events[0].target_uuid != question.question_uuid || events[0].after.is_some() {
+            return Err(format!("Unexpected purge events: {:?}", events));
+        }
+
+        let events = audit_doa
+            .get_events(AuditFilter {
+                action: Some("answer.purged".to_owned()),
+                ..filter()
+            })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if events.len() != 1 || events[0].target_uuid != answer.answer_uuid || events[0].after.is_some() {
+            return Err(format!("Expected the answer purged with its question to be audited: {:?}", events));
+        }
+
+        let published = sqlx::query_scalar!("SELECT aggregate_uuid FROM outbox_events WHERE event_type = 'answer.purged'")
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        if published.iter().map(ToString::to_string).collect::<Vec<_>>() != [answer.answer_uuid] {
+            return Err(format!("Expected the answer purged with its question to be published: {:?}", published));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn audit_events_should_be_append_only(pool: PgPool) -> Result<(), String> {
+        QuestionsDaoImpl::new(pool.clone())
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        for statement in [
+            "UPDATE audit_events SET action = 'tampered'",
+            "DELETE FROM audit_events",
+            "TRUNCATE audit_events",
+        ] {
+            if sqlx::query(statement).execute(&pool).await.is_ok() {
+                return Err(format!("Expected {} to fail", statement));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+mod unit_of_work_tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        models::{Answer, AuditContext, DBError, Question, QuestionFilter},
+        persistance::{
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+            unit_of_work::{TransactionManager, TransactionManagerImpl, UnitOfWork},
+        },
+    };
+
+    /// Creates a question with an answer through `uow`, returning the question ID.
+    async fn create_question_with_answer(uow: &(dyn UnitOfWork + Send + Sync)) -> Result<String, DBError> {
+        let question = uow
+            .questions()
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await?;
+        uow.answers()
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await?;
+
+        Ok(question.question_uuid)
+    }
+
+    async fn count(pool: &PgPool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .expect("Error counting rows")
+    }
+
+    #[sqlx::test]
+    async fn commit_should_persist_all_changes(pool: PgPool) -> Result<(), String> {
+        let uow = TransactionManagerImpl::new(pool.clone()).begin().await.map_err(|e| format!("{:?}", e))?;
+
+        create_question_with_answer(uow.as_ref()).await.map_err(|e| format!("{:?}", e))?;
+
+        // Not visible outside the unit of work until it commits
+        if count(&pool, "questions").await != 0 {
+            return Err("Uncommitted question is visible".to_owned());
+        }
+
+        uow.commit().await.map_err(|e| format!("{:?}", e))?;
+
+        let counts = (count(&pool, "questions").await, count(&pool, "answers").await, count(&pool, "audit_events").await);
+        if counts != (1, 1, 2) {
+            return Err(format!("Unexpected question, answer and audit event counts: {:?}", counts));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn dropping_should_roll_back_all_changes(pool: PgPool) -> Result<(), String> {
+        let uow = TransactionManagerImpl::new(pool.clone()).begin().await.map_err(|e| format!("{:?}", e))?;
+
+        let question_uuid = create_question_with_answer(uow.as_ref()).await.map_err(|e| format!("{:?}", e))?;
+        let seen = uow.questions().get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+        drop(uow);
+
+        if seen.len() != 1 || seen[0].question_uuid != question_uuid {
+            return Err(format!("Unit of work did not see its own question: {:?}", seen));
+        }
+
+        let counts = (count(&pool, "questions").await, count(&pool, "answers").await, count(&pool, "audit_events").await);
+        if counts != (0, 0, 0) {
+            return Err(format!("Changes were not rolled back: {:?}", counts));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn error_should_roll_back_earlier_steps(pool: PgPool) -> Result<(), String> {
+        let transactions = TransactionManagerImpl::new(pool.clone());
+
+        let result = async {
+            let uow = transactions.begin().await?;
+            uow.questions()
+                .create_question(
+                    Question {
+                        title: "test title".to_owned(),
+                        description: "test description".to_owned(),
+                    },
+                    AuditContext::default(),
+                )
+                .await?;
+            uow.answers()
+                .create_answer(
+                    Answer {
+                        question_uuid: "b068cd2f-edac-479e-98f1-c5f91008dcbd".to_owned(),
+                        content: "test content".to_owned(),
+                    },
+                    AuditContext::default(),
+                )
+                .await?;
+            uow.commit().await
+        }
+        .await;
+
+        if !matches!(result, Err(DBError::InvalidUUID(_))) {
+            return Err(format!("Expected an invalid UUID error but got: {:?}", result));
+        }
+
+        let questions = QuestionsDaoImpl::new(pool).get_questions(QuestionFilter { include_deleted: true, ..QuestionFilter::default() }).await.map_err(|e| format!("{:?}", e))?;
+        if !questions.is_empty() {
+            return Err(format!("Question was not rolled back: {:?}", questions));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn failed_step_should_not_poison_the_unit_of_work(pool: PgPool) -> Result<(), String> {
+        let uow = TransactionManagerImpl::new(pool.clone()).begin().await.map_err(|e| format!("{:?}", e))?;
+
+        let failed = uow
+            .answers()
+            .create_answer(
+                Answer {
+                    question_uuid: "b068cd2f-edac-479e-98f1-c5f91008dcbd".to_owned(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await;
+        if failed.is_ok() {
+            return Err("Expected the answer to an unknown question to fail".to_owned());
+        }
+
+        // The failed step only rolled back to its own savepoint
+        create_question_with_answer(uow.as_ref()).await.map_err(|e| format!("{:?}", e))?;
+        uow.commit().await.map_err(|e| format!("{:?}", e))?;
+
+        if count(&pool, "answers").await != 1 {
+            return Err("Expected the later answer to be committed".to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+mod idempotency_tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use crate::persistance::idempotency_dao::{IdempotencyDao, IdempotencyDaoImpl, IdempotentRequest};
+
+    #[sqlx::test]
+    async fn claim_should_return_the_earlier_request(pool: PgPool) -> Result<(), String> {
+        let doa = IdempotencyDaoImpl::new(pool);
+        let ttl = Duration::from_secs(60);
+
+        let first = doa
+            .claim("ip:1".to_owned(), "key".to_owned(), "a".repeat(64), ttl)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let pending = doa
+            .claim("ip:1".to_owned(), "key".to_owned(), "b".repeat(64), ttl)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        doa.complete("ip:1".to_owned(), "key".to_owned(), serde_json::json!({"ok": true}))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let completed = doa
+            .claim("ip:1".to_owned(), "key".to_owned(), "a".repeat(64), ttl)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let other_client = doa
+            .claim("ip:2".to_owned(), "key".to_owned(), "a".repeat(64), ttl)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let expected_pending = IdempotentRequest {
+            request_hash: "a".repeat(64),
+            response: None,
+        };
+        let expected_completed = IdempotentRequest {
+            request_hash: "a".repeat(64),
+            response: Some(serde_json::json!({"ok": true})),
+        };
+        if first.is_some() || pending != Some(expected_pending) || completed != Some(expected_completed) || other_client.is_some() {
+            return Err(format!("Unexpected claims: {:?}, {:?}, {:?}, {:?}", first, pending, completed, other_client));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn expired_keys_should_be_reclaimed_and_purged(pool: PgPool) -> Result<(), String> {
+        let doa = IdempotencyDaoImpl::new(pool);
+
+        for key in ["expired", "released"] {
+            doa.claim("ip:1".to_owned(), key.to_owned(), "a".repeat(64), Duration::ZERO)
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+        }
+        doa.release("ip:1".to_owned(), "released".to_owned())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let reclaimed = doa
+            .claim("ip:1".to_owned(), "expired".to_owned(), "b".repeat(64), Duration::ZERO)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let purged = doa.purge_expired().await.map_err(|e| format!("{:?}", e))?;
+
+        if reclaimed.is_some() || purged != 1 {
+            return Err(format!("Unexpected reclaim {:?} or purge count {}", reclaimed, purged));
+        }
+
+        Ok(())
+    }
+}
+
+mod question_events_tests {
+    use sqlx::PgPool;
+
+    use super::reputation_tests::{thread, users};
+    use super::as_user;
+    use crate::{
+        models::{Answer, AuditContext, DBError, IfMatch, PostType, Question},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            question_events_dao::{QuestionEventsDao, QuestionEventsDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+            votes_dao::{VotesDao, VotesDaoImpl},
+        },
+    };
+
+    #[sqlx::test]
+    async fn mutations_should_be_recorded_as_question_events(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let user_uuid = users[0].clone();
+        let questions_dao = QuestionsDaoImpl::new(pool.clone());
+        let answers_dao = AnswersDaoImpl::new(pool.clone());
+        let dao = QuestionEventsDaoImpl::new(pool.clone());
+
+        let question = questions_dao
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let before = dao
+            .latest_event_id(question.question_uuid.clone())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let answer = answers_dao
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        answers_dao
+            .delete_answer(answer.answer_uuid.clone(), IfMatch::Any, true, as_user(&user_uuid))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        answers_dao
+            .restore_answer(answer.answer_uuid.clone(), IfMatch::Any, as_user(&user_uuid))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let events = dao
+            .get_events(question.question_uuid.clone(), before, 100)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let types: Vec<&str> = events.iter().map(|event| event.event_type.as_str()).collect();
+        if types != ["answer.created", "answer.deleted", "answer.restored"] {
+            return Err(format!("Unexpected events after {}: {:?}", before, events));
+        }
+
+        let created = dao
+            .get_events(question.question_uuid.clone(), 0, 1)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        if created.len() != 1 || created[0].event_id != before || created[0].event_type != "question.created" {
+            return Err(format!("Unexpected first event: {:?}", created));
+        }
+        if events[0].payload != serde_json::to_value(&answer).map_err(|e| format!("{:?}", e))? {
+            return Err(format!("Unexpected payload: {:?}", events[0].payload));
+        }
+
+        let latest = dao
+            .latest_event_id(question.question_uuid.clone())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let rest = dao
+            .get_events(question.question_uuid, events[0].event_id, 1)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        if latest != events[2].event_id || rest.len() != 1 || rest[0] != events[1] {
+            return Err(format!("Unexpected latest event {} or page {:?}", latest, rest));
+        }
+
+        let (question_uuid, answers) = thread(&pool, &users[0], &[&users[1]]).await;
+        let votes = VotesDaoImpl::new(pool);
+        let before = dao
+            .latest_event_id(question_uuid.clone())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        votes
+            .vote(PostType::Question, question_uuid.clone(), 1, as_user(&users[1]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        votes
+            .vote(PostType::Answer, answers[0].clone(), 1, as_user(&users[0]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        votes
+            .accept_answer(answers[0].clone(), as_user(&users[0]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let events = dao
+            .get_events(question_uuid, before, 100)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let types: Vec<&str> = events.iter().map(|event| event.event_type.as_str()).collect();
+        if types != ["question.voted", "answer.voted", "answer.accepted"] {
+            return Err(format!("Unexpected vote events after {}: {:?}", before, events));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn latest_event_id_should_fail_for_missing_question(pool: PgPool) -> Result<(), String> {
+        let dao = QuestionEventsDaoImpl::new(pool);
+
+        let missing = dao.latest_event_id("b068cd2f-edac-479e-a6ea-b4ad8bd6b2ac".to_owned()).await;
+        let malformed = dao.latest_event_id("not a uuid".to_owned()).await;
+
+        match (missing, malformed) {
+            (Err(DBError::NotFound(_)), Err(DBError::InvalidUUID(_))) => Ok(()),
+            (missing, malformed) => Err(format!("Unexpected results: {:?}, {:?}", missing, malformed)),
+        }
+    }
+}
+
+mod webhooks_tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use crate::{
+        models::{Answer, AuditContext, DBError, Question, Webhook, WebhookDeliveryFilter},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+            webhooks_dao::{WebhooksDao, WebhooksDaoImpl},
+        },
+    };
+
+    #[sqlx::test]
+    async fn deliveries_should_be_queued_for_subscribed_events(pool: PgPool) -> Result<(), String> {
+        let dao = WebhooksDaoImpl::new(pool.clone());
+        let webhook = dao
+            .create_subscription(Webhook {
+                url: "http://localhost/hook".to_owned(),
+                event_types: vec!["answer.created".to_owned()],
+                secret: "0123456789abcdef".to_owned(),
+            })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let question = QuestionsDaoImpl::new(pool.clone())
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        AnswersDaoImpl::new(pool)
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid,
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let deliveries = dao
+            .get_deliveries(WebhookDeliveryFilter {
+                subscription_uuid: webhook.subscription_uuid.clone(),
+                status: None,
+                before_delivery_id: None,
+                limit: 10,
+            })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        if deliveries.len() != 1 || deliveries[0].event_type != "answer.created" {
+            return Err(format!("Unexpected deliveries: {:?}", deliveries));
+        }
+
+        let first = dao.claim_due(10, Duration::from_secs(60)).await.map_err(|e| format!("{:?}", e))?;
+        let second = dao.claim_due(10, Duration::from_secs(60)).await.map_err(|e| format!("{:?}", e))?;
+        if first.len() != 1 || first[0].delivery_id != deliveries[0].delivery_id || !second.is_empty() {
+            return Err(format!("Unexpected claims: {:?}, {:?}", first, second));
+        }
+
+        dao.delete_subscription(webhook.subscription_uuid.clone())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        match dao.delete_subscription(webhook.subscription_uuid).await {
+            Err(DBError::NotFound(_)) => Ok(()),
+            other => Err(format!("Unexpected result deleting twice: {:?}", other)),
+        }
+    }
+}
+
+mod outbox_tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use super::{as_user, test_user};
+    use crate::{
+        models::{Answer, AuditContext, IfMatch, Question},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            outbox_dao::{OutboxDao, OutboxDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+        },
+    };
+
+    async fn claim_all(dao: &OutboxDaoImpl) -> Result<Vec<String>, String> {
+        let claimed = dao
+            .claim_due(10, Duration::ZERO)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        for pending in &claimed {
+            dao.mark_published(pending.event.event_id)
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+        }
+        Ok(claimed.into_iter().map(|pending| pending.event.event_type).collect())
+    }
+
+    #[sqlx::test]
+    async fn writes_should_append_events_published_in_order_per_aggregate(pool: PgPool) -> Result<(), String> {
+        let user_uuid = test_user(&pool).await;
+        let questions_dao = QuestionsDaoImpl::new(pool.clone());
+        let answers_dao = AnswersDaoImpl::new(pool.clone());
+        let dao = OutboxDaoImpl::new(pool);
+
+        let question = questions_dao
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let answer = answers_dao
+            .create_answer(
+                Answer {
+                    question_uuid: question.question_uuid.clone(),
+                    content: "test content".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        answers_dao
+            .delete_answer(answer.answer_uuid.clone(), IfMatch::Any, true, as_user(&user_uuid))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        answers_dao
+            .purge_deleted(Duration::ZERO)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        // Only the oldest unpublished event of each aggregate can be claimed
+        let batches = [claim_all(&dao).await?, claim_all(&dao).await?, claim_all(&dao).await?, claim_all(&dao).await?];
+        let expected: [Vec<&str>; 4] = [
+            vec!["question.created", "answer.created"],
+            vec!["answer.deleted"],
+            vec!["answer.purged"],
+            vec![],
+        ];
+        if batches != expected {
+            return Err(format!("Unexpected outbox batches: {:?}", batches));
+        }
+
+        let purged = dao
+            .purge_published(Duration::ZERO)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        if purged != 4 {
+            return Err(format!("Expected 4 published events to be removed, got {}", purged));
+        }
+
+        Ok(())
+    }
+}
+
+mod jobs_tests {
+    use std::time::Duration;
+
+    use sqlx::types::time::OffsetDateTime;
+    use sqlx::PgPool;
+
+    use crate::persistance::jobs_dao::{JobsDao, JobsDaoImpl, NewJob};
+
+    fn job() -> NewJob {
+        NewJob {
+            kind: "test".to_owned(),
+            payload: serde_json::json!({}),
+            run_at: None,
+            max_attempts: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn claimed_jobs_should_be_skipped_until_their_lease_runs_out(pool: PgPool) -> Result<(), String> {
+        let dao = JobsDaoImpl::new(pool);
+        let job_id = dao.enqueue(job()).await.map_err(|e| format!("{:?}", e))?;
+        dao.enqueue(NewJob {
+            run_at: Some(OffsetDateTime::now_utc() + Duration::from_secs(3600)),
+            ..job()
+        })
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+
+        let claimed = dao.claim_due(10, Duration::ZERO).await.map_err(|e| format!("{:?}", e))?;
+        if claimed.iter().map(|job| (job.job_id, job.attempts)).collect::<Vec<_>>() != [(job_id, 1)] {
+            return Err(format!("Expected only job {} to be claimed, got {:?}", job_id, claimed));
+        }
+
+        // An expired lease: the job is abandoned and can be claimed again
+        let claimed = dao.claim_due(10, Duration::from_secs(3600)).await.map_err(|e| format!("{:?}", e))?;
+        if claimed.iter().map(|job| (job.job_id, job.attempts)).collect::<Vec<_>>() != [(job_id, 2)] {
+            return Err(format!("Expected job {} to be claimed again, got {:?}", job_id, claimed));
+        }
+
+        let claimed = dao.claim_due(10, Duration::from_secs(3600)).await.map_err(|e| format!("{:?}", e))?;
+        if !claimed.is_empty() {
+            return Err(format!("Expected leased job to be skipped, got {:?}", claimed));
+        }
+
+        dao.complete(job_id).await.map_err(|e| format!("{:?}", e))?;
+        let purged = dao.purge_finished(Duration::ZERO).await.map_err(|e| format!("{:?}", e))?;
+        if purged != 1 {
+            return Err(format!("Expected 1 finished job to be removed, got {}", purged));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn scheduled_jobs_should_be_enqueued_once_per_run(pool: PgPool) -> Result<(), String> {
+        let dao = JobsDaoImpl::new(pool.clone());
+        let next_run_at = OffsetDateTime::now_utc() + Duration::from_secs(3600);
+
+        // A new schedule waits for its first run
+        let enqueued = dao.enqueue_scheduled("test", next_run_at, job()).await.map_err(|e| format!("{:?}", e))?;
+        if enqueued.is_some() {
+            return Err("Expected a new schedule not to enqueue a job".to_owned());
+        }
+
+        sqlx::query!("UPDATE job_schedules SET next_run_at = CURRENT_TIMESTAMP WHERE name = 'test'")
+            .execute(&pool)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let first = dao.enqueue_scheduled("test", next_run_at, job()).await.map_err(|e| format!("{:?}", e))?;
+        let second = dao.enqueue_scheduled("test", next_run_at, job()).await.map_err(|e| format!("{:?}", e))?;
+        if first.is_none() || second.is_some() {
+            return Err(format!("Expected a single job to be enqueued, got {:?} then {:?}", first, second));
+        }
+
+        Ok(())
+    }
+}
+
+mod reputation_tests {
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use crate::{
+        models::{Answer, DBError, PostType, Question, QuestionFilter, User},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+            reputation_dao::{ReputationDao, ReputationDaoImpl},
+            users_dao::{UsersDao, UsersDaoImpl},
+            votes_dao::{VotesDao, VotesDaoImpl},
+        },
+    };
+
+    /// Creates users `0..count` and returns their IDs.
+    pub(super) async fn users(pool: &PgPool, count: usize) -> Vec<String> {
+        let mut users = vec![];
+        for n in 0..count {
+            let user = UsersDaoImpl::new(pool.clone())
+                .create_user(User { username: format!("user {}", n) }, format!("{:064}", n))
+                .await
+                .expect("Error creating test user");
+            users.push(user.user_uuid);
+        }
+        users
+    }
+
+    /// Creates a question asked by `asker` and an answer to it by each of `answerers`.
+    pub(super) async fn thread(pool: &PgPool, asker: &str, answerers: &[&str]) -> (String, Vec<String>) {
+        let question = QuestionsDaoImpl::new(pool.clone())
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                as_user(asker),
+            )
+            .await
+            .expect("Error creating test question");
+        let mut answers = vec![];
+        for answerer in answerers {
+            let answer = AnswersDaoImpl::new(pool.clone())
+                .create_answer(
+                    Answer {
+                        question_uuid: question.question_uuid.clone(),
+                        content: "test content".to_owned(),
+                    },
+                    as_user(*answerer),
+                )
+                .await
+                .expect("Error creating test answer");
+            answers.push(answer.answer_uuid);
+        }
+        (question.question_uuid, answers)
+    }
+
+    pub(super) async fn reputation(dao: &ReputationDaoImpl, user_uuid: &str) -> Result<(i32, Vec<(String, i32)>), String> {
+        let total = dao.get_reputation(user_uuid.to_owned()).await.map_err(|e| format!("{:?}", e))?;
+        let history = dao
+            .get_history(user_uuid.to_owned(), None, 100)
+            .await
+            .map_err(|e| format!("{:?}", e))?
+            .into_iter()
+            .rev()
+            .map(|entry| (entry.reason, entry.amount))
+            .collect();
+        Ok((total, history))
+    }
+
+    fn entries(entries: &[(&str, i32)]) -> Vec<(String, i32)> {
+        entries.iter().map(|(reason, amount)| (reason.to_string(), *amount)).collect()
+    }
+
+    #[sqlx::test]
+    async fn votes_should_be_credited_and_reversed(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let (author, voter) = (&users[0], &users[1]);
+        let (_, answers) = thread(&pool, voter, &[author]).await;
+        let votes = VotesDaoImpl::new(pool.clone());
+        let reputation_dao = ReputationDaoImpl::new(pool.clone());
+
+        for value in [1, 1, -1, 0] {
+            votes
+                .vote(PostType::Answer, answers[0].clone(), value, as_user(voter))
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+        }
+
+        // Voting twice the same way is a no-op; changing or retracting a vote reverses it first
+        let expected = entries(&[
+            ("answer_upvoted", 10),
+            ("vote_retracted", -10),
+            ("answer_downvoted", -2),
+            ("vote_retracted", 2),
+        ]);
+        if reputation(&reputation_dao, author).await? != (1, expected.clone()) {
+            return Err(format!("Expected the author to have 1 reputation from {:?}, got {:?}", expected, reputation(&reputation_dao, author).await?));
+        }
+        let expected = entries(&[("downvote_cast", -1), ("vote_retracted", 1)]);
+        if reputation(&reputation_dao, voter).await? != (1, expected.clone()) {
+            return Err(format!("Expected the voter to have 1 reputation from {:?}, got {:?}", expected, reputation(&reputation_dao, voter).await?));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn vote_should_fail_on_own_and_missing_posts(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 1).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        let votes = VotesDaoImpl::new(pool.clone());
+
+        let result = votes.vote(PostType::Question, question, 1, as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected a self-vote to be forbidden, got {:?}", result));
+        }
+
+        let missing = "2f8ab7d6-52c8-4a3b-9a0a-0b5b5e1f0e0d".to_owned();
+        let result = votes.vote(PostType::Answer, missing, 1, as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::NotFound(_))) {
+            return Err(format!("Expected a vote on a missing answer to fail, got {:?}", result));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn accepting_another_answer_should_move_the_credit(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 3).await;
+        let (asker, first, second) = (&users[0], &users[1], &users[2]);
+        let (question, answers) = thread(&pool, asker, &[first, second]).await;
+        let votes = VotesDaoImpl::new(pool.clone());
+        let reputation_dao = ReputationDaoImpl::new(pool.clone());
+
+        let result = votes.accept_answer(answers[0].clone(), as_user(first)).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected only the asker to be able to accept, got {:?}", result));
+        }
+
+        votes.accept_answer(answers[0].clone(), as_user(asker)).await.map_err(|e| format!("{:?}", e))?;
+        votes.accept_answer(answers[1].clone(), as_user(asker)).await.map_err(|e| format!("{:?}", e))?;
+
+        let expected = (1, entries(&[("answer_accepted", 15), ("accept_retracted", -15)]));
+        if reputation(&reputation_dao, first).await? != expected {
+            return Err(format!("Expected {:?}, got {:?}", expected, reputation(&reputation_dao, first).await?));
+        }
+        let expected = (16, entries(&[("answer_accepted", 15)]));
+        if reputation(&reputation_dao, second).await? != expected {
+            return Err(format!("Expected {:?}, got {:?}", expected, reputation(&reputation_dao, second).await?));
+        }
+        let expected = (3, entries(&[("accepted_answer", 2), ("accept_retracted", -2), ("accepted_answer", 2)]));
+        if reputation(&reputation_dao, asker).await? != expected {
+            return Err(format!("Expected {:?}, got {:?}", expected, reputation(&reputation_dao, asker).await?));
+        }
+
+        let questions = QuestionsDaoImpl::new(pool.clone()).get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+        let accepted = questions.iter().find(|q| q.question_uuid == question).and_then(|q| q.accepted_answer_uuid.clone());
+        if accepted.as_ref() != Some(&answers[1]) {
+            return Err(format!("Expected answer {} to be accepted, got {:?}", answers[1], accepted));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn recompute_should_fix_drifted_totals(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let (_, answers) = thread(&pool, &users[1], &[&users[0]]).await;
+        VotesDaoImpl::new(pool.clone())
+            .vote(PostType::Answer, answers[0].clone(), 1, as_user(&users[1]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        sqlx::query!("UPDATE users SET reputation = 500")
+            .execute(&pool)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let dao = ReputationDaoImpl::new(pool);
+
+        let fixed = dao.recompute().await.map_err(|e| format!("{:?}", e))?;
+
+        if fixed != 2 {
+            return Err(format!("Expected 2 totals to be fixed, got {}", fixed));
+        }
+        for (user, expected) in [(&users[0], 11), (&users[1], 1)] {
+            let total = dao.get_reputation(user.clone()).await.map_err(|e| format!("{:?}", e))?;
+            if total != expected {
+                return Err(format!("Expected user {} to have {} reputation, got {}", user, expected, total));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+mod badges_tests {
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use super::reputation_tests::{thread, users};
+    use crate::{
+        models::{BadgeLevel, DBError, PostType},
+        persistance::{
+            badges_dao::{BadgeProgress, BadgesDao, BadgesDaoImpl},
+            votes_dao::{VotesDao, VotesDaoImpl},
+        },
+    };
+
+    #[sqlx::test]
+    async fn get_progress_should_count_posts_and_votes(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 3).await;
+        let (question, answers) = thread(&pool, &users[0], &[&users[1], &users[1]]).await;
+        let votes = VotesDaoImpl::new(pool.clone());
+        for voter in [&users[0], &users[2]] {
+            votes.vote(PostType::Answer, answers[0].clone(), 1, as_user(voter)).await.map_err(|e| format!("{:?}", e))?;
+        }
+        votes.vote(PostType::Answer, answers[1].clone(), -1, as_user(&users[2])).await.map_err(|e| format!("{:?}", e))?;
+        votes.vote(PostType::Question, question, 1, as_user(&users[1])).await.map_err(|e| format!("{:?}", e))?;
+        votes.accept_answer(answers[1].clone(), as_user(&users[0])).await.map_err(|e| format!("{:?}", e))?;
+        let dao = BadgesDaoImpl::new(pool);
+
+        let progress = dao.get_progress(users[1].clone()).await.map_err(|e| format!("{:?}", e))?;
+
+        let expected = BadgeProgress {
+            answers: 2,
+            accepted_answers: 1,
+            upvoted_answers: 1,
+            votes_cast: 1,
+            ..BadgeProgress::default()
+        };
+        if progress != expected {
+            return Err(format!("Expected {:?}, got {:?}", expected, progress));
+        }
+        let author = dao.get_author(PostType::Answer, answers[0].clone()).await.map_err(|e| format!("{:?}", e))?;
+        if author.as_ref() != Some(&users[1]) {
+            return Err(format!("Expected the answer to be by {}, got {:?}", users[1], author));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn award_should_be_idempotent(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 1).await;
+        let dao = BadgesDaoImpl::new(pool);
+
+        let badges = dao.get_user_badges(users[0].clone()).await.map_err(|e| format!("{:?}", e))?;
+        if !badges.is_empty() {
+            return Err(format!("Expected no badges yet, got {:?}", badges));
+        }
+
+        let first = dao.award(users[0].clone(), "first_vote", "Supporter", BadgeLevel::Bronze).await.map_err(|e| format!("{:?}", e))?;
+        let second = dao.award(users[0].clone(), "first_vote", "Supporter", BadgeLevel::Bronze).await.map_err(|e| format!("{:?}", e))?;
+
+        if (first, second) != (true, false) {
+            return Err(format!("Expected the badge to be awarded once, got {:?}", (first, second)));
+        }
+        let badges = dao.get_user_badges(users[0].clone()).await.map_err(|e| format!("{:?}", e))?;
+        if badges.iter().map(|badge| (badge.name.as_str(), badge.level)).collect::<Vec<_>>() != [("first_vote", BadgeLevel::Bronze)] {
+            return Err(format!("Expected the first_vote badge, got {:?}", badges));
+        }
+        let awarded = dao.count_awards().await.map_err(|e| format!("{:?}", e))?;
+        if awarded.get("first_vote") != Some(&1) {
+            return Err(format!("Expected first_vote to be awarded once, got {:?}", awarded));
+        }
+
+        let result = dao.get_user_badges("2f8ab7d6-52c8-4a3b-9a0a-0b5b5e1f0e0d".to_owned()).await;
+        if !matches!(result, Err(DBError::NotFound(_))) {
+            return Err(format!("Expected NotFound for a missing user, got {:?}", result));
+        }
+
+        Ok(())
+    }
+}
+
+mod users_tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        models::{DBError, Role, User},
+        persistance::users_dao::{UsersDao, UsersDaoImpl},
+    };
+
+    #[sqlx::test]
+    async fn create_user_should_succeed(pool: PgPool) -> Result<(), String> {
+        let doa = UsersDaoImpl::new(pool);
+
+        let result = doa
+            .create_user(User { username: "alice".to_owned() }, "a".repeat(64))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if result.username != "alice" || result.role != Role::User {
+            return Err(format!("Unexpected user: {:?}", result));
+        }
+
+        let found = doa
+            .get_user_by_api_key_hash("a".repeat(64))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        if found != Some(result) {
+            return Err(format!("User was not found by API key: {:?}", found));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn create_user_should_fail_with_duplicate_username(pool: PgPool) -> Result<(), String> {
+        let doa = UsersDaoImpl::new(pool);
+
+        doa.create_user(User { username: "alice".to_owned() }, "a".repeat(64))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let result = doa
+            .create_user(User { username: "alice".to_owned() }, "b".repeat(64))
+            .await;
+
+        if let Err(DBError::Conflict(_)) = result {
+            Ok(())
+        } else {
+            Err(format!("Expected a conflict error but got: {:?}", result))
+        }
+    }
+
+    #[sqlx::test]
+    async fn get_user_by_api_key_hash_should_return_none_for_unknown_key(pool: PgPool) -> Result<(), String> {
+        let doa = UsersDaoImpl::new(pool);
+
+        let result = doa
+            .get_user_by_api_key_hash("c".repeat(64))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        match result {
+            None => Ok(()),
+            Some(user) => Err(format!("Expected no user but got: {:?}", user)),
+        }
+    }
+}
+
+mod bounties_tests {
+    use std::time::Duration;
+
+    use sqlx::types::Uuid;
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use super::reputation_tests::{reputation, thread, users};
+    use crate::{
+        models::{Answer, Bounty, BountyStatus, DBError, IfMatch, PostType, QuestionFilter},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            bounties_dao::{BountiesDao, BountiesDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+            reputation_dao::{record_reputation, ReputationChange, ReputationDaoImpl},
+            votes_dao::{VotesDao, VotesDaoImpl},
+        },
+    };
+
+    const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    /// Gives the user enough reputation to offer bounties, through the ledger.
+    async fn grant(pool: &PgPool, user_uuid: &str, question_uuid: &str) {
+        let mut conn = pool.acquire().await.expect("Error acquiring a connection");
+        record_reputation(&mut conn, ReputationChange {
+            user_uuid: Uuid::parse_str(user_uuid).unwrap(),
+            reason: "granted",
+            amount: 200,
+            target_type: "question",
+            target_uuid: Uuid::parse_str(question_uuid).unwrap(),
+            actor_uuid: None,
+        }).await.expect("Error granting reputation");
+    }
+
+    fn bounty(question_uuid: &str, amount: i32) -> Bounty {
+        Bounty {
+            question_uuid: question_uuid.to_owned(),
+            amount,
+        }
+    }
+
+    #[sqlx::test]
+    async fn offer_bounty_should_escrow_reputation(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        thread(&pool, &users[0], &[]).await;
+        grant(&pool, &users[0], &question).await;
+        let dao = BountiesDaoImpl::new(pool.clone());
+
+        let result = dao.offer_bounty(bounty(&question, 100), WEEK, as_user(&users[1])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected only the asker to offer a bounty, got {:?}", result));
+        }
+        let offered = dao.offer_bounty(bounty(&question, 100), WEEK, as_user(&users[0])).await.map_err(|e| format!("{:?}", e))?;
+        let result = dao.offer_bounty(bounty(&question, 50), WEEK, as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected a second open bounty to conflict, got {:?}", result));
+        }
+
+        let (total, _) = reputation(&ReputationDaoImpl::new(pool.clone()), &users[0]).await?;
+        if (offered.status, total) != (BountyStatus::Open, 101) {
+            return Err(format!("Expected 100 reputation to be escrowed, got {:?} and {}", offered, total));
+        }
+        let featured = QuestionsDaoImpl::new(pool)
+            .get_questions(QuestionFilter { open_bounty: true, ..QuestionFilter::default() })
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        match featured.as_slice() {
+            [featured] if featured.question_uuid == question && featured.bounty.as_ref().map(|bounty| bounty.amount) == Some(100) => Ok(()),
+            _ => Err(format!("Expected only the question with a bounty, got {:?}", featured)),
+        }
+    }
+
+    #[sqlx::test]
+    async fn award_bounty_should_credit_the_answer(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let (question, answers) = thread(&pool, &users[0], &[&users[1]]).await;
+        grant(&pool, &users[0], &question).await;
+        let dao = BountiesDaoImpl::new(pool.clone());
+        dao.offer_bounty(bounty(&question, 100), WEEK, as_user(&users[0])).await.map_err(|e| format!("{:?}", e))?;
+
+        let result = dao.award_bounty(answers[0].clone(), as_user(&users[1])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected only the offerer to award the bounty, got {:?}", result));
+        }
+        let awarded = dao.award_bounty(answers[0].clone(), as_user(&users[0])).await.map_err(|e| format!("{:?}", e))?;
+
+        let (total, history) = reputation(&ReputationDaoImpl::new(pool.clone()), &users[1]).await?;
+        if awarded.status != BountyStatus::Awarded || awarded.awarded_amount != Some(100) || total != 101 {
+            return Err(format!("Expected the answerer to get 100 reputation, got {:?}, {} and {:?}", awarded, total, history));
+        }
+        let result = dao.award_bounty(answers[0].clone(), as_user(&users[0])).await;
+        match result {
+            Err(DBError::NotFound(_)) => Ok(()),
+            _ => Err(format!("Expected no open bounty left, got {:?}", result)),
+        }
+    }
+
+    #[sqlx::test]
+    async fn delete_question_should_refund_the_bounty(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 1).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        grant(&pool, &users[0], &question).await;
+        BountiesDaoImpl::new(pool.clone())
+            .offer_bounty(bounty(&question, 150), WEEK, as_user(&users[0]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        QuestionsDaoImpl::new(pool.clone())
+            .delete_question(question, IfMatch::Any, false, as_user(&users[0]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let (total, history) = reputation(&ReputationDaoImpl::new(pool), &users[0]).await?;
+        let reasons: Vec<&str> = history.iter().map(|(reason, _)| reason.as_str()).collect();
+        if total != 201 || reasons != ["granted", "bounty_offered", "bounty_refunded"] {
+            return Err(format!("Expected the bounty to be refunded, got {} and {:?}", total, history));
+        }
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn expire_due_should_award_half_to_the_best_new_answer(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 3).await;
+        let (question, old_answers) = thread(&pool, &users[0], &[&users[1]]).await;
+        grant(&pool, &users[0], &question).await;
+        let dao = BountiesDaoImpl::new(pool.clone());
+        dao.offer_bounty(bounty(&question, 100), WEEK, as_user(&users[0])).await.map_err(|e| format!("{:?}", e))?;
+        let new_answer = AnswersDaoImpl::new(pool.clone())
+            .create_answer(
+                Answer {
+                    question_uuid: question.clone(),
+                    content: "test content".to_owned(),
+                },
+                as_user(&users[2]),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        // The older answer scores higher, but was posted before the bounty
+        let votes = VotesDaoImpl::new(pool.clone());
+        for voter in [&users[0], &users[2]] {
+            votes.vote(PostType::Answer, old_answers[0].clone(), 1, as_user(voter)).await.map_err(|e| format!("{:?}", e))?;
+        }
+        votes.vote(PostType::Answer, new_answer.answer_uuid, 1, as_user(&users[1])).await.map_err(|e| format!("{:?}", e))?;
+
+        if dao.expire_due(1).await.map_err(|e| format!("{:?}", e))? != 0 {
+            return Err("Expected no bounty to be due yet".to_owned());
+        }
+        sqlx::query!("UPDATE bounties SET expires_at = CURRENT_TIMESTAMP")
+            .execute(&pool)
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let closed = dao.expire_due(1).await.map_err(|e| format!("{:?}", e))?;
+
+        let (total, history) = reputation(&ReputationDaoImpl::new(pool), &users[2]).await?;
+        if closed != 1 || total != 61 || history.last() != Some(&("bounty_awarded".to_owned(), 50)) {
+            return Err(format!("Expected half of the bounty to go to the new answer, got {}, {} and {:?}", closed, total, history));
+        }
+        Ok(())
+    }
+}
+
+mod closing_tests {
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use super::reputation_tests::{thread, users};
+    use crate::{
+        models::{Answer, CloseReason, CloseVote, DBError, QuestionFilter},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            closing_dao::{ClosingDao, ClosingDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+        },
+    };
+
+    fn vote(question_uuid: &str, reason: CloseReason, duplicate_of: Option<&str>) -> CloseVote {
+        CloseVote {
+            question_uuid: question_uuid.to_owned(),
+            reason,
+            duplicate_of: duplicate_of.map(str::to_owned),
+        }
+    }
+
+    async fn answer(pool: &PgPool, question_uuid: &str, user_uuid: &str) -> Result<(), DBError> {
+        let answer = Answer {
+            question_uuid: question_uuid.to_owned(),
+            content: "test content".to_owned(),
+        };
+        AnswersDaoImpl::new(pool.clone()).create_answer(answer, as_user(user_uuid)).await.map(|_| ())
+    }
+
+    #[sqlx::test]
+    async fn vote_to_close_should_close_for_the_most_common_reason(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 4).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        let (original, _) = thread(&pool, &users[0], &[]).await;
+        let dao = ClosingDaoImpl::new(pool.clone());
+
+        let votes = [
+            vote(&question, CloseReason::Duplicate, Some(&original)),
+            vote(&question, CloseReason::OffTopic, None),
+            vote(&question, CloseReason::Duplicate, Some(&original)),
+        ];
+        let mut results = vec![];
+        for (vote, voter) in votes.into_iter().zip(&users[1..]) {
+            let result = dao.vote_to_close(vote, 3, false, as_user(voter)).await.map_err(|e| format!("{:?}", e))?;
+            results.push((result.votes, result.closed));
+        }
+        if results != [(1, false), (2, false), (3, true)] {
+            return Err(format!("Expected the third vote to close the question, got {:?}", results));
+        }
+
+        let questions = QuestionsDaoImpl::new(pool.clone()).get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+        let closed = questions.iter().find(|q| q.question_uuid == question).ok_or("Question not listed")?;
+        if closed.closed_reason != Some(CloseReason::Duplicate) || closed.duplicate_of.as_ref() != Some(&original) {
+            return Err(format!("Expected the question to be closed as a duplicate, got {:?}", closed));
+        }
+        let result = dao.vote_to_close(vote(&question, CloseReason::OffTopic, None), 3, false, as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected closing a closed question to conflict, got {:?}", result));
+        }
+        match answer(&pool, &question, &users[1]).await {
+            Err(DBError::QuestionClosed(_)) => Ok(()),
+            result => Err(format!("Expected closed questions to refuse answers, got {:?}", result)),
+        }
+    }
+
+    #[sqlx::test]
+    async fn binding_votes_should_close_and_reopen_right_away(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        let dao = ClosingDaoImpl::new(pool.clone());
+
+        let result = dao.vote_to_reopen(question.clone(), 3, true, as_user(&users[1])).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected reopening an open question to conflict, got {:?}", result));
+        }
+        let closed = dao.vote_to_close(vote(&question, CloseReason::NeedsDetails, None), 3, true, as_user(&users[1])).await.map_err(|e| format!("{:?}", e))?;
+        let reopened = dao.vote_to_reopen(question.clone(), 3, true, as_user(&users[1])).await.map_err(|e| format!("{:?}", e))?;
+        if !closed.closed || reopened.closed {
+            return Err(format!("Expected the question to be closed then reopened, got {:?} and {:?}", closed, reopened));
+        }
+
+        answer(&pool, &question, &users[1]).await.map_err(|e| format!("{:?}", e))
+    }
+}
+
+mod flags_tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use super::reputation_tests::{thread, users};
+    use crate::{
+        models::{DBError, Flag, FlagReason, FlagStatus, PostType, QuestionFilter},
+        persistance::{
+            flags_dao::{FlagsDao, FlagsDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+        },
+    };
+
+    const CLAIM_TIMEOUT: Duration = Duration::from_secs(1800);
+
+    fn spam(question_uuid: &str) -> Flag {
+        Flag {
+            target_type: PostType::Question,
+            target_uuid: question_uuid.to_owned(),
+            reason: FlagReason::Spam,
+            details: None,
+        }
+    }
+
+    async fn hidden(pool: &PgPool, question_uuid: &str) -> Result<bool, String> {
+        let questions = QuestionsDaoImpl::new(pool.clone()).get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+        Ok(!questions.iter().any(|q| q.question_uuid == question_uuid))
+    }
+
+    #[sqlx::test]
+    async fn spam_flags_should_hide_posts_until_declined(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 5).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        let dao = FlagsDaoImpl::new(pool.clone());
+
+        let mut flags = vec![];
+        for flagger in &users[1..3] {
+            flags.push(dao.create_flag(spam(&question), 2, as_user(flagger)).await.map_err(|e| format!("{:?}", e))?);
+        }
+        if !hidden(&pool, &question).await? {
+            return Err("Expected two spam flags to hide the question".to_owned());
+        }
+        let result = dao.create_flag(spam(&question), 2, as_user(&users[1])).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected flagging twice to conflict, got {:?}", result));
+        }
+        let result = dao.create_flag(spam(&question), 2, as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected flagging one's own post to be forbidden, got {:?}", result));
+        }
+
+        dao.resolve_flag(flags[0].flag_id, FlagStatus::Declined, 2, CLAIM_TIMEOUT, as_user(&users[4])).await.map_err(|e| format!("{:?}", e))?;
+        if hidden(&pool, &question).await? {
+            return Err("Expected declining a spam flag to show the question again".to_owned());
+        }
+        dao.resolve_flag(flags[1].flag_id, FlagStatus::Helpful, 2, CLAIM_TIMEOUT, as_user(&users[4])).await.map_err(|e| format!("{:?}", e))?;
+        let result = dao.resolve_flag(flags[1].flag_id, FlagStatus::Declined, 2, CLAIM_TIMEOUT, as_user(&users[4])).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected resolving a flag twice to conflict, got {:?}", result));
+        }
+
+        let declined = dao.get_stats(users[1].clone()).await.map_err(|e| format!("{:?}", e))?;
+        let helpful = dao.get_stats(users[2].clone()).await.map_err(|e| format!("{:?}", e))?;
+        match ((declined.declined, declined.accuracy), (helpful.helpful, helpful.accuracy)) {
+            ((1, Some(0.0)), (1, Some(1.0))) => Ok(()),
+            stats => Err(format!("Expected the outcomes in the flaggers' stats, got {:?}", stats)),
+        }
+    }
+
+    #[sqlx::test]
+    async fn claimed_flags_should_be_left_to_their_moderator(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 4).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        let (first, second) = (&users[2], &users[3]);
+        let dao = FlagsDaoImpl::new(pool.clone());
+        let flag = dao.create_flag(spam(&question), 3, as_user(&users[1])).await.map_err(|e| format!("{:?}", e))?;
+
+        dao.claim_flag(flag.flag_id, CLAIM_TIMEOUT, as_user(first)).await.map_err(|e| format!("{:?}", e))?;
+        let result = dao.claim_flag(flag.flag_id, CLAIM_TIMEOUT, as_user(second)).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected claiming a claimed flag to conflict, got {:?}", result));
+        }
+        let result = dao.resolve_flag(flag.flag_id, FlagStatus::Helpful, 3, CLAIM_TIMEOUT, as_user(second)).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected resolving another moderator's flag to conflict, got {:?}", result));
+        }
+        let queue = dao.get_queue(second.clone(), CLAIM_TIMEOUT, 10).await.map_err(|e| format!("{:?}", e))?;
+        if !queue.is_empty() {
+            return Err(format!("Expected claimed flags to be left out of other queues, got {:?}", queue));
+        }
+
+        // Expired claims are up for grabs
+        let queue = dao.get_queue(second.clone(), Duration::ZERO, 10).await.map_err(|e| format!("{:?}", e))?;
+        if queue.len() != 1 {
+            return Err(format!("Expected expired claims in other queues, got {:?}", queue));
+        }
+        let resolved = dao.resolve_flag(flag.flag_id, FlagStatus::Helpful, 3, Duration::ZERO, as_user(second)).await.map_err(|e| format!("{:?}", e))?;
+        match resolved.resolved_by {
+            Some(moderator) if &moderator == second => Ok(()),
+            resolved_by => Err(format!("Expected the flag to be resolved by the second moderator, got {:?}", resolved_by)),
+        }
+    }
+}
+
+mod suggested_edits_tests {
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use super::reputation_tests::{thread, users};
+    use crate::{
+        models::{
+            AnswerEdit, DBError, IfMatch, PostType, QuestionEdit, QuestionFilter, SuggestedEdit, SuggestedEditReview,
+            SuggestedEditStatus,
+        },
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+            suggested_edits_dao::{SuggestedEditsDao, SuggestedEditsDaoImpl},
+            unit_of_work::{TransactionManager, TransactionManagerImpl},
+        },
+    };
+
+    fn suggestion(question_uuid: &str, title: &str) -> SuggestedEdit {
+        SuggestedEdit {
+            target_type: PostType::Question,
+            target_uuid: question_uuid.to_owned(),
+            title: Some(title.to_owned()),
+            description: None,
+            content: None,
+            summary: Some("clearer title".to_owned()),
+        }
+    }
+
+    fn title_edit(question_uuid: &str, title: &str) -> QuestionEdit {
+        QuestionEdit {
+            question_uuid: question_uuid.to_owned(),
+            title: Some(title.to_owned()),
+            description: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn only_authors_should_edit_without_the_privilege(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let (question, answers) = thread(&pool, &users[0], &[&users[1]]).await;
+        let questions_dao = QuestionsDaoImpl::new(pool.clone());
+
+        let result = questions_dao.update_question(title_edit(&question, "hijacked"), IfMatch::Any, false, as_user(&users[1])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected editing someone else's question to be forbidden, got {:?}", result));
+        }
+        let edited = questions_dao
+            .update_question(title_edit(&question, "new title"), IfMatch::Versions(vec![1]), false, as_user(&users[0]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        if edited.title != "new title" || edited.description != "test description" || edited.version != 2 {
+            return Err(format!("Expected only the title to change, got {:?}", edited));
+        }
+        let result = questions_dao.update_question(title_edit(&question, "stale"), IfMatch::Versions(vec![1]), false, as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::PreconditionFailed(_))) {
+            return Err(format!("Expected a stale edit to fail its precondition, got {:?}", result));
+        }
+
+        let edit = AnswerEdit {
+            answer_uuid: answers[0].clone(),
+            content: "edited by a privileged user".to_owned(),
+        };
+        let edited = AnswersDaoImpl::new(pool.clone())
+            .update_answer(edit, IfMatch::Any, true, as_user(&users[0]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        match edited.content.as_str() {
+            "edited by a privileged user" => Ok(()),
+            content => Err(format!("Expected the answer to be edited, got {:?}", content)),
+        }
+    }
+
+    #[sqlx::test]
+    async fn only_authors_and_moderators_should_delete(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 3).await;
+        let (question, answers) = thread(&pool, &users[0], &[&users[1]]).await;
+        let questions_dao = QuestionsDaoImpl::new(pool.clone());
+        let answers_dao = AnswersDaoImpl::new(pool.clone());
+
+        let result = questions_dao.delete_question(question.clone(), IfMatch::Any, false, as_user(&users[1])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected deleting someone else's question to be forbidden, got {:?}", result));
+        }
+        let result = answers_dao.delete_answer(answers[0].clone(), IfMatch::Any, false, as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected deleting someone else's answer to be forbidden, got {:?}", result));
+        }
+
+        answers_dao
+            .delete_answer(answers[0].clone(), IfMatch::Any, false, as_user(&users[1]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        questions_dao
+            .delete_question(question, IfMatch::Any, true, as_user(&users[2]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let remaining = questions_dao
+            .get_questions(QuestionFilter::default())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        match remaining.as_slice() {
+            [] => Ok(()),
+            _ => Err(format!("Expected a moderator to delete the question, got {:?}", remaining)),
+        }
+    }
+
+    #[sqlx::test]
+    async fn approved_suggestions_should_apply_in_the_same_unit_of_work(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 3).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        let (suggester, reviewer) = (&users[1], &users[2]);
+        let dao = SuggestedEditsDaoImpl::new(pool.clone());
+
+        let result = dao.create_suggested_edit(suggestion(&question, "own"), as_user(&users[0])).await;
+        if !matches!(result, Err(DBError::Forbidden(_))) {
+            return Err(format!("Expected authors to be told to edit directly, got {:?}", result));
+        }
+        let suggested = dao.create_suggested_edit(suggestion(&question, "suggested title"), as_user(suggester)).await.map_err(|e| format!("{:?}", e))?;
+        let result = dao.create_suggested_edit(suggestion(&question, "another"), as_user(reviewer)).await;
+        if !matches!(result, Err(DBError::Conflict(_))) {
+            return Err(format!("Expected a second pending suggestion to conflict, got {:?}", result));
+        }
+
+        let uow = TransactionManagerImpl::new(pool.clone()).begin().await.map_err(|e| format!("{:?}", e))?;
+        let edit = uow.suggested_edits().lock_pending(suggested.edit_id).await.map_err(|e| format!("{:?}", e))?;
+        uow.questions()
+            .update_question(title_edit(&question, edit.title.as_deref().unwrap_or_default()), IfMatch::Versions(vec![edit.base_version]), true, as_user(reviewer))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let review = SuggestedEditReview {
+            status: SuggestedEditStatus::Approved,
+            comment: Some("thanks".to_owned()),
+        };
+        uow.suggested_edits().record_review(suggested.edit_id, review, as_user(reviewer)).await.map_err(|e| format!("{:?}", e))?;
+        uow.commit().await.map_err(|e| format!("{:?}", e))?;
+
+        let questions = QuestionsDaoImpl::new(pool.clone()).get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+        if questions.iter().all(|q| q.title != "suggested title") {
+            return Err(format!("Expected the suggested title to be applied, got {:?}", questions));
+        }
+        let queue = dao.get_queue(10).await.map_err(|e| format!("{:?}", e))?;
+        if !queue.is_empty() {
+            return Err(format!("Expected reviewed suggestions to leave the queue, got {:?}", queue));
+        }
+        match dao.lock_pending(suggested.edit_id).await {
+            Err(DBError::Conflict(_)) => Ok(()),
+            result => Err(format!("Expected reviewing twice to conflict, got {:?}", result)),
+        }
+    }
+}
+
+mod screening_tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use super::reputation_tests::{thread, users};
+    use crate::{
+        models::{FlagStatus, PostType, QuestionFilter, ScreeningDecision},
+        persistance::{
+            flags_dao::{FlagsDao, FlagsDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+            screening_dao::{ScreeningDao, ScreeningDaoImpl, ScreeningRecord},
+        },
+    };
+
+    const WINDOW: Duration = Duration::from_secs(3600);
+
+    fn record(author_uuid: &str, target_uuid: Option<&str>, decision: ScreeningDecision, content_hash: &str) -> ScreeningRecord {
+        ScreeningRecord {
+            author_uuid: Some(author_uuid.to_owned()),
+            target_type: PostType::Question,
+            target_uuid: target_uuid.map(str::to_owned),
+            decision,
+            rule: (decision != ScreeningDecision::Accepted).then(|| "links".to_owned()),
+            reason: (decision != ScreeningDecision::Accepted).then(|| "3 links".to_owned()),
+            content_hash: content_hash.to_owned(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn quarantined_posts_should_be_hidden_until_the_flag_is_declined(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let (question, _) = thread(&pool, &users[0], &[]).await;
+        let dao = ScreeningDaoImpl::new(pool.clone());
+        let flags_dao = FlagsDaoImpl::new(pool.clone());
+        let questions_dao = QuestionsDaoImpl::new(pool.clone());
+
+        dao.record_decision(record(&users[0], Some(&question), ScreeningDecision::Quarantined, "abc"), 3, as_user(&users[0]))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        let questions = questions_dao.get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+        if questions.iter().any(|q| q.question_uuid == question) {
+            return Err("Expected a quarantined question to be hidden".to_owned());
+        }
+
+        let queue = flags_dao.get_queue(users[1].clone(), WINDOW, 10).await.map_err(|e| format!("{:?}", e))?;
+        let flag = match queue.as_slice() {
+            [flag] if flag.target_uuid == question && flag.flagged_by.is_none() => flag,
+            queue => return Err(format!("Expected a flag without flagger in the queue, got {:?}", queue)),
+        };
+        flags_dao.resolve_flag(flag.flag_id, FlagStatus::Declined, 3, WINDOW, as_user(&users[1])).await.map_err(|e| format!("{:?}", e))?;
+
+        let questions = questions_dao.get_questions(QuestionFilter::default()).await.map_err(|e| format!("{:?}", e))?;
+        if !questions.iter().any(|q| q.question_uuid == question) {
+            return Err("Expected declining the screening flag to show the question again".to_owned());
+        }
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn counts_should_leave_out_rejected_posts(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 2).await;
+        let dao = ScreeningDaoImpl::new(pool.clone());
+
+        for (author, decision, hash) in [
+            (&users[0], ScreeningDecision::Accepted, "abc"),
+            (&users[0], ScreeningDecision::Rejected, "abc"),
+            (&users[1], ScreeningDecision::Accepted, "abc"),
+            (&users[0], ScreeningDecision::Accepted, "def"),
+        ] {
+            dao.record_decision(record(author, None, decision, hash), 3, as_user(author)).await.map_err(|e| format!("{:?}", e))?;
+        }
+
+        let recent = dao.count_recent_posts(users[0].clone(), WINDOW).await.map_err(|e| format!("{:?}", e))?;
+        let repeats = dao.count_repeats("abc".to_owned(), WINDOW).await.map_err(|e| format!("{:?}", e))?;
+        match (recent, repeats) {
+            (2, 2) => Ok(()),
+            counts => Err(format!("Expected 2 recent posts and 2 repeats, got {:?}", counts)),
+        }
+    }
+}
+
+mod question_links_tests {
+    use sqlx::PgPool;
+
+    use super::as_user;
+    use super::reputation_tests::users;
+    use crate::{
+        models::{Answer, AnswerEdit, AuditContext, DBError, IfMatch, Question},
+        persistance::{
+            answers_dao::{AnswersDao, AnswersDaoImpl},
+            question_links_dao::{QuestionLinksDao, QuestionLinksDaoImpl},
+            questions_dao::{QuestionsDao, QuestionsDaoImpl},
+        },
+    };
+
+    async fn ask(pool: &PgPool, title: &str, description: &str) -> String {
+        let question = Question {
+            title: title.to_owned(),
+            description: description.to_owned(),
+        };
+        QuestionsDaoImpl::new(pool.clone())
+            .create_question(question, AuditContext::default())
+            .await
+            .expect("Error creating test question")
+            .question_uuid
+    }
+
+    async fn linked(pool: &PgPool, question_uuid: &str) -> Result<Vec<String>, String> {
+        let linked = QuestionLinksDaoImpl::new(pool.clone()).get_linked(question_uuid.to_owned(), 10).await.map_err(|e| format!("{:?}", e))?;
+        let mut uuids: Vec<String> = linked.into_iter().map(|q| q.question_uuid).collect();
+        uuids.sort();
+        Ok(uuids)
+    }
+
+    #[sqlx::test]
+    async fn mentions_should_link_questions_both_ways(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 1).await;
+        let first = ask(&pool, "How do I parse JSON", "With serde").await;
+        let second = ask(&pool, "What is serde", "A framework").await;
+        let third = ask(&pool, "Why does serde_json fail", &format!("Like in {}", first)).await;
+        let answers = AnswersDaoImpl::new(pool.clone());
+        let answer = answers
+            .create_answer(
+                Answer {
+                    question_uuid: first.clone(),
+                    content: format!("See https://example.com/questions/{}/related", second.to_uppercase()),
+                },
+                as_user(&users[0]),
+            )
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+        let mut expected = vec![second.clone(), third.clone()];
+        expected.sort();
+        if linked(&pool, &first).await? != expected || linked(&pool, &second).await? != [first.clone()] {
+            return Err("Expected the question to be linked to and from the questions mentioned".to_owned());
+        }
+
+        let edit = AnswerEdit {
+            answer_uuid: answer.answer_uuid,
+            content: "Never mind".to_owned(),
+        };
+        answers.update_answer(edit, IfMatch::Any, true, as_user(&users[0])).await.map_err(|e| format!("{:?}", e))?;
+        match linked(&pool, &first).await? {
+            uuids if uuids == [third] => Ok(()),
+            uuids => Err(format!("Expected editing the mention out to remove the link, got {:?}", uuids)),
+        }
+    }
+
+    #[sqlx::test]
+    async fn find_related_should_leave_out_the_question(pool: PgPool) -> Result<(), String> {
+        let users = users(&pool, 1).await;
+        let question = ask(&pool, "How do I parse JSON in Rust", "serde_json fails on nested objects").await;
+        let similar = ask(&pool, "How can I parse JSON in Rust", "serde_json fails on nested arrays").await;
+        ask(&pool, "Why is my Postgres query slow", "It scans the whole table").await;
+        let dao = QuestionsDaoImpl::new(pool.clone());
+
+        let related = dao.find_related(question.clone(), 0.3, 5).await.map_err(|e| format!("{:?}", e))?;
+        if related.len() != 1 || related[0].question_uuid != similar {
+            return Err(format!("Expected only the similar question, got {:?}", related));
+        }
+
+        dao.delete_question(question.clone(), IfMatch::Any, true, as_user(&users[0])).await.map_err(|e| format!("{:?}", e))?;
+        match dao.find_related(question, 0.3, 5).await {
+            Err(DBError::NotFound(_)) => Ok(()),
+            result => Err(format!("Expected a deleted question to be not found, got {:?}", result)),
+        }
+    }
+}
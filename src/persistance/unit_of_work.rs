@@ -0,0 +1,145 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sqlx::pool::PoolConnection;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::models::DBError;
+
+use super::answers_dao::{AnswersDao, AnswersDaoImpl};
+use super::questions_dao::{QuestionsDao, QuestionsDaoImpl};
+use super::suggested_edits_dao::{SuggestedEditsDao, SuggestedEditsDaoImpl};
+
+type SharedTransaction = Arc<Mutex<Transaction<'static, Postgres>>>;
+
+/// Where a DAO runs its queries: straight on the pool, or inside a unit of work.
+#[derive(Clone)]
+pub(crate) enum Executor {
+    Pool(PgPool),
+    Transaction(SharedTransaction),
+}
+
+impl Executor {
+    /// A connection for the duration of one DAO call. Inside a unit of work this locks the shared
+    /// transaction, and transactions begun on it become savepoints.
+    pub(crate) async fn acquire(&self) -> Result<ExecutorConnection<'_>, DBError> {
+        match self {
+            Executor::Pool(pool) => pool
+                .acquire()
+                .await
+                .map(ExecutorConnection::Pool)
+                .map_err(|e| DBError::Other(Box::new(e))),
+            Executor::Transaction(tx) => Ok(ExecutorConnection::Transaction(tx.lock().await)),
+        }
+    }
+}
+
+pub(crate) enum ExecutorConnection<'a> {
+    Pool(PoolConnection<Postgres>),
+    Transaction(MutexGuard<'a, Transaction<'static, Postgres>>),
+}
+
+impl Deref for ExecutorConnection<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        match self {
+            ExecutorConnection::Pool(conn) => conn,
+            ExecutorConnection::Transaction(tx) => tx,
+        }
+    }
+}
+
+impl DerefMut for ExecutorConnection<'_> {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        match self {
+            ExecutorConnection::Pool(conn) => conn,
+            ExecutorConnection::Transaction(tx) => tx,
+        }
+    }
+}
+
+/// DAOs sharing a single database transaction. Nothing done through them is visible to others
+/// until `commit`; dropping the unit of work instead (e.g. by returning early with `?`) rolls
+/// everything back.
+///
+/// The DAOs are the same trait objects the handlers already take, so any sequence of
+/// `handlers_inner` calls can be made atomic by passing them `uow.questions()`, `uow.answers()` and
+/// `uow.suggested_edits()`.
+#[async_trait]
+pub trait UnitOfWork {
+    fn questions(&self) -> &(dyn QuestionsDao + Send + Sync);
+    fn answers(&self) -> &(dyn AnswersDao + Send + Sync);
+    fn suggested_edits(&self) -> &(dyn SuggestedEditsDao + Send + Sync);
+    async fn commit(self: Box<Self>) -> Result<(), DBError>;
+}
+
+#[async_trait]
+pub trait TransactionManager {
+    async fn begin(&self) -> Result<Box<dyn UnitOfWork + Send + Sync>, DBError>;
+}
+
+pub struct TransactionManagerImpl {
+    db: PgPool,
+}
+
+impl TransactionManagerImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl TransactionManager for TransactionManagerImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "BEGIN"))]
+    async fn begin(&self) -> Result<Box<dyn UnitOfWork + Send + Sync>, DBError> {
+        let tx = self.db.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let tx = Arc::new(Mutex::new(tx));
+
+        Ok(Box::new(UnitOfWorkImpl {
+            questions: QuestionsDaoImpl::with_executor(Executor::Transaction(tx.clone())),
+            answers: AnswersDaoImpl::with_executor(Executor::Transaction(tx.clone())),
+            suggested_edits: SuggestedEditsDaoImpl::with_executor(Executor::Transaction(tx.clone())),
+            tx,
+        }))
+    }
+}
+
+struct UnitOfWorkImpl {
+    questions: QuestionsDaoImpl,
+    answers: AnswersDaoImpl,
+    suggested_edits: SuggestedEditsDaoImpl,
+    tx: SharedTransaction,
+}
+
+#[async_trait]
+impl UnitOfWork for UnitOfWorkImpl {
+    fn questions(&self) -> &(dyn QuestionsDao + Send + Sync) {
+        &self.questions
+    }
+
+    fn answers(&self) -> &(dyn AnswersDao + Send + Sync) {
+        &self.answers
+    }
+
+    fn suggested_edits(&self) -> &(dyn SuggestedEditsDao + Send + Sync) {
+        &self.suggested_edits
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "COMMIT"))]
+    async fn commit(self: Box<Self>) -> Result<(), DBError> {
+        let UnitOfWorkImpl { questions, answers, suggested_edits, tx } = *self;
+        // The DAOs hold the only other handles on the transaction
+        drop(questions);
+        drop(answers);
+        drop(suggested_edits);
+
+        let tx = Arc::try_unwrap(tx)
+            .map_err(|_| DBError::Other("Transaction is still in use".into()))?
+            .into_inner();
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))
+    }
+}
@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::models::{postgres_error_codes, DBError, Role, User, UserDetail};
+
+#[async_trait]
+pub trait UsersDao {
+    async fn create_user(&self, user: User, api_key_hash: String) -> Result<UserDetail, DBError>;
+    async fn get_user_by_api_key_hash(&self, api_key_hash: String) -> Result<Option<UserDetail>, DBError>;
+}
+
+pub struct UsersDaoImpl {
+    db: PgPool,
+}
+
+impl UsersDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+fn parse_role(role: &str) -> Result<Role, DBError> {
+    Role::parse(role).ok_or_else(|| DBError::Other(format!("Unknown role: {}", role).into()))
+}
+
+#[async_trait]
+impl UsersDao for UsersDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO users"))]
+    async fn create_user(&self, user: User, api_key_hash: String) -> Result<UserDetail, DBError> {
+        let record = sqlx::query!(
+                "INSERT INTO users (username, api_key_hash) VALUES ($1, $2) RETURNING *",
+                user.username,
+                api_key_hash
+            )
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e: sqlx::Error| match e {
+                sqlx::Error::Database(e) => {
+                    if let Some(code) = e.code() {
+                        if code.eq(postgres_error_codes::UNIQUE_VIOLATION) {
+                            return DBError::Conflict(format!("Username {} is already taken", user.username));
+                        }
+                    }
+                    DBError::Other(Box::new(e))
+                }
+                e => DBError::Other(Box::new(e)),
+            })?;
+
+        Ok(UserDetail {
+            user_uuid: record.user_uuid.to_string(),
+            username: record.username,
+            role: parse_role(&record.role)?,
+            created_at: record.created_at.to_string(),
+        })
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM users"))]
+    async fn get_user_by_api_key_hash(&self, api_key_hash: String) -> Result<Option<UserDetail>, DBError> {
+        let record = sqlx::query!("SELECT * FROM users WHERE api_key_hash = $1", api_key_hash)
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        record
+            .map(|r| {
+                Ok(UserDetail {
+                    user_uuid: r.user_uuid.to_string(),
+                    username: r.username,
+                    role: parse_role(&r.role)?,
+                    created_at: r.created_at.to_string(),
+                })
+            })
+            .transpose()
+    }
+}
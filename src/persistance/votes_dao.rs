@@ -0,0 +1,273 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::{Connection, PgConnection, PgPool};
+
+use crate::models::{AuditContext, DBError, PostType};
+
+use super::audit_dao::{record_event, AuditRecord};
+use super::outbox_dao::append_event;
+use super::parse_uuid;
+use super::question_events_dao::record_question_event;
+use super::reputation_dao::{record_reputation, ReputationChange};
+
+/// Reputation the author of a post gains from an upvote.
+pub const UPVOTED: i32 = 10;
+/// Reputation the author of a post loses to a downvote.
+pub const DOWNVOTED: i32 = -2;
+/// Reputation a user loses by downvoting an answer, so downvotes aren't free.
+pub const DOWNVOTE_CAST: i32 = -1;
+/// Reputation the author of an answer gains when it is accepted.
+pub const ANSWER_ACCEPTED: i32 = 15;
+/// Reputation the author of a question gains by accepting an answer.
+pub const ACCEPTED_ANSWER: i32 = 2;
+
+/// Payload of the `question.voted` and `answer.voted` events.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteEvent {
+    pub target_type: PostType,
+    pub target_uuid: String,
+    pub voter_uuid: String,
+    pub author_uuid: Option<String>,
+    /// 1, -1, or 0 for a retracted vote.
+    pub value: i16,
+}
+
+/// Payload of the `answer.accepted` event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AcceptEvent {
+    pub question_uuid: String,
+    pub answer_uuid: String,
+    pub author_uuid: Option<String>,
+    pub accepted_by: String,
+}
+
+#[async_trait]
+pub trait VotesDao {
+    /// Records the actor's vote (1, -1, or 0 to retract it) on a question or an answer, replacing
+    /// any earlier one, and credits it.
+    async fn vote(&self, target_type: PostType, target_uuid: String, value: i16, context: AuditContext) -> Result<(), DBError>;
+    /// Marks the answer as the accepted answer of its question, which only the question's author
+    /// may do, and credits it.
+    async fn accept_answer(&self, answer_uuid: String, context: AuditContext) -> Result<(), DBError>;
+}
+
+pub struct VotesDaoImpl {
+    db: PgPool,
+}
+
+impl VotesDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+/// The reputation changes a vote causes, as `(user, reason, amount)`.
+fn vote_credits(target_type: PostType, value: i16, author: Option<Uuid>, voter: Uuid) -> Vec<(Uuid, &'static str, i32)> {
+    let mut credits = vec![];
+    match (target_type, value) {
+        (PostType::Question, 1) => credits.extend(author.map(|author| (author, "question_upvoted", UPVOTED))),
+        (PostType::Answer, 1) => credits.extend(author.map(|author| (author, "answer_upvoted", UPVOTED))),
+        (PostType::Question, -1) => credits.extend(author.map(|author| (author, "question_downvoted", DOWNVOTED))),
+        (PostType::Answer, -1) => {
+            credits.extend(author.map(|author| (author, "answer_downvoted", DOWNVOTED)));
+            credits.push((voter, "downvote_cast", DOWNVOTE_CAST));
+        }
+        _ => {}
+    }
+    credits
+}
+
+/// The reputation changes accepting an answer causes, as `(user, reason, amount)`. Accepting
+/// one's own answer earns nothing.
+fn accept_credits(author: Option<Uuid>, accepter: Uuid) -> Vec<(Uuid, &'static str, i32)> {
+    match author {
+        Some(author) if author == accepter => vec![],
+        Some(author) => vec![(author, "answer_accepted", ANSWER_ACCEPTED), (accepter, "accepted_answer", ACCEPTED_ANSWER)],
+        None => vec![(accepter, "accepted_answer", ACCEPTED_ANSWER)],
+    }
+}
+
+/// Records `credits`, or their reversal under `retraction` if given.
+async fn credit(
+    conn: &mut PgConnection,
+    credits: Vec<(Uuid, &str, i32)>,
+    retraction: Option<&str>,
+    target_type: PostType,
+    target_uuid: Uuid,
+    actor_uuid: Uuid,
+) -> Result<(), DBError> {
+    for (user_uuid, reason, amount) in credits {
+        record_reputation(&mut *conn, ReputationChange {
+            user_uuid,
+            reason: retraction.unwrap_or(reason),
+            amount: if retraction.is_some() { -amount } else { amount },
+            target_type: target_type.as_str(),
+            target_uuid,
+            actor_uuid: Some(actor_uuid),
+        }).await?;
+    }
+    Ok(())
+}
+
+fn require_actor(context: &AuditContext) -> Result<Uuid, DBError> {
+    let actor = context
+        .actor_uuid
+        .as_deref()
+        .ok_or_else(|| DBError::Forbidden("Only signed-in users can vote or accept answers".to_owned()))?;
+    parse_uuid(actor, "user ID")
+}
+
+#[async_trait]
+impl VotesDao for VotesDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO votes", target_uuid = %target_uuid))]
+    async fn vote(&self, target_type: PostType, target_uuid: String, value: i16, context: AuditContext) -> Result<(), DBError> {
+        let uuid = parse_uuid(&target_uuid, &format!("{} ID", target_type.as_str()))?;
+        let voter = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        // Locking the post serializes the votes on it, so a vote is never credited twice
+        let (question_uuid, author) = match target_type {
+            PostType::Question => sqlx::query!(
+                    "SELECT question_uuid, author_uuid FROM questions WHERE question_uuid = $1 AND deleted_at IS NULL FOR NO KEY UPDATE",
+                    uuid
+                )
+                .fetch_optional(&mut *tx)
+                .await
+                .map(|record| record.map(|record| (record.question_uuid, record.author_uuid))),
+            PostType::Answer => sqlx::query!(
+                    "SELECT answers.question_uuid, answers.author_uuid FROM answers JOIN questions USING (question_uuid)
+                     WHERE answer_uuid = $1 AND answers.deleted_at IS NULL AND questions.deleted_at IS NULL
+                     FOR NO KEY UPDATE OF answers",
+                    uuid
+                )
+                .fetch_optional(&mut *tx)
+                .await
+                .map(|record| record.map(|record| (record.question_uuid, record.author_uuid))),
+        }
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No {} with ID {}", target_type.as_str(), target_uuid)))?;
+
+        if author == Some(voter) {
+            return Err(DBError::Forbidden("You cannot vote on your own posts".to_owned()));
+        }
+
+        let previous = sqlx::query_scalar!("SELECT value FROM votes WHERE voter_uuid = $1 AND target_uuid = $2", voter, uuid)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .unwrap_or(0);
+
+        // Voting the same way twice is a no-op
+        if previous == value {
+            return Ok(());
+        }
+
+        credit(&mut tx, vote_credits(target_type, previous, author, voter), Some("vote_retracted"), target_type, uuid, voter).await?;
+        if value == 0 {
+            sqlx::query!("DELETE FROM votes WHERE voter_uuid = $1 AND target_uuid = $2", voter, uuid)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| DBError::Other(Box::new(e)))?;
+        } else {
+            sqlx::query!(
+                    "INSERT INTO votes (voter_uuid, target_type, target_uuid, value) VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (voter_uuid, target_uuid) DO UPDATE SET value = EXCLUDED.value, created_at = CURRENT_TIMESTAMP",
+                    voter,
+                    target_type.as_str(),
+                    uuid,
+                    value
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| DBError::Other(Box::new(e)))?;
+        }
+        credit(&mut tx, vote_credits(target_type, value, author, voter), None, target_type, uuid, voter).await?;
+
+        let event = VoteEvent {
+            target_type,
+            target_uuid: uuid.to_string(),
+            voter_uuid: voter.to_string(),
+            author_uuid: author.map(|author| author.to_string()),
+            value,
+        };
+        let event_type = format!("{}.voted", target_type.as_str());
+        append_event(&mut tx, target_type.as_str(), uuid, &event_type, &event).await?;
+        record_question_event(&mut tx, question_uuid, &event_type, &event).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE questions", answer_uuid = %answer_uuid))]
+    async fn accept_answer(&self, answer_uuid: String, context: AuditContext) -> Result<(), DBError> {
+        let uuid = parse_uuid(&answer_uuid, "answer ID")?;
+        let accepter = require_actor(&context)?;
+
+        let mut conn = self.db.acquire().await.map_err(|e| DBError::Other(Box::new(e)))?;
+        let mut tx = conn.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let before = sqlx::query!(
+                "SELECT answers.question_uuid, answers.author_uuid, questions.author_uuid AS asker_uuid,
+                        questions.accepted_answer_uuid, to_jsonb(questions.*) AS snapshot
+                 FROM answers JOIN questions USING (question_uuid)
+                 WHERE answer_uuid = $1 AND answers.deleted_at IS NULL AND questions.deleted_at IS NULL
+                 FOR NO KEY UPDATE OF questions",
+                uuid
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?
+            .ok_or_else(|| DBError::NotFound(format!("No answer with ID {}", answer_uuid)))?;
+
+        if before.asker_uuid != Some(accepter) {
+            return Err(DBError::Forbidden("Only the author of the question can accept an answer".to_owned()));
+        }
+        // Accepting the accepted answer again is a no-op
+        if before.accepted_answer_uuid == Some(uuid) {
+            return Ok(());
+        }
+
+        if let Some(previous) = before.accepted_answer_uuid {
+            let previous_author = sqlx::query_scalar!("SELECT author_uuid FROM answers WHERE answer_uuid = $1", previous)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| DBError::Other(Box::new(e)))?;
+            credit(&mut tx, accept_credits(previous_author, accepter), Some("accept_retracted"), PostType::Answer, previous, accepter).await?;
+        }
+
+        let after = sqlx::query_scalar!(
+                "UPDATE questions SET accepted_answer_uuid = $2 WHERE question_uuid = $1 RETURNING to_jsonb(questions.*) AS snapshot",
+                before.question_uuid,
+                uuid
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        record_event(&mut tx, &context, AuditRecord {
+            action: "answer.accepted",
+            target_type: "question",
+            target_uuid: before.question_uuid,
+            before: before.snapshot,
+            after,
+        }).await?;
+        credit(&mut tx, accept_credits(before.author_uuid, accepter), None, PostType::Answer, uuid, accepter).await?;
+
+        let event = AcceptEvent {
+            question_uuid: before.question_uuid.to_string(),
+            answer_uuid: uuid.to_string(),
+            author_uuid: before.author_uuid.map(|author| author.to_string()),
+            accepted_by: accepter.to_string(),
+        };
+        append_event(&mut tx, "question", before.question_uuid, "answer.accepted", &event).await?;
+        record_question_event(&mut tx, before.question_uuid, "answer.accepted", &event).await?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+}
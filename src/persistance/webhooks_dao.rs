@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+
+use crate::models::{DBError, DeliveryStatus, Webhook, WebhookDelivery, WebhookDeliveryFilter, WebhookDetail};
+
+use super::parse_uuid;
+
+/// A delivery claimed for an attempt, with what is needed to make it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingDelivery {
+    pub delivery_id: i64,
+    pub url: String,
+    pub secret: String,
+    pub event_id: i64,
+    pub event_type: String,
+    pub payload: JsonValue,
+    /// Failed attempts so far.
+    pub attempts: i32,
+}
+
+/// How an attempt to deliver went.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryOutcome {
+    Delivered { status_code: i32 },
+    /// `retry_in` is `None` when the delivery should not be retried anymore.
+    Failed {
+        status_code: Option<i32>,
+        error: String,
+        retry_in: Option<Duration>,
+    },
+}
+
+#[async_trait]
+pub trait WebhooksDao {
+    async fn create_subscription(&self, webhook: Webhook) -> Result<WebhookDetail, DBError>;
+    async fn get_subscriptions(&self) -> Result<Vec<WebhookDetail>, DBError>;
+    /// Removes the subscription along with its delivery log.
+    async fn delete_subscription(&self, subscription_uuid: String) -> Result<(), DBError>;
+    async fn get_deliveries(&self, filter: WebhookDeliveryFilter) -> Result<Vec<WebhookDelivery>, DBError>;
+    /// Queues a dead delivery again, as if it was new.
+    async fn retry_delivery(&self, delivery_id: i64) -> Result<(), DBError>;
+    /// Claims up to `limit` due deliveries for `lease`: other instances skip them until it runs out,
+    /// so a delivery whose instance went down is picked up again.
+    async fn claim_due(&self, limit: i64, lease: Duration) -> Result<Vec<PendingDelivery>, DBError>;
+    async fn record_attempt(&self, delivery_id: i64, outcome: DeliveryOutcome) -> Result<(), DBError>;
+}
+
+pub struct WebhooksDaoImpl {
+    db: PgPool,
+}
+
+impl WebhooksDaoImpl {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl WebhooksDao for WebhooksDaoImpl {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "INSERT INTO webhook_subscriptions"))]
+    async fn create_subscription(&self, webhook: Webhook) -> Result<WebhookDetail, DBError> {
+        let record = sqlx::query!(
+                "INSERT INTO webhook_subscriptions (url, event_types, secret) VALUES ($1, $2, $3)
+                 RETURNING subscription_uuid, url, event_types, created_at",
+                webhook.url,
+                &webhook.event_types,
+                webhook.secret
+            )
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(WebhookDetail {
+            subscription_uuid: record.subscription_uuid.to_string(),
+            url: record.url,
+            event_types: record.event_types,
+            created_at: record.created_at.to_string(),
+        })
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM webhook_subscriptions"))]
+    async fn get_subscriptions(&self) -> Result<Vec<WebhookDetail>, DBError> {
+        let records = sqlx::query!(
+                "SELECT subscription_uuid, url, event_types, created_at FROM webhook_subscriptions ORDER BY created_at"
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let subscriptions = records
+            .into_iter()
+            .map(|r| WebhookDetail {
+                subscription_uuid: r.subscription_uuid.to_string(),
+                url: r.url,
+                event_types: r.event_types,
+                created_at: r.created_at.to_string(),
+            })
+            .collect();
+
+        Ok(subscriptions)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM webhook_subscriptions"))]
+    async fn delete_subscription(&self, subscription_uuid: String) -> Result<(), DBError> {
+        let uuid = parse_uuid(&subscription_uuid, "webhook ID")?;
+
+        let result = sqlx::query!("DELETE FROM webhook_subscriptions WHERE subscription_uuid = $1", uuid)
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(DBError::NotFound(format!("Webhook {} not found", subscription_uuid)));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "SELECT FROM webhook_deliveries"))]
+    async fn get_deliveries(&self, filter: WebhookDeliveryFilter) -> Result<Vec<WebhookDelivery>, DBError> {
+        let uuid = parse_uuid(&filter.subscription_uuid, "webhook ID")?;
+
+        let records = sqlx::query!(
+                "SELECT delivery_id, subscription_uuid, event_id, event_type, status, attempts, next_attempt_at,
+                        last_attempt_at, last_status_code, last_error, created_at, delivered_at
+                 FROM webhook_deliveries
+                 WHERE subscription_uuid = $1
+                   AND ($2::text IS NULL OR status = $2)
+                   AND ($3::bigint IS NULL OR delivery_id < $3)
+                 ORDER BY delivery_id DESC
+                 LIMIT $4",
+                uuid,
+                filter.status.map(|status| status.as_str()),
+                filter.before_delivery_id,
+                filter.limit
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        records
+            .into_iter()
+            .map(|r| {
+                let status = DeliveryStatus::parse(&r.status)
+                    .ok_or_else(|| DBError::Other(format!("Unknown delivery status {}", r.status).into()))?;
+                Ok(WebhookDelivery {
+                    delivery_id: r.delivery_id,
+                    subscription_uuid: r.subscription_uuid.to_string(),
+                    event_id: r.event_id,
+                    event_type: r.event_type,
+                    status,
+                    attempts: r.attempts,
+                    next_attempt_at: (status == DeliveryStatus::Pending).then(|| r.next_attempt_at.to_string()),
+                    last_attempt_at: r.last_attempt_at.map(|at| at.to_string()),
+                    last_status_code: r.last_status_code,
+                    last_error: r.last_error,
+                    created_at: r.created_at.to_string(),
+                    delivered_at: r.delivered_at.map(|at| at.to_string()),
+                })
+            })
+            .collect()
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE webhook_deliveries"))]
+    async fn retry_delivery(&self, delivery_id: i64) -> Result<(), DBError> {
+        let result = sqlx::query!(
+                "UPDATE webhook_deliveries SET status = 'pending', attempts = 0, next_attempt_at = CURRENT_TIMESTAMP
+                 WHERE delivery_id = $1 AND status = 'dead'",
+                delivery_id
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        if result.rows_affected() == 0 {
+            return Err(DBError::NotFound(format!("No dead delivery with ID {}", delivery_id)));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE webhook_deliveries"))]
+    async fn claim_due(&self, limit: i64, lease: Duration) -> Result<Vec<PendingDelivery>, DBError> {
+        let records = sqlx::query!(
+                "UPDATE webhook_deliveries AS d
+                 SET next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $2)
+                 FROM webhook_subscriptions AS s
+                 WHERE s.subscription_uuid = d.subscription_uuid
+                   AND d.delivery_id IN (
+                       SELECT delivery_id FROM webhook_deliveries
+                       WHERE status = 'pending' AND next_attempt_at <= CURRENT_TIMESTAMP
+                       ORDER BY next_attempt_at, delivery_id
+                       LIMIT $1
+                       FOR UPDATE SKIP LOCKED
+                   )
+                 RETURNING d.delivery_id, s.url, s.secret, d.event_id, d.event_type, d.payload, d.attempts",
+                limit,
+                lease.as_secs_f64()
+            )
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let deliveries = records
+            .into_iter()
+            .map(|r| PendingDelivery {
+                delivery_id: r.delivery_id,
+                url: r.url,
+                secret: r.secret,
+                event_id: r.event_id,
+                event_type: r.event_type,
+                payload: r.payload,
+                attempts: r.attempts,
+            })
+            .collect();
+
+        Ok(deliveries)
+    }
+
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE webhook_deliveries"))]
+    async fn record_attempt(&self, delivery_id: i64, outcome: DeliveryOutcome) -> Result<(), DBError> {
+        let query = match outcome {
+            DeliveryOutcome::Delivered { status_code } => sqlx::query!(
+                "UPDATE webhook_deliveries
+                 SET status = 'delivered', attempts = attempts + 1, last_attempt_at = CURRENT_TIMESTAMP,
+                     last_status_code = $2, last_error = NULL, delivered_at = CURRENT_TIMESTAMP
+                 WHERE delivery_id = $1",
+                delivery_id,
+                status_code
+            ),
+            DeliveryOutcome::Failed { status_code, error, retry_in } => sqlx::query!(
+                "UPDATE webhook_deliveries
+                 SET status = CASE WHEN $4::float8 IS NULL THEN 'dead' ELSE 'pending' END,
+                     next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => COALESCE($4, 0)),
+                     attempts = attempts + 1, last_attempt_at = CURRENT_TIMESTAMP,
+                     last_status_code = $2, last_error = $3
+                 WHERE delivery_id = $1",
+                delivery_id,
+                status_code,
+                error,
+                retry_in.map(|retry_in| retry_in.as_secs_f64())
+            ),
+        };
+
+        query
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+
+use crate::jobs::JobHandler;
+use crate::models::DBError;
+use crate::persistance::unit_of_work::{TransactionManager, TransactionManagerImpl};
+
+/// The `[soft_delete]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct SoftDeleteConfig {
+    /// How long deleted questions and answers can still be restored before they are purged.
+    pub retention_days: u32,
+}
+
+impl Default for SoftDeleteConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+        }
+    }
+}
+
+impl SoftDeleteConfig {
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(u64::from(self.retention_days) * 24 * 60 * 60)
+    }
+}
+
+/// Permanently removes soft-deleted content once it is older than the retention period. Questions
+/// and answers are purged in one unit of work, so a failure leaves both untouched.
+pub async fn purge_deleted(retention: Duration, transactions: &(dyn TransactionManager + Send + Sync)) -> Result<(), DBError> {
+    let purge = async {
+        let uow = transactions.begin().await?;
+        // Questions go first: purging one takes its answers with it.
+        let questions = uow.questions().purge_deleted(retention).await?;
+        let answers = uow.answers().purge_deleted(retention).await?;
+        uow.commit().await?;
+        Ok::<_, DBError>((questions, answers))
+    };
+
+    let (questions, answers) = purge.await?;
+    if questions > 0 || answers > 0 {
+        info!(questions, answers, "Purged deleted content");
+    }
+    Ok(())
+}
+
+/// Runs `purge_deleted` as the `purge_deleted` job, scheduled in `[jobs.schedules]`.
+pub struct PurgeDeletedJob {
+    retention: Duration,
+    transactions: TransactionManagerImpl,
+}
+
+impl PurgeDeletedJob {
+    pub fn new(db: PgPool, config: &SoftDeleteConfig) -> Self {
+        Self {
+            retention: config.retention(),
+            transactions: TransactionManagerImpl::new(db),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl JobHandler for PurgeDeletedJob {
+    fn kind(&self) -> &'static str {
+        "purge_deleted"
+    }
+
+    async fn run(&self, _payload: &JsonValue) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        purge_deleted(self.retention, &self.transactions).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rocket::fairing::{self, Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Build, Data, Request, Response, Rocket};
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+
+use crate::auth::authentication;
+use crate::jobs::JobHandler;
+use crate::models::DBError;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    Memory,
+    Postgres,
+}
+
+/// A token bucket: up to `capacity` requests in a burst, refilled at `refill_per_minute`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub capacity: u32,
+    pub refill_per_minute: u32,
+}
+
+impl Limit {
+    fn refill_per_second(&self) -> f64 {
+        f64::from(self.refill_per_minute) / 60.0
+    }
+
+    /// How long an empty bucket takes to fill up again; `None` when it never refills.
+    pub fn refill_window(&self) -> Option<Duration> {
+        (self.refill_per_minute > 0).then(|| Duration::from_secs_f64(f64::from(self.capacity) / self.refill_per_second()))
+    }
+}
+
+/// The `[rate_limit]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub store: StoreKind,
+    /// Budget for `GET`, `HEAD` and `OPTIONS` requests.
+    pub reads: Limit,
+    /// Budget for every other method.
+    pub writes: Limit,
+}
+
+impl RateLimitConfig {
+    /// How long any bucket takes to fill up again, after which it is no different from a missing one.
+    pub fn refill_window(&self) -> Option<Duration> {
+        Some(self.reads.refill_window()?.max(self.writes.refill_window()?))
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store: StoreKind::Memory,
+            reads: Limit {
+                capacity: 120,
+                refill_per_minute: 120,
+            },
+            writes: Limit {
+                capacity: 10,
+                refill_per_minute: 10,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+    pub tokens: f64,
+    pub updated_at: OffsetDateTime,
+}
+
+/// Outcome of taking a token, with the numbers reported in `RateLimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next token is available; zero when allowed.
+    pub retry_after: u64,
+}
+
+impl Bucket {
+    pub fn full(limit: &Limit, now: OffsetDateTime) -> Self {
+        Self {
+            tokens: f64::from(limit.capacity),
+            updated_at: now,
+        }
+    }
+
+    /// Refills the bucket for the time elapsed since it was last updated and tries to take one token.
+    pub fn take(&mut self, limit: &Limit, now: OffsetDateTime) -> Decision {
+        let rate = limit.refill_per_second();
+        let capacity = f64::from(limit.capacity);
+        let elapsed = (now - self.updated_at).as_seconds_f64().max(0.0);
+
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated_at = now;
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+
+        let seconds_until = |tokens: f64| {
+            if rate > 0.0 {
+                (tokens / rate).ceil() as u64
+            } else {
+                u64::MAX
+            }
+        };
+
+        Decision {
+            allowed,
+            limit: limit.capacity,
+            remaining: self.tokens.floor() as u32,
+            reset: seconds_until(capacity - self.tokens),
+            retry_after: if allowed { 0 } else { seconds_until(1.0 - self.tokens) },
+        }
+    }
+}
+
+#[async_trait]
+pub trait RateLimitStore {
+    async fn take(&self, key: &str, limit: &Limit) -> Result<Decision, DBError>;
+}
+
+/// How often, in seconds, `InMemoryStore` drops the buckets that are full again.
+const SWEEP_INTERVAL_SECONDS: i64 = 60;
+
+/// Buckets kept in this process; limits are per instance. Buckets that are full again are
+/// dropped, since a missing bucket starts out full anyway.
+#[derive(Default)]
+pub struct InMemoryStore {
+    buckets: Mutex<InMemoryBuckets>,
+}
+
+#[derive(Default)]
+struct InMemoryBuckets {
+    /// Each bucket with the seconds it takes, from its last update, to be full again.
+    by_key: HashMap<String, (Bucket, u64)>,
+    swept_at: Option<OffsetDateTime>,
+}
+
+impl InMemoryStore {
+    async fn take_at(&self, key: &str, limit: &Limit, now: OffsetDateTime) -> Decision {
+        let mut buckets = self.buckets.lock().await;
+
+        if buckets.swept_at.is_none_or(|swept_at| (now - swept_at).whole_seconds() >= SWEEP_INTERVAL_SECONDS) {
+            buckets.by_key.retain(|_, (bucket, reset)| {
+                u64::try_from((now - bucket.updated_at).whole_seconds()).map_or(true, |elapsed| elapsed < *reset)
+            });
+            buckets.swept_at = Some(now);
+        }
+
+        let (bucket, reset) = buckets
+            .by_key
+            .entry(key.to_owned())
+            .or_insert_with(|| (Bucket::full(limit, now), 0));
+        let decision = bucket.take(limit, now);
+        *reset = decision.reset;
+
+        decision
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryStore {
+    async fn take(&self, key: &str, limit: &Limit) -> Result<Decision, DBError> {
+        Ok(self.take_at(key, limit, OffsetDateTime::now_utc()).await)
+    }
+}
+
+/// Buckets stored in `rate_limit_buckets`, shared by every instance using the same database.
+pub struct PostgresStore {
+    db: PgPool,
+}
+
+impl PostgresStore {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Deletes the buckets left untouched for longer than `refill_window`, which are full again,
+    /// returning how many were removed.
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "DELETE FROM rate_limit_buckets"))]
+    pub async fn prune(&self, refill_window: Duration) -> Result<u64, DBError> {
+        let result = sqlx::query!(
+                "DELETE FROM rate_limit_buckets WHERE updated_at < CURRENT_TIMESTAMP - make_interval(secs => $1)",
+                refill_window.as_secs_f64()
+            )
+            .execute(&self.db)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Prunes `rate_limit_buckets` as the `prune_rate_limit_buckets` job, scheduled in `[jobs.schedules]`.
+pub struct PruneRateLimitBucketsJob {
+    refill_window: Option<Duration>,
+    store: PostgresStore,
+}
+
+impl PruneRateLimitBucketsJob {
+    pub fn new(db: PgPool, config: &RateLimitConfig) -> Self {
+        Self {
+            refill_window: config.refill_window(),
+            store: PostgresStore::new(db),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl JobHandler for PruneRateLimitBucketsJob {
+    fn kind(&self) -> &'static str {
+        "prune_rate_limit_buckets"
+    }
+
+    async fn run(&self, _payload: &JsonValue) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Buckets that never refill stay empty, so dropping them would hand out a fresh budget.
+        let Some(refill_window) = self.refill_window else {
+            return Ok(());
+        };
+        let pruned = self.store.prune(refill_window).await?;
+        if pruned > 0 {
+            info!(buckets = pruned, "Pruned full rate limit buckets");
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresStore {
+    #[instrument(name = "db.query", skip_all, fields(otel.kind = "client", db.system = "postgresql", db.statement = "UPDATE rate_limit_buckets"))]
+    async fn take(&self, key: &str, limit: &Limit) -> Result<Decision, DBError> {
+        let now = OffsetDateTime::now_utc();
+        let mut tx = self.db.begin().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        sqlx::query!(
+            "INSERT INTO rate_limit_buckets (bucket_key, tokens, updated_at) VALUES ($1, $2, $3) ON CONFLICT (bucket_key) DO NOTHING",
+            key,
+            f64::from(limit.capacity),
+            now
+        )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        // Row lock serialises concurrent requests for the same key across instances.
+        let record = sqlx::query!(
+            "SELECT tokens, updated_at FROM rate_limit_buckets WHERE bucket_key = $1 FOR UPDATE",
+            key
+        )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        let mut bucket = Bucket {
+            tokens: record.tokens,
+            updated_at: record.updated_at,
+        };
+        let decision = bucket.take(limit, now);
+
+        sqlx::query!(
+            "UPDATE rate_limit_buckets SET tokens = $2, updated_at = $3 WHERE bucket_key = $1",
+            key,
+            bucket.tokens,
+            bucket.updated_at
+        )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DBError::Other(Box::new(e)))?;
+
+        tx.commit().await.map_err(|e| DBError::Other(Box::new(e)))?;
+
+        Ok(decision)
+    }
+}
+
+/// Identifies the caller a bucket belongs to: the user, when the API key authenticates one, the
+/// client IP otherwise, so made-up keys can't be used to get fresh buckets.
+pub async fn client_key(request: &Request<'_>) -> String {
+    if let Ok(actor) = authentication(request).await {
+        return format!("user:{}", actor.user_uuid);
+    }
+
+    match request.client_ip() {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_owned(),
+    }
+}
+
+fn is_read(method: Method) -> bool {
+    matches!(method, Method::Get | Method::Head | Method::Options)
+}
+
+struct RateLimiter {
+    config: RateLimitConfig,
+    store: Box<dyn RateLimitStore + Send + Sync>,
+}
+
+/// Decision cached for the current request by `RateLimitFairing`.
+struct CachedDecision(Option<Decision>);
+
+/// Takes a token for every request and reports the budget in `RateLimit-*` headers.
+/// Routes opt into enforcement with the `RateLimit` guard.
+pub struct RateLimitFairing {
+    db: PgPool,
+}
+
+impl RateLimitFairing {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimitFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Per-client rate limiting",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = if rocket.figment().contains("rate_limit") {
+            match rocket.figment().extract_inner::<RateLimitConfig>("rate_limit") {
+                Ok(config) => config,
+                Err(err) => {
+                    error!("Invalid rate limit configuration: {}", err);
+                    return Err(rocket);
+                }
+            }
+        } else {
+            RateLimitConfig::default()
+        };
+
+        let store: Box<dyn RateLimitStore + Send + Sync> = match config.store {
+            StoreKind::Memory => Box::new(InMemoryStore::default()),
+            StoreKind::Postgres => Box::new(PostgresStore::new(self.db.clone())),
+        };
+
+        Ok(rocket.manage(RateLimiter { config, store }))
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(limiter) = request.rocket().state::<RateLimiter>() else {
+            return;
+        };
+        if !limiter.config.enabled {
+            return;
+        }
+
+        let (scope, limit) = if is_read(request.method()) {
+            ("read", &limiter.config.reads)
+        } else {
+            ("write", &limiter.config.writes)
+        };
+        let key = format!("{}:{}", scope, client_key(request).await);
+
+        let decision = match limiter.store.take(&key, limit).await {
+            Ok(decision) => Some(decision),
+            Err(err) => {
+                // Fail open: an unavailable bucket store should not take the API down with it.
+                error!("Rate limit store error: {:?}", err);
+                None
+            }
+        };
+
+        request.local_cache(move || CachedDecision(decision));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(decision) = request.local_cache(|| CachedDecision(None)).0 else {
+            return;
+        };
+
+        response.set_header(Header::new("RateLimit-Limit", decision.limit.to_string()));
+        response.set_header(Header::new("RateLimit-Remaining", decision.remaining.to_string()));
+        response.set_header(Header::new("RateLimit-Reset", decision.reset.to_string()));
+        if !decision.allowed {
+            response.set_header(Header::new("Retry-After", decision.retry_after.to_string()));
+        }
+    }
+}
+
+/// Request guard failing with `429 Too Many Requests` once the caller's bucket is empty.
+pub struct RateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimit {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.local_cache(|| CachedDecision(None)).0 {
+            Some(decision) if !decision.allowed => Outcome::Error((Status::TooManyRequests, ())),
+            _ => Outcome::Success(RateLimit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::auth::{hash_api_key, API_KEY_HEADER};
+    use crate::models::User;
+    use crate::persistance::users_dao::{UsersDao, UsersDaoImpl};
+    use rocket::figment::providers::Serialized;
+    use rocket::local::asynchronous::Client;
+    use std::time::Duration;
+
+    const LIMIT: Limit = Limit {
+        capacity: 2,
+        refill_per_minute: 60,
+    };
+
+    #[test]
+    fn bucket_should_refill_over_time() {
+        let start = OffsetDateTime::now_utc();
+        let mut bucket = Bucket::full(&LIMIT, start);
+
+        assert!(bucket.take(&LIMIT, start).allowed);
+        assert!(bucket.take(&LIMIT, start).allowed);
+
+        let denied = bucket.take(&LIMIT, start);
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert_eq!(denied.retry_after, 1);
+        assert_eq!(denied.reset, 2);
+
+        let later = bucket.take(&LIMIT, start + Duration::from_secs(1));
+        assert!(later.allowed);
+    }
+
+    #[get("/limited")]
+    fn limited(_rate_limit: RateLimit) -> &'static str {
+        "ok"
+    }
+
+    #[post("/limited")]
+    fn limited_write(_rate_limit: RateLimit) -> &'static str {
+        "ok"
+    }
+
+    async fn client(pool: PgPool, config: RateLimitConfig) -> Client {
+        let figment = rocket::Config::figment().merge(Serialized::default("rate_limit", config));
+        let users_dao: Box<dyn UsersDao + Send + Sync> = Box::new(UsersDaoImpl::new(pool.clone()));
+        let rocket = rocket::custom(figment)
+            .mount("/", routes![limited, limited_write])
+            .manage(users_dao)
+            .attach(RateLimitFairing::new(pool));
+        Client::tracked(rocket).await.expect("valid rocket instance")
+    }
+
+    fn config(store: StoreKind) -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            store,
+            reads: LIMIT,
+            writes: Limit {
+                capacity: 1,
+                refill_per_minute: 1,
+            },
+        }
+    }
+
+    #[sqlx::test]
+    async fn should_reject_with_429_once_budget_is_spent(pool: PgPool) {
+        let client = client(pool, config(StoreKind::Memory)).await;
+
+        for remaining in ["1", "0"] {
+            let response = client.get("/limited").dispatch().await;
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.headers().get_one("RateLimit-Limit"), Some("2"));
+            assert_eq!(response.headers().get_one("RateLimit-Remaining"), Some(remaining));
+        }
+
+        let response = client.get("/limited").dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("1"));
+    }
+
+    #[sqlx::test]
+    async fn should_keep_separate_budgets_for_reads_writes_and_clients(pool: PgPool) {
+        UsersDaoImpl::new(pool.clone())
+            .create_user(User { username: "known".to_owned() }, hash_api_key("known-key"))
+            .await
+            .expect("Error creating test user");
+        let client = client(pool, config(StoreKind::Memory)).await;
+
+        assert_eq!(client.post("/limited").dispatch().await.status(), Status::Ok);
+        assert_eq!(
+            client.post("/limited").dispatch().await.status(),
+            Status::TooManyRequests
+        );
+
+        // Reads and authenticated users are unaffected, but unknown keys share the IP's bucket.
+        assert_eq!(client.get("/limited").dispatch().await.status(), Status::Ok);
+        let response = client
+            .post("/limited")
+            .header(Header::new(API_KEY_HEADER, "made-up-key"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let response = client
+            .post("/limited")
+            .header(Header::new(API_KEY_HEADER, "known-key"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn in_memory_store_should_drop_full_buckets() {
+        let store = InMemoryStore::default();
+        let start = OffsetDateTime::now_utc();
+
+        store.take_at("read:ip:127.0.0.1", &LIMIT, start).await;
+        store.take_at("read:ip:10.0.0.1", &LIMIT, start + Duration::from_secs(61)).await;
+
+        let buckets = store.buckets.lock().await;
+        assert_eq!(buckets.by_key.keys().collect::<Vec<_>>(), ["read:ip:10.0.0.1"]);
+    }
+
+    #[sqlx::test]
+    async fn postgres_store_should_prune_full_buckets(pool: PgPool) {
+        let store = PostgresStore::new(pool.clone());
+        store.take("write:ip:127.0.0.1", &LIMIT).await.unwrap();
+        store.take("write:ip:10.0.0.1", &LIMIT).await.unwrap();
+        sqlx::query!("UPDATE rate_limit_buckets SET updated_at = updated_at - INTERVAL '1 minute' WHERE bucket_key = 'write:ip:127.0.0.1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(store.prune(LIMIT.refill_window().unwrap()).await.unwrap(), 1);
+        let keys = sqlx::query_scalar!("SELECT bucket_key FROM rate_limit_buckets").fetch_all(&pool).await.unwrap();
+        assert_eq!(keys, ["write:ip:10.0.0.1"]);
+    }
+
+    #[sqlx::test]
+    async fn postgres_store_should_share_buckets(pool: PgPool) {
+        let first = PostgresStore::new(pool.clone());
+        let second = PostgresStore::new(pool);
+
+        assert!(first.take("write:ip:127.0.0.1", &LIMIT).await.unwrap().allowed);
+        assert!(second.take("write:ip:127.0.0.1", &LIMIT).await.unwrap().allowed);
+
+        let decision = first.take("write:ip:127.0.0.1", &LIMIT).await.unwrap();
+        assert!(!decision.allowed);
+        assert!(second.take("write:ip:10.0.0.1", &LIMIT).await.unwrap().allowed);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::JsonValue;
+use sqlx::PgPool;
+
+use crate::jobs::JobHandler;
+use crate::persistance::reputation_dao::{ReputationDao, ReputationDaoImpl};
+
+/// The `[reputation]` table of the Rocket configuration: the reputation needed for each
+/// privilege. Moderators have every privilege regardless.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReputationConfig {
+    pub upvote: i32,
+    pub downvote: i32,
+    /// To vote to close or reopen questions.
+    pub close: i32,
+    /// To flag posts for moderators.
+    pub flag: i32,
+    /// To edit other users' posts and review suggested edits.
+    pub edit: i32,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            upvote: 15,
+            downvote: 125,
+            close: 250,
+            flag: 15,
+            edit: 2000,
+        }
+    }
+}
+
+/// Recomputes the cached reputation totals from the ledger, as the `recompute_reputation` job.
+/// Totals are kept up to date as entries are added, so this only repairs drift.
+pub struct RecomputeReputationJob {
+    reputation_dao: ReputationDaoImpl,
+}
+
+impl RecomputeReputationJob {
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            reputation_dao: ReputationDaoImpl::new(db),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl JobHandler for RecomputeReputationJob {
+    fn kind(&self) -> &'static str {
+        "recompute_reputation"
+    }
+
+    async fn run(&self, _payload: &JsonValue) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let fixed = self.reputation_dao.recompute().await?;
+        if fixed > 0 {
+            warn!(users = fixed, "Fixed cached reputation totals that had drifted from the ledger");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Correlation ID of the current request. Taken from the `X-Request-Id` header when the
+/// client sends a sane one, generated otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    fn from_header(value: &str) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_REQUEST_ID_LEN
+            && value.bytes().all(|b| b.is_ascii_graphic());
+
+        valid.then(|| Self(value.to_owned()))
+    }
+
+    /// Returns the ID assigned by `RequestIdFairing`, or a fresh one when the fairing is not attached.
+    pub fn of<'r>(request: &'r Request<'_>) -> &'r RequestId {
+        request.local_cache(RequestId::generate)
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r RequestId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(request))
+    }
+}
+
+struct RequestStart(Instant);
+
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Assign and echo X-Request-Id",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let request_id = request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .and_then(RequestId::from_header)
+            .unwrap_or_else(RequestId::generate);
+
+        request.local_cache(move || request_id);
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_id = RequestId::of(request);
+        response.set_header(Header::new(REQUEST_ID_HEADER, request_id.0.clone()));
+
+        let started = request.local_cache(|| RequestStart(Instant::now()));
+        info!(
+            request_id = %request_id,
+            method = %request.method(),
+            uri = %request.uri(),
+            status = response.status().code,
+            elapsed_ms = started.0.elapsed().as_millis() as u64,
+            "request completed"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::local::asynchronous::Client;
+
+    #[get("/")]
+    fn echo(request_id: &RequestId) -> String {
+        request_id.to_string()
+    }
+
+    async fn client() -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![echo])
+            .attach(RequestIdFairing);
+        Client::tracked(rocket).await.expect("valid rocket instance")
+    }
+
+    #[tokio::test]
+    async fn should_echo_client_request_id() {
+        let client = client().await;
+
+        let response = client
+            .get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "abc-123"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.headers().get_one(REQUEST_ID_HEADER), Some("abc-123"));
+        assert_eq!(response.into_string().await.unwrap(), "abc-123");
+    }
+
+    #[tokio::test]
+    async fn should_generate_request_id_when_missing_or_invalid() {
+        let client = client().await;
+
+        let response = client
+            .get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "has spaces"))
+            .dispatch()
+            .await;
+
+        let header = response.headers().get_one(REQUEST_ID_HEADER).unwrap().to_owned();
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+        assert_eq!(response.into_string().await.unwrap(), header);
+    }
+}
@@ -0,0 +1,472 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::models::{AuditContext, DBError, PostType, ScreeningDecision};
+use crate::persistance::screening_dao::{ScreeningDao, ScreeningRecord};
+
+/// What a rule does with the posts it catches.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreeningAction {
+    Quarantine,
+    Reject,
+}
+
+impl ScreeningAction {
+    fn decision(self) -> ScreeningDecision {
+        match self {
+            ScreeningAction::Quarantine => ScreeningDecision::Quarantined,
+            ScreeningAction::Reject => ScreeningDecision::Rejected,
+        }
+    }
+}
+
+/// The `[screening]` table of the Rocket configuration, with a table per built-in rule.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ScreeningConfig {
+    pub links: LinksConfig,
+    pub banned_phrases: BannedPhrasesConfig,
+    pub repeated_content: RepeatedContentConfig,
+    pub burst: BurstConfig,
+}
+
+/// Caps the links in posts by users with little reputation, anonymous ones included.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct LinksConfig {
+    pub enabled: bool,
+    pub max_links: usize,
+    /// Users with at least this much reputation can post any number of links.
+    pub below_reputation: i32,
+    pub action: ScreeningAction,
+}
+
+impl Default for LinksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_links: 2,
+            below_reputation: 10,
+            action: ScreeningAction::Quarantine,
+        }
+    }
+}
+
+/// Catches posts containing any of `phrases`, ignoring case and whitespace.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct BannedPhrasesConfig {
+    pub enabled: bool,
+    pub phrases: Vec<String>,
+    pub action: ScreeningAction,
+}
+
+impl Default for BannedPhrasesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            phrases: vec![],
+            action: ScreeningAction::Reject,
+        }
+    }
+}
+
+/// Catches text already posted `max_copies` times within the window, by anyone.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct RepeatedContentConfig {
+    pub enabled: bool,
+    pub window_minutes: u64,
+    pub max_copies: i64,
+    pub action: ScreeningAction,
+}
+
+impl Default for RepeatedContentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_minutes: 60,
+            max_copies: 1,
+            action: ScreeningAction::Reject,
+        }
+    }
+}
+
+/// Catches authors who already made `max_posts` posts within the window.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct BurstConfig {
+    pub enabled: bool,
+    pub window_seconds: u64,
+    pub max_posts: i64,
+    pub action: ScreeningAction,
+}
+
+impl Default for BurstConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_seconds: 60,
+            max_posts: 5,
+            action: ScreeningAction::Quarantine,
+        }
+    }
+}
+
+/// Lowercases the text and collapses its whitespace, so trivial variations match.
+fn normalize(text: &str) -> String {
+    text.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A new question or answer to screen.
+#[derive(Debug, Clone)]
+pub struct Submission {
+    /// `None` for anonymous posts.
+    pub author_uuid: Option<String>,
+    /// 0 for anonymous posts.
+    pub reputation: i32,
+    pub target_type: PostType,
+    /// The title and description of a question, or the content of an answer.
+    pub text: String,
+    /// SHA-256 of the text, lowercased and with whitespace collapsed.
+    pub content_hash: String,
+}
+
+impl Submission {
+    pub fn new(author_uuid: Option<String>, reputation: i32, target_type: PostType, text: String) -> Self {
+        let content_hash = format!("{:x}", Sha256::digest(normalize(&text).as_bytes()));
+        Self {
+            author_uuid,
+            reputation,
+            target_type,
+            text,
+            content_hash,
+        }
+    }
+}
+
+/// Why a rule caught a post, and what to do with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub action: ScreeningAction,
+    pub reason: String,
+}
+
+/// The outcome of screening a post: `rule` and `reason` are set unless it was accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub decision: ScreeningDecision,
+    pub rule: Option<&'static str>,
+    pub reason: Option<String>,
+}
+
+impl Verdict {
+    pub fn accepted() -> Self {
+        Self {
+            decision: ScreeningDecision::Accepted,
+            rule: None,
+            reason: None,
+        }
+    }
+}
+
+/// A check new posts go through before they are created.
+#[async_trait]
+pub trait ContentRule {
+    /// Recorded with the decisions the rule makes.
+    fn name(&self) -> &'static str;
+    /// `None` to let the post through.
+    async fn check(&self, submission: &Submission, dao: &(dyn ScreeningDao + Send + Sync)) -> Result<Option<Finding>, DBError>;
+}
+
+pub struct LinksRule {
+    config: LinksConfig,
+    links: Regex,
+}
+
+impl LinksRule {
+    pub fn new(config: &LinksConfig) -> Self {
+        Self {
+            config: config.clone(),
+            links: Regex::new(r"(?i)\bhttps?://").expect("valid link pattern"),
+        }
+    }
+}
+
+#[async_trait]
+impl ContentRule for LinksRule {
+    fn name(&self) -> &'static str {
+        "links"
+    }
+
+    async fn check(&self, submission: &Submission, _: &(dyn ScreeningDao + Send + Sync)) -> Result<Option<Finding>, DBError> {
+        if submission.reputation >= self.config.below_reputation {
+            return Ok(None);
+        }
+        let links = self.links.find_iter(&submission.text).count();
+        Ok((links > self.config.max_links).then(|| Finding {
+            action: self.config.action,
+            reason: format!("{} links, but new users can post at most {}", links, self.config.max_links),
+        }))
+    }
+}
+
+pub struct BannedPhrasesRule {
+    config: BannedPhrasesConfig,
+}
+
+impl BannedPhrasesRule {
+    pub fn new(config: &BannedPhrasesConfig) -> Self {
+        let mut config = config.clone();
+        config.phrases = config.phrases.iter().map(|phrase| normalize(phrase)).collect();
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl ContentRule for BannedPhrasesRule {
+    fn name(&self) -> &'static str {
+        "banned_phrases"
+    }
+
+    async fn check(&self, submission: &Submission, _: &(dyn ScreeningDao + Send + Sync)) -> Result<Option<Finding>, DBError> {
+        let text = normalize(&submission.text);
+        Ok(self.config.phrases.iter().find(|phrase| text.contains(phrase.as_str())).map(|phrase| Finding {
+            action: self.config.action,
+            reason: format!("contains the banned phrase \"{}\"", phrase),
+        }))
+    }
+}
+
+pub struct RepeatedContentRule {
+    config: RepeatedContentConfig,
+}
+
+impl RepeatedContentRule {
+    pub fn new(config: &RepeatedContentConfig) -> Self {
+        Self { config: config.clone() }
+    }
+}
+
+#[async_trait]
+impl ContentRule for RepeatedContentRule {
+    fn name(&self) -> &'static str {
+        "repeated_content"
+    }
+
+    async fn check(&self, submission: &Submission, dao: &(dyn ScreeningDao + Send + Sync)) -> Result<Option<Finding>, DBError> {
+        let window = Duration::from_secs(self.config.window_minutes * 60);
+        let copies = dao.count_repeats(submission.content_hash.clone(), window).await?;
+        Ok((copies >= self.config.max_copies).then(|| Finding {
+            action: self.config.action,
+            reason: format!("the same text was already posted in the last {} minutes", self.config.window_minutes),
+        }))
+    }
+}
+
+pub struct BurstRule {
+    config: BurstConfig,
+}
+
+impl BurstRule {
+    pub fn new(config: &BurstConfig) -> Self {
+        Self { config: config.clone() }
+    }
+}
+
+#[async_trait]
+impl ContentRule for BurstRule {
+    fn name(&self) -> &'static str {
+        "burst"
+    }
+
+    async fn check(&self, submission: &Submission, dao: &(dyn ScreeningDao + Send + Sync)) -> Result<Option<Finding>, DBError> {
+        // Anonymous posts are only rate limited per client
+        let Some(author) = &submission.author_uuid else {
+            return Ok(None);
+        };
+        let window = Duration::from_secs(self.config.window_seconds);
+        let posts = dao.count_recent_posts(author.clone(), window).await?;
+        Ok((posts >= self.config.max_posts).then(|| Finding {
+            action: self.config.action,
+            reason: format!("{} posts in the last {} seconds", posts, self.config.window_seconds),
+        }))
+    }
+}
+
+/// Runs new posts through the registered rules and logs every decision.
+pub struct ContentScreener {
+    rules: Vec<Box<dyn ContentRule + Send + Sync>>,
+    dao: Box<dyn ScreeningDao + Send + Sync>,
+    /// Passed on when quarantining, see `ScreeningDao::record_decision`.
+    hide_after_spam_flags: i64,
+}
+
+impl ContentScreener {
+    pub fn new(dao: Box<dyn ScreeningDao + Send + Sync>, hide_after_spam_flags: i64) -> Self {
+        Self {
+            rules: Vec::new(),
+            dao,
+            hide_after_spam_flags,
+        }
+    }
+
+    /// A screener with the built-in rules enabled in `config`.
+    pub fn from_config(config: &ScreeningConfig, dao: Box<dyn ScreeningDao + Send + Sync>, hide_after_spam_flags: i64) -> Self {
+        let mut screener = Self::new(dao, hide_after_spam_flags);
+        if config.links.enabled {
+            screener = screener.register(LinksRule::new(&config.links));
+        }
+        if config.banned_phrases.enabled {
+            screener = screener.register(BannedPhrasesRule::new(&config.banned_phrases));
+        }
+        if config.repeated_content.enabled {
+            screener = screener.register(RepeatedContentRule::new(&config.repeated_content));
+        }
+        if config.burst.enabled {
+            screener = screener.register(BurstRule::new(&config.burst));
+        }
+        screener
+    }
+
+    /// Adds a rule; rules are checked in the order they are registered.
+    pub fn register(mut self, rule: impl ContentRule + Send + Sync + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Checks the post against every rule. The first rule rejecting it decides, or else the first
+    /// quarantining it.
+    pub async fn screen(&self, submission: &Submission) -> Result<Verdict, DBError> {
+        let mut verdict = Verdict::accepted();
+        for rule in &self.rules {
+            let Some(finding) = rule.check(submission, self.dao.as_ref()).await? else {
+                continue;
+            };
+            let decision = finding.action.decision();
+            if decision > verdict.decision {
+                verdict = Verdict {
+                    decision,
+                    rule: Some(rule.name()),
+                    reason: Some(finding.reason),
+                };
+            }
+            if decision == ScreeningDecision::Rejected {
+                break;
+            }
+        }
+        Ok(verdict)
+    }
+
+    /// Logs the verdict on the post, created as `target_uuid` unless it was rejected, quarantining
+    /// it if need be.
+    pub async fn record(&self, submission: Submission, verdict: &Verdict, target_uuid: Option<String>, context: AuditContext) -> Result<(), DBError> {
+        match verdict.decision {
+            ScreeningDecision::Accepted => debug!(target_uuid = ?target_uuid, "Post accepted by screening"),
+            decision => warn!(
+                target_uuid = ?target_uuid,
+                author_uuid = ?submission.author_uuid,
+                rule = verdict.rule,
+                reason = verdict.reason.as_deref(),
+                "Post {} by screening",
+                decision.as_str()
+            ),
+        }
+
+        let record = ScreeningRecord {
+            author_uuid: submission.author_uuid,
+            target_type: submission.target_type,
+            target_uuid,
+            decision: verdict.decision,
+            rule: verdict.rule.map(str::to_owned),
+            reason: verdict.reason.clone(),
+            content_hash: submission.content_hash,
+        };
+        self.dao.record_decision(record, self.hide_after_spam_flags, context).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reports the same counts for every query.
+    struct ScreeningDaoMock {
+        recent_posts: i64,
+        repeats: i64,
+    }
+
+    #[async_trait]
+    impl ScreeningDao for ScreeningDaoMock {
+        async fn count_recent_posts(&self, _: String, _: Duration) -> Result<i64, DBError> {
+            Ok(self.recent_posts)
+        }
+        async fn count_repeats(&self, _: String, _: Duration) -> Result<i64, DBError> {
+            Ok(self.repeats)
+        }
+        async fn record_decision(&self, _: ScreeningRecord, _: i64, _: AuditContext) -> Result<(), DBError> {
+            Ok(())
+        }
+    }
+
+    fn screener(recent_posts: i64, repeats: i64) -> ContentScreener {
+        let config = ScreeningConfig {
+            banned_phrases: BannedPhrasesConfig {
+                phrases: vec!["Cheap Pills".to_owned()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        ContentScreener::from_config(&config, Box::new(ScreeningDaoMock { recent_posts, repeats }), 3)
+    }
+
+    fn submission(reputation: i32, text: &str) -> Submission {
+        Submission::new(Some("789".to_owned()), reputation, PostType::Answer, text.to_owned())
+    }
+
+    #[test]
+    fn submissions_should_hash_normalized_text() {
+        let first = submission(1, "How do I  parse\nJSON?");
+        let second = submission(1, "how do i parse json?");
+
+        assert_eq!(first.content_hash, second.content_hash);
+        assert_ne!(first.content_hash, submission(1, "How do I parse TOML?").content_hash);
+    }
+
+    #[tokio::test]
+    async fn links_should_only_be_limited_for_new_users() {
+        let text = "see https://a.example, http://b.example and HTTPS://c.example";
+
+        let verdict = screener(0, 0).screen(&submission(1, text)).await.unwrap();
+        assert_eq!(verdict.decision, ScreeningDecision::Quarantined);
+        assert_eq!(verdict.rule, Some("links"));
+
+        let verdict = screener(0, 0).screen(&submission(10, text)).await.unwrap();
+        assert_eq!(verdict, Verdict::accepted());
+    }
+
+    #[tokio::test]
+    async fn rejections_should_win_over_quarantines() {
+        // Both a burst (quarantine) and a banned phrase (reject)
+        let verdict = screener(5, 0).screen(&submission(100, "buy CHEAP   pills here")).await.unwrap();
+
+        assert_eq!(verdict.decision, ScreeningDecision::Rejected);
+        assert_eq!(verdict.rule, Some("banned_phrases"));
+    }
+
+    #[tokio::test]
+    async fn repeated_content_and_bursts_should_use_the_counts() {
+        let verdict = screener(0, 1).screen(&submission(100, "hello")).await.unwrap();
+        assert_eq!(verdict.rule, Some("repeated_content"));
+
+        let verdict = screener(4, 0).screen(&submission(100, "hello")).await.unwrap();
+        assert_eq!(verdict, Verdict::accepted());
+        let verdict = screener(5, 0).screen(&submission(100, "hello")).await.unwrap();
+        assert_eq!(verdict.rule, Some("burst"));
+    }
+}
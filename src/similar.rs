@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// The `[similar_questions]` table of the Rocket configuration, for the duplicates suggested while
+/// asking a question.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct SimilarQuestionsConfig {
+    /// The trigram similarity, from 0 to 1, the title or description must reach to be suggested.
+    pub min_similarity: f32,
+    /// How many questions to suggest at most.
+    pub limit: i64,
+}
+
+impl Default for SimilarQuestionsConfig {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.3,
+            limit: 5,
+        }
+    }
+}
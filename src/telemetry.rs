@@ -0,0 +1,310 @@
+use opentelemetry::{global, propagation::Extractor, trace::TracerProvider};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::figment::Figment;
+use rocket::http::HeaderMap;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use serde::Deserialize;
+use std::convert::Infallible;
+use tracing::{field::Empty, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::request_id::RequestId;
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+/// Settings read from the root of the Rocket figment (`Rocket.toml` or `ROCKET_*` env vars).
+#[derive(Deserialize, Debug)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub log_format: LogFormat,
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`.
+    /// Spans are not exported anywhere when unset.
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+    #[serde(default = "default_service_name")]
+    pub otel_service_name: String,
+}
+
+fn default_service_name() -> String {
+    env!("CARGO_PKG_NAME").to_owned()
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            log_format: LogFormat::default(),
+            otlp_endpoint: None,
+            otel_service_name: default_service_name(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    pub fn from_figment(figment: &Figment) -> Self {
+        // The subscriber is not installed yet, so a bad value can only be reported on stderr.
+        figment.extract().unwrap_or_else(|err| {
+            eprintln!("Invalid telemetry configuration, using defaults: {}", err);
+            Self::default()
+        })
+    }
+}
+
+/// Handle on the installed exporters; `shutdown` flushes spans that are still buffered.
+pub struct Telemetry {
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    pub fn shutdown(&self) {
+        if let Some(provider) = &self.tracer_provider {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("Failed to flush traces: {}", err);
+            }
+        }
+    }
+}
+
+/// Installs the global `tracing` subscriber. Records emitted through the `log` crate
+/// (Rocket, sqlx) are forwarded to it as well. Filtering follows `RUST_LOG`.
+pub fn init(config: &TelemetryConfig) -> Telemetry {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt_layer = match config.log_format {
+        LogFormat::Human => fmt::layer().boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+
+    let tracer_provider = config.otlp_endpoint.as_ref().and_then(|endpoint| {
+        let exporter = match opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+        {
+            Ok(exporter) => exporter,
+            Err(err) => {
+                eprintln!("Unable to build OTLP span exporter, not exporting traces: {}", err);
+                return None;
+            }
+        };
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.otel_service_name.clone())
+                    .build(),
+            )
+            .build();
+        Some(provider)
+    });
+    let otel_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otel_layer)
+        .init();
+
+    Telemetry { tracer_provider }
+}
+
+struct HeaderExtractor<'a, 'h>(&'a HeaderMap<'h>);
+
+impl Extractor for HeaderExtractor<'_, '_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get_one(key)
+    }
+
+    // Rocket's header iterator cannot lend names for `&self`; the W3C propagator only
+    // reads the two fields below anyway.
+    fn keys(&self) -> Vec<&str> {
+        ["traceparent", "tracestate"]
+            .into_iter()
+            .filter(|key| self.0.contains(*key))
+            .collect()
+    }
+}
+
+/// Root span of a request. Routes use it as the explicit parent of their own span, since
+/// Rocket does not poll handlers inside it.
+pub struct RequestSpan(pub Span);
+
+impl RequestSpan {
+    pub fn of<'r>(request: &'r Request<'_>) -> &'r RequestSpan {
+        request.local_cache(|| RequestSpan(Span::none()))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r RequestSpan {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestSpan::of(request))
+    }
+}
+
+/// Opens a server span per request, continuing the caller's trace when a W3C `traceparent`
+/// header is present. Must be attached after `RequestIdFairing` so the span carries its ID.
+pub struct TracingFairing;
+
+#[rocket::async_trait]
+impl Fairing for TracingFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Trace requests",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+
+        let span = info_span!(
+            "HTTP request",
+            otel.name = Empty,
+            otel.kind = "server",
+            otel.status_code = Empty,
+            http.request.method = %request.method(),
+            url.path = %request.uri().path(),
+            http.route = Empty,
+            http.response.status_code = Empty,
+            request_id = %RequestId::of(request),
+        );
+        span.set_parent(parent);
+
+        request.local_cache(move || RequestSpan(span));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let span = &RequestSpan::of(request).0;
+        let status = response.status();
+
+        match request.route() {
+            Some(route) => {
+                span.record("http.route", route.uri.to_string());
+                span.record("otel.name", format!("{} {}", request.method(), route.uri));
+            }
+            None => {
+                span.record("otel.name", request.method().as_str());
+            }
+        }
+        span.record("http.response.status_code", status.code);
+        if status.code >= 500 {
+            span.record("otel.status_code", "ERROR");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use opentelemetry::trace::{SpanId, TraceId};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SpanData};
+    use rocket::http::Header;
+    use rocket::local::asynchronous::Client;
+    use sqlx::PgPool;
+
+    use crate::models::QuestionFilter;
+    use crate::persistance::questions_dao::{QuestionsDao, QuestionsDaoImpl};
+    use crate::request_id::RequestIdFairing;
+
+    /// Collects finished spans in memory instead of shipping them to a collector.
+    fn in_process_collector() -> (InMemorySpanExporter, SdkTracerProvider, tracing::subscriber::DefaultGuard) {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        (exporter, provider, tracing::subscriber::set_default(subscriber))
+    }
+
+    fn find<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+        spans
+            .iter()
+            .find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("span {} was not exported", name))
+    }
+
+    #[get("/traced")]
+    #[instrument(name = "route", parent = &request_span.0, skip_all)]
+    async fn traced(request_span: &RequestSpan) -> &'static str {
+        "ok"
+    }
+
+    #[tokio::test]
+    async fn should_continue_trace_from_traceparent_header() {
+        let (exporter, provider, _guard) = in_process_collector();
+
+        let rocket = rocket::build()
+            .mount("/", routes![traced])
+            .attach(RequestIdFairing)
+            .attach(TracingFairing);
+        let client = Client::tracked(rocket).await.expect("valid rocket instance");
+
+        let response = client
+            .get("/traced")
+            .header(Header::new(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ))
+            .dispatch()
+            .await;
+        assert_eq!(response.into_string().await.unwrap(), "ok");
+
+        provider.force_flush().unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+
+        let request_span = find(&spans, "GET /traced");
+        assert_eq!(
+            request_span.span_context.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+        assert_eq!(request_span.parent_span_id, SpanId::from_hex("00f067aa0ba902b7").unwrap());
+
+        let route_span = find(&spans, "route");
+        assert_eq!(route_span.parent_span_id, request_span.span_context.span_id());
+    }
+
+    #[sqlx::test]
+    async fn dao_queries_should_be_traced(pool: PgPool) {
+        let (exporter, provider, _guard) = in_process_collector();
+
+        let questions_dao = QuestionsDaoImpl::new(pool);
+        questions_dao.get_questions(QuestionFilter::default()).await.unwrap();
+
+        provider.force_flush().unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+
+        let query_span = find(&spans, "db.query");
+        assert!(query_span
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "db.system" && kv.value.as_str() == "postgresql"));
+    }
+}
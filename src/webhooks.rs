@@ -0,0 +1,510 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::futures::future::join_all;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect;
+use reqwest::Url;
+use rocket::{Orbit, Rocket};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+
+use crate::drain::spawn_until_shutdown;
+use crate::events::QuestionEventsHub;
+use crate::models::DBError;
+use crate::persistance::webhooks_dao::{DeliveryOutcome, PendingDelivery, WebhooksDao, WebhooksDaoImpl};
+
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// `sha256=<hex HMAC-SHA256 of "<timestamp>.<body>" keyed with the subscription secret>`.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// The `[webhooks]` table of the Rocket configuration.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    /// Attempts after which a delivery is moved to the dead letters.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after every further failure up to `max_delay_seconds`.
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+    pub timeout_seconds: u64,
+    /// How often due deliveries are looked for when no new events wake the dispatcher up.
+    pub poll_interval_seconds: u64,
+    pub batch_size: i64,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_delay_seconds: 10,
+            max_delay_seconds: 60 * 60,
+            timeout_seconds: 10,
+            poll_interval_seconds: 5,
+            batch_size: 20,
+        }
+    }
+}
+
+impl WebhooksConfig {
+    /// How long to wait after the `attempts`th failed attempt, or `None` to give up.
+    pub fn retry_delay(&self, attempts: u32) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let delay = self
+            .base_delay_seconds
+            .saturating_mul(1 << attempts.saturating_sub(1).min(32))
+            .min(self.max_delay_seconds);
+        Some(Duration::from_secs(delay))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+}
+
+/// Whether `ip` can be reached over the public internet. Webhooks are only delivered to such
+/// addresses, so subscriptions can't be used to reach the API's own network or cloud metadata.
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network", carrier-grade NAT, IETF protocol assignments, benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a, b, c) == (192, 0, 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let [first, second, ..] = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, link-local and documentation
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first, second) == (0x2001, 0xdb8))
+}
+
+/// The address a URL names directly, skipping DNS resolution and thus `PublicResolver`.
+fn literal_address(url: &Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host).parse().ok()
+}
+
+/// Rejects webhook URLs that obviously point at a non-public host. Hostnames are checked again
+/// when delivering, once they are resolved.
+pub fn validate_url(url: &Url) -> Result<(), String> {
+    if let Some(ip) = literal_address(url) {
+        if !is_public_address(ip) {
+            return Err(format!("Webhooks can't be delivered to the non-public address {}", ip));
+        }
+    }
+    let is_localhost = |host: &str| host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost");
+    if url.host_str().is_some_and(is_localhost) {
+        return Err("Webhooks can't be delivered to localhost".to_owned());
+    }
+    Ok(())
+}
+
+/// Resolves webhook hosts, failing for any host with a non-public address so a hostname can't be
+/// pointed at an internal service. The connection then uses the addresses checked here.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(address) = addresses.iter().find(|address| !is_public_address(address.ip())) {
+                return Err(format!("{} resolves to the non-public address {}", name.as_str(), address.ip()).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The client deliveries are made with: it only connects to public addresses and doesn't follow
+/// redirects, which could lead anywhere.
+pub fn client(config: &WebhooksConfig) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(config.timeout())
+        .redirect(redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+}
+
+/// The `SIGNATURE_HEADER` of a delivery.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// The JSON body of a delivery.
+#[derive(Serialize, Debug)]
+struct DeliveryBody<'a> {
+    delivery_id: i64,
+    event: &'a str,
+    event_id: i64,
+    /// The `QuestionDetail` or `AnswerDetail` of the event, or its `VoteEvent` or `AcceptEvent`.
+    data: &'a serde_json::Value,
+}
+
+async fn attempt(client: &reqwest::Client, delivery: &PendingDelivery) -> Result<i32, (Option<i32>, String)> {
+    let body = serde_json::to_vec(&DeliveryBody {
+        delivery_id: delivery.delivery_id,
+        event: &delivery.event_type,
+        event_id: delivery.event_id,
+        data: &delivery.payload,
+    })
+    .map_err(|err| (None, err.to_string()))?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let url = Url::parse(&delivery.url).map_err(|err| (None, err.to_string()))?;
+    if let Some(ip) = literal_address(&url).filter(|ip| !is_public_address(*ip)) {
+        return Err((None, format!("{} is not a public address", ip)));
+    }
+
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(DELIVERY_HEADER, delivery.delivery_id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, sign(&delivery.secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| (None, err.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(i32::from(status.as_u16()))
+    } else {
+        Err((Some(i32::from(status.as_u16())), format!("Unexpected status {}", status)))
+    }
+}
+
+/// Attempts one batch of due deliveries, concurrently, and returns how many were attempted.
+pub async fn deliver_due(
+    config: &WebhooksConfig,
+    client: &reqwest::Client,
+    webhooks_dao: &(dyn WebhooksDao + Send + Sync),
+) -> Result<usize, DBError> {
+    // Leave each attempt enough time to finish before another instance may claim it again
+    let lease = config.timeout() * 2;
+    let deliveries = webhooks_dao.claim_due(config.batch_size, lease).await?;
+
+    let attempts = deliveries.iter().map(|delivery| async move {
+        let outcome = match attempt(client, delivery).await {
+            Ok(status_code) => DeliveryOutcome::Delivered { status_code },
+            Err((status_code, error)) => {
+                let attempts = u32::try_from(delivery.attempts).unwrap_or(0) + 1;
+                let retry_in = config.retry_delay(attempts);
+                if retry_in.is_none() {
+                    warn!(delivery_id = delivery.delivery_id, attempts, "Giving up on webhook delivery: {}", error);
+                }
+                DeliveryOutcome::Failed { status_code, error, retry_in }
+            }
+        };
+        if let Err(err) = webhooks_dao.record_attempt(delivery.delivery_id, outcome).await {
+            error!("Error recording webhook delivery attempt: {:?}", err);
+        }
+    });
+    join_all(attempts).await;
+
+    Ok(deliveries.len())
+}
+
+/// Delivers queued webhooks in the background once Rocket has launched.
+pub struct WebhooksFairing {
+    db: PgPool,
+}
+
+impl WebhooksFairing {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for WebhooksFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Webhook deliveries",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let config = rocket
+            .figment()
+            .extract_inner::<WebhooksConfig>("webhooks")
+            .unwrap_or_default();
+        let client = match client(&config) {
+            Ok(client) => client,
+            Err(err) => {
+                error!("Error creating the webhook client: {:?}", err);
+                return;
+            }
+        };
+        // New events usually come with new deliveries
+        let mut watch = rocket.state::<QuestionEventsHub>().map(QuestionEventsHub::watch_all);
+        let dao = WebhooksDaoImpl::new(self.db.clone());
+
+        spawn_until_shutdown(rocket, "webhook deliveries", async move {
+            let poll_interval = Duration::from_secs(config.poll_interval_seconds);
+            loop {
+                match deliver_due(&config, &client, &dao).await {
+                    // A full batch: there may be more
+                    Ok(attempted) if attempted as i64 >= config.batch_size => continue,
+                    Ok(_) => {}
+                    Err(err) => error!("Error claiming webhook deliveries: {:?}", err),
+                }
+                match &mut watch {
+                    Some(watch) => {
+                        let _ = tokio::time::timeout(poll_interval, watch.changed()).await;
+                    }
+                    None => tokio::time::sleep(poll_interval).await,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use crate::models::{AuditContext, DeliveryStatus, Question, Webhook, WebhookDeliveryFilter};
+    use crate::persistance::questions_dao::{QuestionsDao, QuestionsDaoImpl};
+
+    const SECRET: &str = "0123456789abcdef";
+
+    #[derive(Debug, Clone)]
+    struct StubRequest {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl StubRequest {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// A local HTTP endpoint recording the requests it gets and answering with the given statuses,
+    /// then `200`.
+    struct Stub {
+        url: String,
+        requests: Arc<Mutex<Vec<StubRequest>>>,
+    }
+
+    async fn stub(statuses: &[u16]) -> Stub {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        // By name: deliveries to loopback literals are refused outright
+        let url = format!("http://localhost:{}/hook", listener.local_addr().unwrap().port());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(statuses.iter().copied().collect::<VecDeque<_>>()));
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let mut socket = BufReader::new(socket);
+                let mut headers = Vec::new();
+                let mut line = String::new();
+                socket.read_line(&mut line).await.unwrap();
+                loop {
+                    line.clear();
+                    socket.read_line(&mut line).await.unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+                    headers.push((name.to_owned(), value.to_owned()));
+                }
+                let request = StubRequest { headers, body: Vec::new() };
+                let length = request.header("Content-Length").map_or(0, |length| length.parse().unwrap());
+                let mut body = vec![0; length];
+                socket.read_exact(&mut body).await.unwrap();
+                recorded.lock().unwrap().push(StubRequest { body, ..request });
+
+                let status = statuses.lock().unwrap().pop_front().unwrap_or(200);
+                let response = format!("HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        Stub { url, requests }
+    }
+
+    fn config(max_attempts: u32) -> WebhooksConfig {
+        WebhooksConfig {
+            max_attempts,
+            base_delay_seconds: 0,
+            ..WebhooksConfig::default()
+        }
+    }
+
+    async fn subscribe(pool: &PgPool, url: &str) -> String {
+        WebhooksDaoImpl::new(pool.clone())
+            .create_subscription(Webhook {
+                url: url.to_owned(),
+                event_types: vec!["question.created".to_owned()],
+                secret: SECRET.to_owned(),
+            })
+            .await
+            .expect("Error creating webhook")
+            .subscription_uuid
+    }
+
+    async fn ask(pool: &PgPool) -> String {
+        QuestionsDaoImpl::new(pool.clone())
+            .create_question(
+                Question {
+                    title: "test title".to_owned(),
+                    description: "test description".to_owned(),
+                },
+                AuditContext::default(),
+            )
+            .await
+            .expect("Error creating question")
+            .question_uuid
+    }
+
+    async fn deliveries(dao: &WebhooksDaoImpl, subscription_uuid: &str) -> Vec<crate::models::WebhookDelivery> {
+        dao.get_deliveries(WebhookDeliveryFilter {
+            subscription_uuid: subscription_uuid.to_owned(),
+            status: None,
+            before_delivery_id: None,
+            limit: 10,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn retry_delay_should_back_off_exponentially() {
+        let config = WebhooksConfig {
+            max_attempts: 5,
+            base_delay_seconds: 10,
+            max_delay_seconds: 60,
+            ..WebhooksConfig::default()
+        };
+
+        let delays: Vec<_> = (1..=5).map(|attempts| config.retry_delay(attempts)).collect();
+
+        assert_eq!(
+            delays,
+            [10, 20, 40, 60].map(|secs| Some(Duration::from_secs(secs))).into_iter().chain([None]).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn is_public_address_should_reject_internal_ranges() {
+        for internal in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:10.0.0.1"] {
+            assert!(!is_public_address(internal.parse().unwrap()), "{}", internal);
+        }
+        for public in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public_address(public.parse().unwrap()), "{}", public);
+        }
+    }
+
+    #[sqlx::test]
+    async fn should_not_deliver_to_non_public_addresses(pool: PgPool) {
+        let stub = stub(&[]).await;
+        let by_name = subscribe(&pool, &stub.url).await;
+        let by_address = subscribe(&pool, &stub.url.replace("localhost", "127.0.0.1")).await;
+        ask(&pool).await;
+        let dao = WebhooksDaoImpl::new(pool);
+
+        deliver_due(&config(3), &client(&config(3)).unwrap(), &dao).await.unwrap();
+
+        for subscription_uuid in [by_name, by_address] {
+            let delivery = deliveries(&dao, &subscription_uuid).await.remove(0);
+            assert_eq!((delivery.status, delivery.attempts, delivery.last_status_code), (DeliveryStatus::Pending, 1, None));
+        }
+        assert!(stub.requests.lock().unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn should_deliver_signed_events(pool: PgPool) {
+        let stub = stub(&[]).await;
+        let subscription_uuid = subscribe(&pool, &stub.url).await;
+        let question_uuid = ask(&pool).await;
+        let dao = WebhooksDaoImpl::new(pool);
+
+        let attempted = deliver_due(&config(3), &reqwest::Client::new(), &dao).await.unwrap();
+
+        let requests = stub.requests.lock().unwrap().clone();
+        assert_eq!((attempted, requests.len()), (1, 1));
+        let request = &requests[0];
+        let timestamp: u64 = request.header(TIMESTAMP_HEADER).unwrap().parse().unwrap();
+        assert_eq!(request.header(SIGNATURE_HEADER), Some(sign(SECRET, timestamp, &request.body).as_str()));
+        assert_eq!(request.header(EVENT_HEADER), Some("question.created"));
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["data"]["question_uuid"], question_uuid);
+
+        let deliveries = deliveries(&dao, &subscription_uuid).await;
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].last_status_code, Some(200));
+    }
+
+    #[sqlx::test]
+    async fn should_retry_failed_deliveries_then_give_up(pool: PgPool) {
+        let stub = stub(&[500, 503, 500]).await;
+        let subscription_uuid = subscribe(&pool, &stub.url).await;
+        ask(&pool).await;
+        let dao = WebhooksDaoImpl::new(pool);
+        let client = reqwest::Client::new();
+
+        deliver_due(&config(2), &client, &dao).await.unwrap();
+        let retried = deliveries(&dao, &subscription_uuid).await;
+        deliver_due(&config(2), &client, &dao).await.unwrap();
+        let dead = deliveries(&dao, &subscription_uuid).await;
+        let idle = deliver_due(&config(2), &client, &dao).await.unwrap();
+
+        dao.retry_delivery(dead[0].delivery_id).await.unwrap();
+        deliver_due(&config(2), &client, &dao).await.unwrap();
+        let requeued = deliveries(&dao, &subscription_uuid).await;
+
+        assert_eq!((retried[0].status, retried[0].attempts), (DeliveryStatus::Pending, 1));
+        assert_eq!((dead[0].status, dead[0].attempts, dead[0].last_status_code), (DeliveryStatus::Dead, 2, Some(503)));
+        assert_eq!(idle, 0);
+        assert_eq!((requeued[0].status, requeued[0].attempts), (DeliveryStatus::Pending, 1));
+        assert_eq!(stub.requests.lock().unwrap().len(), 3);
+    }
+}